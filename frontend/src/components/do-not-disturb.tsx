@@ -0,0 +1,151 @@
+import React, { useState } from 'react';
+import { Button } from './ui/button';
+import { Input } from './ui/input';
+import { Label } from './ui/label';
+import { ToggleGroup, ToggleGroupItem } from './ui/toggle-group';
+import { Plus, Trash2 } from 'lucide-react';
+import { DoNotDisturb as Settings, errorMessage, QuietWindow, Vacation, Weekday } from '../hooks/useTauri';
+
+const WEEKDAYS: { day: Weekday; label: string }[] = [
+  { day: 'mon', label: 'M' },
+  { day: 'tue', label: 'T' },
+  { day: 'wed', label: 'W' },
+  { day: 'thu', label: 'T' },
+  { day: 'fri', label: 'F' },
+  { day: 'sat', label: 'S' },
+  { day: 'sun', label: 'S' }
+];
+
+const PAUSES = [
+  { label: '1h', seconds: 3600 },
+  { label: '4h', seconds: 4 * 3600 },
+  { label: '24h', seconds: 24 * 3600 }
+];
+
+const today = () => new Date().toISOString().slice(0, 10);
+
+interface DoNotDisturbProps {
+  value: Settings;
+  onChange: (value: Settings) => Promise<void>;
+}
+
+export function DoNotDisturb({ value, onChange }: DoNotDisturbProps) {
+  const [error, setError] = useState<string | null>(null);
+
+  const save = async (next: Partial<Settings>) => {
+    try {
+      setError(null);
+      await onChange({ ...value, ...next });
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+
+  const setWindow = (index: number, window: Partial<QuietWindow>) =>
+    save({ windows: value.windows.map((w, i) => (i === index ? { ...w, ...window } : w)) });
+
+  const setVacation = (index: number, vacation: Partial<Vacation>) =>
+    save({ vacations: value.vacations.map((v, i) => (i === index ? { ...v, ...vacation } : v)) });
+
+  const pausedUntil = value.pausedUntil && value.pausedUntil * 1000 > Date.now()
+    ? new Date(value.pausedUntil * 1000)
+    : null;
+
+  return (
+    <div className="space-y-3">
+      <div className="space-y-2">
+        <Label>Quiet hours</Label>
+        {value.windows.map((window, index) => (
+          <div key={index} className="space-y-1">
+            <ToggleGroup
+              type="multiple"
+              size="sm"
+              value={window.days}
+              onValueChange={(days: string[]) => setWindow(index, { days: days as Weekday[] })}
+            >
+              {WEEKDAYS.map(({ day, label }) => (
+                <ToggleGroupItem key={day} value={day} aria-label={day}>
+                  {label}
+                </ToggleGroupItem>
+              ))}
+            </ToggleGroup>
+            <div className="flex items-center space-x-2">
+              <Input type="time" value={window.start} onChange={(e) => setWindow(index, { start: e.target.value })} />
+              <span className="text-xs text-muted-foreground">to</span>
+              <Input type="time" value={window.end} onChange={(e) => setWindow(index, { end: e.target.value })} />
+              <Button
+                variant="ghost"
+                size="sm"
+                onClick={() => save({ windows: value.windows.filter((_, i) => i !== index) })}
+              >
+                <Trash2 className="h-3 w-3" />
+              </Button>
+            </div>
+          </div>
+        ))}
+        <Button
+          variant="outline"
+          size="sm"
+          onClick={() => save({ windows: [...value.windows, { days: WEEKDAYS.map(w => w.day), start: '22:00', end: '07:00' }] })}
+        >
+          <Plus className="h-3 w-3 mr-1" />
+          Add quiet hours
+        </Button>
+        <p className="text-xs text-muted-foreground">An end not after the start runs into the next day</p>
+      </div>
+
+      <div className="space-y-2">
+        <Label>Holidays and vacations</Label>
+        {value.vacations.map((vacation, index) => (
+          <div key={index} className="flex items-center space-x-2">
+            <Input type="date" value={vacation.from} onChange={(e) => setVacation(index, { from: e.target.value })} />
+            <Input type="date" value={vacation.to} onChange={(e) => setVacation(index, { to: e.target.value })} />
+            <Button
+              variant="ghost"
+              size="sm"
+              onClick={() => save({ vacations: value.vacations.filter((_, i) => i !== index) })}
+            >
+              <Trash2 className="h-3 w-3" />
+            </Button>
+          </div>
+        ))}
+        <Button
+          variant="outline"
+          size="sm"
+          onClick={() => save({ vacations: [...value.vacations, { from: today(), to: today() }] })}
+        >
+          <Plus className="h-3 w-3 mr-1" />
+          Add vacation
+        </Button>
+      </div>
+
+      <div className="space-y-2">
+        <Label>Pause notifications</Label>
+        {pausedUntil ? (
+          <div className="flex items-center justify-between">
+            <p className="text-xs text-muted-foreground">Paused until {pausedUntil.toLocaleString()}</p>
+            <Button variant="outline" size="sm" onClick={() => save({ pausedUntil: null })}>
+              Resume
+            </Button>
+          </div>
+        ) : (
+          <div className="flex items-center space-x-2">
+            {PAUSES.map(({ label, seconds }) => (
+              <Button
+                key={label}
+                variant="outline"
+                size="sm"
+                onClick={() => save({ pausedUntil: Math.floor(Date.now() / 1000) + seconds })}
+              >
+                {label}
+              </Button>
+            ))}
+          </div>
+        )}
+        <p className="text-xs text-muted-foreground">Notifications due while quiet are delivered afterwards as a digest</p>
+      </div>
+
+      {error && <p className="text-xs text-destructive">{error}</p>}
+    </div>
+  );
+}
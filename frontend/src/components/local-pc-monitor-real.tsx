@@ -15,7 +15,7 @@ import {
   RefreshCw
 } from 'lucide-react';
 import { LineChart, Line, XAxis, YAxis, CartesianGrid, Tooltip, ResponsiveContainer } from 'recharts';
-import { useSystemMonitorReal } from '../hooks/useSystemMonitorReal';
+import { useSystemMonitorReal, formatReading, readingValue, type Reading } from '../hooks/useSystemMonitorReal';
 import { Button } from './ui/button';
 
 /**
@@ -43,17 +43,24 @@ export function LocalPCMonitorReal() {
     const now = new Date();
     const timeStr = `${now.getHours().toString().padStart(2, '0')}:${now.getMinutes().toString().padStart(2, '0')}:${now.getSeconds().toString().padStart(2, '0')}`;
 
-    console.log(`📈 VERA: Updating charts - CPU: ${metrics.cpu.usage.toFixed(1)}%, RAM: ${metrics.ram.percentage.toFixed(1)}%`);
+    console.log(`📈 VERA: Updating charts - CPU: ${formatReading(metrics.cpu.usage, 1, '%')}, RAM: ${formatReading(metrics.ram.percentage, 1, '%')}`);
 
-    setCpuHistory(prev => {
-      const newHistory = [...prev, { time: timeStr, value: Math.round(metrics.cpu.usage) }];
-      return newHistory.slice(-30); // Keep last 30 points
-    });
+    // Unavailable samples are skipped rather than plotted as zero
+    const cpuUsage = readingValue(metrics.cpu.usage);
+    if (cpuUsage !== null) {
+      setCpuHistory(prev => {
+        const newHistory = [...prev, { time: timeStr, value: Math.round(cpuUsage) }];
+        return newHistory.slice(-30); // Keep last 30 points
+      });
+    }
 
-    setRamHistory(prev => {
-      const newHistory = [...prev, { time: timeStr, value: Math.round(metrics.ram.percentage) }];
-      return newHistory.slice(-30);
-    });
+    const ramPercentage = readingValue(metrics.ram.percentage);
+    if (ramPercentage !== null) {
+      setRamHistory(prev => {
+        const newHistory = [...prev, { time: timeStr, value: Math.round(ramPercentage) }];
+        return newHistory.slice(-30);
+      });
+    }
   }, [metrics]);
 
   // Show error if Tauri not available
@@ -120,9 +127,9 @@ export function LocalPCMonitorReal() {
     );
   }
 
-  const formatBytes = (bytes: number): string => {
-    const gb = bytes / 1024 / 1024 / 1024;
-    return `${gb.toFixed(2)} GB`;
+  const formatBytesReading = (bytes: Reading<number>): string => {
+    if (bytes.status === 'unavailable') return 'n/a';
+    return formatReading({ ...bytes, value: bytes.value / 1024 / 1024 / 1024 }, 2, ' GB');
   };
 
   const formatUptime = (seconds: number): string => {
@@ -172,27 +179,49 @@ export function LocalPCMonitorReal() {
               <div>
                 <div className="flex items-baseline justify-between mb-2">
                   <span className="text-3xl font-bold">
-                    {metrics.cpu.usage.toFixed(1)}%
+                    {formatReading(metrics.cpu.usage, 1, '%')}
                   </span>
                   <span className="text-xs text-muted-foreground">
-                    {metrics.cpu.cores} cores
+                    {formatReading(metrics.cpu.cores, 0, ' cores')}
                   </span>
                 </div>
                 <Progress 
-                  value={metrics.cpu.usage} 
-                  className={metrics.cpu.usage > 80 ? 'bg-red-200' : ''}
+                  value={readingValue(metrics.cpu.usage) ?? 0} 
+                  className={(readingValue(metrics.cpu.usage) ?? 0) > 80 ? 'bg-red-200' : ''}
                 />
               </div>
               <div className="text-xs space-y-1">
                 <div className="flex justify-between">
                   <span className="text-muted-foreground">Frequenza:</span>
-                  <span>{metrics.cpu.frequency} MHz</span>
+                  <span>{formatReading(metrics.cpu.frequency, 0, ' MHz')}</span>
                 </div>
                 <div className="flex justify-between">
                   <span className="text-muted-foreground">Temperatura:</span>
-                  <span>{metrics.cpu.temperature.toFixed(1)}°C</span>
+                  <span>{formatReading(metrics.cpu.temperature, 1, '°C')}</span>
                 </div>
+                {metrics.cpu.per_core[0]?.governor && (
+                  <div className="flex justify-between">
+                    <span className="text-muted-foreground">Governor:</span>
+                    <span>{metrics.cpu.per_core[0].governor}</span>
+                  </div>
+                )}
               </div>
+              {metrics.cpu.per_core.length > 0 && (
+                <div className="grid grid-cols-8 gap-1" title="Utilizzo per core">
+                  {metrics.cpu.per_core.map(core => (
+                    <div
+                      key={core.id}
+                      className="h-6 rounded-sm bg-muted relative overflow-hidden"
+                      title={`CPU ${core.id}${metrics.cpu.hybrid ? ` (${core.core_type === 'efficiency' ? 'E' : 'P'}-core)` : ''}: ${formatReading(core.usage, 0, '%')} @ ${formatReading(core.frequency, 0, ' MHz')}`}
+                    >
+                      <div
+                        className={core.core_type === 'efficiency' ? 'absolute bottom-0 w-full bg-emerald-500' : 'absolute bottom-0 w-full bg-blue-500'}
+                        style={{ height: `${readingValue(core.usage) ?? 0}%` }}
+                      />
+                    </div>
+                  ))}
+                </div>
+              )}
             </div>
           </CardContent>
         </Card>
@@ -210,22 +239,22 @@ export function LocalPCMonitorReal() {
               <div>
                 <div className="flex items-baseline justify-between mb-2">
                   <span className="text-3xl font-bold">
-                    {metrics.ram.percentage.toFixed(1)}%
+                    {formatReading(metrics.ram.percentage, 1, '%')}
                   </span>
                 </div>
                 <Progress 
-                  value={metrics.ram.percentage} 
-                  className={metrics.ram.percentage > 85 ? 'bg-red-200' : ''}
+                  value={readingValue(metrics.ram.percentage) ?? 0} 
+                  className={(readingValue(metrics.ram.percentage) ?? 0) > 85 ? 'bg-red-200' : ''}
                 />
               </div>
               <div className="text-xs space-y-1">
                 <div className="flex justify-between">
                   <span className="text-muted-foreground">In uso:</span>
-                  <span>{formatBytes(metrics.ram.used)}</span>
+                  <span>{formatBytesReading(metrics.ram.used)}</span>
                 </div>
                 <div className="flex justify-between">
                   <span className="text-muted-foreground">Totale:</span>
-                  <span>{formatBytes(metrics.ram.total)}</span>
+                  <span>{formatBytesReading(metrics.ram.total)}</span>
                 </div>
               </div>
             </div>
@@ -241,26 +270,41 @@ export function LocalPCMonitorReal() {
             </CardTitle>
           </CardHeader>
           <CardContent>
-            <div className="space-y-3">
-              <div>
-                <div className="flex items-baseline justify-between mb-2">
-                  <span className="text-3xl font-bold">
-                    {metrics.gpu.usage.toFixed(1)}%
-                  </span>
-                </div>
-                <Progress value={metrics.gpu.usage} />
-              </div>
-              <div className="text-xs space-y-1">
-                <div className="flex justify-between">
-                  <span className="text-muted-foreground">VRAM:</span>
-                  <span>{formatBytes(metrics.gpu.memory)} / {formatBytes(metrics.gpu.memory_total)}</span>
-                </div>
-                <div className="flex justify-between">
-                  <span className="text-muted-foreground">Temperatura:</span>
-                  <span>{metrics.gpu.temperature.toFixed(1)}°C</span>
-                </div>
+            {metrics.gpus.length === 0 ? (
+              <p className="text-sm text-muted-foreground">n/a</p>
+            ) : (
+              <div className="space-y-4">
+                {metrics.gpus.map(gpu => (
+                  <div key={gpu.id} className="space-y-3">
+                    <div>
+                      <div className="flex items-baseline justify-between mb-2">
+                        <span className="text-3xl font-bold">
+                          {formatReading(gpu.usage, 1, '%')}
+                        </span>
+                        <span className="text-xs text-muted-foreground">
+                          {gpu.vendor.toUpperCase()} · {gpu.driver ?? gpu.id}
+                        </span>
+                      </div>
+                      <Progress value={readingValue(gpu.usage) ?? 0} />
+                    </div>
+                    <div className="text-xs space-y-1">
+                      <div className="flex justify-between">
+                        <span className="text-muted-foreground">VRAM:</span>
+                        <span>{formatBytesReading(gpu.memory)} / {formatBytesReading(gpu.memory_total)}</span>
+                      </div>
+                      <div className="flex justify-between">
+                        <span className="text-muted-foreground">Temperatura:</span>
+                        <span>{formatReading(gpu.temperature, 1, '°C')}</span>
+                      </div>
+                      <div className="flex justify-between">
+                        <span className="text-muted-foreground">Potenza:</span>
+                        <span>{formatReading(gpu.power_draw, 1, 'W')}</span>
+                      </div>
+                    </div>
+                  </div>
+                ))}
               </div>
-            </div>
+            )}
           </CardContent>
         </Card>
 
@@ -277,22 +321,22 @@ export function LocalPCMonitorReal() {
               <div>
                 <div className="flex items-baseline justify-between mb-2">
                   <span className="text-3xl font-bold">
-                    {metrics.disk.usage.toFixed(1)}%
+                    {formatReading(metrics.disk.usage, 1, '%')}
                   </span>
                 </div>
                 <Progress 
-                  value={metrics.disk.usage}
-                  className={metrics.disk.usage > 90 ? 'bg-red-200' : ''}
+                  value={readingValue(metrics.disk.usage) ?? 0}
+                  className={(readingValue(metrics.disk.usage) ?? 0) > 90 ? 'bg-red-200' : ''}
                 />
               </div>
               <div className="text-xs space-y-1">
                 <div className="flex justify-between">
                   <span className="text-muted-foreground">Lettura:</span>
-                  <span>{metrics.disk.read_speed.toFixed(1)} MB/s</span>
+                  <span>{formatReading(metrics.disk.read_speed, 1, ' MB/s')}</span>
                 </div>
                 <div className="flex justify-between">
                   <span className="text-muted-foreground">Scrittura:</span>
-                  <span>{metrics.disk.write_speed.toFixed(1)} MB/s</span>
+                  <span>{formatReading(metrics.disk.write_speed, 1, ' MB/s')}</span>
                 </div>
               </div>
             </div>
@@ -373,21 +417,24 @@ export function LocalPCMonitorReal() {
           <div className="grid grid-cols-2 md:grid-cols-4 gap-4 text-sm">
             <div>
               <p className="text-muted-foreground">Uptime</p>
-              <p className="font-semibold">{formatUptime(metrics.system.uptime)}</p>
+              <p className="font-semibold">{metrics.system.uptime.status === 'unavailable' ? 'n/a' : formatUptime(metrics.system.uptime.value)}</p>
             </div>
             <div>
               <p className="text-muted-foreground">Processi</p>
-              <p className="font-semibold">{metrics.system.processes}</p>
+              <p className="font-semibold">{formatReading(metrics.system.processes, 0)}</p>
             </div>
             <div>
               <p className="text-muted-foreground">Temperatura</p>
-              <p className="font-semibold">{metrics.system.temperature.toFixed(1)}°C</p>
+              <p className="font-semibold">{formatReading(metrics.system.temperature, 1, '°C')}</p>
             </div>
             <div>
               <p className="text-muted-foreground">Potenza</p>
-              <p className="font-semibold">{metrics.power.power_draw.toFixed(1)}W</p>
+              <p className="font-semibold">{formatReading(metrics.power.power_draw, 1, 'W')}</p>
             </div>
           </div>
+          <p className="mt-3 text-xs text-muted-foreground">
+            Overhead VERA: CPU {formatReading(metrics.overhead.cpu_usage, 1, '%')} · {formatReading(metrics.overhead.estimated_power, 2, 'W')} · campionamento {formatReading(metrics.overhead.sample_duration_ms, 1, 'ms')}
+          </p>
         </CardContent>
       </Card>
 
@@ -12,6 +12,8 @@ export function NotificationMenu() {
     markAsRead, 
     markAllAsRead,
     deleteNotification,
+    hasMore,
+    loadMore,
     loading 
   } = useTauriNotifications();
 
@@ -93,6 +95,7 @@ export function NotificationMenu() {
                       <div className={cn(
                         "h-2 w-2 rounded-full shrink-0",
                         notification.type === 'warning' && "bg-yellow-500",
+                        notification.type === 'critical' && "bg-red-500",
                         notification.type === 'success' && "bg-green-500",
                         notification.type === 'info' && "bg-blue-500"
                       )} />
@@ -105,6 +108,14 @@ export function NotificationMenu() {
                     <span className="text-xs text-muted-foreground pl-4">{notification.timestamp}</span>
                   </div>
                 ))}
+                {hasMore && (
+                  <button
+                    onClick={loadMore}
+                    className="w-full p-2 text-xs text-muted-foreground hover:text-foreground transition-colors"
+                  >
+                    Carica notifiche precedenti
+                  </button>
+                )}
               </>
             )}
           </div>
@@ -0,0 +1,188 @@
+import React, { useState } from 'react';
+import { Button } from './ui/button';
+import { Input } from './ui/input';
+import { Label } from './ui/label';
+import { Switch } from './ui/switch';
+import { Textarea } from './ui/textarea';
+import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from './ui/select';
+import { Pencil, Plus, Trash2 } from 'lucide-react';
+import { errorMessage, NotificationRule, RuleTrigger } from '../hooks/useTauri';
+import { useNotificationRules } from '../hooks/useTauriNotifications';
+
+const emptyRule = (): NotificationRule => ({
+  id: '',
+  name: '',
+  enabled: true,
+  trigger: { kind: 'schedule', cron: '0 18 * * *' },
+  conditions: [],
+  template: { title: '', message: 'Oggi: {day.kwh:.2} kWh', type: 'info' }
+});
+
+function describeTrigger(trigger: RuleTrigger): string {
+  switch (trigger.kind) {
+    case 'schedule':
+      return `Schedule ${trigger.cron}`;
+    case 'threshold':
+      return `When ${trigger.metric} goes above ${trigger.above}`;
+    case 'goal':
+      return `Each ${trigger.period} within ${trigger.kwh} kWh`;
+    case 'anomaly':
+      return `Power ${trigger.factor}× the ${trigger.windowMinutes} min average`;
+    case 'alert': {
+      const limits = trigger.limits
+        .map(l => (l.above != null ? `${l.metric} above ${l.above}` : `${l.metric} below ${l.below}`))
+        .join(' and ');
+      return `When ${limits} for ${Math.round(trigger.durationSecs / 60)} min`;
+    }
+  }
+}
+
+export function NotificationRules() {
+  const { rules, loading, saveRule, deleteRule } = useNotificationRules();
+  const [editing, setEditing] = useState<NotificationRule | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  const save = async (rule: NotificationRule) => {
+    try {
+      setError(null);
+      await saveRule(rule);
+      setEditing(null);
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+
+  const remove = async (id: string) => {
+    try {
+      setError(null);
+      await deleteRule(id);
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+
+  if (loading) {
+    return <p className="text-xs text-muted-foreground">Loading rules…</p>;
+  }
+
+  return (
+    <div className="space-y-3">
+      {rules.map(rule => (
+        <div key={rule.id} className="flex items-center justify-between">
+          <div className="min-w-0">
+            <Label htmlFor={`rule-${rule.id}`}>{rule.name}</Label>
+            <p className="text-xs text-muted-foreground truncate">{describeTrigger(rule.trigger)}</p>
+          </div>
+          <div className="flex items-center space-x-1">
+            <Button variant="ghost" size="sm" onClick={() => setEditing(rule)}>
+              <Pencil className="h-3 w-3" />
+            </Button>
+            <Button variant="ghost" size="sm" onClick={() => remove(rule.id)}>
+              <Trash2 className="h-3 w-3" />
+            </Button>
+            <Switch
+              id={`rule-${rule.id}`}
+              checked={rule.enabled}
+              onCheckedChange={(enabled) => save({ ...rule, enabled })}
+            />
+          </div>
+        </div>
+      ))}
+
+      {editing ? (
+        <RuleEditor rule={editing} onSave={save} onCancel={() => setEditing(null)} />
+      ) : (
+        <Button variant="outline" size="sm" onClick={() => setEditing(emptyRule())}>
+          <Plus className="h-3 w-3 mr-1" />
+          Add rule
+        </Button>
+      )}
+
+      {error && <p className="text-xs text-destructive">{error}</p>}
+    </div>
+  );
+}
+
+interface RuleEditorProps {
+  rule: NotificationRule;
+  onSave: (rule: NotificationRule) => void;
+  onCancel: () => void;
+}
+
+function RuleEditor({ rule, onSave, onCancel }: RuleEditorProps) {
+  const [draft, setDraft] = useState(rule);
+  const setTemplate = (patch: Partial<NotificationRule['template']>) =>
+    setDraft(prev => ({ ...prev, template: { ...prev.template, ...patch } }));
+  // Rewritten texts are no longer translated from the catalogue
+  const setText = (patch: Pick<Partial<NotificationRule['template']>, 'title' | 'message'>) =>
+    setTemplate({ ...patch, id: undefined });
+
+  return (
+    <div className="space-y-2 rounded-md border p-3">
+      <div className="space-y-1">
+        <Label htmlFor="rule-name">Name</Label>
+        <Input id="rule-name" value={draft.name} onChange={(e) => setDraft({ ...draft, name: e.target.value })} />
+      </div>
+
+      {draft.trigger.kind === 'schedule' ? (
+        <div className="space-y-1">
+          <Label htmlFor="rule-cron">Schedule</Label>
+          <Input
+            id="rule-cron"
+            className="font-mono"
+            value={draft.trigger.cron}
+            onChange={(e) => setDraft({ ...draft, trigger: { kind: 'schedule', cron: e.target.value } })}
+          />
+          <p className="text-xs text-muted-foreground">minute hour day month weekday, e.g. 0 18 * * 1-5</p>
+        </div>
+      ) : (
+        <p className="text-xs text-muted-foreground">{describeTrigger(draft.trigger)}</p>
+      )}
+
+      <div className="space-y-1">
+        <Label htmlFor="rule-title">Title</Label>
+        <Input id="rule-title" value={draft.template.title} onChange={(e) => setText({ title: e.target.value })} />
+      </div>
+
+      <div className="space-y-1">
+        <Label htmlFor="rule-message">Message</Label>
+        <Textarea
+          id="rule-message"
+          rows={2}
+          value={draft.template.message}
+          onChange={(e) => setText({ message: e.target.value })}
+        />
+        <p className="text-xs text-muted-foreground">
+          Placeholders: {'{day.kwh:.2}'}, {'{week.trend}'}, {'{month.cost:currency}'}, {'{day.bottles:bottle|bottles}'}, {'{power}'}
+        </p>
+      </div>
+
+      <div className="space-y-1">
+        <Label>Type</Label>
+        <Select
+          value={draft.template.type}
+          onValueChange={(type) => setTemplate({ type: type as NotificationRule['template']['type'] })}
+        >
+          <SelectTrigger>
+            <SelectValue />
+          </SelectTrigger>
+          <SelectContent>
+            <SelectItem value="info">Info</SelectItem>
+            <SelectItem value="warning">Warning</SelectItem>
+            <SelectItem value="critical">Critical</SelectItem>
+            <SelectItem value="success">Success</SelectItem>
+          </SelectContent>
+        </Select>
+      </div>
+
+      <div className="flex justify-end space-x-2 pt-1">
+        <Button variant="outline" size="sm" onClick={onCancel}>
+          Cancel
+        </Button>
+        <Button size="sm" onClick={() => onSave(draft)}>
+          Save rule
+        </Button>
+      </div>
+    </div>
+  );
+}
@@ -0,0 +1,174 @@
+import React, { useCallback, useEffect, useState } from 'react';
+import { Button } from './ui/button';
+import { Input } from './ui/input';
+import { Label } from './ui/label';
+import { Switch } from './ui/switch';
+import { Textarea } from './ui/textarea';
+import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from './ui/select';
+import { errorMessage, useTauri, Webhook, WebhookDelivery } from '../hooks/useTauri';
+
+interface WebhookSettingsProps {
+  value: Webhook;
+  onChange: (value: Webhook) => Promise<void>;
+}
+
+const json = (value: unknown) => (value == null ? '' : JSON.stringify(value, null, 2));
+
+function outcome(delivery: WebhookDelivery): string {
+  return delivery.error ?? (delivery.status != null ? `HTTP ${delivery.status}` : '');
+}
+
+export function WebhookSettings({ value, onChange }: WebhookSettingsProps) {
+  const { invoke, isReady } = useTauri();
+  const [draft, setDraft] = useState(value);
+  const [headers, setHeaders] = useState(json(value.headers));
+  const [body, setBody] = useState(json(value.body));
+  const [deliveries, setDeliveries] = useState<WebhookDelivery[]>([]);
+  const [error, setError] = useState<string | null>(null);
+  const [testing, setTesting] = useState(false);
+
+  useEffect(() => {
+    setDraft(value);
+    setHeaders(json(value.headers));
+    setBody(json(value.body));
+  }, [value]);
+
+  const refresh = useCallback(async () => {
+    try {
+      setDeliveries(await invoke('get_webhook_deliveries', { limit: 10 }) as WebhookDelivery[]);
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  }, [invoke]);
+
+  useEffect(() => {
+    if (isReady) refresh();
+  }, [isReady, refresh]);
+
+  const save = async (next: Partial<Webhook> = {}) => {
+    try {
+      setError(null);
+      await onChange({
+        ...draft,
+        headers: headers.trim() ? JSON.parse(headers) : {},
+        body: body.trim() ? JSON.parse(body) : null,
+        ...next
+      });
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+
+  const test = async () => {
+    try {
+      setError(null);
+      setTesting(true);
+      const delivery = await invoke('test_webhook') as WebhookDelivery;
+      if (!delivery.delivered) setError(`Test failed: ${outcome(delivery)}`);
+    } catch (err) {
+      setError(errorMessage(err));
+    } finally {
+      setTesting(false);
+      refresh();
+    }
+  };
+
+  return (
+    <div className="space-y-3">
+      <div className="flex items-center justify-between">
+        <Label htmlFor="webhook-enabled">Webhook</Label>
+        <Switch
+          id="webhook-enabled"
+          checked={value.enabled}
+          onCheckedChange={(enabled: boolean) => save({ enabled })}
+        />
+      </div>
+
+      <div className="flex items-center space-x-2">
+        <Select
+          value={draft.method}
+          onValueChange={(method) => setDraft({ ...draft, method: method as Webhook['method'] })}
+        >
+          <SelectTrigger className="w-28">
+            <SelectValue />
+          </SelectTrigger>
+          <SelectContent>
+            <SelectItem value="POST">POST</SelectItem>
+            <SelectItem value="PUT">PUT</SelectItem>
+            <SelectItem value="PATCH">PATCH</SelectItem>
+          </SelectContent>
+        </Select>
+        <Input
+          placeholder="https://chat.example.com/hooks/…"
+          value={draft.url}
+          onChange={(e) => setDraft({ ...draft, url: e.target.value })}
+        />
+      </div>
+
+      <div className="space-y-2">
+        <Label>Headers</Label>
+        <Textarea
+          rows={2}
+          placeholder={'{ "Authorization": "Bearer …" }'}
+          value={headers}
+          onChange={(e) => setHeaders(e.target.value)}
+        />
+      </div>
+
+      <div className="space-y-2">
+        <Label>Body</Label>
+        <Textarea
+          rows={4}
+          placeholder={'{ "text": "{title}: {message}" }'}
+          value={body}
+          onChange={(e) => setBody(e.target.value)}
+        />
+        <p className="text-xs text-muted-foreground">
+          JSON with {'{id}'}, {'{title}'}, {'{message}'}, {'{type}'}, {'{timestamp}'} or {'{notification}'}; empty sends the notification as is
+        </p>
+      </div>
+
+      <div className="space-y-2">
+        <Label>Signing secret</Label>
+        <Input
+          type="password"
+          value={draft.secret}
+          onChange={(e) => setDraft({ ...draft, secret: e.target.value })}
+        />
+        <p className="text-xs text-muted-foreground">Signs each request in the X-Vera-Signature header</p>
+      </div>
+
+      <div className="flex items-center justify-between">
+        <Label htmlFor="webhook-quiet">Respect quiet hours</Label>
+        <Switch
+          id="webhook-quiet"
+          checked={draft.quietHours}
+          onCheckedChange={(quietHours: boolean) => setDraft({ ...draft, quietHours })}
+        />
+      </div>
+
+      <div className="flex items-center space-x-2">
+        <Button size="sm" onClick={() => save()}>
+          Save
+        </Button>
+        <Button variant="outline" size="sm" disabled={testing || !value.url} onClick={test}>
+          {testing ? 'Sending…' : 'Send test'}
+        </Button>
+      </div>
+
+      {deliveries.length > 0 && (
+        <div className="space-y-1">
+          <Label>Recent deliveries</Label>
+          {deliveries.map((delivery, index) => (
+            <p key={index} className="text-xs text-muted-foreground truncate">
+              {delivery.delivered ? '✓' : '✗'} {new Date(delivery.timestamp * 1000).toLocaleString()} · attempt{' '}
+              {delivery.attempt} · {outcome(delivery)} · {delivery.notificationId}
+            </p>
+          ))}
+        </div>
+      )}
+
+      {error && <p className="text-xs text-destructive">{error}</p>}
+    </div>
+  );
+}
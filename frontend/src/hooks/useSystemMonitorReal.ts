@@ -6,40 +6,115 @@ import { useTauri } from './useTauri';
  * Reads actual system metrics from Tauri backend
  */
 
+/**
+ * Version of the get_system_metrics contract this hook understands.
+ * Must match METRICS_SCHEMA_VERSION in src-tauri/src/system_monitor/reading.rs
+ */
+export const SYSTEM_METRICS_SCHEMA_VERSION = 2;
+
+/**
+ * Every metric says whether it was measured, estimated by a model, or is unavailable
+ */
+export type Reading<T> =
+  | { status: 'measured'; value: T }
+  | { status: 'estimated'; value: T; method: string }
+  | { status: 'unavailable' };
+
+export function readingValue<T>(reading: Reading<T>): T | null {
+  return reading.status === 'unavailable' ? null : reading.value;
+}
+
+/**
+ * Format a numeric reading, "n/a" when unavailable and "~" prefix when estimated
+ */
+export function formatReading(
+  reading: Reading<number>,
+  digits: number = 1,
+  unit: string = ''
+): string {
+  if (reading.status === 'unavailable') return 'n/a';
+  const prefix = reading.status === 'estimated' ? '~' : '';
+  return `${prefix}${reading.value.toFixed(digits)}${unit}`;
+}
+
+export interface CoreMetricsReal {
+  id: number;
+  core_type: 'performance' | 'efficiency' | 'unknown';
+  usage: Reading<number>;
+  frequency: Reading<number>;
+  min_frequency: Reading<number>;
+  max_frequency: Reading<number>;
+  governor: string | null;
+  idle_states: Array<{ name: string; residency: Reading<number> }>;
+}
+
+export interface GpuMetricsReal {
+  id: string;
+  vendor: 'amd' | 'intel' | 'nvidia' | 'unknown';
+  driver: string | null;
+  pci_slot: string | null;
+  usage: Reading<number>;
+  temperature: Reading<number>;
+  memory: Reading<number>;
+  memory_total: Reading<number>;
+  power_draw: Reading<number>;
+  frequency: Reading<number>;
+  rc6_residency: Reading<number>;
+}
+
+export interface SensorReal {
+  id: string;
+  chip: string;
+  kind: 'temperature' | 'fan' | 'voltage' | 'power' | 'current';
+  label: string;
+  value: number | null;
+  max: number | null;
+  crit: number | null;
+  unit: string;
+}
+
+/** Resources used by VERA itself */
+export interface SelfOverheadReal {
+  cpu_usage: Reading<number>;          // % of one core
+  memory: Reading<number>;             // bytes
+  estimated_power: Reading<number>;    // W
+  sample_duration_ms: Reading<number>;
+}
+
 export interface SystemMetricsReal {
+  schema_version: number;
   cpu: {
-    usage: number;
-    temperature: number;
-    cores: number;
-    frequency: number;
-  };
-  gpu: {
-    usage: number;
-    temperature: number;
-    memory: number;
-    memory_total: number;
+    usage: Reading<number>;
+    temperature: Reading<number>;
+    cores: Reading<number>;
+    frequency: Reading<number>;
+    hybrid: boolean;
+    per_core: CoreMetricsReal[];
   };
+  gpus: GpuMetricsReal[];
   ram: {
-    used: number;
-    total: number;
-    percentage: number;
+    used: Reading<number>;
+    total: Reading<number>;
+    percentage: Reading<number>;
   };
   disk: {
-    read_speed: number;
-    write_speed: number;
-    usage: number;
+    read_speed: Reading<number>;
+    write_speed: Reading<number>;
+    usage: Reading<number>;
   };
   power: {
-    source: string;
-    battery_percentage: number;
-    power_draw: number;
-    estimated_time: number;
+    source: Reading<string>;
+    battery_percentage: Reading<number>;
+    power_draw: Reading<number>;
+    estimated_time: Reading<number>;
   };
   system: {
-    uptime: number;
-    processes: number;
-    temperature: number;
+    uptime: Reading<number>;
+    processes: Reading<number>;
+    temperature: Reading<number>;
   };
+  sensors: SensorReal[];
+  overhead: SelfOverheadReal;
 }
 
 export interface ActiveApplication {
@@ -73,12 +148,18 @@ export function useSystemMonitorReal() {
       console.log('📊 VERA: Fetching real system metrics from Tauri...');
       
       const data = await invoke('get_system_metrics') as SystemMetricsReal;
+
+      if (data.schema_version !== SYSTEM_METRICS_SCHEMA_VERSION) {
+        throw new Error(
+          `Unsupported system metrics schema v${data.schema_version ?? 1} (expected v${SYSTEM_METRICS_SCHEMA_VERSION})`
+        );
+      }
       
       console.log('✅ VERA: Real data received:', {
-        cpu: `${data.cpu.usage.toFixed(1)}%`,
-        ram: `${data.ram.percentage.toFixed(1)}%`,
-        gpu: `${data.gpu.usage.toFixed(1)}%`,
-        disk: `${data.disk.usage.toFixed(1)}%`,
+        cpu: formatReading(data.cpu.usage, 1, '%'),
+        ram: formatReading(data.ram.percentage, 1, '%'),
+        gpus: data.gpus.map(gpu => formatReading(gpu.usage, 1, '%')),
+        disk: formatReading(data.disk.usage, 1, '%'),
         timestamp: new Date().toISOString(),
       });
 
@@ -17,15 +17,157 @@ export interface EnergyStats {
   maxPower: number;
 }
 
+// Error returned by every backend command (see src-tauri/src/error.rs)
+export type VeraErrorCode =
+  | 'STORAGE_ERROR'
+  | 'STORAGE_BACKPRESSURE'
+  | 'SENSOR_UNAVAILABLE'
+  | 'PERMISSION_DENIED'
+  | 'AUTHENTICATION_ERROR'
+  | 'INVALID_ARGUMENT'
+  | 'INVALID_STATE'
+  | 'NOT_FOUND'
+  | 'CONFIG_ERROR'
+  | 'INTERNAL_ERROR';
+
+export interface VeraError {
+  code: VeraErrorCode;
+  message: string;
+  details: Record<string, unknown> | null;
+}
+
+export const isVeraError = (err: unknown): err is VeraError =>
+  typeof err === 'object' && err !== null && 'code' in err && 'message' in err;
+
+export const errorMessage = (err: unknown, fallback = 'Unknown error'): string => {
+  if (isVeraError(err) || err instanceof Error) return err.message;
+  if (typeof err === 'string') return err;
+  return fallback;
+};
+
+// Do not disturb (see src-tauri/src/notifications/quiet.rs)
+export type Weekday = 'mon' | 'tue' | 'wed' | 'thu' | 'fri' | 'sat' | 'sun';
+
+export interface QuietWindow {
+  days: Weekday[];
+  start: string;             // HH:MM
+  end: string;               // HH:MM, next day when not after start
+}
+
+export interface Vacation {
+  from: string;              // YYYY-MM-DD, included
+  to: string;                // YYYY-MM-DD, included
+  name?: string;
+}
+
+export interface DoNotDisturb {
+  windows: QuietWindow[];
+  vacations: Vacation[];
+  pausedUntil?: number | null; // Unix seconds
+}
+
+// Outbound webhook (see src-tauri/src/notifications/webhook.rs)
+export interface Webhook {
+  enabled: boolean;
+  url: string;
+  method: 'POST' | 'PUT' | 'PATCH';
+  headers: Record<string, string>;
+  body?: unknown;            // JSON template, null for the notification itself
+  secret: string;            // signs X-Vera-Signature when set
+  quietHours: boolean;       // hold back like desktop notifications
+}
+
+export interface WebhookDelivery {
+  notificationId: string;
+  url: string;
+  attempt: number;
+  timestamp: number;         // Unix seconds
+  status?: number | null;
+  error?: string | null;
+  durationMs: number;
+  delivered: boolean;
+}
+
 export interface UserSettings {
-  quietHoursStart: number;   // 0-23
-  quietHoursEnd: number;     // 0-23
+  doNotDisturb: DoNotDisturb;
   region: string;
   theme: string;
   notificationsEnabled: boolean;
   deviceType: 'pc' | 'cellulare' | 'tablet';
+  locale: 'it' | 'en';       // language of notifications
+  apiEnabled: boolean;       // local REST API
+  apiBind: string;           // host:port
+  apiToken: string;          // Bearer token, empty until the API is enabled
+  webhook: Webhook;
+}
+
+// Notification rules (see src-tauri/src/notifications/rules.rs)
+export type RulePeriod = 'day' | 'week' | 'month';
+
+export type RuleTrigger =
+  | { kind: 'schedule'; cron: string }           // minute hour day month weekday
+  | { kind: 'threshold'; metric: 'power' | 'dayKwh' | 'weekKwh' | 'monthKwh'; above: number }
+  | { kind: 'goal'; period: RulePeriod; kwh: number }
+  | { kind: 'anomaly'; factor: number; windowMinutes: number }
+  | { kind: 'alert'; limits: AlertLimit[]; durationSecs: number; cooldownSecs: number };
+
+// One limit of an alert rule, checked on every reading
+export interface AlertLimit {
+  metric: 'power' | 'batteryPercent' | 'batteryDischarge';
+  above?: number | null;     // above or below, not both
+  below?: number | null;
+  hysteresis: number;        // same unit as the metric
+}
+
+export interface RuleCondition {
+  kind: 'kwh' | 'trend';
+  period: RulePeriod;
+  min?: number | null;       // inclusive
+  max?: number | null;       // exclusive
 }
 
+export interface NotificationRule {
+  id: string;                // empty for a new rule, assigned on save
+  name: string;
+  enabled: boolean;
+  trigger: RuleTrigger;
+  conditions: RuleCondition[];
+  template: {
+    id?: string;             // catalogue entry, translated with the locale setting
+    title: string;           // placeholders like {day.kwh:.2}
+    message: string;
+    type: 'info' | 'warning' | 'critical' | 'success';
+  };
+  lastRun?: number | null;   // Unix seconds, latest scheduled time handled
+}
+
+const mockRules: NotificationRule[] = [
+  {
+    id: 'daily-summary',
+    name: 'Riepilogo giornaliero',
+    enabled: true,
+    trigger: { kind: 'schedule', cron: '0 18 * * *' },
+    conditions: [{ kind: 'kwh', period: 'day', max: 2 }],
+    template: {
+      title: 'Riepilogo giornaliero',
+      message: 'Oggi: {day.kwh:.2} kWh ≈ {day.bottles:.1} bottiglie 💧 | CO₂: {day.co2:.0}g',
+      type: 'info'
+    }
+  },
+  {
+    id: 'monthly-summary',
+    name: 'Report mensile',
+    enabled: true,
+    trigger: { kind: 'schedule', cron: '0 10 1 * *' },
+    conditions: [],
+    template: {
+      title: 'Report mensile 📊',
+      message: 'Report mensile: {previousMonth.kwh:.2} kWh ≈ {previousMonth.bottles:.0} bottiglie 💧',
+      type: 'info'
+    }
+  }
+];
+
 // Check if we're running in Tauri
 const isTauri = () => {
   return typeof window !== 'undefined' && '__TAURI__' in window;
@@ -65,16 +207,24 @@ const mockTauriAPI = {
       
       case 'get_settings':
         return Promise.resolve({
-          quietHoursStart: 19,
-          quietHoursEnd: 8,
+          doNotDisturb: {
+            windows: [{ days: ['mon', 'tue', 'wed', 'thu', 'fri', 'sat', 'sun'], start: '19:00', end: '08:00' }],
+            vacations: [],
+            pausedUntil: null
+          },
           region: 'Lombardia',
           theme: 'light',
           notificationsEnabled: true,
-          deviceType: 'pc'
+          deviceType: 'pc',
+          locale: 'it',
+          apiEnabled: false,
+          apiBind: '127.0.0.1:7878',
+          apiToken: '',
+          webhook: { enabled: false, url: '', method: 'POST', headers: {}, body: null, secret: '', quietHours: false }
         });
       
       case 'get_notifications':
-        return Promise.resolve([
+        return Promise.resolve({ notifications: [
           {
             id: '1',
             title: 'Consumo elevato',
@@ -99,11 +249,39 @@ const mockTauriAPI = {
             read: true,
             type: 'info'
           }
-        ]);
+        ], nextCursor: null });
+
+      case 'count_unread_notifications':
+        return Promise.resolve({ warning: 1, success: 1 });
+      
+      case 'get_notification_rules':
+        return Promise.resolve(mockRules);
+      
+      case 'save_notification_rule':
+        return Promise.resolve({
+          ...args?.rule,
+          id: args?.rule?.id || `rule-${Math.random().toString(16).slice(2, 14)}`
+        });
       
+      case 'get_webhook_deliveries':
+        return Promise.resolve([]);
+
+      case 'test_webhook':
+        return Promise.resolve({
+          notificationId: 'webhook-test-000000000000',
+          url: 'https://example.com/hook',
+          attempt: 1,
+          timestamp: Math.floor(Date.now() / 1000),
+          status: 200,
+          error: null,
+          durationMs: 42,
+          delivered: true
+        });
+
       case 'mark_notification_read':
       case 'mark_all_notifications_read':
       case 'delete_notification':
+      case 'delete_notification_rule':
         console.log(`[Mock Tauri] ${cmd}:`, args);
         return Promise.resolve(null);
       
@@ -178,15 +356,14 @@ const mockTauriAPI = {
 // Get Tauri API (real or mock)
 const getTauriAPI = () => {
   if (isTauri()) {
-    // Real Tauri environment
+    // Real Tauri environment (v2 globals, enabled by app.withGlobalTauri)
     // @ts-ignore - Tauri API will be available at runtime
-    const { invoke } = window.__TAURI__.tauri;
-    // @ts-ignore
-    const { listen, emit } = window.__TAURI__.event;
-    // @ts-ignore
-    const { appWindow } = window.__TAURI__.window;
-    // @ts-ignore
-    const { sendNotification, requestPermission } = window.__TAURI__.notification;
+    const tauri = window.__TAURI__;
+    const { invoke } = tauri.core;
+    const { listen, emit } = tauri.event;
+    const appWindow = tauri.webviewWindow?.getCurrentWebviewWindow();
+    // The notification plugin is optional, fall back to the web API
+    const { sendNotification, requestPermission } = tauri.notification ?? mockTauriAPI.notification;
 
     return {
       invoke,
@@ -242,7 +419,7 @@ export function useEnergyStats(deviceType: 'pc' | 'cellulare' | 'tablet' = 'pc')
         setStats(data as EnergyStats);
         setError(null);
       } catch (err) {
-        setError(err instanceof Error ? err.message : 'Failed to fetch energy stats');
+        setError(errorMessage(err, 'Failed to fetch energy stats'));
       } finally {
         setLoading(false);
       }
@@ -1,5 +1,5 @@
 import { useState, useEffect, useCallback } from 'react';
-import { useTauri } from './useTauri';
+import { useTauri, NotificationRule } from './useTauri';
 
 export interface Notification {
   id: string;
@@ -7,17 +7,53 @@ export interface Notification {
   message: string;
   timestamp: string;
   read: boolean;
-  type: 'info' | 'warning' | 'success';
+  type: 'info' | 'warning' | 'critical' | 'success';
 }
 
+// Filtri e pagine dell'inbox (see src-tauri/src/storage/mod.rs)
+export interface NotificationQuery {
+  type?: Notification['type'];
+  read?: boolean;
+  from?: number;             // Unix seconds, included
+  to?: number;               // Unix seconds, excluded
+  cursor?: string;           // nextCursor della pagina precedente
+  limit?: number;            // 50 by default, at most 500
+}
+
+interface NotificationPage {
+  notifications: any[];
+  nextCursor: string | null;
+}
+
+// Timestamp dal database (Unix timestamp in secondi)
+const formatNotification = (n: any): Notification => ({
+  ...n,
+  timestamp: formatTimestamp(new Date(n.timestamp * 1000))
+});
+
 /**
  * Hook per gestire notifiche in tempo reale dal backend Tauri
  */
 export function useTauriNotifications() {
   const { invoke, listen, isReady } = useTauri();
   const [notifications, setNotifications] = useState<Notification[]>([]);
+  const [nextCursor, setNextCursor] = useState<string | null>(null);
+  const [unreadByType, setUnreadByType] = useState<Record<string, number>>({});
   const [loading, setLoading] = useState(true);
 
+  // Conteggi dal database, che comprendono le pagine non caricate
+  const refreshUnread = useCallback(async () => {
+    try {
+      setUnreadByType(await invoke('count_unread_notifications') as Record<string, number>);
+    } catch (err) {
+      console.error('Failed to count unread notifications:', err);
+    }
+  }, [invoke]);
+
+  const markReadLocally = useCallback((id: string) => {
+    setNotifications(prev => prev.map(n => (n.id === id ? { ...n, read: true } : n)));
+  }, []);
+
   // Carica notifiche iniziali dal database
   useEffect(() => {
     if (!isReady) return;
@@ -25,13 +61,13 @@ export function useTauriNotifications() {
     const loadNotifications = async () => {
       try {
         setLoading(true);
-        const data = await invoke('get_notifications');
-        // Formatta i timestamp dal database (Unix timestamp in secondi)
-        const formattedNotifications = (data as any[]).map((n: any) => ({
-          ...n,
-          timestamp: formatTimestamp(new Date(n.timestamp * 1000))
-        }));
-        setNotifications(formattedNotifications);
+        const [page, counts] = await Promise.all([
+          invoke('get_notifications', { query: { limit: 20 } }) as Promise<NotificationPage>,
+          invoke('count_unread_notifications') as Promise<Record<string, number>>
+        ]);
+        setNotifications(page.notifications.map(formatNotification));
+        setNextCursor(page.nextCursor);
+        setUnreadByType(counts);
       } catch (err) {
         console.error('Failed to load notifications:', err);
         // Fallback a notifiche mock in caso di errore
@@ -61,6 +97,7 @@ export function useTauriNotifications() {
             type: 'info'
           }
         ]);
+        setUnreadByType({ warning: 1, success: 1 });
       } finally {
         setLoading(false);
       }
@@ -74,16 +111,21 @@ export function useTauriNotifications() {
     if (!isReady) return;
 
     let unlisten: (() => void) | undefined;
+    let unlistenAction: (() => void) | undefined;
 
     const setupListener = async () => {
       try {
         unlisten = await listen('new-notification', (event: any) => {
-          const notification = event.payload;
-          const formattedNotification: Notification = {
-            ...notification,
-            timestamp: formatTimestamp(new Date(notification.timestamp * 1000))
-          };
-          setNotifications(prev => [formattedNotification, ...prev]);
+          const notification = formatNotification(event.payload);
+          setNotifications(prev => [notification, ...prev]);
+          refreshUnread();
+        });
+        // "Vedi report" sul desktop: la notifica è stata vista
+        unlistenAction = await listen('notification-action', (event: any) => {
+          const { id, action } = event.payload;
+          if (action !== 'view') return;
+          invoke('mark_notification_read', { id }).then(refreshUnread, () => {});
+          markReadLocally(id);
         });
       } catch (err) {
         console.error('Failed to setup notification listener:', err);
@@ -94,61 +136,109 @@ export function useTauriNotifications() {
 
     return () => {
       if (unlisten) unlisten();
+      if (unlistenAction) unlistenAction();
     };
-  }, [isReady]);
+  }, [isReady, markReadLocally, refreshUnread]);
+
+  // Pagina successiva, più vecchia
+  const loadMore = useCallback(async () => {
+    if (!nextCursor) return;
+    try {
+      const page = await invoke('get_notifications', { query: { cursor: nextCursor, limit: 20 } }) as NotificationPage;
+      setNotifications(prev => [...prev, ...page.notifications.map(formatNotification)]);
+      setNextCursor(page.nextCursor);
+    } catch (err) {
+      console.error('Failed to load more notifications:', err);
+    }
+  }, [invoke, nextCursor]);
 
   // Segna notifica come letta
   const markAsRead = useCallback(async (id: string) => {
     try {
       await invoke('mark_notification_read', { id });
-      setNotifications(prev =>
-        prev.map(n => (n.id === id ? { ...n, read: true } : n))
-      );
     } catch (err) {
       console.error('Failed to mark notification as read:', err);
       // Aggiorna localmente anche se fallisce il backend
-      setNotifications(prev =>
-        prev.map(n => (n.id === id ? { ...n, read: true } : n))
-      );
     }
-  }, [invoke]);
+    markReadLocally(id);
+    refreshUnread();
+  }, [invoke, markReadLocally, refreshUnread]);
 
   // Segna tutte come lette
   const markAllAsRead = useCallback(async () => {
     try {
       await invoke('mark_all_notifications_read');
-      setNotifications(prev => prev.map(n => ({ ...n, read: true })));
     } catch (err) {
       console.error('Failed to mark all notifications as read:', err);
       // Aggiorna localmente anche se fallisce il backend
-      setNotifications(prev => prev.map(n => ({ ...n, read: true })));
     }
+    setNotifications(prev => prev.map(n => ({ ...n, read: true })));
+    setUnreadByType({});
   }, [invoke]);
 
   // Elimina notifica
   const deleteNotification = useCallback(async (id: string) => {
     try {
       await invoke('delete_notification', { id });
-      setNotifications(prev => prev.filter(n => n.id !== id));
     } catch (err) {
       console.error('Failed to delete notification:', err);
       // Elimina localmente anche se fallisce il backend
-      setNotifications(prev => prev.filter(n => n.id !== id));
     }
-  }, [invoke]);
+    setNotifications(prev => prev.filter(n => n.id !== id));
+    refreshUnread();
+  }, [invoke, refreshUnread]);
 
-  const unreadCount = notifications.filter(n => !n.read).length;
+  const unreadCount = Object.values(unreadByType).reduce((sum, count) => sum + count, 0);
 
   return {
     notifications,
     loading,
     unreadCount,
+    unreadByType,
+    hasMore: nextCursor !== null,
+    loadMore,
     markAsRead,
     markAllAsRead,
     deleteNotification
   };
 }
 
+/**
+ * Hook per leggere e modificare le regole di notifica
+ */
+export function useNotificationRules() {
+  const { invoke, isReady } = useTauri();
+  const [rules, setRules] = useState<NotificationRule[]>([]);
+  const [loading, setLoading] = useState(true);
+
+  useEffect(() => {
+    if (!isReady) return;
+
+    invoke('get_notification_rules')
+      .then((data: NotificationRule[]) => setRules(data))
+      .catch((err: unknown) => console.error('Failed to load notification rules:', err))
+      .finally(() => setLoading(false));
+  }, [isReady, invoke]);
+
+  // Crea o aggiorna una regola; il backend la valida e assegna l'id
+  const saveRule = useCallback(async (rule: NotificationRule) => {
+    const saved: NotificationRule = await invoke('save_notification_rule', { rule });
+    setRules(prev =>
+      prev.some(r => r.id === saved.id)
+        ? prev.map(r => (r.id === saved.id ? saved : r))
+        : [...prev, saved]
+    );
+    return saved;
+  }, [invoke]);
+
+  const deleteRule = useCallback(async (id: string) => {
+    await invoke('delete_notification_rule', { id });
+    setRules(prev => prev.filter(r => r.id !== id));
+  }, [invoke]);
+
+  return { rules, loading, saveRule, deleteRule };
+}
+
 // Helper per formattare il timestamp
 function formatTimestamp(date: Date): string {
   const now = new Date();
@@ -5,6 +5,10 @@ import { Switch } from '../components/ui/switch';
 import { Label } from '../components/ui/label';
 import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from '../components/ui/select';
 import { Badge } from '../components/ui/badge';
+import { NotificationRules } from '../components/notification-rules';
+import { DoNotDisturb } from '../components/do-not-disturb';
+import { WebhookSettings } from '../components/webhook-settings';
+import { useSettings, UserSettings } from '../hooks/useTauri';
 import { Settings, Bell, Moon, BarChart3, Shield, Palette } from 'lucide-react';
 
 interface UserData {
@@ -25,15 +29,13 @@ interface SettingsPanelProps {
 
 export function SettingsPanel({ isOpen, onClose, embedded = false, user, theme = 'light', onThemeChange }: SettingsPanelProps) {
   const [settings, setSettings] = useState({
-    dailyNotifications: true,
-    weeklyNotifications: true,
-    monthlyNotifications: true,
-    quietHours: true,
     autoStart: true,
     region: 'lombardy',
     notificationPosition: 'top-right'
   });
 
+  const { settings: userSettings, updateSettings } = useSettings();
+
   if (!isOpen && !embedded) return null;
 
   const handleSettingChange = (key: string, value: boolean | string) => {
@@ -102,44 +104,38 @@ export function SettingsPanel({ isOpen, onClose, embedded = false, user, theme =
             </div>
             
             <div className="space-y-3 ml-6">
-              <div className="flex items-center justify-between">
-                <Label htmlFor="daily-notifications">Daily pop-ups</Label>
-                <Switch
-                  id="daily-notifications"
-                  checked={settings.dailyNotifications}
-                  onCheckedChange={(checked) => handleSettingChange('dailyNotifications', checked)}
-                />
-              </div>
-              
-              <div className="flex items-center justify-between">
-                <Label htmlFor="weekly-notifications">Weekly summaries</Label>
-                <Switch
-                  id="weekly-notifications"
-                  checked={settings.weeklyNotifications}
-                  onCheckedChange={(checked) => handleSettingChange('weeklyNotifications', checked)}
-                />
+              <NotificationRules />
+
+              <div className="space-y-2">
+                <Label>Language</Label>
+                <Select
+                  value={userSettings?.locale ?? 'it'}
+                  onValueChange={(locale) => updateSettings({ locale: locale as UserSettings['locale'] })}
+                >
+                  <SelectTrigger>
+                    <SelectValue />
+                  </SelectTrigger>
+                  <SelectContent>
+                    <SelectItem value="it">Italiano</SelectItem>
+                    <SelectItem value="en">English</SelectItem>
+                  </SelectContent>
+                </Select>
+                <p className="text-xs text-muted-foreground">Summaries, numbers and dates in notifications</p>
               </div>
               
-              <div className="flex items-center justify-between">
-                <Label htmlFor="monthly-notifications">Monthly questionnaire</Label>
-                <Switch
-                  id="monthly-notifications"
-                  checked={settings.monthlyNotifications}
-                  onCheckedChange={(checked) => handleSettingChange('monthlyNotifications', checked)}
+              {userSettings && (
+                <DoNotDisturb
+                  value={userSettings.doNotDisturb}
+                  onChange={(doNotDisturb) => updateSettings({ doNotDisturb })}
                 />
-              </div>
-              
-              <div className="flex items-center justify-between">
-                <div>
-                  <Label htmlFor="quiet-hours">Quiet hours (19:00-08:00)</Label>
-                  <p className="text-xs text-muted-foreground">No notifications during these hours</p>
-                </div>
-                <Switch
-                  id="quiet-hours"
-                  checked={settings.quietHours}
-                  onCheckedChange={(checked) => handleSettingChange('quietHours', checked)}
+              )}
+
+              {userSettings && (
+                <WebhookSettings
+                  value={userSettings.webhook}
+                  onChange={(webhook) => updateSettings({ webhook })}
                 />
-              </div>
+              )}
             </div>
           </div>
 
@@ -0,0 +1,92 @@
+// Submit latency of the write-behind writer
+//
+// Simulates the monitoring loop queueing readings while the database is
+// idle and while another connection holds the write lock. `submit` must stay
+// in the microsecond range in both cases: a slow or locked disk shows up as
+// backpressure in the writer status, never as a stalled tick.
+//
+//     cargo bench --bench writer
+use rusqlite::Connection;
+use std::time::{Duration, Instant};
+use vera_lib::storage::writer::ReadingWriter;
+
+const SAMPLES: usize = 5_000;
+/// The loop ticks every second at most, anything near this would be visible
+const MAX_SUBMIT: Duration = Duration::from_millis(5);
+
+fn setup(path: &std::path::Path) {
+    let conn = Connection::open(path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE energy_readings (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL,
+            device_type TEXT NOT NULL,
+            power_watts REAL NOT NULL,
+            kwh_increment REAL NOT NULL
+        );
+        CREATE TABLE energy_gaps (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            start_timestamp INTEGER NOT NULL,
+            end_timestamp INTEGER NOT NULL,
+            reason TEXT NOT NULL
+        );",
+    )
+    .unwrap();
+}
+
+fn measure(label: &str, writer: &ReadingWriter) -> Duration {
+    let mut latencies = Vec::with_capacity(SAMPLES);
+    let mut rejected = 0;
+
+    for i in 0..SAMPLES {
+        let started = Instant::now();
+        if writer.queue_reading("pc", 60.0 + (i % 40) as f64, 0.0001).is_err() {
+            rejected += 1;
+        }
+        latencies.push(started.elapsed());
+    }
+
+    latencies.sort();
+    let percentile = |p: usize| latencies[(latencies.len() * p / 100).min(latencies.len() - 1)];
+    let max = *latencies.last().unwrap();
+    let status = writer.status();
+
+    println!(
+        "{:<10} p50 {:>8.2?}  p99 {:>8.2?}  max {:>8.2?}  rejected {:>5}  queue {:>4}/{}",
+        label,
+        percentile(50),
+        percentile(99),
+        max,
+        rejected,
+        status.queue_depth,
+        status.capacity,
+    );
+    max
+}
+
+fn main() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("vera_bench.db");
+    setup(&path);
+    let writer = ReadingWriter::open(&path).unwrap();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let idle = measure("idle", &writer);
+    runtime.block_on(writer.flush()).unwrap();
+
+    // Another connection holds the write lock for the whole run
+    let blocker = Connection::open(&path).unwrap();
+    blocker.execute_batch("BEGIN IMMEDIATE").unwrap();
+    let locked = measure("locked", &writer);
+    blocker.execute_batch("COMMIT").unwrap();
+    runtime.block_on(writer.flush()).unwrap();
+
+    let status = writer.status();
+    println!(
+        "written {}  dropped {}  batches {}  last batch {:?} ms",
+        status.written, status.dropped, status.batches, status.last_batch_ms
+    );
+
+    assert!(idle < MAX_SUBMIT, "submit took {:?} with an idle database", idle);
+    assert!(locked < MAX_SUBMIT, "submit took {:?} with a locked database", locked);
+}
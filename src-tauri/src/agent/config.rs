@@ -0,0 +1,198 @@
+// vera-agent configuration file
+//
+// TOML, every key optional:
+//
+//     database = "/var/lib/vera/vera_data.db"
+//     interval_secs = 5
+//     notifications = true
+//     log_level = "info"
+//     rollup_after_days = 30
+//     retention_days = 0
+//
+//     [api]
+//     enabled = true
+//     bind = "127.0.0.1:7878"
+//     token = "at least 16 characters"
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use super::logging::Level;
+use crate::api::{ApiConfig, DEFAULT_BIND};
+use crate::energy::service::{DEFAULT_INTERVAL, MAX_INTERVAL_SECS, MIN_INTERVAL_SECS};
+use crate::error::{Result, VeraError};
+
+pub const DEFAULT_CONFIG_PATH: &str = "/etc/vera/agent.toml";
+const DEFAULT_STATE_DIR: &str = "/var/lib/vera";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AgentConfig {
+    pub database: PathBuf,
+    pub interval_secs: u64,
+    /// Run the daily/weekly/monthly summaries
+    pub notifications: bool,
+    pub log_level: Level,
+    /// Readings older than this are merged into one per hour; 0 keeps them
+    pub rollup_after_days: u32,
+    /// Readings older than this are deleted; 0 keeps them forever
+    pub retention_days: u32,
+    pub api: ApiSection,
+}
+
+/// Local REST API; the agent has no settings screen, so the token is set here
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiSection {
+    pub enabled: bool,
+    pub bind: String,
+    pub token: String,
+}
+
+impl Default for ApiSection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: DEFAULT_BIND.to_string(),
+            token: String::new(),
+        }
+    }
+}
+
+impl ApiSection {
+    /// `None` when disabled
+    pub fn config(&self) -> Result<Option<ApiConfig>> {
+        if !self.enabled {
+            return Ok(None);
+        }
+        ApiConfig::new(&self.bind, &self.token)
+            .map(Some)
+            .map_err(|e| VeraError::Config(format!("[api] {}", e)))
+    }
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        // systemd sets STATE_DIRECTORY when the unit has StateDirectory=
+        let state_dir = std::env::var_os("STATE_DIRECTORY")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_STATE_DIR));
+
+        Self {
+            database: state_dir.join("vera_data.db"),
+            interval_secs: DEFAULT_INTERVAL.as_secs(),
+            notifications: true,
+            log_level: Level::Info,
+            rollup_after_days: 30,
+            retention_days: 0,
+            api: ApiSection::default(),
+        }
+    }
+}
+
+impl AgentConfig {
+    /// Read `path`, or the default location if it exists. Without a file
+    /// the built-in defaults are used.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path, true),
+            None => (Path::new(DEFAULT_CONFIG_PATH), false),
+        };
+
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text)
+                .map_err(|e| VeraError::Config(format!("{}: {}", path.display(), e))),
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(VeraError::Config(format!("{}: {}", path.display(), e))),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let config: Self = toml::from_str(text).map_err(|e| VeraError::Config(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// The settings in effect, for `--check`; the API token is never shown
+    pub fn summary(&self) -> String {
+        let api = if self.api.enabled {
+            format!("enabled on {}, token set", self.api.bind)
+        } else {
+            "disabled".to_string()
+        };
+        let log_level = format!("{:?}", self.log_level).to_lowercase();
+        format!(
+            "Configuration OK\n\
+             database           {}\n\
+             interval_secs      {}\n\
+             notifications      {}\n\
+             log_level          {}\n\
+             rollup_after_days  {}\n\
+             retention_days     {}\n\
+             api                {}",
+            self.database.display(),
+            self.interval_secs,
+            self.notifications,
+            log_level,
+            self.rollup_after_days,
+            self.retention_days,
+            api
+        )
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !(MIN_INTERVAL_SECS..=MAX_INTERVAL_SECS).contains(&self.interval_secs) {
+            return Err(VeraError::Config(format!(
+                "interval_secs must be between {} and {}",
+                MIN_INTERVAL_SECS, MAX_INTERVAL_SECS
+            )));
+        }
+        if self.database.as_os_str().is_empty() {
+            return Err(VeraError::Config("database must not be empty".to_string()));
+        }
+        self.api.config()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_file_keeps_defaults() {
+        let config = AgentConfig::parse("interval_secs = 10\nlog_level = \"debug\"\n").unwrap();
+        assert_eq!(config.interval_secs, 10);
+        assert_eq!(config.log_level, Level::Debug);
+        assert!(config.notifications);
+        assert_eq!((config.rollup_after_days, config.retention_days), (30, 0));
+        assert_eq!(config.database, AgentConfig::default().database);
+        assert_eq!(config.api.config().unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_files_are_config_errors() {
+        for text in [
+            "interval_secs = 0",
+            "database = \"\"",
+            "intervall = 5",
+            "log_level = \"loud\"",
+            "retention_days = -1",
+            "[api]\nenabled = true\ntoken = \"short\"",
+            "[api]\nenabled = true\nbind = \"localhost\"\ntoken = \"0123456789abcdef\"",
+        ] {
+            let error = AgentConfig::parse(text).unwrap_err();
+            assert_eq!(error.code(), "CONFIG_ERROR", "{}", text);
+        }
+
+        let error = AgentConfig::load(Some(Path::new("/nonexistent/vera.toml"))).unwrap_err();
+        assert_eq!(error.code(), "CONFIG_ERROR");
+    }
+
+    #[test]
+    fn test_summary_hides_the_token() {
+        let config = AgentConfig::parse("[api]\nenabled = true\ntoken = \"0123456789abcdef\"").unwrap();
+        let summary = config.summary();
+        assert!(summary.contains("api                enabled on 127.0.0.1:7878, token set"), "{}", summary);
+        assert!(!summary.contains("0123456789abcdef"));
+    }
+}
@@ -0,0 +1,87 @@
+// Line-oriented logging for the agent
+//
+// Under systemd stdout goes to the journal, which reads the `<N>` syslog
+// priority prefix on each line; in a terminal the level is written instead.
+use serde::Deserialize;
+use std::fmt::Display;
+use std::io::Write;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Error = 3,
+    Warn = 4,
+    Info = 6,
+    Debug = 7,
+}
+
+impl Level {
+    fn label(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        }
+    }
+}
+
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+pub fn set_level(level: Level) {
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// True when stdout is connected to the journal (systemd sets JOURNAL_STREAM)
+fn to_journal() -> bool {
+    std::env::var_os("JOURNAL_STREAM").is_some()
+}
+
+fn format_line(level: Level, message: &dyn Display, journal: bool) -> String {
+    if journal {
+        format!("<{}>{}", level as u8, message)
+    } else {
+        format!(
+            "{} {:<5} {}",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+            level.label(),
+            message
+        )
+    }
+}
+
+pub fn log(level: Level, message: impl Display) {
+    if level as u8 > MAX_LEVEL.load(Ordering::Relaxed) {
+        return;
+    }
+    let line = format_line(level, &message, to_journal());
+    writeln!(std::io::stdout().lock(), "{}", line).ok();
+}
+
+pub fn error(message: impl Display) {
+    log(Level::Error, message);
+}
+
+pub fn warn(message: impl Display) {
+    log(Level::Warn, message);
+}
+
+pub fn info(message: impl Display) {
+    log(Level::Info, message);
+}
+
+pub fn debug(message: impl Display) {
+    log(Level::Debug, message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_lines_carry_priority() {
+        assert_eq!(format_line(Level::Warn, &"queue is full", true), "<4>queue is full");
+        assert!(format_line(Level::Info, &"started", false).ends_with(" INFO  started"));
+    }
+}
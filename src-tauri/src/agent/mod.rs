@@ -0,0 +1,184 @@
+// Headless agent: monitoring, storage and notifications without a window
+//
+// Runs the same background tasks as the desktop app against a database the
+// GUI can attach to read-only (VERA_AGENT_DATABASE). Meant to run as a
+// systemd `Type=notify` service, see packaging/vera-agent.service.
+pub mod config;
+pub mod logging;
+pub mod systemd;
+
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::api::ApiServer;
+use crate::energy::service::MonitoringService;
+use crate::error::Result;
+use crate::events;
+use crate::notifications;
+use crate::storage;
+use crate::storage::writer::ReadingWriter;
+use config::AgentConfig;
+
+/// How often the unit status line shown by `systemctl status` is refreshed
+const STATUS_INTERVAL: Duration = Duration::from_secs(60);
+/// How often old readings are rolled up and pruned
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Run until SIGTERM or Ctrl-C, then write pending readings and return
+pub async fn run(config: AgentConfig) -> Result<()> {
+    logging::set_level(config.log_level);
+
+    if let Some(dir) = config.database.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    storage::db::set_database_path(config.database.clone());
+    storage::db::init_database().await?;
+    let writer = ReadingWriter::open(&config.database)?;
+
+    tokio::spawn(log_events());
+
+    let monitoring = MonitoringService::new(Duration::from_secs(config.interval_secs));
+    monitoring.start(&writer)?;
+    if config.notifications {
+        tokio::spawn(notifications::scheduler::start());
+        tokio::spawn(notifications::webhook::start());
+        // A user service still reaches the session's notification daemon
+        tokio::spawn(notifications::desktop::start(notifications::desktop::Headless));
+    }
+
+    if config.rollup_after_days > 0 || config.retention_days > 0 {
+        tokio::spawn(maintain_readings(config.rollup_after_days, config.retention_days));
+    }
+
+    let api = ApiServer::default();
+    api.apply(config.api.config()?).await?;
+    if let Some(addr) = api.local_addr().await {
+        logging::info(format!("REST API listening on http://{}/api", addr));
+    }
+
+    logging::info(format!(
+        "Monitoring every {}s, database {}",
+        config.interval_secs,
+        config.database.display()
+    ));
+    systemd::ready("Monitoring");
+
+    if let Some(period) = systemd::watchdog_interval() {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(period);
+            loop {
+                ticker.tick().await;
+                systemd::watchdog();
+            }
+        });
+    }
+
+    let mut status_ticker = tokio::time::interval(STATUS_INTERVAL);
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            _ = status_ticker.tick() => report_status(&monitoring, &writer),
+            _ = &mut shutdown => break,
+        }
+    }
+
+    logging::info("Shutting down");
+    systemd::stopping();
+    api.shutdown().await;
+    monitoring.shutdown().await;
+    writer.flush().await?;
+    Ok(())
+}
+
+fn report_status(monitoring: &MonitoringService, writer: &ReadingWriter) {
+    let storage = writer.status();
+    if storage.backpressure {
+        logging::warn(format!(
+            "Storage is falling behind: {}/{} readings queued",
+            storage.queue_depth, storage.capacity
+        ));
+    }
+
+    match monitoring.status() {
+        Ok(status) => {
+            let line = format!(
+                "Monitoring, {} samples, {} readings written, {} dropped",
+                status.sample_count, storage.written, storage.dropped
+            );
+            if let Some(error) = status.last_error {
+                logging::warn(format!("Last monitoring error: {}", error));
+            }
+            logging::debug(&line);
+            systemd::status(&line);
+        }
+        Err(e) => logging::error(format!("Failed to read monitoring status: {}", e)),
+    }
+}
+
+/// Prune, then roll up old readings, on start and hourly after that
+async fn maintain_readings(rollup_after_days: u32, retention_days: u32) {
+    let mut ticker = tokio::time::interval(MAINTENANCE_INTERVAL);
+    loop {
+        ticker.tick().await;
+        if retention_days > 0 {
+            match storage::db::cleanup_old_data(retention_days.into()).await {
+                Ok(0) => {}
+                Ok(deleted) => logging::info(format!("Deleted {} readings older than {} days", deleted, retention_days)),
+                Err(e) => logging::error(format!("Failed to delete old readings: {}", e)),
+            }
+        }
+        if rollup_after_days > 0 {
+            match storage::db::rollup_old_data(rollup_after_days.into()).await {
+                Ok(0) => {}
+                Ok(merged) => logging::info(format!(
+                    "Merged readings older than {} days into hourly ones, {} rows fewer",
+                    rollup_after_days, merged
+                )),
+                Err(e) => logging::error(format!("Failed to roll up old readings: {}", e)),
+            }
+        }
+    }
+}
+
+/// Notifications go to the log; there is no window to show them in
+async fn log_events() {
+    let mut events = events::subscribe();
+    loop {
+        match events.recv().await {
+            Ok(event) if event.name == "new-notification" => {
+                let title = event.payload["title"].as_str().unwrap_or_default();
+                let message = event.payload["message"].as_str().unwrap_or_default();
+                logging::info(format!("Notification: {} - {}", title, message));
+            }
+            Ok(event) => logging::debug(format!("{}: {}", event.name, event.payload)),
+            Err(RecvError::Lagged(skipped)) => {
+                logging::debug(format!("Skipped {} events", skipped));
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn shutdown_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut terminate) => {
+            tokio::select! {
+                _ = terminate.recv() => {}
+                _ = tokio::signal::ctrl_c() => {}
+            }
+        }
+        Err(e) => {
+            logging::warn(format!("Cannot listen for SIGTERM: {}", e));
+            tokio::signal::ctrl_c().await.ok();
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn shutdown_signal() {
+    tokio::signal::ctrl_c().await.ok();
+}
@@ -0,0 +1,73 @@
+// systemd service notifications (Type=notify, WatchdogSec=)
+//
+// All calls are no-ops when the agent is not started by systemd, or on
+// platforms without it.
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use sd_notify::NotifyState;
+    use std::time::Duration;
+
+    fn send(state: &[NotifyState]) {
+        if let Err(e) = sd_notify::notify(false, state) {
+            super::super::logging::warn(format!("Failed to notify systemd: {}", e));
+        }
+    }
+
+    pub fn ready(status: &str) {
+        send(&[NotifyState::Ready, NotifyState::Status(status)]);
+    }
+
+    pub fn status(status: &str) {
+        send(&[NotifyState::Status(status)]);
+    }
+
+    pub fn stopping() {
+        send(&[NotifyState::Stopping]);
+    }
+
+    pub fn watchdog() {
+        send(&[NotifyState::Watchdog]);
+    }
+
+    pub fn watchdog_timeout() -> Option<Duration> {
+        let mut usec = 0;
+        sd_notify::watchdog_enabled(false, &mut usec).then(|| Duration::from_micros(usec))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use std::time::Duration;
+
+    pub fn ready(_status: &str) {}
+    pub fn status(_status: &str) {}
+    pub fn stopping() {}
+    pub fn watchdog() {}
+    pub fn watchdog_timeout() -> Option<Duration> {
+        None
+    }
+}
+
+/// Startup finished: systemd considers the unit active from here
+pub fn ready(status: &str) {
+    imp::ready(status);
+}
+
+pub fn status(status: &str) {
+    imp::status(status);
+}
+
+pub fn stopping() {
+    imp::stopping();
+}
+
+pub fn watchdog() {
+    imp::watchdog();
+}
+
+/// How often to ping the watchdog, if the unit has WatchdogSec= set
+pub fn watchdog_interval() -> Option<Duration> {
+    imp::watchdog_timeout().map(|timeout| timeout / 2)
+}
@@ -0,0 +1,250 @@
+// Route handlers for the local REST API
+//
+// Energy figures follow docs/API.md: power in W, energy in Wh, carbon
+// footprint in kg CO2. Readings come from the local store only.
+use axum::extract::rejection::QueryRejection;
+use axum::extract::{Query, State};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use super::{ok, ApiError, ApiState};
+use crate::energy::calculator;
+use crate::error::{Result, VeraError};
+use crate::storage::{db, EnergyReading};
+use crate::system_monitor::SystemMetrics;
+use crate::utils::parse;
+
+type ApiResult = std::result::Result<Json<serde_json::Value>, ApiError>;
+
+const DEFAULT_PAGE_SIZE: u64 = 100;
+const MAX_PAGE_SIZE: u64 = 1000;
+const DEFAULT_BATCH_SIZE: u64 = 1000;
+const MAX_BATCH_SIZE: u64 = 10_000;
+
+/// Turn serde errors on the query string into `INVALID_ARGUMENT`
+pub(super) fn query<T>(query: std::result::Result<Query<T>, QueryRejection>) -> std::result::Result<T, ApiError> {
+    query
+        .map(|Query(value)| value)
+        .map_err(|rejection| VeraError::invalid_argument("query", rejection.body_text()).into())
+}
+
+fn iso(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|time| time.to_rfc3339())
+        .unwrap_or_default()
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiReading {
+    device_id: String,
+    timestamp: String,
+    power_consumption: f64,   // W
+    energy: f64,              // Wh since the previous reading
+    carbon_footprint: f64,    // kg CO2
+}
+
+impl ApiReading {
+    fn new(reading: EnergyReading, region: &str) -> Self {
+        Self {
+            device_id: reading.device_type,
+            timestamp: iso(reading.timestamp),
+            power_consumption: reading.power_watts,
+            energy: reading.kwh_increment * 1000.0,
+            carbon_footprint: calculator::kwh_to_co2(reading.kwh_increment, region),
+        }
+    }
+}
+
+/// Region for carbon figures; national average if settings are unreadable
+async fn region() -> String {
+    db::load_settings()
+        .await
+        .map(|settings| settings.region)
+        .unwrap_or_else(|_| "national".to_string())
+}
+
+fn page_size(value: Option<u64>, default: u64, max: u64) -> std::result::Result<u64, ApiError> {
+    match value {
+        None => Ok(default),
+        Some(limit) if (1..=max).contains(&limit) => Ok(limit),
+        Some(_) => Err(VeraError::invalid_argument("limit", format!("must be between 1 and {}", max)).into()),
+    }
+}
+
+pub(super) async fn health() -> Json<serde_json::Value> {
+    ok(serde_json::json!({
+        "status": "ok",
+        "version": env!("CARGO_PKG_VERSION"),
+    }))
+}
+
+#[derive(Deserialize)]
+pub(super) struct DeviceQuery {
+    device: Option<String>,
+}
+
+/// GET /api/energy/data: latest reading
+pub(super) async fn energy_data(params: std::result::Result<Query<DeviceQuery>, QueryRejection>) -> ApiResult {
+    let params = query(params)?;
+    let reading = db::get_latest_reading(params.device.as_deref())
+        .await?
+        .ok_or_else(|| VeraError::NotFound("reading".to_string()))?;
+
+    Ok(ok(ApiReading::new(reading, &region().await)))
+}
+
+#[derive(Deserialize)]
+pub(super) struct BatchQuery {
+    from: Option<String>,
+    to: Option<String>,
+    device: Option<String>,
+    limit: Option<u64>,
+}
+
+/// GET /api/energy/batch: raw readings between two times, oldest first
+pub(super) async fn energy_batch(params: std::result::Result<Query<BatchQuery>, QueryRejection>) -> ApiResult {
+    let params = query(params)?;
+    let now = chrono::Local::now();
+    let from = parse::parse_time(params.from.as_deref().unwrap_or("1h"), now)
+        .map_err(|e| VeraError::invalid_argument("from", e))?;
+    let to = parse::parse_time(params.to.as_deref().unwrap_or("now"), now)
+        .map_err(|e| VeraError::invalid_argument("to", e))?;
+    if from > to {
+        return Err(VeraError::invalid_argument("from", "must not be after `to`").into());
+    }
+    let limit = page_size(params.limit, DEFAULT_BATCH_SIZE, MAX_BATCH_SIZE)?;
+
+    // `to` is inclusive, like `now`
+    let readings = db::get_readings_page(from, to + 1, params.device.as_deref(), limit, 0).await?;
+    let region = region().await;
+    let readings: Vec<_> = readings
+        .into_iter()
+        .map(|reading| ApiReading::new(reading, &region))
+        .collect();
+
+    Ok(ok(serde_json::json!({
+        "count": readings.len(),
+        "readings": readings,
+    })))
+}
+
+#[derive(Deserialize)]
+pub(super) struct HistoryQuery {
+    timeframe: Option<String>,
+    device: Option<String>,
+    page: Option<u64>,
+    limit: Option<u64>,
+}
+
+/// GET /api/energy/history: paginated readings over the last `timeframe`
+pub(super) async fn energy_history(params: std::result::Result<Query<HistoryQuery>, QueryRejection>) -> ApiResult {
+    let params = query(params)?;
+    let timeframe = parse::parse_duration(params.timeframe.as_deref().unwrap_or("24h"))
+        .map_err(|e| VeraError::invalid_argument("timeframe", e))?;
+    let page = params.page.unwrap_or(1);
+    if page == 0 {
+        return Err(VeraError::invalid_argument("page", "pages start at 1").into());
+    }
+    let limit = page_size(params.limit, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE)?;
+
+    let to = chrono::Local::now().timestamp() + 1;
+    let from = to - 1 - timeframe;
+    let device = params.device.as_deref();
+    let summary = db::get_summary(from, to, device).await?;
+    let offset = (page - 1).saturating_mul(limit);
+    let readings = db::get_readings_page(from, to, device, limit, offset).await?;
+
+    let region = region().await;
+    let readings: Vec<_> = readings
+        .into_iter()
+        .map(|reading| ApiReading::new(reading, &region))
+        .collect();
+
+    Ok(ok(serde_json::json!({
+        "readings": readings,
+        "pagination": {
+            "page": page,
+            "limit": limit,
+            "total": summary.count,
+            "pages": summary.count.div_ceil(limit),
+        },
+        "summary": {
+            "totalConsumption": summary.kwh * 1000.0,
+            "averageConsumption": summary.avg_power,
+            "peakConsumption": summary.max_power,
+            "totalCarbonFootprint": calculator::kwh_to_co2(summary.kwh, &region),
+        },
+    })))
+}
+
+#[derive(Deserialize)]
+pub(super) struct StatsQuery {
+    period: Option<String>,
+    device: Option<String>,
+}
+
+/// GET /api/energy/stats: current day/week/month/year against the
+/// window of the same length just before it
+pub(super) async fn energy_stats(params: std::result::Result<Query<StatsQuery>, QueryRejection>) -> ApiResult {
+    let params = query(params)?;
+    let period = params.period.as_deref().unwrap_or("day");
+    let (from, to) = db::period_range(period)?;
+    let device = params.device.as_deref();
+
+    let current = db::get_summary(from, to + 1, device).await?;
+    let previous = db::get_summary(from - (to - from), from, device).await?;
+
+    let region = region().await;
+    let carbon = calculator::kwh_to_co2(current.kwh, &region);
+    let previous_carbon = calculator::kwh_to_co2(previous.kwh, &region);
+    let hours = ((to - from) as f64 / 3600.0).max(1.0);
+    let reduction = if previous_carbon > 0.0 {
+        (previous_carbon - carbon) / previous_carbon
+    } else {
+        0.0
+    };
+
+    Ok(ok(serde_json::json!({
+        "period": period,
+        "from": iso(from),
+        "to": iso(to),
+        "consumption": {
+            "total": current.kwh * 1000.0,
+            "average": current.avg_power,
+            "peak": current.max_power,
+            "minimum": current.min_power,
+        },
+        "carbonFootprint": {
+            "total": carbon,
+            "average": carbon / hours,
+            "reduction": reduction,
+        },
+        "waterBottles": calculator::kwh_to_bottles(current.kwh),
+        "trend": calculator::calculate_trend(current.kwh, previous.kwh),
+    })))
+}
+
+/// GET /api/system/metrics: a fresh sample from this machine
+pub(super) async fn system_metrics(State(state): State<Arc<ApiState>>) -> ApiResult {
+    // Sensor reads touch sysfs and can block
+    let metrics = tokio::task::spawn_blocking(move || {
+        state
+            .monitor
+            .lock()
+            .map(|mut monitor| monitor.get_metrics())
+            .map_err(|_| VeraError::Internal("system monitor lock poisoned".to_string()))
+    })
+    .await
+    .map_err(|e| VeraError::Internal(e.to_string()))??;
+
+    Ok(ok(serde_json::json!({ "metrics": metrics_json(metrics)? })))
+}
+
+/// Metrics as JSON with the time they were sampled
+pub(super) fn metrics_json(metrics: SystemMetrics) -> Result<serde_json::Value> {
+    let mut metrics = serde_json::to_value(metrics).map_err(|e| VeraError::Internal(e.to_string()))?;
+    metrics["timestamp"] = chrono::Utc::now().to_rfc3339().into();
+    Ok(metrics)
+}
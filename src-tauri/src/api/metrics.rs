@@ -0,0 +1,361 @@
+// Prometheus / OpenMetrics exporter
+//
+// GET /metrics renders a fresh `SystemMonitor` sample and the stored energy
+// totals in the OpenMetrics text format. Every sample carries the `device`
+// label of the recorded readings and the `region` label from the settings.
+use axum::extract::State;
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Instant;
+
+use super::{ApiError, ApiState};
+use crate::energy::calculator;
+use crate::energy::monitor::DEVICE_TYPE;
+use crate::error::VeraError;
+use crate::storage::{db, UserSettings};
+use crate::system_monitor::{cpu, ActiveApplication, SystemMetrics};
+
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// CPU power attributed to each application category, integrated between scrapes
+#[derive(Debug, Default)]
+pub(crate) struct AppEnergy {
+    last_update: Option<Instant>,
+    categories: BTreeMap<String, CategoryEnergy>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct CategoryEnergy {
+    watts: f64,
+    kwh: f64,
+}
+
+impl AppEnergy {
+    /// Split `cpu_watts` by each application's share of total CPU capacity.
+    /// Process CPU usage covers the time since the previous call, so the
+    /// power is counted over the same interval.
+    fn update(&mut self, apps: &[ActiveApplication], cpu_watts: f64, cores: usize, now: Instant) {
+        let capacity = 100.0 * cores.max(1) as f64;
+        let elapsed = self
+            .last_update
+            .map(|last| now.duration_since(last).as_secs_f64())
+            .unwrap_or(0.0);
+        self.last_update = Some(now);
+
+        for energy in self.categories.values_mut() {
+            energy.watts = 0.0;
+        }
+        for app in apps {
+            let share = (app.cpu_usage as f64 / capacity).clamp(0.0, 1.0);
+            self.categories.entry(app.category.clone()).or_default().watts += cpu_watts * share;
+        }
+        for energy in self.categories.values_mut() {
+            energy.kwh += calculator::watts_to_kwh(energy.watts, elapsed);
+        }
+    }
+}
+
+/// Everything one scrape reports
+struct Snapshot {
+    device: String,
+    region: String,
+    metrics: SystemMetrics,
+    /// Power of the last reading stored by the energy pipeline
+    recorded_watts: Option<f64>,
+    total_kwh: f64,
+    categories: Vec<(String, CategoryEnergy)>,
+}
+
+pub(super) async fn metrics(State(state): State<Arc<ApiState>>) -> std::result::Result<Response, ApiError> {
+    let settings = db::load_settings().await.unwrap_or_else(|_| UserSettings::default());
+    // The pipeline records every reading under one device
+    let device = DEVICE_TYPE.to_string();
+    let total_kwh = db::get_summary(0, i64::MAX, Some(&device)).await?.kwh;
+    let recorded_watts = db::get_latest_reading(Some(&device)).await?.map(|r| r.power_watts);
+
+    // Process and sensor reads touch procfs/sysfs and can block
+    let (metrics, categories) = tokio::task::spawn_blocking(move || {
+        let mut monitor = state.monitor.lock()?;
+        let metrics = monitor.get_metrics();
+        let apps = monitor.get_top_applications(usize::MAX);
+        drop(monitor);
+
+        let cpu_watts = cpu::estimate_cpu_power(&metrics.cpu.per_core)
+            .or_else(|| metrics.power.power_draw.get())
+            .unwrap_or(0.0) as f64;
+        let cores = metrics.cpu.cores.get().unwrap_or(1);
+
+        let mut app_energy = state.app_energy.lock()?;
+        app_energy.update(&apps, cpu_watts, cores, Instant::now());
+        let categories = app_energy
+            .categories
+            .iter()
+            .map(|(category, energy)| (category.clone(), *energy))
+            .collect();
+        Ok::<_, VeraError>((metrics, categories))
+    })
+    .await
+    .map_err(|e| VeraError::Internal(e.to_string()))??;
+
+    let body = render(&Snapshot {
+        device,
+        region: settings.region,
+        metrics,
+        recorded_watts,
+        total_kwh,
+        categories,
+    });
+    Ok(([(header::CONTENT_TYPE, CONTENT_TYPE)], body).into_response())
+}
+
+/// Writes metric families in order; labels common to every sample are prepended
+struct Encoder {
+    out: String,
+    common: Vec<(&'static str, String)>,
+}
+
+impl Encoder {
+    fn family(&mut self, name: &str, kind: &str, unit: Option<&str>, help: &str) {
+        writeln!(self.out, "# TYPE {} {}", name, kind).ok();
+        if let Some(unit) = unit {
+            writeln!(self.out, "# UNIT {} {}", name, unit).ok();
+        }
+        writeln!(self.out, "# HELP {} {}", name, escape(help, false)).ok();
+    }
+
+    /// Non-finite values are skipped: an absent series reads better than NaN
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+
+        let labels: Vec<String> = self
+            .common
+            .iter()
+            .map(|(key, value)| (*key, value.as_str()))
+            .chain(labels.iter().copied())
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape(value, true)))
+            .collect();
+        writeln!(self.out, "{}{{{}}} {}", name, labels.join(","), value).ok();
+    }
+
+    fn finish(mut self) -> String {
+        self.out.push_str("# EOF\n");
+        self.out
+    }
+}
+
+/// Escape backslash and newline, plus double quotes inside label values
+fn escape(text: &str, quotes: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '"' if quotes => escaped.push_str("\\\""),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn render(snapshot: &Snapshot) -> String {
+    let metrics = &snapshot.metrics;
+    let mut encoder = Encoder {
+        out: String::new(),
+        common: vec![
+            ("device", snapshot.device.clone()),
+            ("region", snapshot.region.clone()),
+        ],
+    };
+
+    encoder.family("vera_build", "info", None, "VERA version.");
+    encoder.sample("vera_build_info", &[("version", env!("CARGO_PKG_VERSION"))], 1.0);
+
+    encoder.family("vera_power_watts", "gauge", Some("watts"), "Current power draw per source.");
+    if let Some(watts) = metrics.power.power_draw.get() {
+        encoder.sample("vera_power_watts", &[("source", "system")], watts as f64);
+    }
+    if let Some(watts) = cpu::estimate_cpu_power(&metrics.cpu.per_core) {
+        encoder.sample("vera_power_watts", &[("source", "cpu")], watts as f64);
+    }
+    for gpu in &metrics.gpus {
+        if let Some(watts) = gpu.power_draw.get() {
+            encoder.sample("vera_power_watts", &[("source", "gpu"), ("gpu", &gpu.id)], watts as f64);
+        }
+    }
+    if let Some(watts) = snapshot.recorded_watts {
+        encoder.sample("vera_power_watts", &[("source", "recorded")], watts);
+    }
+
+    let co2_kg = calculator::kwh_to_co2(snapshot.total_kwh, &snapshot.region);
+    encoder.family(
+        "vera_energy_kilowatt_hours",
+        "counter",
+        Some("kilowatt_hours"),
+        "Energy recorded by the monitoring pipeline.",
+    );
+    encoder.sample("vera_energy_kilowatt_hours_total", &[], snapshot.total_kwh);
+    // Readings do not keep the region they were taken in, so a region change
+    // rescales the whole total: a gauge, not a counter
+    encoder.family(
+        "vera_co2_grams",
+        "gauge",
+        Some("grams"),
+        "CO2 for the recorded energy at the factor of the current region.",
+    );
+    encoder.sample("vera_co2_grams", &[], co2_kg * 1000.0);
+    let price = calculator::ITALY_AVG_PRICE_PER_KWH;
+    encoder.family(
+        "vera_energy_cost_euros",
+        "counter",
+        Some("euros"),
+        "Cost of the recorded energy at price_per_kwh, the fixed Italian average.",
+    );
+    encoder.sample(
+        "vera_energy_cost_euros_total",
+        &[("price_per_kwh", &price.to_string())],
+        calculator::kwh_to_euro(snapshot.total_kwh, price),
+    );
+
+    encoder.family("vera_cpu_utilization_ratio", "gauge", Some("ratio"), "CPU utilisation across all cores.");
+    encoder.sample("vera_cpu_utilization_ratio", &[], metrics.cpu.usage.get().unwrap_or(f32::NAN) as f64 / 100.0);
+    encoder.family("vera_gpu_utilization_ratio", "gauge", Some("ratio"), "GPU utilisation.");
+    for gpu in &metrics.gpus {
+        if let Some(usage) = gpu.usage.get() {
+            encoder.sample("vera_gpu_utilization_ratio", &[("gpu", &gpu.id)], usage as f64 / 100.0);
+        }
+    }
+    encoder.family("vera_memory_utilization_ratio", "gauge", Some("ratio"), "RAM in use.");
+    encoder.sample(
+        "vera_memory_utilization_ratio",
+        &[],
+        metrics.ram.percentage.get().unwrap_or(f32::NAN) as f64 / 100.0,
+    );
+
+    encoder.family("vera_temperature_celsius", "gauge", Some("celsius"), "Temperature sensors.");
+    for sensor in metrics.sensors.iter().filter(|s| s.kind == crate::system_monitor::SensorKind::Temperature) {
+        if let Some(value) = sensor.value {
+            encoder.sample(
+                "vera_temperature_celsius",
+                &[
+                    ("sensor", &sensor.id),
+                    ("chip", &sensor.chip),
+                    ("label", &sensor.label),
+                    ("component", sensor.component()),
+                ],
+                value,
+            );
+        }
+    }
+
+    encoder.family("vera_app_power_watts", "gauge", Some("watts"), "CPU power attributed to each application category.");
+    for (category, energy) in &snapshot.categories {
+        encoder.sample("vera_app_power_watts", &[("category", category)], energy.watts);
+    }
+    encoder.family(
+        "vera_app_energy_kilowatt_hours",
+        "counter",
+        Some("kilowatt_hours"),
+        "CPU energy attributed to each application category since the exporter started.",
+    );
+    for (category, energy) in &snapshot.categories {
+        encoder.sample("vera_app_energy_kilowatt_hours_total", &[("category", category)], energy.kwh);
+    }
+
+    encoder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system_monitor::SystemMonitor;
+    use prometheus_parse::Value;
+    use std::time::Duration;
+
+    fn app(category: &str, cpu_usage: f32) -> ActiveApplication {
+        ActiveApplication {
+            name: category.to_lowercase(),
+            category: category.to_string(),
+            duration: 0,
+            cpu_usage,
+            memory_usage: 0,
+        }
+    }
+
+    #[test]
+    fn test_app_energy_follows_cpu_share() {
+        let start = Instant::now();
+        let mut energy = AppEnergy::default();
+        // The first sample only sets the baseline
+        energy.update(&[app("Browser", 100.0)], 40.0, 4, start);
+        assert_eq!(energy.categories["Browser"].kwh, 0.0);
+
+        // Two browser processes at one core each out of four: half of 40 W
+        let apps = [app("Browser", 100.0), app("Browser", 100.0), app("Media", 50.0)];
+        energy.update(&apps, 40.0, 4, start + Duration::from_secs(3600));
+        assert_eq!(energy.categories["Browser"].watts, 20.0);
+        assert!((energy.categories["Browser"].kwh - 0.020).abs() < 1e-9);
+        assert_eq!(energy.categories["Media"].watts, 5.0);
+
+        // Categories that go quiet keep their counter
+        energy.update(&[], 40.0, 4, start + Duration::from_secs(7200));
+        assert_eq!(energy.categories["Media"].watts, 0.0);
+        assert!((energy.categories["Media"].kwh - 0.005).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_exposition_parses() {
+        let mut categories = vec![
+            ("Browser".to_string(), CategoryEnergy { watts: 12.5, kwh: 0.25 }),
+            ("Other".to_string(), CategoryEnergy::default()),
+        ];
+        // Label values that need escaping
+        categories.push(("Odd \"name\"\\\n".to_string(), CategoryEnergy { watts: 1.0, kwh: 1e-9 }));
+
+        let snapshot = Snapshot {
+            device: "pc".to_string(),
+            region: "Valle d'Aosta".to_string(),
+            metrics: SystemMonitor::new().get_metrics(),
+            recorded_watts: Some(71.5),
+            total_kwh: 2.0,
+            categories,
+        };
+        let text = render(&snapshot);
+        assert!(text.ends_with("# EOF\n"), "{}", text);
+        let scrape = prometheus_parse::Scrape::parse(text.lines().map(|line| Ok(line.to_string()))).unwrap();
+
+        let find = |name: &str, extra: &[(&str, &str)]| {
+            scrape
+                .samples
+                .iter()
+                .find(|s| s.metric == name && extra.iter().all(|(k, v)| s.labels.get(k) == Some(*v)))
+                .map(|s| s.value.clone())
+        };
+        let value = |name: &str, extra: &[(&str, &str)]| match find(name, extra) {
+            Some(Value::Counter(v) | Value::Gauge(v) | Value::Untyped(v)) => Some(v),
+            _ => None,
+        };
+        // Every line but the comments is a sample the parser understood
+        let sample_lines = text.lines().filter(|line| !line.starts_with('#')).count();
+        assert_eq!(scrape.samples.len(), sample_lines, "{}", text);
+        assert!(scrape
+            .samples
+            .iter()
+            .all(|s| s.labels.get("device") == Some("pc") && s.labels.get("region") == Some("Valle d'Aosta")));
+        assert!(scrape.docs.contains_key("vera_co2_grams"));
+
+        assert_eq!(value("vera_energy_kilowatt_hours_total", &[]), Some(2.0));
+        // 0.22 kg/kWh in Valle d'Aosta, reported as a gauge since the factor follows the region setting
+        assert!(matches!(find("vera_co2_grams", &[]), Some(Value::Gauge(v)) if (v - 440.0).abs() < 1e-9));
+        assert!((value("vera_energy_cost_euros_total", &[("price_per_kwh", "0.3")]).unwrap() - 0.6).abs() < 1e-9);
+        assert_eq!(find("vera_power_watts", &[("source", "recorded")]), Some(Value::Gauge(71.5)));
+        assert!(value("vera_power_watts", &[("source", "system")]).is_some());
+        assert_eq!(value("vera_app_power_watts", &[("category", "Browser")]), Some(12.5));
+        // Label values come back as written on the wire, escapes included
+        assert_eq!(value("vera_app_energy_kilowatt_hours_total", &[("category", "Odd \\\"name\\\"\\\\\\n")]), Some(1e-9));
+    }
+}
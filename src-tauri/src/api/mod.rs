@@ -0,0 +1,395 @@
+// Local REST API
+//
+// Opt-in HTTP server exposing the energy and system read endpoints of
+// docs/API.md from the local store, for scripts, Home Assistant REST sensors
+// and dashboards that should not go through the webview, a live event
+// stream and a Prometheus exporter on /metrics. Every route except
+// /api/health requires `Authorization: Bearer <token>`.
+mod handlers;
+pub mod metrics;
+pub mod stream;
+
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::{oneshot, watch};
+use tokio::task::JoinHandle;
+
+use crate::error::{Result, VeraError};
+use crate::storage::UserSettings;
+use crate::system_monitor::SystemMonitor;
+
+pub const DEFAULT_BIND: &str = "127.0.0.1:7878";
+/// Shorter tokens are rejected, generated ones are 48 hex characters
+pub const MIN_TOKEN_LEN: usize = 16;
+/// How long a restart waits for open requests before dropping them
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiConfig {
+    pub bind: SocketAddr,
+    pub token: String,
+}
+
+impl ApiConfig {
+    pub fn new(bind: &str, token: &str) -> Result<Self> {
+        let bind = bind
+            .parse()
+            .map_err(|_| VeraError::invalid_argument("apiBind", "expected an address like 127.0.0.1:7878"))?;
+        if token.len() < MIN_TOKEN_LEN {
+            return Err(VeraError::invalid_argument(
+                "apiToken",
+                format!("must be at least {} characters", MIN_TOKEN_LEN),
+            ));
+        }
+
+        Ok(Self {
+            bind,
+            token: token.to_string(),
+        })
+    }
+
+    /// `None` when the API is disabled
+    pub fn from_settings(settings: &UserSettings) -> Result<Option<Self>> {
+        if !settings.api_enabled {
+            return Ok(None);
+        }
+        Self::new(&settings.api_bind, &settings.api_token).map(Some)
+    }
+}
+
+struct Running {
+    config: ApiConfig,
+    addr: SocketAddr,
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+    sampler: JoinHandle<()>,
+}
+
+/// Handle to the server; starts, restarts or stops it as settings change
+#[derive(Default)]
+pub struct ApiServer {
+    running: tokio::sync::Mutex<Option<Running>>,
+}
+
+impl ApiServer {
+    /// Run with `config`, or stop when `None`. No-op if nothing changed.
+    pub async fn apply(&self, config: Option<ApiConfig>) -> Result<()> {
+        let mut running = self.running.lock().await;
+        if running.as_ref().map(|r| &r.config) == config.as_ref() {
+            return Ok(());
+        }
+
+        if let Some(previous) = running.take() {
+            stop(previous).await;
+        }
+
+        if let Some(config) = config {
+            let listener = TcpListener::bind(config.bind)
+                .await
+                .map_err(|e| VeraError::Config(format!("Cannot listen on {}: {}", config.bind, e)))?;
+            let addr = listener.local_addr()?;
+            if !addr.ip().is_loopback() {
+                eprintln!("REST API listening on {}, reachable from other machines", addr);
+            }
+
+            let state = Arc::new(ApiState::new(config.token.clone()));
+            let sampler = tokio::spawn(stream::sample_metrics(Arc::clone(&state)));
+            let app = router(Arc::clone(&state));
+            let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+            let task = tokio::spawn(async move {
+                let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+                    shutdown_rx.await.ok();
+                    // End open streams, they would hold the shutdown otherwise
+                    state.closed.send_replace(true);
+                });
+                if let Err(e) = server.await {
+                    eprintln!("REST API stopped: {}", e);
+                }
+            });
+
+            *running = Some(Running {
+                config,
+                addr,
+                shutdown,
+                task,
+                sampler,
+            });
+        }
+        Ok(())
+    }
+
+    /// Address actually bound, useful with port 0
+    pub async fn local_addr(&self) -> Option<SocketAddr> {
+        self.running.lock().await.as_ref().map(|r| r.addr)
+    }
+
+    pub async fn shutdown(&self) {
+        if let Some(running) = self.running.lock().await.take() {
+            stop(running).await;
+        }
+    }
+}
+
+async fn stop(running: Running) {
+    running.sampler.abort();
+    running.shutdown.send(()).ok();
+    let abort = running.task.abort_handle();
+    if tokio::time::timeout(SHUTDOWN_TIMEOUT, running.task).await.is_err() {
+        abort.abort();
+    }
+}
+
+pub(crate) struct ApiState {
+    token: String,
+    // Own instance: the API also runs in the agent, where there is no AppState
+    monitor: Mutex<SystemMonitor>,
+    app_energy: Mutex<metrics::AppEnergy>,
+    /// Streams subscribed to the metrics topic
+    metrics_streams: AtomicUsize,
+    /// Set on shutdown
+    closed: watch::Sender<bool>,
+}
+
+impl ApiState {
+    fn new(token: String) -> Self {
+        Self {
+            token,
+            monitor: Mutex::new(SystemMonitor::new()),
+            app_energy: Mutex::default(),
+            metrics_streams: AtomicUsize::new(0),
+            closed: watch::Sender::new(false),
+        }
+    }
+}
+
+fn router(state: Arc<ApiState>) -> Router {
+    let auth = middleware::from_fn_with_state(Arc::clone(&state), require_token);
+    let protected = Router::new()
+        .route("/energy/data", get(handlers::energy_data))
+        .route("/energy/batch", get(handlers::energy_batch))
+        .route("/energy/history", get(handlers::energy_history))
+        .route("/energy/stats", get(handlers::energy_stats))
+        .route("/system/metrics", get(handlers::system_metrics))
+        .route("/stream", get(stream::stream))
+        .route_layer(auth.clone());
+
+    let api = Router::new()
+        .route("/health", get(handlers::health))
+        .merge(protected);
+
+    // Prometheus scrapes /metrics by default
+    let exporter = Router::new()
+        .route("/metrics", get(metrics::metrics))
+        .route_layer(auth);
+
+    Router::new()
+        .nest("/api", api)
+        .merge(exporter)
+        .with_state(state)
+        .fallback(|| async { ApiError(VeraError::NotFound("route".to_string())) })
+}
+
+async fn require_token(State(state): State<Arc<ApiState>>, request: Request, next: Next) -> Response {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match token {
+        Some(token) if constant_time_eq(token.as_bytes(), state.token.as_bytes()) => next.run(request).await,
+        _ => ApiError(VeraError::Unauthorized("missing or invalid bearer token".to_string())).into_response(),
+    }
+}
+
+/// Compare without returning early, so response timing does not leak the token
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// `{success: true, data}` envelope used by docs/API.md
+fn ok<T: Serialize>(data: T) -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "success": true, "data": data }))
+}
+
+pub(crate) struct ApiError(VeraError);
+
+impl From<VeraError> for ApiError {
+    fn from(e: VeraError) -> Self {
+        ApiError(e)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match &self.0 {
+            VeraError::InvalidArgument { .. } => StatusCode::BAD_REQUEST,
+            VeraError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            VeraError::PermissionDenied(_) => StatusCode::FORBIDDEN,
+            VeraError::NotFound(_) => StatusCode::NOT_FOUND,
+            VeraError::SensorUnavailable(_) | VeraError::Backpressure(_) => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let body = Json(serde_json::json!({
+            "success": false,
+            "error": self.0,
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+        }));
+        let mut response = (status, body).into_response();
+        if status == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const TOKEN: &str = "0123456789abcdef0123";
+
+    /// Minimal HTTP/1.1 client: status code and JSON body
+    async fn get(addr: SocketAddr, path: &str, token: Option<&str>) -> (u16, serde_json::Value) {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let auth = token
+            .map(|t| format!("Authorization: Bearer {}\r\n", t))
+            .unwrap_or_default();
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n", path, auth);
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response.split("\r\n\r\n").nth(1).unwrap_or_default();
+        (status, serde_json::from_str(body).unwrap_or(serde_json::Value::Null))
+    }
+
+    async fn start() -> (ApiServer, SocketAddr) {
+        let server = ApiServer::default();
+        server
+            .apply(Some(ApiConfig::new("127.0.0.1:0", TOKEN).unwrap()))
+            .await
+            .unwrap();
+        let addr = server.local_addr().await.unwrap();
+        (server, addr)
+    }
+
+    #[tokio::test]
+    async fn test_requests_need_the_token() {
+        let (server, addr) = start().await;
+
+        let (status, body) = get(addr, "/api/health", None).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["data"]["status"], "ok");
+
+        let (status, body) = get(addr, "/api/energy/stats", None).await;
+        assert_eq!(status, 401);
+        assert_eq!(body["success"], false);
+        assert_eq!(body["error"]["code"], "AUTHENTICATION_ERROR");
+
+        let (status, _) = get(addr, "/api/system/metrics", Some("not-the-token-at-all")).await;
+        assert_eq!(status, 401);
+
+        let (status, body) = get(addr, "/api/system/metrics", Some(TOKEN)).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["success"], true);
+        assert!(body["data"]["metrics"]["timestamp"].is_string());
+
+        server.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_bad_requests_use_the_error_envelope() {
+        let (server, addr) = start().await;
+
+        let (status, body) = get(addr, "/api/energy/history?timeframe=soon", Some(TOKEN)).await;
+        assert_eq!(status, 400);
+        assert_eq!(body["error"]["code"], "INVALID_ARGUMENT");
+        assert_eq!(body["error"]["details"]["field"], "timeframe");
+
+        let (status, body) = get(addr, "/api/energy/history?page=0", Some(TOKEN)).await;
+        assert_eq!(status, 400);
+        assert_eq!(body["error"]["details"]["field"], "page");
+
+        let (status, body) = get(addr, "/api/energy/nope", Some(TOKEN)).await;
+        assert_eq!(status, 404);
+        assert_eq!(body["error"]["code"], "NOT_FOUND");
+
+        // Stopping frees the port
+        server.apply(None).await.unwrap();
+        assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+    }
+
+    /// Read from an open response until `needle` shows up
+    async fn read_until(stream: &mut tokio::net::TcpStream, received: &mut String, needle: &str) {
+        let mut buffer = [0u8; 4096];
+        while !received.contains(needle) {
+            let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buffer))
+                .await
+                .unwrap_or_else(|_| panic!("no {:?} in {:?}", needle, received))
+                .unwrap();
+            assert!(read > 0, "closed before {:?}", needle);
+            received.push_str(&String::from_utf8_lossy(&buffer[..read]));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_filters_topics() {
+        let (server, addr) = start().await;
+
+        let (status, body) = get(addr, "/api/stream?topics=weather", Some(TOKEN)).await;
+        assert_eq!(status, 400);
+        assert_eq!(body["error"]["details"]["field"], "topics");
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "GET /api/stream?topics=notifications HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {}\r\n\r\n",
+            TOKEN
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut received = String::new();
+        read_until(&mut stream, &mut received, "event: ready").await;
+        assert!(received.contains("text/event-stream"));
+
+        crate::events::publish("reading", &serde_json::json!({ "timestamp": 1, "powerWatts": 80.0 }));
+        crate::events::publish("new-notification", &serde_json::json!({ "id": "stream-test" }));
+        read_until(&mut stream, &mut received, "stream-test").await;
+        assert!(received.contains("event: new-notification"));
+        assert!(!received.contains("event: reading"));
+
+        // Open streams do not hold up the shutdown
+        tokio::time::timeout(Duration::from_secs(2), server.shutdown())
+            .await
+            .expect("shutdown waited for the stream");
+    }
+
+    #[test]
+    fn test_config_from_settings() {
+        let mut settings = UserSettings::default();
+        assert_eq!(ApiConfig::from_settings(&settings).unwrap(), None);
+
+        settings.api_enabled = true;
+        settings.ensure_api_token().unwrap();
+        let config = ApiConfig::from_settings(&settings).unwrap().unwrap();
+        assert_eq!(config.bind.to_string(), DEFAULT_BIND);
+        assert_eq!(config.token.len(), 48);
+
+        settings.api_token = "short".to_string();
+        assert_eq!(ApiConfig::from_settings(&settings).unwrap_err().code(), "INVALID_ARGUMENT");
+    }
+}
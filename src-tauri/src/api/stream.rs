@@ -0,0 +1,312 @@
+// Live event stream (Server-Sent Events)
+//
+// GET /api/stream relays the in-process event bus: every stored reading,
+// system metrics snapshots, notifications and dashboard stats, as JSON.
+// Clients pick topics and may ask for the last minutes of readings and
+// notifications before the live events start.
+use axum::extract::rejection::QueryRejection;
+use axum::extract::{Query, State};
+use axum::response::sse::{Event as SseEvent, KeepAlive, Sse};
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+
+use super::{ApiError, ApiState};
+use crate::energy::service::DEFAULT_INTERVAL;
+use crate::error::VeraError;
+use crate::events::{self, Event};
+use crate::storage::{db, NotificationQuery};
+use crate::utils::parse;
+
+/// Longest backfill a client can ask for
+const MAX_BACKFILL_SECS: i64 = 24 * 3600;
+/// Readings sent at most in a backfill
+const MAX_BACKFILL_READINGS: u64 = 10_000;
+/// Event published while at least one client streams metrics
+pub const METRICS_EVENT: &str = "system-metrics";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topic {
+    Readings,
+    Metrics,
+    Notifications,
+    Stats,
+}
+
+impl Topic {
+    const ALL: [Topic; 4] = [Topic::Readings, Topic::Metrics, Topic::Notifications, Topic::Stats];
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "readings" => Some(Topic::Readings),
+            "metrics" => Some(Topic::Metrics),
+            "notifications" => Some(Topic::Notifications),
+            "stats" => Some(Topic::Stats),
+            _ => None,
+        }
+    }
+
+    /// Topic of a bus event; events without one are not streamed
+    fn of(event: &str) -> Option<Self> {
+        match event {
+            "reading" => Some(Topic::Readings),
+            METRICS_EVENT => Some(Topic::Metrics),
+            "new-notification" => Some(Topic::Notifications),
+            "energy-update" => Some(Topic::Stats),
+            _ => None,
+        }
+    }
+}
+
+/// Comma separated topics; all of them when empty
+fn parse_topics(text: Option<&str>) -> std::result::Result<Vec<Topic>, VeraError> {
+    let names: Vec<&str> = text
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .collect();
+    if names.is_empty() {
+        return Ok(Topic::ALL.to_vec());
+    }
+
+    names
+        .into_iter()
+        .map(|name| {
+            Topic::parse(name).ok_or_else(|| {
+                VeraError::invalid_argument("topics", "expected readings, metrics, notifications or stats")
+            })
+        })
+        .collect()
+}
+
+/// Keeps the metrics sampler running while a client wants metrics
+struct MetricsSubscription(Arc<ApiState>);
+
+impl MetricsSubscription {
+    fn new(state: Arc<ApiState>) -> Self {
+        state.metrics_streams.fetch_add(1, Ordering::SeqCst);
+        Self(state)
+    }
+}
+
+impl Drop for MetricsSubscription {
+    fn drop(&mut self) {
+        self.0.metrics_streams.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Publish a metrics snapshot every interval, only while someone streams them
+pub(super) async fn sample_metrics(state: Arc<ApiState>) {
+    let mut ticker = tokio::time::interval(DEFAULT_INTERVAL);
+    loop {
+        ticker.tick().await;
+        if state.metrics_streams.load(Ordering::SeqCst) == 0 {
+            continue;
+        }
+
+        let monitor_state = Arc::clone(&state);
+        let sample = tokio::task::spawn_blocking(move || {
+            let mut monitor = monitor_state.monitor.lock()?;
+            Ok::<_, VeraError>(monitor.get_metrics())
+        })
+        .await;
+        match sample {
+            // Same shape as /api/system/metrics
+            Ok(Ok(metrics)) => match super::handlers::metrics_json(metrics) {
+                Ok(metrics) => events::publish(METRICS_EVENT, &metrics),
+                Err(e) => eprintln!("Failed to serialize system metrics: {}", e),
+            },
+            Ok(Err(e)) => eprintln!("Failed to sample system metrics: {}", e),
+            Err(e) => eprintln!("Failed to sample system metrics: {}", e),
+        }
+    }
+}
+
+fn sse_event(name: &str, payload: &serde_json::Value) -> SseEvent {
+    SseEvent::default().event(name).data(payload.to_string())
+}
+
+#[derive(Deserialize)]
+pub(super) struct StreamQuery {
+    topics: Option<String>,
+    /// How far back to replay, e.g. `10m`
+    backfill: Option<String>,
+    /// Only readings of this device
+    device: Option<String>,
+}
+
+/// GET /api/stream
+pub(super) async fn stream(
+    State(state): State<Arc<ApiState>>,
+    params: std::result::Result<Query<StreamQuery>, QueryRejection>,
+) -> std::result::Result<Sse<impl Stream<Item = std::result::Result<SseEvent, Infallible>>>, ApiError> {
+    let params = super::handlers::query(params)?;
+    let topics = parse_topics(params.topics.as_deref())?;
+    let backfill = match params.backfill.as_deref() {
+        Some(text) => parse::parse_duration(text).map_err(|e| VeraError::invalid_argument("backfill", e))?,
+        None => 0,
+    };
+    if backfill > MAX_BACKFILL_SECS {
+        return Err(VeraError::invalid_argument("backfill", "at most 24h").into());
+    }
+
+    // Subscribe before reading the backfill so nothing falls in between
+    let (recent, events) = events::subscribe_with_history();
+    let subscription = topics
+        .contains(&Topic::Metrics)
+        .then(|| MetricsSubscription::new(Arc::clone(&state)));
+
+    let mut replay = Vec::new();
+    let mut filter = Filter {
+        topics,
+        device: params.device,
+        replayed_until: i64::MIN,
+        replayed_notifications: HashSet::new(),
+    };
+    if backfill > 0 {
+        let now = chrono::Utc::now().timestamp();
+        let since = now - backfill;
+        if filter.topics.contains(&Topic::Readings) {
+            let readings =
+                db::get_readings_page(since, now + 1, filter.device.as_deref(), MAX_BACKFILL_READINGS, 0).await?;
+            filter.replayed_until = since - 1;
+            for reading in readings {
+                filter.replayed_until = filter.replayed_until.max(reading.timestamp);
+                replay.push(sse_event("reading", &serde_json::to_value(reading).unwrap_or_default()));
+            }
+            // Readings still in the writer's queue are only on the bus
+            for event in recent.iter().filter(|event| event.name == "reading") {
+                if let Some(sse) = filter.accept(event) {
+                    replay.push(sse);
+                    filter.replayed_until = event.payload["timestamp"].as_i64().unwrap_or(filter.replayed_until);
+                }
+            }
+        }
+        if filter.topics.contains(&Topic::Notifications) {
+            let query = NotificationQuery {
+                from: Some(since),
+                limit: Some(NotificationQuery::MAX_LIMIT),
+                ..Default::default()
+            };
+            let mut notifications = db::get_notifications(&query).await?.notifications;
+            notifications.reverse();
+            for notification in notifications {
+                filter.replayed_notifications.insert(notification.id.clone());
+                replay.push(sse_event("new-notification", &serde_json::to_value(notification).unwrap_or_default()));
+            }
+        }
+    }
+    replay.push(sse_event(
+        "ready",
+        &serde_json::json!({
+            "topics": filter.topics.iter().map(|t| format!("{:?}", t).to_lowercase()).collect::<Vec<_>>(),
+            "backfilled": replay.len(),
+        }),
+    ));
+
+    let closed = state.closed.subscribe();
+    let live = stream::unfold(
+        (events, closed, filter, subscription),
+        |(mut events, mut closed, filter, subscription)| async move {
+            loop {
+                let received = tokio::select! {
+                    received = events.recv() => received,
+                    // Let graceful shutdown finish instead of waiting on open streams
+                    _ = closed.wait_for(|closed| *closed) => return None,
+                };
+                let event = match received {
+                    Ok(event) => match filter.accept(&event) {
+                        Some(event) => event,
+                        None => continue,
+                    },
+                    Err(RecvError::Lagged(skipped)) => sse_event("lagged", &serde_json::json!({ "skipped": skipped })),
+                    Err(RecvError::Closed) => return None,
+                };
+                return Some((Ok(event), (events, closed, filter, subscription)));
+            }
+        },
+    );
+
+    let replay = stream::iter(replay.into_iter().map(Ok));
+    Ok(Sse::new(replay.chain(live)).keep_alive(KeepAlive::default()))
+}
+
+struct Filter {
+    topics: Vec<Topic>,
+    device: Option<String>,
+    /// Newest backfilled reading, so it is not sent twice
+    replayed_until: i64,
+    /// Backfilled notifications, which may also be on the bus
+    replayed_notifications: HashSet<String>,
+}
+
+impl Filter {
+    fn accept(&self, event: &Event) -> Option<SseEvent> {
+        let topic = Topic::of(event.name)?;
+        if !self.topics.contains(&topic) {
+            return None;
+        }
+        if topic == Topic::Readings {
+            let timestamp = event.payload["timestamp"].as_i64().unwrap_or_default();
+            let device = event.payload["deviceType"].as_str();
+            if timestamp <= self.replayed_until
+                || self.device.as_deref().is_some_and(|wanted| device != Some(wanted))
+            {
+                return None;
+            }
+        }
+        if topic == Topic::Notifications
+            && event.payload["id"].as_str().is_some_and(|id| self.replayed_notifications.contains(id))
+        {
+            return None;
+        }
+        Some(sse_event(event.name, &event.payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_topics() {
+        assert_eq!(parse_topics(None).unwrap(), Topic::ALL.to_vec());
+        assert_eq!(
+            parse_topics(Some("readings, notifications")).unwrap(),
+            vec![Topic::Readings, Topic::Notifications]
+        );
+        assert_eq!(parse_topics(Some("weather")).unwrap_err().code(), "INVALID_ARGUMENT");
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = Filter {
+            topics: vec![Topic::Readings, Topic::Notifications],
+            device: Some("pc".to_string()),
+            replayed_until: 100,
+            replayed_notifications: HashSet::from(["daily-summary-1".to_string()]),
+        };
+        let reading = |timestamp: i64, device: &str| Event {
+            name: "reading",
+            payload: json!({ "timestamp": timestamp, "deviceType": device, "powerWatts": 80.0 }),
+        };
+
+        assert!(filter.accept(&reading(101, "pc")).is_some());
+        // Already backfilled, other device, other topic
+        assert!(filter.accept(&reading(100, "pc")).is_none());
+        assert!(filter.accept(&reading(101, "tablet")).is_none());
+        assert!(filter.accept(&Event { name: "energy-update", payload: json!({}) }).is_none());
+        assert!(filter.accept(&Event { name: "unrelated", payload: json!({}) }).is_none());
+
+        // Published between subscribing and the backfill query
+        let notification = |id: &str| Event { name: "new-notification", payload: json!({ "id": id }) };
+        assert!(filter.accept(&notification("daily-summary-1")).is_none());
+        assert!(filter.accept(&notification("daily-summary-2")).is_some());
+    }
+}
@@ -0,0 +1,75 @@
+// vera-agent: headless VERA monitoring service
+//
+//     vera-agent [--config PATH] [--check]
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use vera_lib::agent::{self, config::AgentConfig, logging};
+
+const USAGE: &str = "Usage: vera-agent [--config PATH] [--check]
+
+Options:
+  -c, --config PATH  Configuration file (default: /etc/vera/agent.toml)
+      --check        Validate the configuration and exit
+  -h, --help         Show this help
+  -V, --version      Show the version";
+
+fn main() -> ExitCode {
+    let mut config_path = None;
+    let mut check = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-c" | "--config" => match args.next() {
+                Some(path) => config_path = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("--config requires a path\n\n{}", USAGE);
+                    return ExitCode::from(2);
+                }
+            },
+            "--check" => check = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            "-V" | "--version" => {
+                println!("vera-agent {}", env!("CARGO_PKG_VERSION"));
+                return ExitCode::SUCCESS;
+            }
+            other => {
+                eprintln!("Unknown argument `{}`\n\n{}", other, USAGE);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    let config = match AgentConfig::load(config_path.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            logging::error(e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if check {
+        println!("{}", config.summary());
+        return ExitCode::SUCCESS;
+    }
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            logging::error(format!("Failed to start runtime: {}", e));
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match runtime.block_on(agent::run(config)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            logging::error(e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,688 @@
+// `vera` command-line interface
+//
+// Reads the same SQLite store as the desktop app and vera-agent through
+// `storage::db`. Queries open the database read-only; only `settings set`,
+// the notification commands and `webhook test` write to it.
+mod table;
+
+use chrono::{Local, TimeZone};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use crate::energy::calculator;
+use crate::error::{Result, VeraError};
+use crate::notifications::webhook;
+use crate::storage::{self, HistoryBucket, NotificationQuery, UserSettings, UserSettingsPatch};
+use crate::system_monitor::SystemMonitor;
+use crate::utils::parse;
+use table::Table;
+
+/// Tauri stores app data under the bundle identifier (tauri.conf.json)
+const APP_IDENTIFIER: &str = "com.vera.environmental";
+const AGENT_DATABASE: &str = "/var/lib/vera/vera_data.db";
+
+#[derive(Debug, Parser)]
+#[command(name = "vera", version, about = "Query and export VERA energy data")]
+pub struct Cli {
+    /// Database file [default: the desktop app's, then vera-agent's]
+    #[arg(long, global = true, env = "VERA_DATABASE", value_name = "PATH")]
+    db: Option<PathBuf>,
+
+    /// Print JSON instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Energy, water and CO₂ totals for today, this week and this month
+    Stats {
+        /// Period the trend is computed over
+        #[arg(long, default_value = "day", value_parser = ["day", "week", "month"])]
+        period: String,
+        #[arg(long, default_value = "pc")]
+        device: String,
+    },
+    /// Energy over time, summed into buckets
+    History {
+        /// Start: 2024-05-01, "2024-05-01 18:30", Unix seconds or a duration ago (7d)
+        #[arg(long, default_value = "24h")]
+        from: String,
+        #[arg(long, default_value = "now")]
+        to: String,
+        /// Bucket size: 15m, 1h, 1d, ...
+        #[arg(long, default_value = "1h")]
+        bucket: String,
+        #[arg(long, default_value = "pc")]
+        device: String,
+    },
+    /// Busiest applications right now
+    Apps {
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// Raw readings as CSV or JSON
+    Export {
+        /// Defaults to CSV, or JSON with --json
+        #[arg(long, value_enum)]
+        format: Option<ExportFormat>,
+        /// Start, same formats as `history` [default: first reading]
+        #[arg(long)]
+        from: Option<String>,
+        #[arg(long, default_value = "now")]
+        to: String,
+        /// Only readings for this device type
+        #[arg(long)]
+        device: Option<String>,
+        /// Write to a file instead of stdout
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Show or change settings
+    #[command(subcommand)]
+    Settings(SettingsCommand),
+    /// Notification inbox
+    #[command(subcommand)]
+    Notifications(NotificationsCommand),
+    /// Webhook deliveries
+    #[command(subcommand)]
+    Webhook(WebhookCommand),
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+enum SettingsCommand {
+    /// All settings, or the value of one
+    Get { key: Option<String> },
+    /// Change one setting, e.g. `vera settings set locale en`; objects such
+    /// as `doNotDisturb` are given as JSON
+    Set { key: String, value: String },
+}
+
+#[derive(Debug, Subcommand)]
+enum NotificationsCommand {
+    /// Most recent notifications first
+    List {
+        #[arg(long)]
+        unread: bool,
+        /// Only this type: info, warning, critical, success
+        #[arg(long = "type")]
+        type_: Option<String>,
+        /// Start, same formats as `history`
+        #[arg(long)]
+        from: Option<String>,
+        #[arg(long)]
+        to: Option<String>,
+        /// Continue after the previous page
+        #[arg(long)]
+        cursor: Option<String>,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Unread notifications per type
+    Unread,
+    /// Mark one notification, or all of them, as read
+    Read {
+        #[arg(required_unless_present = "all")]
+        id: Option<String>,
+        #[arg(long, conflicts_with = "id")]
+        all: bool,
+    },
+    Delete { id: String },
+    /// Hold notifications back for a while, e.g. `2h`, or until a time
+    Pause { until: String },
+    /// End a pause
+    Resume,
+}
+
+#[derive(Debug, Subcommand)]
+enum WebhookCommand {
+    /// Latest delivery attempts first
+    Log {
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Post a test notification to the configured webhook
+    Test,
+}
+
+/// Entry point of the `vera` binary when given arguments
+pub fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let json = cli.json;
+
+    let result = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(VeraError::from)
+        .and_then(|runtime| runtime.block_on(run(cli)));
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            if json {
+                eprintln!("{}", serde_json::to_string(&e).unwrap_or_default());
+            } else {
+                eprintln!("error: {}", e);
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let json = cli.json;
+
+    match cli.command {
+        Command::Stats { period, device } => {
+            open_database(cli.db, false).await?;
+            stats(&period, &device, json).await
+        }
+        Command::History { from, to, bucket, device } => {
+            open_database(cli.db, false).await?;
+            let now = Local::now();
+            let from = parse::parse_time(&from, now).map_err(|e| VeraError::invalid_argument("from", e))?;
+            let to = parse::parse_time(&to, now).map_err(|e| VeraError::invalid_argument("to", e))?;
+            let bucket = parse::parse_duration(&bucket).map_err(|e| VeraError::invalid_argument("bucket", e))?;
+            history(from, to, bucket, &device, json).await
+        }
+        Command::Apps { top } => apps(top, json).await,
+        Command::Export { format, from, to, device, output } => {
+            open_database(cli.db, false).await?;
+            let now = Local::now();
+            let from = match from {
+                Some(from) => parse::parse_time(&from, now).map_err(|e| VeraError::invalid_argument("from", e))?,
+                None => 0,
+            };
+            let to = parse::parse_time(&to, now).map_err(|e| VeraError::invalid_argument("to", e))?;
+            let format = format.unwrap_or(if json { ExportFormat::Json } else { ExportFormat::Csv });
+            export(format, from, to, device.as_deref(), output).await
+        }
+        Command::Settings(SettingsCommand::Get { key }) => {
+            open_database(cli.db, false).await?;
+            settings_get(key.as_deref(), json).await
+        }
+        Command::Settings(SettingsCommand::Set { key, value }) => {
+            open_database(cli.db, true).await?;
+            settings_set(&key, &value, json).await
+        }
+        Command::Notifications(NotificationsCommand::List { unread, type_, from, to, cursor, limit }) => {
+            open_database(cli.db, false).await?;
+            let now = Local::now();
+            let time = |field: &str, value: Option<String>| {
+                value
+                    .map(|v| parse::parse_time(&v, now).map_err(|e| VeraError::invalid_argument(field, e)))
+                    .transpose()
+            };
+            let query = NotificationQuery {
+                type_,
+                read: unread.then_some(false),
+                from: time("from", from)?,
+                to: time("to", to)?,
+                cursor,
+                limit: Some(limit),
+            };
+            notifications_list(&query, json).await
+        }
+        Command::Notifications(NotificationsCommand::Unread) => {
+            open_database(cli.db, false).await?;
+            notifications_unread(json).await
+        }
+        Command::Notifications(NotificationsCommand::Read { id, all }) => {
+            open_database(cli.db, true).await?;
+            match id {
+                Some(id) if !all => storage::db::mark_notification_read(&id).await,
+                _ => storage::db::mark_all_notifications_read().await,
+            }
+        }
+        Command::Notifications(NotificationsCommand::Delete { id }) => {
+            open_database(cli.db, true).await?;
+            storage::db::delete_notification(&id).await
+        }
+        Command::Notifications(NotificationsCommand::Pause { until }) => {
+            let now = Local::now();
+            let until = match parse::parse_duration(&until) {
+                Ok(seconds) => now.timestamp() + seconds,
+                Err(_) => parse::parse_time(&until, now).map_err(|e| VeraError::invalid_argument("until", e))?,
+            };
+            open_database(cli.db, true).await?;
+            pause_notifications(Some(until), json).await
+        }
+        Command::Notifications(NotificationsCommand::Resume) => {
+            open_database(cli.db, true).await?;
+            pause_notifications(None, json).await
+        }
+        Command::Webhook(WebhookCommand::Log { limit }) => {
+            open_database(cli.db, false).await?;
+            webhook_log(limit, json).await
+        }
+        Command::Webhook(WebhookCommand::Test) => {
+            open_database(cli.db, true).await?;
+            webhook_test(json).await
+        }
+    }
+}
+
+/// The desktop app's database if it exists, otherwise vera-agent's
+fn locate_database(explicit: Option<PathBuf>) -> Result<PathBuf> {
+    if let Some(path) = explicit {
+        return if path.exists() {
+            Ok(path)
+        } else {
+            Err(VeraError::NotFound(path.display().to_string()))
+        };
+    }
+
+    let desktop = dirs::data_dir().map(|dir| dir.join(APP_IDENTIFIER).join("vera_data.db"));
+    desktop
+        .into_iter()
+        .chain(std::iter::once(PathBuf::from(AGENT_DATABASE)))
+        .find(|path| path.exists())
+        .ok_or_else(|| {
+            VeraError::NotFound("no VERA database found, pass --db or set VERA_DATABASE".to_string())
+        })
+}
+
+async fn open_database(explicit: Option<PathBuf>, write: bool) -> Result<()> {
+    let path = locate_database(explicit)?;
+    if write {
+        storage::db::set_database_path(path);
+    } else {
+        storage::db::attach_read_only(path);
+    }
+    storage::db::init_database().await
+}
+
+fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    let text = serde_json::to_string_pretty(value).map_err(|e| VeraError::Internal(e.to_string()))?;
+    print_text(&(text + "\n"))
+}
+
+/// Write to stdout; a closed pipe (`vera export | head`) is not an error
+fn print_text(text: &str) -> Result<()> {
+    match std::io::stdout().lock().write_all(text.as_bytes()) {
+        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+fn format_time(timestamp: i64) -> String {
+    Local
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+async fn stats(period: &str, device: &str, json: bool) -> Result<()> {
+    let stats = storage::db::get_stats(period, device).await?;
+    if json {
+        return print_json(&stats);
+    }
+
+    let mut table = Table::new(&["Period", ">kWh", ">Bottles", ">CO₂ g"]);
+    for (label, kwh, bottles, co2) in [
+        ("Today", stats.today_kwh, stats.bottles_today, stats.co2_today),
+        ("This week", stats.weekly_kwh, stats.bottles_week, stats.co2_week),
+        ("This month", stats.monthly_kwh, stats.bottles_month, stats.co2_month),
+    ] {
+        table.row(vec![
+            label.to_string(),
+            format!("{:.3}", kwh),
+            format!("{:.1}", bottles),
+            format!("{:.0}", co2),
+        ]);
+    }
+
+    print_text(&format!(
+        "{}\nTrend vs previous {}: {:+.1}%\nPeak today: {:.0} W\n",
+        table.render(),
+        period,
+        stats.weekly_trend,
+        stats.peak_today
+    ))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HistoryRow {
+    #[serde(flatten)]
+    bucket: HistoryBucket,
+    co2_grams: f64,
+}
+
+async fn history(from: i64, to: i64, bucket: i64, device: &str, json: bool) -> Result<()> {
+    let region = storage::db::load_settings().await?.region;
+    let rows: Vec<HistoryRow> = storage::db::get_history(from, to, bucket, device)
+        .await?
+        .into_iter()
+        .map(|bucket| HistoryRow {
+            co2_grams: calculator::kwh_to_co2(bucket.kwh, &region) * 1000.0,
+            bucket,
+        })
+        .collect();
+
+    if json {
+        return print_json(&rows);
+    }
+
+    let mut table = Table::new(&["Start", ">kWh", ">Avg W", ">Max W", ">CO₂ g", ">Samples"]);
+    for row in &rows {
+        table.row(vec![
+            format_time(row.bucket.start),
+            format!("{:.4}", row.bucket.kwh),
+            format!("{:.1}", row.bucket.avg_power),
+            format!("{:.1}", row.bucket.max_power),
+            format!("{:.1}", row.co2_grams),
+            row.bucket.samples.to_string(),
+        ]);
+    }
+    if table.is_empty() {
+        return print_text("No readings in this range\n");
+    }
+
+    let total_kwh: f64 = rows.iter().map(|r| r.bucket.kwh).sum();
+    print_text(&format!(
+        "{}\nTotal: {:.4} kWh ≈ {:.1} bottles, {:.0} g CO₂ ({})\n",
+        table.render(),
+        total_kwh,
+        calculator::kwh_to_bottles(total_kwh),
+        calculator::kwh_to_co2(total_kwh, &region) * 1000.0,
+        region
+    ))
+}
+
+async fn apps(top: usize, json: bool) -> Result<()> {
+    // CPU usage is a delta, so sample twice
+    let mut monitor = SystemMonitor::new();
+    monitor.get_top_applications(top);
+    tokio::time::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL.max(std::time::Duration::from_millis(500))).await;
+    let apps = monitor.get_top_applications(top);
+
+    if json {
+        return print_json(&apps);
+    }
+
+    let mut table = Table::new(&["Application", "Category", ">CPU %", ">Memory MB"]);
+    for app in &apps {
+        table.row(vec![
+            app.name.clone(),
+            app.category.clone(),
+            format!("{:.1}", app.cpu_usage),
+            app.memory_usage.to_string(),
+        ]);
+    }
+    print_text(&table.render())
+}
+
+async fn export(format: ExportFormat, from: i64, to: i64, device: Option<&str>, output: Option<PathBuf>) -> Result<()> {
+    let readings = storage::db::get_readings(from, to, device).await?;
+
+    let sink: Box<dyn Write> = match &output {
+        Some(path) => Box::new(std::fs::File::create(path)?),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let mut out = BufWriter::new(sink);
+
+    let written = match format {
+        ExportFormat::Json => serde_json::to_writer_pretty(&mut out, &readings)
+            .map_err(std::io::Error::from)
+            .and_then(|()| writeln!(out)),
+        ExportFormat::Csv => write_csv(&mut out, &readings),
+    }
+    .and_then(|()| out.flush());
+
+    match written {
+        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => return Err(e.into()),
+        _ => {}
+    }
+
+    if let Some(path) = output {
+        eprintln!("Exported {} readings to {}", readings.len(), path.display());
+    }
+    Ok(())
+}
+
+fn write_csv(out: &mut impl Write, readings: &[storage::EnergyReading]) -> std::io::Result<()> {
+    writeln!(out, "timestamp,time,device_type,power_watts,kwh_increment")?;
+    for r in readings {
+        let time = Local
+            .timestamp_opt(r.timestamp, 0)
+            .single()
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_default();
+        // Device types are short identifiers; quote defensively anyway
+        let device = if r.device_type.contains([',', '"', '\n']) {
+            format!("\"{}\"", r.device_type.replace('"', "\"\""))
+        } else {
+            r.device_type.clone()
+        };
+        writeln!(out, "{},{},{},{},{}", r.timestamp, time, device, r.power_watts, r.kwh_increment)?;
+    }
+    Ok(())
+}
+
+/// `device_type` and `deviceType` both name the same setting
+fn settings_key(key: &str) -> Result<String> {
+    let mut camel = String::new();
+    let mut upper = false;
+    for c in key.chars() {
+        if c == '_' || c == '-' {
+            upper = true;
+        } else if upper {
+            camel.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            camel.push(c);
+        }
+    }
+
+    let known = settings_map(&UserSettings::default())?;
+    if known.contains_key(&camel) {
+        Ok(camel)
+    } else {
+        let keys: Vec<&str> = known.keys().map(String::as_str).collect();
+        Err(VeraError::invalid_argument("key", format!("unknown setting, expected one of {}", keys.join(", "))))
+    }
+}
+
+fn settings_map(settings: &UserSettings) -> Result<serde_json::Map<String, serde_json::Value>> {
+    match serde_json::to_value(settings) {
+        Ok(serde_json::Value::Object(map)) => Ok(map),
+        _ => Err(VeraError::Internal("settings are not an object".to_string())),
+    }
+}
+
+async fn settings_get(key: Option<&str>, json: bool) -> Result<()> {
+    let settings = settings_map(&storage::db::load_settings().await?)?;
+
+    let key = key.map(settings_key).transpose()?;
+    match key {
+        Some(key) if json => print_json(&settings[&key]),
+        Some(key) => match &settings[&key] {
+            serde_json::Value::String(s) => print_text(&format!("{}\n", s)),
+            other => print_text(&format!("{}\n", other)),
+        },
+        None if json => print_json(&settings),
+        None => {
+            let mut table = Table::new(&["Setting", "Value"]);
+            for (key, value) in &settings {
+                let value = value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string());
+                table.row(vec![key.clone(), value]);
+            }
+            print_text(&table.render())
+        }
+    }
+}
+
+async fn settings_set(key: &str, value: &str, json: bool) -> Result<()> {
+    let key = settings_key(key)?;
+    let mut settings = storage::db::load_settings().await?;
+
+    // Numbers and booleans are given as text on the command line
+    let current = &settings_map(&settings)?[&key];
+    let value = match current {
+        serde_json::Value::Bool(_) => match value {
+            "true" | "on" | "yes" | "1" => serde_json::Value::Bool(true),
+            "false" | "off" | "no" | "0" => serde_json::Value::Bool(false),
+            _ => return Err(VeraError::invalid_argument(&key, "expected true or false")),
+        },
+        serde_json::Value::Number(_) => value
+            .parse::<u64>()
+            .map(serde_json::Value::from)
+            .map_err(|_| VeraError::invalid_argument(&key, "expected a number"))?,
+        serde_json::Value::Object(_) => {
+            serde_json::from_str(value).map_err(|e| VeraError::invalid_argument(&key, format!("expected JSON: {}", e)))?
+        }
+        _ => serde_json::Value::String(value.to_string()),
+    };
+
+    let patch: UserSettingsPatch = serde_json::from_value(serde_json::json!({ key.clone(): value }))
+        .map_err(|e| VeraError::invalid_argument(&key, e.to_string()))?;
+    settings.apply(patch);
+    settings.ensure_api_token()?;
+    settings.validate()?;
+    storage::db::save_settings(settings.clone()).await?;
+
+    if json {
+        print_json(&settings)
+    } else {
+        Ok(())
+    }
+}
+
+async fn pause_notifications(until: Option<i64>, json: bool) -> Result<()> {
+    let mut settings = storage::db::load_settings().await?;
+    settings.do_not_disturb.paused_until = until;
+    storage::db::save_settings(settings.clone()).await?;
+
+    if json {
+        print_json(&settings.do_not_disturb)
+    } else {
+        Ok(())
+    }
+}
+
+async fn notifications_list(query: &NotificationQuery, json: bool) -> Result<()> {
+    let page = storage::db::get_notifications(query).await?;
+
+    if json {
+        return print_json(&page);
+    }
+    if page.notifications.is_empty() {
+        return print_text("No notifications\n");
+    }
+
+    let mut table = Table::new(&["", "Time", "Type", "Title", "ID"]);
+    for n in &page.notifications {
+        table.row(vec![
+            if n.read { " " } else { "*" }.to_string(),
+            format_time(n.timestamp),
+            n.type_.clone(),
+            n.title.clone(),
+            n.id.clone(),
+        ]);
+    }
+    let mut text = table.render();
+    if let Some(cursor) = &page.next_cursor {
+        text.push_str(&format!("More: --cursor {}\n", cursor));
+    }
+    print_text(&text)
+}
+
+async fn notifications_unread(json: bool) -> Result<()> {
+    let counts = storage::db::count_unread_notifications().await?;
+
+    if json {
+        return print_json(&counts);
+    }
+    if counts.is_empty() {
+        return print_text("No unread notifications\n");
+    }
+
+    let mut table = Table::new(&["Type", "Unread"]);
+    for (type_, count) in &counts {
+        table.row(vec![type_.clone(), count.to_string()]);
+    }
+    print_text(&table.render())
+}
+
+fn delivery_outcome(delivery: &storage::WebhookDelivery) -> String {
+    match (&delivery.error, delivery.status) {
+        (Some(error), _) => error.clone(),
+        (None, Some(status)) => format!("HTTP {}", status),
+        (None, None) => String::new(),
+    }
+}
+
+async fn webhook_log(limit: usize, json: bool) -> Result<()> {
+    let deliveries = storage::db::get_webhook_deliveries(limit).await?;
+
+    if json {
+        return print_json(&deliveries);
+    }
+    if deliveries.is_empty() {
+        return print_text("No webhook deliveries\n");
+    }
+
+    let mut table = Table::new(&["Time", ">Attempt", "Result", ">ms", "Notification"]);
+    for d in &deliveries {
+        table.row(vec![
+            format_time(d.timestamp),
+            d.attempt.to_string(),
+            delivery_outcome(d),
+            d.duration_ms.to_string(),
+            d.notification_id.clone(),
+        ]);
+    }
+    print_text(&table.render())
+}
+
+async fn webhook_test(json: bool) -> Result<()> {
+    let settings = storage::db::load_settings().await?;
+    let delivery = webhook::test(&settings.webhook, settings.locale()).await?;
+
+    if json {
+        return print_json(&delivery);
+    }
+    if !delivery.delivered {
+        return Err(VeraError::Internal(format!("webhook failed: {}", delivery_outcome(&delivery))));
+    }
+    print_text(&format!("Delivered to {} ({}, {} ms)\n", delivery.url, delivery_outcome(&delivery), delivery.duration_ms))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_keys_accept_both_spellings() {
+        assert_eq!(settings_key("do_not_disturb").unwrap(), "doNotDisturb");
+        assert_eq!(settings_key("theme").unwrap(), "theme");
+        assert_eq!(settings_key("volume").unwrap_err().code(), "INVALID_ARGUMENT");
+    }
+
+    #[test]
+    fn test_arguments_parse() {
+        let cli = Cli::try_parse_from(["vera", "history", "--from", "7d", "--bucket", "1d", "--json"]).unwrap();
+        assert!(cli.json);
+        assert!(matches!(cli.command, Command::History { ref bucket, .. } if bucket == "1d"));
+
+        assert!(Cli::try_parse_from(["vera", "stats", "--period", "year"]).is_err());
+        assert!(Cli::try_parse_from(["vera", "notifications", "read"]).is_err());
+        assert!(Cli::try_parse_from(["vera", "notifications", "pause", "2h"]).is_ok());
+        let cli = Cli::try_parse_from(["vera", "webhook", "log", "--limit", "5"]).unwrap();
+        assert!(matches!(cli.command, Command::Webhook(WebhookCommand::Log { limit: 5 })));
+    }
+}
@@ -0,0 +1,79 @@
+// Plain-text tables for terminal output
+use std::fmt::Write;
+
+pub struct Table {
+    headers: Vec<&'static str>,
+    // Numeric columns are right-aligned
+    numeric: Vec<bool>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Headers prefixed with `>` are right-aligned, e.g. `">kWh"`
+    pub fn new(headers: &[&'static str]) -> Self {
+        Self {
+            headers: headers.iter().map(|h| h.trim_start_matches('>')).collect(),
+            numeric: headers.iter().map(|h| h.starts_with('>')).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn row(&mut self, cells: Vec<String>) {
+        self.rows.push(cells);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn render(&self) -> String {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let mut out = String::new();
+        let headers: Vec<String> = self.headers.iter().map(|h| h.to_string()).collect();
+        self.render_row(&mut out, &headers, &widths);
+        let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+        self.render_row(&mut out, &rule, &widths);
+        for row in &self.rows {
+            self.render_row(&mut out, row, &widths);
+        }
+        out
+    }
+
+    fn render_row(&self, out: &mut String, cells: &[String], widths: &[usize]) {
+        let mut line = String::new();
+        for (i, (cell, width)) in cells.iter().zip(widths).enumerate() {
+            if i > 0 {
+                line.push_str("  ");
+            }
+            if self.numeric[i] {
+                write!(line, "{:>width$}", cell, width = width).ok();
+            } else {
+                write!(line, "{:<width$}", cell, width = width).ok();
+            }
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_columns_are_aligned() {
+        let mut table = Table::new(&["Name", ">kWh"]);
+        table.row(vec!["firefox".to_string(), "1.25".to_string()]);
+        table.row(vec!["vim".to_string(), "10.50".to_string()]);
+        assert_eq!(
+            table.render(),
+            "Name       kWh\n-------  -----\nfirefox   1.25\nvim      10.50\n"
+        );
+    }
+}
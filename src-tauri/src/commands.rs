@@ -0,0 +1,189 @@
+// Tauri commands exposed to the frontend
+use std::collections::BTreeMap;
+use tauri::State;
+
+use crate::api::ApiConfig;
+use crate::energy::{service::MonitoringStatus, EnergyStats};
+use crate::error::{Result, VeraError};
+use crate::notifications::{rules::Rule, webhook};
+use crate::permissions;
+use crate::storage::{self, NotificationPage, NotificationQuery, UserSettings, UserSettingsPatch, WebhookDelivery};
+use crate::storage::writer::{ReadingWriter, WriterStatus};
+use crate::system_monitor::{ActiveApplication, SystemMetrics};
+use crate::AppState;
+
+#[tauri::command]
+pub fn greet(name: &str) -> String {
+    format!("Hello {}! Welcome to VERA Energy Monitor", name)
+}
+
+#[tauri::command]
+pub async fn get_current_power(state: State<'_, AppState>) -> Result<f32> {
+    current_power(&state)
+}
+
+/// The draw both dashboard commands report, from the shared monitor
+fn current_power(state: &AppState) -> Result<f32> {
+    let mut monitor = state.monitor.lock()?;
+    monitor
+        .power_draw()
+        .get()
+        .ok_or_else(|| VeraError::SensorUnavailable("power_draw".to_string()))
+}
+
+#[tauri::command]
+pub async fn get_system_metrics(state: State<'_, AppState>) -> Result<SystemMetrics> {
+    let mut monitor = state.monitor.lock()?;
+    Ok(monitor.get_metrics())
+}
+
+#[tauri::command]
+pub async fn get_active_applications(state: State<'_, AppState>) -> Result<Vec<ActiveApplication>> {
+    let mut monitor = state.monitor.lock()?;
+    Ok(monitor.get_active_applications())
+}
+
+#[tauri::command]
+pub async fn get_energy_stats(state: State<'_, AppState>, period: String, device_type: String) -> Result<EnergyStats> {
+    let mut stats = storage::db::get_stats(&period, &device_type).await?;
+
+    if let Ok(power) = current_power(&state) {
+        stats.current_power = f64::from(power);
+    }
+
+    Ok(stats)
+}
+
+#[tauri::command]
+pub async fn get_settings() -> Result<UserSettings> {
+    storage::db::load_settings().await
+}
+
+#[tauri::command]
+pub async fn update_settings(
+    state: State<'_, AppState>,
+    settings: UserSettingsPatch,
+) -> Result<UserSettings> {
+    let previous = storage::db::load_settings().await?;
+    let mut current = previous.clone();
+    current.apply(settings);
+    current.ensure_api_token()?;
+    current.validate()?;
+
+    // Apply first so a bind error leaves the saved settings untouched
+    state.api.apply(ApiConfig::from_settings(&current)?).await?;
+    if let Err(e) = storage::db::save_settings(current.clone()).await {
+        state.api.apply(ApiConfig::from_settings(&previous).ok().flatten()).await.ok();
+        return Err(e);
+    }
+
+    Ok(current)
+}
+
+/// Newest first, one page at a time; without a query the latest 50
+#[tauri::command]
+pub async fn get_notifications(query: Option<NotificationQuery>) -> Result<NotificationPage> {
+    storage::db::get_notifications(&query.unwrap_or_default()).await
+}
+
+/// Unread notifications per type, for badges
+#[tauri::command]
+pub async fn count_unread_notifications() -> Result<BTreeMap<String, u64>> {
+    storage::db::count_unread_notifications().await
+}
+
+#[tauri::command]
+pub async fn mark_notification_read(id: String) -> Result<()> {
+    storage::db::mark_notification_read(&id).await
+}
+
+#[tauri::command]
+pub async fn mark_all_notifications_read() -> Result<()> {
+    storage::db::mark_all_notifications_read().await
+}
+
+#[tauri::command]
+pub async fn delete_notification(id: String) -> Result<()> {
+    storage::db::delete_notification(&id).await
+}
+
+#[tauri::command]
+pub async fn get_notification_rules() -> Result<Vec<Rule>> {
+    storage::db::get_notification_rules().await
+}
+
+/// Create or update a rule; new rules are sent without an id
+#[tauri::command]
+pub async fn save_notification_rule(mut rule: Rule) -> Result<Rule> {
+    if rule.id.is_empty() {
+        let suffix = crate::utils::random_hex(6).map_err(|e| VeraError::Internal(e.to_string()))?;
+        rule.id = format!("rule-{}", suffix);
+    }
+    rule.validate()?;
+    storage::db::save_notification_rule(&rule).await?;
+    Ok(rule)
+}
+
+#[tauri::command]
+pub async fn delete_notification_rule(id: String) -> Result<()> {
+    storage::db::delete_notification_rule(&id).await
+}
+
+/// Latest webhook attempts, newest first
+#[tauri::command]
+pub async fn get_webhook_deliveries(limit: Option<usize>) -> Result<Vec<WebhookDelivery>> {
+    storage::db::get_webhook_deliveries(limit.unwrap_or(50)).await
+}
+
+/// Post a test notification to the saved webhook, once
+#[tauri::command]
+pub async fn test_webhook() -> Result<WebhookDelivery> {
+    let settings = storage::db::load_settings().await?;
+    webhook::test(&settings.webhook, settings.locale()).await
+}
+
+#[tauri::command]
+pub async fn check_permissions() -> Result<bool> {
+    permissions::request_permissions().await?;
+    Ok(permissions::is_elevated())
+}
+
+#[tauri::command]
+pub async fn start_monitoring(state: State<'_, AppState>) -> Result<MonitoringStatus> {
+    state.monitoring.start(writer(&state)?)?;
+    state.monitoring.status()
+}
+
+#[tauri::command]
+pub fn pause_monitoring(state: State<'_, AppState>) -> Result<MonitoringStatus> {
+    state.monitoring.pause()?;
+    state.monitoring.status()
+}
+
+#[tauri::command]
+pub fn stop_monitoring(state: State<'_, AppState>) -> Result<MonitoringStatus> {
+    state.monitoring.stop()?;
+    state.monitoring.status()
+}
+
+#[tauri::command]
+pub fn set_monitoring_interval(seconds: u64, state: State<'_, AppState>) -> Result<MonitoringStatus> {
+    state.monitoring.set_interval(seconds)?;
+    state.monitoring.status()
+}
+
+#[tauri::command]
+pub fn get_monitoring_status(state: State<'_, AppState>) -> Result<MonitoringStatus> {
+    state.monitoring.status()
+}
+
+#[tauri::command]
+pub fn get_storage_status(state: State<'_, AppState>) -> Result<WriterStatus> {
+    Ok(writer(&state)?.status())
+}
+
+fn writer(state: &AppState) -> Result<&ReadingWriter> {
+    state.writer.get().ok_or_else(|| {
+        VeraError::InvalidState("attached read-only, readings are stored by vera-agent".to_string())
+    })
+}
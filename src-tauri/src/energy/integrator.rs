@@ -0,0 +1,211 @@
+// Power → energy integration over irregular samples
+//
+// Energy between two readings is the trapezoid of their power over the
+// monotonic time that actually elapsed, so late ticks or slow writes do not
+// skew totals. Intervals that cannot be trusted are reported as gaps and
+// contribute no energy: a stall (no reading for much longer than the
+// sampling interval) or a suspend, recognised because the wall clock moved
+// on while the monotonic clock, which stops during sleep, did not.
+use serde::Serialize;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Wall clock running ahead of the monotonic clock by more than this means
+/// the machine was asleep
+const SUSPEND_TOLERANCE: Duration = Duration::from_secs(5);
+/// Never treat an interval shorter than this as a stall
+const MIN_STALL_THRESHOLD: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GapReason {
+    Suspend,
+    Stall,
+    Restart,
+    Paused,
+}
+
+impl GapReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GapReason::Suspend => "suspend",
+            GapReason::Stall => "stall",
+            GapReason::Restart => "restart",
+            GapReason::Paused => "paused",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// First reading of a session (app start or resume), nothing to integrate
+    Start,
+    Energy { kwh: f64, seconds: f64 },
+    /// No energy is attributed between `started_at` and `ended_at` (Unix seconds)
+    Gap { started_at: i64, ended_at: i64, reason: GapReason },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    power: f64,
+    monotonic: Instant,
+    wall: SystemTime,
+}
+
+pub struct EnergyIntegrator {
+    last: Option<Sample>,
+    stall_threshold: Duration,
+}
+
+impl EnergyIntegrator {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            last: None,
+            stall_threshold: stall_threshold(interval),
+        }
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.stall_threshold = stall_threshold(interval);
+    }
+
+    /// Forget the previous reading, the next one starts a new session
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+
+    pub fn has_previous(&self) -> bool {
+        self.last.is_some()
+    }
+
+    pub fn push(&mut self, power: f64) -> Step {
+        self.push_at(power, Instant::now(), SystemTime::now())
+    }
+
+    fn push_at(&mut self, power: f64, monotonic: Instant, wall: SystemTime) -> Step {
+        let current = Sample { power, monotonic, wall };
+        let previous = match self.last.replace(current) {
+            Some(previous) => previous,
+            None => return Step::Start,
+        };
+
+        let elapsed = monotonic.saturating_duration_since(previous.monotonic);
+        // A wall clock stepped backwards (NTP, manual change) is not a suspend
+        let wall_elapsed = wall.duration_since(previous.wall).unwrap_or(elapsed);
+
+        let reason = if wall_elapsed.saturating_sub(elapsed) > SUSPEND_TOLERANCE {
+            Some(GapReason::Suspend)
+        } else if elapsed > self.stall_threshold {
+            Some(GapReason::Stall)
+        } else {
+            None
+        };
+
+        match reason {
+            Some(reason) => Step::Gap {
+                started_at: unix_seconds(previous.wall),
+                ended_at: unix_seconds(wall),
+                reason,
+            },
+            None => {
+                let seconds = elapsed.as_secs_f64();
+                let average_watts = (previous.power + power) / 2.0;
+                Step::Energy {
+                    kwh: average_watts * seconds / 3_600_000.0,
+                    seconds,
+                }
+            }
+        }
+    }
+}
+
+fn stall_threshold(interval: Duration) -> Duration {
+    (interval * 3).max(MIN_STALL_THRESHOLD)
+}
+
+fn unix_seconds(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kwh(step: Step) -> f64 {
+        match step {
+            Step::Energy { kwh, .. } => kwh,
+            other => panic!("expected energy, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_trapezoid_uses_actual_elapsed_time() {
+        let mut integrator = EnergyIntegrator::new(Duration::from_secs(5));
+        let t0 = Instant::now();
+        let w0 = SystemTime::now();
+
+        assert_eq!(integrator.push_at(100.0, t0, w0), Step::Start);
+
+        // A late tick: 7.2 s instead of 5 s, power ramping from 100 to 200 W
+        let step = integrator.push_at(200.0, t0 + Duration::from_millis(7200), w0 + Duration::from_millis(7200));
+        let expected = 150.0 * 7.2 / 3_600_000.0;
+        assert!((kwh(step) - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_one_hour_at_constant_power() {
+        let mut integrator = EnergyIntegrator::new(Duration::from_secs(5));
+        let t0 = Instant::now();
+        let w0 = SystemTime::now();
+        integrator.push_at(60.0, t0, w0);
+
+        let mut total = 0.0;
+        for i in 1..=720 {
+            let offset = Duration::from_secs(5 * i);
+            total += kwh(integrator.push_at(60.0, t0 + offset, w0 + offset));
+        }
+        assert!((total - 0.06).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_suspend_is_a_gap() {
+        let mut integrator = EnergyIntegrator::new(Duration::from_secs(5));
+        let t0 = Instant::now();
+        let w0 = SystemTime::now();
+        integrator.push_at(80.0, t0, w0);
+
+        // Monotonic clock advanced 5 s, wall clock 2 hours: the laptop slept
+        let step = integrator.push_at(80.0, t0 + Duration::from_secs(5), w0 + Duration::from_secs(7200));
+        match step {
+            Step::Gap { started_at, ended_at, reason } => {
+                assert_eq!(reason, GapReason::Suspend);
+                assert_eq!(ended_at - started_at, 7200);
+            }
+            other => panic!("expected gap, got {:?}", other),
+        }
+
+        // Integration resumes normally from the post-resume reading
+        let next = integrator.push_at(80.0, t0 + Duration::from_secs(10), w0 + Duration::from_secs(7205));
+        assert!(kwh(next) > 0.0);
+    }
+
+    #[test]
+    fn test_stall_is_a_gap_and_clock_step_back_is_not() {
+        let mut integrator = EnergyIntegrator::new(Duration::from_secs(5));
+        let t0 = Instant::now();
+        let w0 = SystemTime::now();
+        integrator.push_at(50.0, t0, w0);
+
+        let stalled = integrator.push_at(50.0, t0 + Duration::from_secs(60), w0 + Duration::from_secs(60));
+        assert!(matches!(stalled, Step::Gap { reason: GapReason::Stall, .. }));
+
+        // Wall clock corrected backwards by NTP during a normal interval
+        let step = integrator.push_at(50.0, t0 + Duration::from_secs(65), w0 + Duration::from_secs(30));
+        assert!((kwh(step) - 50.0 * 5.0 / 3_600_000.0).abs() < 1e-12);
+
+        integrator.reset();
+        assert!(!integrator.has_previous());
+        assert_eq!(integrator.push_at(50.0, t0 + Duration::from_secs(70), w0), Step::Start);
+    }
+}
@@ -1,10 +1,13 @@
 // Energy monitoring module
 pub mod monitor;
 pub mod calculator;
+pub mod integrator;
+pub mod service;
 
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct EnergyStats {
     pub current_power: f64,      // Watts
     pub today_kwh: f64,
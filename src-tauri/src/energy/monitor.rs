@@ -1,81 +1,133 @@
 // Real-time energy monitoring
-use tauri::AppHandle;
-use tokio::time::{interval, Duration};
-use sysinfo::{System, SystemExt, CpuExt};
-use super::EnergyStats;
+use once_cell::sync::Lazy;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use crate::error::{Result, VeraError};
+use super::integrator::{GapReason, Step};
+use super::service::EnergySink;
+use crate::events;
 use crate::storage;
+use crate::storage::writer::ReadingWriter;
+use crate::system_monitor::SystemMonitor;
 
-// Coefficienti di conversione per l'Italia
-const ITALY_CO2_PER_KWH: f64 = 0.5;     // kg CO₂/kWh (media Italia 2024)
-const BOTTLES_PER_KWH: f64 = 3.0;        // bottiglie da 0.5L per kWh (1.5L acqua)
+/// Dispositivo delle letture registrate dalla pipeline
+pub const DEVICE_TYPE: &str = "pc";
 
-pub async fn get_current_consumption() -> Result<f64, Box<dyn std::error::Error>> {
-    let mut sys = System::new_all();
-    sys.refresh_all();
-    
-    // Stima del consumo basata su CPU usage
-    // Questo è un approccio semplificato. Per un monitoraggio più accurato:
-    // - Windows: usare Performance Counters o RAPL (Intel)
-    // - Linux: usare /sys/class/powercap/intel-rapl
-    // - macOS: usare powermetrics
-    
-    let cpu_usage = sys.global_cpu_info().cpu_usage();
-    
-    // Modello di stima del consumo:
-    // - PC base: 50W (idle)
-    // - CPU usage: fino a 100W aggiuntivi
-    // - Memoria/disco: ~20W
-    
-    let base_consumption = 50.0;
-    let cpu_power = (cpu_usage / 100.0) * 100.0;
-    let system_overhead = 20.0;
-    
-    let total_power = base_consumption + cpu_power + system_overhead;
-    
-    Ok(total_power)
+// Istanza riusata tra le chiamate: l'utilizzo è calcolato sul delta
+// rispetto al refresh precedente
+static MONITOR: Lazy<Mutex<SystemMonitor>> = Lazy::new(|| Mutex::new(SystemMonitor::new()));
+
+/// Consumo dell'intero sistema con lo stesso modello della dashboard: il
+/// consumo della batteria mentre si scarica, altrimenti la stima per core da
+/// carico, frequenza e C-state (`cpu::estimate_cpu_power`)
+pub async fn get_current_consumption() -> Result<f64> {
+    let mut monitor = MONITOR.lock()?;
+    monitor
+        .power_draw()
+        .get()
+        .map(f64::from)
+        .ok_or_else(|| VeraError::SensorUnavailable("power_draw".to_string()))
 }
 
-pub async fn start_monitoring(app: AppHandle) {
-    let mut check_interval = interval(Duration::from_secs(5));
-    let mut peak_power_today = 0.0;
-    
-    loop {
-        check_interval.tick().await;
-        
-        match get_current_consumption().await {
-            Ok(power) => {
-                // Aggiorna peak power
-                if power > peak_power_today {
-                    peak_power_today = power;
-                }
-                
-                // Calcola incremento kWh: (Watt * secondi) / (1000 * 3600)
-                let kwh_increment = (power * 5.0) / (1000.0 * 3600.0);
-                
-                // Salva lettura nel database
-                if let Err(e) = storage::db::save_reading(power, kwh_increment).await {
-                    eprintln!("Error saving energy reading: {}", e);
-                    continue;
-                }
-                
-                // Recupera statistiche dal database
-                match storage::db::get_stats("day", "pc").await {
-                    Ok(mut stats) => {
-                        stats.current_power = power;
-                        stats.peak_today = peak_power_today;
-                        
-                        // Emetti evento al frontend per aggiornamento real-time
-                        app.emit_all("energy-update", &stats).ok();
-                    }
-                    Err(e) => {
-                        eprintln!("Error getting stats: {}", e);
+/// Accoda le letture al writer e pubblica le statistiche aggiornate
+///
+/// Nessuna operazione attende il database: le scritture passano dalla coda
+/// di `ReadingWriter` e le statistiche sono calcolate in un task separato.
+pub struct StorageSink {
+    writer: ReadingWriter,
+    // Il primo Start dopo l'avvio segue un riavvio, i successivi una pausa
+    session_started: AtomicBool,
+    // Ultima lettura accodata (Unix seconds), 0 finché non è nota
+    last_stored_at: AtomicI64,
+    // Evita di accumulare calcoli delle statistiche se il database è lento
+    stats_pending: Arc<AtomicBool>,
+}
+
+impl StorageSink {
+    pub fn new(writer: ReadingWriter) -> Self {
+        Self {
+            writer,
+            session_started: AtomicBool::new(false),
+            last_stored_at: AtomicI64::new(0),
+            stats_pending: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn store_step(&self, power: f64, step: Step) -> Result<()> {
+        let now = chrono::Utc::now().timestamp();
+        let kwh_increment = match step {
+            Step::Energy { kwh, .. } => kwh,
+            Step::Start => {
+                let reason = if self.session_started.swap(true, Ordering::SeqCst) {
+                    GapReason::Paused
+                } else {
+                    GapReason::Restart
+                };
+                // Il tempo dall'ultima lettura salvata non è stato misurato.
+                // Solo dopo un riavvio serve chiederla al database.
+                let last = match self.last_stored_at.load(Ordering::SeqCst) {
+                    0 => storage::db::last_reading_timestamp().await?,
+                    last => Some(last),
+                };
+                if let Some(last) = last {
+                    if now > last {
+                        self.writer.queue_gap(last, now, reason.as_str())?;
                     }
                 }
+                0.0
             }
-            Err(e) => {
-                eprintln!("Error monitoring energy: {}", e);
+            Step::Gap { started_at, ended_at, reason } => {
+                self.writer.queue_gap(started_at, ended_at, reason.as_str())?;
+                0.0
             }
+        };
+        
+        self.writer.queue_reading(DEVICE_TYPE, power, kwh_increment)?;
+        self.last_stored_at.store(now, Ordering::SeqCst);
+        // Ogni lettura anche come evento, per lo stream dell'API locale
+        events::publish(
+            "reading",
+            &storage::EnergyReading {
+                timestamp: now,
+                device_type: DEVICE_TYPE.to_string(),
+                power_watts: power,
+                kwh_increment,
+            },
+        );
+        
+        self.emit_stats(power);
+        Ok(())
+    }
+
+    fn emit_stats(&self, power: f64) {
+        if self.stats_pending.swap(true, Ordering::SeqCst) {
+            return;
         }
+        
+        let pending = Arc::clone(&self.stats_pending);
+        tokio::spawn(async move {
+            // Il picco di oggi arriva dal database, così si azzera a mezzanotte
+            match storage::db::get_stats("day", DEVICE_TYPE).await {
+                Ok(mut stats) => {
+                    stats.current_power = power;
+                    // Aggiornamento real-time per il frontend e gli altri ascoltatori
+                    events::publish("energy-update", &stats);
+                }
+                Err(e) => eprintln!("Failed to compute energy stats: {}", e),
+            }
+            pending.store(false, Ordering::SeqCst);
+        });
+    }
+}
+
+impl EnergySink for StorageSink {
+    fn measure(&self) -> impl Future<Output = Result<f64>> + Send {
+        get_current_consumption()
+    }
+
+    fn store(&self, power: f64, step: Step) -> impl Future<Output = Result<()>> + Send {
+        self.store_step(power, step)
     }
 }
 
@@ -83,21 +135,21 @@ pub async fn start_monitoring(app: AppHandle) {
 // (Richiedono crate aggiuntive e privilegi amministratore)
 
 #[cfg(target_os = "windows")]
-pub async fn get_detailed_power_windows() -> Result<f64, Box<dyn std::error::Error>> {
+pub async fn get_detailed_power_windows() -> Result<f64> {
     // TODO: Implementare usando Windows Performance Counters
     // Requires: winapi crate
     // Counter: \\Processor(_Total)\\% Processor Time
     // Counter: \\System\\Processor Queue Length
     
-    Err("Not implemented yet".into())
+    Err(VeraError::SensorUnavailable("hardware power counters".to_string()))
 }
 
 #[cfg(target_os = "linux")]
-pub async fn get_detailed_power_linux() -> Result<f64, Box<dyn std::error::Error>> {
+pub async fn get_detailed_power_linux() -> Result<f64> {
     // TODO: Implementare leggendo da /sys/class/powercap/intel-rapl
     // Richiede: lettura file system
     
-    Err("Not implemented yet".into())
+    Err(VeraError::SensorUnavailable("hardware power counters".to_string()))
 }
 
 // Funzione per calibrare il modello di stima
@@ -0,0 +1,358 @@
+// Controllable energy sampling service
+//
+// The sampling loop runs as a background task driven by a watch channel:
+// start/pause/stop and interval changes take effect immediately, and
+// stopping (or quitting the app) records the energy accumulated since the
+// last tick instead of dropping it. Readings go through `EnergyIntegrator`
+// and the resulting steps are persisted by an `EnergySink`.
+use serde::Serialize;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, MissedTickBehavior};
+
+use super::integrator::{EnergyIntegrator, Step};
+use super::monitor::StorageSink;
+use crate::error::{Result, VeraError};
+use crate::storage::writer::ReadingWriter;
+
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+pub const MIN_INTERVAL_SECS: u64 = 1;
+pub const MAX_INTERVAL_SECS: u64 = 3600;
+
+/// How long app exit waits for the final reading to be written
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MonitoringState {
+    Running,
+    Paused,
+    Stopped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitoringStatus {
+    pub state: MonitoringState,
+    pub interval_secs: u64,
+    pub uptime_secs: Option<u64>,
+    pub sample_count: u64,
+    pub last_sample_at: Option<i64>,   // Unix seconds
+    pub last_error: Option<String>,
+}
+
+/// Where readings come from and where integrated steps go
+pub trait EnergySink: Send + Sync + 'static {
+    /// Current power draw in Watts
+    fn measure(&self) -> impl Future<Output = Result<f64>> + Send;
+
+    fn store(&self, power: f64, step: Step) -> impl Future<Output = Result<()>> + Send;
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Control {
+    state: MonitoringState,
+    interval: Duration,
+    // Bumped on every start so a task still flushing after stop() exits
+    generation: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    started_at: Option<Instant>,
+    sample_count: u64,
+    last_sample_at: Option<i64>,
+    last_error: Option<String>,
+}
+
+impl Counters {
+    fn record(&mut self, result: Result<()>) {
+        match result {
+            Ok(()) => {
+                self.sample_count += 1;
+                self.last_sample_at = Some(chrono::Utc::now().timestamp());
+            }
+            Err(e) => {
+                eprintln!("Error monitoring energy: {}", e);
+                self.last_error = Some(e.to_string());
+            }
+        }
+    }
+}
+
+pub struct MonitoringService {
+    control: watch::Sender<Control>,
+    counters: Arc<Mutex<Counters>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl MonitoringService {
+    pub fn new(interval: Duration) -> Self {
+        let (control, _) = watch::channel(Control {
+            state: MonitoringState::Stopped,
+            interval,
+            generation: 0,
+        });
+
+        Self {
+            control,
+            counters: Arc::new(Mutex::new(Counters::default())),
+            task: Mutex::new(None),
+        }
+    }
+
+    /// Start sampling, or resume if paused. No-op when already running.
+    /// Must be called from within the Tokio runtime.
+    pub fn start(&self, writer: &ReadingWriter) -> Result<()> {
+        self.start_with(StorageSink::new(writer.clone()))
+    }
+
+    fn start_with<K: EnergySink>(&self, sink: K) -> Result<()> {
+        match self.state() {
+            MonitoringState::Running => Ok(()),
+            MonitoringState::Paused => {
+                self.set_state(MonitoringState::Running);
+                Ok(())
+            }
+            MonitoringState::Stopped => {
+                let mut task = self.task.lock()?;
+                self.control.send_modify(|c| {
+                    c.state = MonitoringState::Running;
+                    c.generation += 1;
+                });
+                {
+                    let mut counters = self.counters.lock()?;
+                    counters.started_at = Some(Instant::now());
+                    counters.last_error = None;
+                }
+
+                let control = self.control.subscribe();
+                let counters = Arc::clone(&self.counters);
+                *task = Some(tokio::spawn(run(sink, control, counters)));
+                Ok(())
+            }
+        }
+    }
+
+    /// Stop taking samples but keep the task alive
+    pub fn pause(&self) -> Result<()> {
+        match self.state() {
+            MonitoringState::Stopped => Err(VeraError::InvalidState(
+                "monitoring is stopped".to_string(),
+            )),
+            _ => {
+                self.set_state(MonitoringState::Paused);
+                Ok(())
+            }
+        }
+    }
+
+    /// Stop the task; the pending reading is written before it exits
+    pub fn stop(&self) -> Result<()> {
+        self.set_state(MonitoringState::Stopped);
+        self.counters.lock()?.started_at = None;
+        Ok(())
+    }
+
+    pub fn set_interval(&self, seconds: u64) -> Result<()> {
+        if !(MIN_INTERVAL_SECS..=MAX_INTERVAL_SECS).contains(&seconds) {
+            return Err(VeraError::invalid_argument(
+                "seconds",
+                format!("must be between {} and {}", MIN_INTERVAL_SECS, MAX_INTERVAL_SECS),
+            ));
+        }
+
+        self.control.send_modify(|c| c.interval = Duration::from_secs(seconds));
+        Ok(())
+    }
+
+    pub fn state(&self) -> MonitoringState {
+        self.control.borrow().state
+    }
+
+    pub fn status(&self) -> Result<MonitoringStatus> {
+        let control = *self.control.borrow();
+        let counters = self.counters.lock()?;
+
+        Ok(MonitoringStatus {
+            state: control.state,
+            interval_secs: control.interval.as_secs(),
+            uptime_secs: counters.started_at.map(|t| t.elapsed().as_secs()),
+            sample_count: counters.sample_count,
+            last_sample_at: counters.last_sample_at,
+            last_error: counters.last_error.clone(),
+        })
+    }
+
+    /// Stop and wait for the final reading to be flushed (app exit)
+    pub async fn shutdown(&self) {
+        if let Err(e) = self.stop() {
+            eprintln!("Error stopping monitoring: {}", e);
+        }
+
+        let task = self.task.lock().ok().and_then(|mut t| t.take());
+        if let Some(task) = task {
+            if tokio::time::timeout(SHUTDOWN_TIMEOUT, task).await.is_err() {
+                eprintln!("Monitoring did not stop within {:?}", SHUTDOWN_TIMEOUT);
+            }
+        }
+    }
+
+    fn set_state(&self, state: MonitoringState) {
+        self.control.send_modify(|c| c.state = state);
+    }
+}
+
+impl Default for MonitoringService {
+    fn default() -> Self {
+        Self::new(DEFAULT_INTERVAL)
+    }
+}
+
+async fn run<K: EnergySink>(sink: K, mut control: watch::Receiver<Control>, counters: Arc<Mutex<Counters>>) {
+    let mut current = *control.borrow_and_update();
+    let generation = current.generation;
+    let mut ticker = interval(current.interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut integrator = EnergyIntegrator::new(current.interval);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                if current.state != MonitoringState::Running {
+                    continue;
+                }
+                let result = sample(&sink, &mut integrator).await;
+                counters.lock().unwrap().record(result);
+            }
+            changed = control.changed() => {
+                let next = *control.borrow_and_update();
+                let finished = changed.is_err()
+                    || next.state == MonitoringState::Stopped
+                    || next.generation != generation;
+
+                // Leaving Running: account for the partial interval now
+                if (finished || next.state != MonitoringState::Running) && integrator.has_previous() {
+                    let result = sample(&sink, &mut integrator).await;
+                    counters.lock().unwrap().record(result);
+                    integrator.reset();
+                }
+
+                if finished {
+                    break;
+                }
+
+                if next.interval != current.interval {
+                    ticker = interval(next.interval);
+                    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                    integrator.set_interval(next.interval);
+                }
+                current = next;
+            }
+        }
+    }
+}
+
+async fn sample<K: EnergySink>(sink: &K, integrator: &mut EnergyIntegrator) -> Result<()> {
+    let power = sink.measure().await?;
+    let step = integrator.push(power);
+    sink.store(power, step).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct FakeSink {
+        steps: Arc<Mutex<Vec<Step>>>,
+        fail: bool,
+    }
+
+    impl FakeSink {
+        fn steps(&self) -> Vec<Step> {
+            self.steps.lock().unwrap().clone()
+        }
+    }
+
+    impl EnergySink for FakeSink {
+        fn measure(&self) -> impl Future<Output = Result<f64>> + Send {
+            let result = if self.fail {
+                Err(VeraError::SensorUnavailable("power".to_string()))
+            } else {
+                Ok(100.0)
+            };
+            std::future::ready(result)
+        }
+
+        fn store(&self, _power: f64, step: Step) -> impl Future<Output = Result<()>> + Send {
+            self.steps.lock().unwrap().push(step);
+            std::future::ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_start_pause_stop_flushes() {
+        let service = MonitoringService::new(Duration::from_millis(20));
+        let sink = FakeSink::default();
+        service.start_with(sink.clone()).unwrap();
+        tokio::time::sleep(Duration::from_millis(70)).await;
+
+        // First reading after start has nothing to integrate
+        assert_eq!(sink.steps()[0], Step::Start);
+        assert!(sink.steps().len() >= 2);
+        assert!(matches!(sink.steps()[1], Step::Energy { .. }));
+
+        service.pause().unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let paused_count = sink.steps().len();
+        // Pausing flushed the partial interval
+        assert!(matches!(sink.steps().last(), Some(Step::Energy { .. })));
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(sink.steps().len(), paused_count);
+        assert_eq!(service.status().unwrap().state, MonitoringState::Paused);
+
+        // Resuming starts a new session
+        service.start_with(sink.clone()).unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(sink.steps()[paused_count], Step::Start);
+        service.shutdown().await;
+
+        let status = service.status().unwrap();
+        assert_eq!(status.state, MonitoringState::Stopped);
+        assert_eq!(status.uptime_secs, None);
+        assert_eq!(status.sample_count as usize, sink.steps().len());
+        assert!(matches!(sink.steps().last(), Some(Step::Energy { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_reported_in_status() {
+        let service = MonitoringService::new(Duration::from_millis(10));
+        let sink = FakeSink {
+            fail: true,
+            ..Default::default()
+        };
+        service.start_with(sink.clone()).unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        service.shutdown().await;
+
+        let status = service.status().unwrap();
+        assert!(sink.steps().is_empty());
+        assert_eq!(status.sample_count, 0);
+        assert_eq!(status.last_error.as_deref(), Some("Sensor unavailable: power"));
+    }
+
+    #[test]
+    fn test_invalid_transitions() {
+        let service = MonitoringService::default();
+        assert_eq!(service.state(), MonitoringState::Stopped);
+        assert_eq!(service.pause().unwrap_err().code(), "INVALID_STATE");
+        assert_eq!(service.set_interval(0).unwrap_err().code(), "INVALID_ARGUMENT");
+        service.set_interval(60).unwrap();
+        assert_eq!(service.status().unwrap().interval_secs, 60);
+    }
+}
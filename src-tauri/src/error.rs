@@ -0,0 +1,141 @@
+// Crate-wide error type
+//
+// Every Tauri command returns `Result<T, VeraError>`. Errors reach the
+// frontend as `{code, message, details}` so the UI can branch on `code`
+// instead of parsing messages.
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+pub type Result<T> = std::result::Result<T, VeraError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum VeraError {
+    #[error("Storage error: {0}")]
+    Storage(#[from] rusqlite::Error),
+
+    #[error("Storage is falling behind, write queue of {0} is full")]
+    Backpressure(usize),
+
+    #[error("Sensor unavailable: {0}")]
+    SensorUnavailable(String),
+
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Invalid argument `{field}`: {message}")]
+    InvalidArgument { field: String, message: String },
+
+    #[error("Invalid state: {0}")]
+    InvalidState(String),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
+
+impl VeraError {
+    pub fn invalid_argument(field: &str, message: impl Into<String>) -> Self {
+        VeraError::InvalidArgument {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+
+    /// Stable identifier the frontend can match on
+    pub fn code(&self) -> &'static str {
+        match self {
+            VeraError::Storage(_) => "STORAGE_ERROR",
+            VeraError::Backpressure(_) => "STORAGE_BACKPRESSURE",
+            VeraError::SensorUnavailable(_) => "SENSOR_UNAVAILABLE",
+            VeraError::PermissionDenied(_) => "PERMISSION_DENIED",
+            VeraError::Unauthorized(_) => "AUTHENTICATION_ERROR",
+            VeraError::InvalidArgument { .. } => "INVALID_ARGUMENT",
+            VeraError::InvalidState(_) => "INVALID_STATE",
+            VeraError::NotFound(_) => "NOT_FOUND",
+            VeraError::Config(_) => "CONFIG_ERROR",
+            VeraError::Internal(_) => "INTERNAL_ERROR",
+        }
+    }
+
+    fn details(&self) -> Option<Value> {
+        match self {
+            VeraError::Storage(e) => Some(serde_json::json!({ "source": e.to_string() })),
+            VeraError::Backpressure(capacity) => Some(serde_json::json!({ "capacity": capacity })),
+            VeraError::SensorUnavailable(sensor) => Some(serde_json::json!({ "sensor": sensor })),
+            VeraError::InvalidArgument { field, .. } => Some(serde_json::json!({ "field": field })),
+            VeraError::NotFound(id) => Some(serde_json::json!({ "id": id })),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for VeraError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("VeraError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("details", &self.details())?;
+        state.end()
+    }
+}
+
+// Poisoned locks only happen after a panic in another thread
+impl<T> From<std::sync::PoisonError<T>> for VeraError {
+    fn from(e: std::sync::PoisonError<T>) -> Self {
+        VeraError::Internal(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for VeraError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::PermissionDenied => VeraError::PermissionDenied(e.to_string()),
+            std::io::ErrorKind::NotFound => VeraError::NotFound(e.to_string()),
+            _ => VeraError::Internal(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialization_shape() {
+        let error = VeraError::invalid_argument("period", "expected day, week or month");
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "code": "INVALID_ARGUMENT",
+                "message": "Invalid argument `period`: expected day, week or month",
+                "details": { "field": "period" }
+            })
+        );
+
+        let error = VeraError::Config("missing region".to_string());
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "code": "CONFIG_ERROR",
+                "message": "Configuration error: missing region",
+                "details": null
+            })
+        );
+    }
+
+    #[test]
+    fn test_storage_error_conversion() {
+        let error: VeraError = rusqlite::Error::QueryReturnedNoRows.into();
+        assert_eq!(error.code(), "STORAGE_ERROR");
+        assert!(error.details().is_some());
+    }
+}
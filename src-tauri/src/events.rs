@@ -0,0 +1,82 @@
+// In-process event bus
+//
+// Background tasks publish here instead of talking to a Tauri window, so the
+// same code runs in the GUI and in the headless agent. The GUI forwards every
+// event to the webview under the same name; the agent just logs them.
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+/// Events kept for subscribers that fall behind before they start lagging
+const CAPACITY: usize = 256;
+/// Recent events handed to new subscribers that ask for them
+const HISTORY: usize = 256;
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// Event name, e.g. `energy-update` or `new-notification`
+    pub name: &'static str,
+    pub payload: Value,
+}
+
+static BUS: Lazy<broadcast::Sender<Event>> = Lazy::new(|| broadcast::channel(CAPACITY).0);
+// Also locked while sending, so a history snapshot and a subscription
+// taken together neither miss nor repeat an event
+static RECENT: Lazy<Mutex<VecDeque<Event>>> = Lazy::new(|| Mutex::new(VecDeque::with_capacity(HISTORY)));
+
+/// Send `payload` to every current subscriber; dropped if nobody listens
+pub fn publish<T: Serialize>(name: &'static str, payload: &T) {
+    let payload = match serde_json::to_value(payload) {
+        Ok(payload) => payload,
+        Err(e) => {
+            eprintln!("Failed to serialize {} event: {}", name, e);
+            return;
+        }
+    };
+
+    let event = Event { name, payload };
+    let mut recent = RECENT.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if recent.len() == HISTORY {
+        recent.pop_front();
+    }
+    recent.push_back(event.clone());
+    BUS.send(event).ok();
+}
+
+pub fn subscribe() -> broadcast::Receiver<Event> {
+    BUS.subscribe()
+}
+
+/// The last events published, oldest first, and a receiver for the ones after
+pub fn subscribe_with_history() -> (Vec<Event>, broadcast::Receiver<Event>) {
+    let recent = RECENT.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    (recent.iter().cloned().collect(), BUS.subscribe())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_and_subscription_do_not_overlap() {
+        publish("test-event", &"before");
+        let (history, mut receiver) = subscribe_with_history();
+        publish("test-event", &"after");
+
+        let seen = |events: &[Event], text: &str| {
+            events.iter().any(|e| e.name == "test-event" && e.payload == text)
+        };
+        assert!(seen(&history, "before"));
+        assert!(!seen(&history, "after"));
+
+        let mut live = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            live.push(event);
+        }
+        assert!(seen(&live, "after"));
+        assert!(!seen(&live, "before"));
+    }
+}
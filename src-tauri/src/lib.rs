@@ -0,0 +1,210 @@
+// VERA backend: system monitoring, energy accounting, storage and notifications
+pub mod agent;
+pub mod api;
+pub mod cli;
+#[cfg(feature = "gui")]
+pub mod commands;
+pub mod energy;
+pub mod error;
+pub mod events;
+pub mod notifications;
+pub mod permissions;
+pub mod storage;
+pub mod system_monitor;
+pub mod utils;
+
+#[cfg(feature = "gui")]
+pub use gui::{run, AppState};
+
+#[cfg(feature = "gui")]
+mod gui {
+    use std::sync::{Mutex, OnceLock};
+    use tauri::{AppHandle, Emitter, Manager, RunEvent, UserAttentionType};
+
+    use crate::api::{ApiConfig, ApiServer};
+    use crate::energy::service::{MonitoringService, DEFAULT_INTERVAL};
+    use crate::notifications::desktop;
+    use crate::storage::writer::ReadingWriter;
+    use crate::system_monitor::SystemMonitor;
+    use crate::{commands, events, notifications, storage};
+
+    /// Set to the database of a running `vera-agent` to view it read-only
+    const AGENT_DATABASE_ENV: &str = "VERA_AGENT_DATABASE";
+
+    pub struct AppState {
+        pub(crate) monitor: Mutex<SystemMonitor>,
+        pub(crate) monitoring: MonitoringService,
+        // Unset when attached read-only to the agent's database
+        pub(crate) writer: OnceLock<ReadingWriter>,
+        pub(crate) api: ApiServer,
+    }
+
+    pub fn run() {
+        tauri::Builder::default()
+            .plugin(tauri_plugin_notification::init())
+            .manage(AppState {
+                monitor: Mutex::new(SystemMonitor::new()),
+                monitoring: MonitoringService::default(),
+                writer: OnceLock::new(),
+                api: ApiServer::default(),
+            })
+            .setup(|app| {
+                let handle = app.handle().clone();
+                tauri::async_runtime::spawn(forward_events(handle.clone()));
+                tauri::async_runtime::spawn(desktop::start(WindowHost(handle.clone())));
+
+                if let Some(path) = std::env::var_os(AGENT_DATABASE_ENV) {
+                    // The agent owns monitoring, storage, notifications and the REST API
+                    storage::db::attach_read_only(path.into());
+                    tauri::async_runtime::spawn(async {
+                        if let Err(e) = storage::db::init_database().await {
+                            eprintln!("Failed to attach to agent database: {}", e);
+                            return;
+                        }
+                        poll_stats().await;
+                    });
+                    return Ok(());
+                }
+
+                // Keep the database next to the other app data, not in the working directory
+                let data_dir = app.path().app_data_dir()?;
+                std::fs::create_dir_all(&data_dir)?;
+                storage::db::set_database_path(data_dir.join("vera_data.db"));
+                // Readings are persisted off the monitoring loop, on their own connection
+                let writer = ReadingWriter::open(&storage::db::database_path())?;
+                app.state::<AppState>().writer.set(writer).ok();
+
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = storage::db::init_database().await {
+                        eprintln!("Failed to initialize database: {}", e);
+                        return;
+                    }
+
+                    let state = handle.state::<AppState>();
+                    if let Some(writer) = state.writer.get() {
+                        if let Err(e) = state.monitoring.start(writer) {
+                            eprintln!("Failed to start monitoring: {}", e);
+                        }
+                    }
+                    tauri::async_runtime::spawn(notifications::scheduler::start());
+                    tauri::async_runtime::spawn(notifications::webhook::start());
+                    start_api(&state.api).await;
+                });
+
+                Ok(())
+            })
+            .invoke_handler(tauri::generate_handler![
+                commands::greet,
+                commands::get_current_power,
+                commands::get_system_metrics,
+                commands::get_active_applications,
+                commands::get_energy_stats,
+                commands::get_settings,
+                commands::update_settings,
+                commands::get_notifications,
+                commands::count_unread_notifications,
+                commands::mark_notification_read,
+                commands::mark_all_notifications_read,
+                commands::delete_notification,
+                commands::get_notification_rules,
+                commands::save_notification_rule,
+                commands::delete_notification_rule,
+                commands::get_webhook_deliveries,
+                commands::test_webhook,
+                commands::check_permissions,
+                commands::start_monitoring,
+                commands::pause_monitoring,
+                commands::stop_monitoring,
+                commands::set_monitoring_interval,
+                commands::get_monitoring_status,
+                commands::get_storage_status
+            ])
+            .build(tauri::generate_context!())
+            .expect("error while building tauri application")
+            .run(|app, event| {
+                // Write the reading in progress before the process goes away
+                if let RunEvent::Exit = event {
+                    let state = app.state::<AppState>();
+                    tauri::async_runtime::block_on(async {
+                        state.api.shutdown().await;
+                        state.monitoring.shutdown().await;
+                        if let Some(writer) = state.writer.get() {
+                            if let Err(e) = writer.flush().await {
+                                eprintln!("Failed to flush pending readings: {}", e);
+                            }
+                        }
+                    });
+                }
+            });
+    }
+
+    /// Relay background events to the webview under the same name
+    async fn forward_events(app: AppHandle) {
+        use tokio::sync::broadcast::error::RecvError;
+
+        let mut events = events::subscribe();
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    app.emit(event.name, &event.payload).ok();
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// Desktop notifications bring the main window back
+    struct WindowHost(AppHandle);
+
+    impl desktop::Host for WindowHost {
+        #[cfg(not(target_os = "linux"))]
+        fn show(&self, notification: &storage::Notification) -> crate::error::Result<()> {
+            use tauri_plugin_notification::NotificationExt;
+
+            self.0
+                .notification()
+                .builder()
+                .title(&notification.title)
+                .body(&notification.message)
+                .show()
+                .map_err(|e| crate::error::VeraError::Internal(e.to_string()))
+        }
+
+        fn view(&self, _notification: &storage::Notification) {
+            if let Some(window) = self.0.get_webview_window("main") {
+                window.show().ok();
+                window.unminimize().ok();
+                window.set_focus().ok();
+            }
+        }
+
+        fn fallback(&self, _notification: &storage::Notification) {
+            if let Some(window) = self.0.get_webview_window("main") {
+                window.request_user_attention(Some(UserAttentionType::Informational)).ok();
+            }
+        }
+    }
+
+    /// Serve the REST API if it is enabled in the settings
+    async fn start_api(api: &ApiServer) {
+        let config = storage::db::load_settings()
+            .await
+            .and_then(|settings| ApiConfig::from_settings(&settings));
+        if let Err(e) = async { api.apply(config?).await }.await {
+            eprintln!("Failed to start REST API: {}", e);
+        }
+    }
+
+    /// Without a local monitor, refresh the dashboard from the agent's readings
+    async fn poll_stats() {
+        let mut ticker = tokio::time::interval(DEFAULT_INTERVAL);
+        loop {
+            ticker.tick().await;
+            match storage::db::get_stats("day", "pc").await {
+                Ok(stats) => events::publish("energy-update", &stats),
+                Err(e) => eprintln!("Failed to read agent stats: {}", e),
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+// Real-time alerts
+//
+// Alert rules are checked on every power sample rather than once a minute.
+// An alert fires when all of its limits have been exceeded for the minimum
+// duration. It then stays quiet until one of them clears by its hysteresis,
+// so a value hovering around the threshold alerts once, and it does not
+// fire again within the cooldown.
+use super::rules::{Limit, Rule, SampleMetric, Trigger};
+use crate::storage::EnergyReading;
+use crate::system_monitor::power_supply::{self, PowerSupplyState};
+
+/// Samples further apart than this, e.g. around a pause, start over
+pub const MAX_GAP_SECS: i64 = 300;
+
+/// What alert limits are checked against
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Sample {
+    pub timestamp: i64,
+    pub power: Option<f64>,
+    pub battery_percent: Option<f64>,
+    pub battery_discharge: Option<f64>,
+}
+
+impl Sample {
+    /// `reading` with the battery state, read only when an alert needs it
+    pub fn read(reading: &EnergyReading, rules: &[Rule]) -> Self {
+        let battery = rules.iter().filter(|r| r.enabled).any(|rule| match &rule.trigger {
+            Trigger::Alert { limits, .. } => limits.iter().any(|l| l.metric != SampleMetric::Power),
+            _ => false,
+        });
+        let supply = if battery { power_supply::read() } else { PowerSupplyState::default() };
+        Self::new(reading, &supply)
+    }
+
+    pub fn new(reading: &EnergyReading, supply: &PowerSupplyState) -> Self {
+        let discharge = match supply.discharging {
+            true => supply.battery_power.map(f64::from),
+            false => Some(0.0),
+        };
+        Self {
+            timestamp: reading.timestamp,
+            power: Some(reading.power_watts),
+            battery_percent: supply.battery_percentage.map(f64::from),
+            battery_discharge: discharge.filter(|_| supply.battery_present),
+        }
+    }
+
+    pub fn value(&self, metric: SampleMetric) -> Option<f64> {
+        match metric {
+            SampleMetric::Power => self.power,
+            SampleMetric::BatteryPercent => self.battery_percent,
+            SampleMetric::BatteryDischarge => self.battery_discharge,
+        }
+    }
+}
+
+/// Where one alert rule stands
+#[derive(Debug, Default)]
+pub struct AlertState {
+    /// When the limits were first exceeded, until they clear
+    since: Option<i64>,
+    /// Already fired since then
+    fired: bool,
+    last_fired: Option<i64>,
+    last_sample: Option<i64>,
+}
+
+impl AlertState {
+    /// Whether the alert fires on `sample`. A limit whose value the sample
+    /// lacks, such as the battery on a desktop, counts as cleared.
+    pub fn update(&mut self, limits: &[Limit], duration_secs: u64, cooldown_secs: u64, sample: &Sample) -> bool {
+        let now = sample.timestamp;
+        if self.last_sample.is_some_and(|last| now - last > MAX_GAP_SECS) {
+            self.clear();
+        }
+        self.last_sample = Some(now);
+
+        let values: Option<Vec<f64>> = limits.iter().map(|l| sample.value(l.metric)).collect();
+        let Some(values) = values else {
+            self.clear();
+            return false;
+        };
+        let mut limits = limits.iter().zip(values);
+        let since = match self.since {
+            Some(_) if limits.any(|(limit, value)| limit.cleared(value)) => {
+                self.clear();
+                return false;
+            }
+            Some(since) => since,
+            None if limits.all(|(limit, value)| limit.exceeded(value)) => *self.since.insert(now),
+            None => return false,
+        };
+
+        if self.fired || now - since < duration_secs as i64 {
+            return false;
+        }
+        if self.last_fired.is_some_and(|last| now - last < cooldown_secs as i64) {
+            return false;
+        }
+        self.fired = true;
+        self.last_fired = Some(now);
+        true
+    }
+
+    /// When the limits were first exceeded, while they still are
+    pub fn since(&self) -> Option<i64> {
+        self.since
+    }
+
+    fn clear(&mut self) {
+        self.since = None;
+        self.fired = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn above(metric: SampleMetric, threshold: f64, hysteresis: f64) -> Limit {
+        Limit { metric, above: Some(threshold), below: None, hysteresis }
+    }
+
+    fn power(timestamp: i64, power: f64) -> Sample {
+        Sample { timestamp, power: Some(power), ..Default::default() }
+    }
+
+    /// Times `state` fired over `(seconds, watts)` samples
+    fn fired(state: &mut AlertState, limits: &[Limit], duration: u64, cooldown: u64, samples: &[(i64, f64)]) -> Vec<i64> {
+        samples
+            .iter()
+            .filter(|(t, w)| state.update(limits, duration, cooldown, &power(*t, *w)))
+            .map(|(t, _)| *t)
+            .collect()
+    }
+
+    #[test]
+    fn test_duration_and_hysteresis() {
+        // Over 200 W for 10 minutes, clearing at 180 W
+        let limits = [above(SampleMetric::Power, 200.0, 20.0)];
+        let mut state = AlertState::default();
+
+        // Dips to 190 W do not restart the 10 minutes
+        let samples = [(0, 150.0), (60, 210.0), (300, 190.0), (600, 220.0), (660, 230.0), (900, 240.0)];
+        assert_eq!(fired(&mut state, &limits, 600, 0, &samples), [660]);
+
+        // Flapping around the threshold after that does not fire again
+        let samples = [(960, 195.0), (1020, 205.0), (1080, 199.0), (1200, 210.0), (1500, 205.0)];
+        assert!(fired(&mut state, &limits, 600, 0, &samples).is_empty());
+
+        // Clearing below 180 W starts over
+        let samples = [(1560, 170.0), (1620, 210.0), (1920, 210.0), (2160, 210.0), (2220, 210.0)];
+        assert_eq!(fired(&mut state, &limits, 600, 0, &samples), [2220]);
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let limits = [above(SampleMetric::Power, 100.0, 0.0)];
+        let mut state = AlertState::default();
+
+        let samples = [(0, 150.0), (60, 90.0), (120, 150.0), (180, 90.0), (420, 90.0), (600, 150.0)];
+        assert_eq!(fired(&mut state, &limits, 0, 600, &samples), [0, 600]);
+
+        // Still over when the cooldown ends: fires then
+        let mut state = AlertState::default();
+        let samples = [(0, 150.0), (60, 90.0), (120, 150.0), (300, 150.0), (600, 150.0), (660, 150.0)];
+        assert_eq!(fired(&mut state, &limits, 0, 600, &samples), [0, 600]);
+    }
+
+    #[test]
+    fn test_gaps_start_over() {
+        let limits = [above(SampleMetric::Power, 100.0, 0.0)];
+        let mut state = AlertState::default();
+
+        // Monitoring paused in between: 10 minutes apart is not 10 minutes over
+        let samples = [(0, 150.0), (600, 150.0), (900, 150.0), (1200, 150.0)];
+        assert_eq!(fired(&mut state, &limits, 600, 0, &samples), [1200]);
+    }
+
+    #[test]
+    fn test_battery_while_discharging() {
+        // Battery below 20% while discharging over 40 W
+        let limits = [
+            Limit { metric: SampleMetric::BatteryPercent, above: None, below: Some(20.0), hysteresis: 5.0 },
+            above(SampleMetric::BatteryDischarge, 40.0, 5.0),
+        ];
+        let reading = |timestamp| EnergyReading {
+            timestamp,
+            device_type: "pc".to_string(),
+            power_watts: 60.0,
+            kwh_increment: 0.0,
+        };
+        let supply = |percent: f32, discharging: bool| PowerSupplyState {
+            battery_present: true,
+            discharging,
+            battery_percentage: Some(percent),
+            battery_power: Some(45.0),
+            ..Default::default()
+        };
+        let mut state = AlertState::default();
+
+        assert!(!state.update(&limits, 0, 0, &Sample::new(&reading(0), &supply(18.0, false))));
+        assert!(state.update(&limits, 0, 0, &Sample::new(&reading(60), &supply(18.0, true))));
+        // Charging to 22% is within the hysteresis, unplugging again is not a new alert
+        assert!(!state.update(&limits, 0, 0, &Sample::new(&reading(120), &supply(22.0, true))));
+        assert!(!state.update(&limits, 0, 0, &Sample::new(&reading(180), &supply(19.0, true))));
+
+        // No battery: never fires
+        let desktop = Sample::new(&reading(240), &PowerSupplyState::default());
+        assert_eq!(desktop.battery_discharge, None);
+        assert!(!AlertState::default().update(&limits, 0, 0, &desktop));
+    }
+}
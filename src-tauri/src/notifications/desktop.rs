@@ -0,0 +1,225 @@
+// Native desktop notifications
+//
+// Every `notification-popup` is handed to the OS, so it is seen while the
+// window is hidden or closed to the tray: each notification as it is
+// delivered, or one digest for several that were held back. On Linux this talks to the
+// freedesktop notification daemon on the session bus directly rather than
+// through the Tauri notification plugin: on desktop the plugin only sets a
+// title, body, icon and sound, with no actions, click callback or urgency,
+// and vera-agent runs without Tauri at all. Elsewhere the GUI shows it
+// through the plugin. Without a notification service the in-app popup is all
+// there is, and the host is asked to draw attention to the window instead.
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+
+#[cfg(target_os = "linux")]
+use super::freedesktop;
+use super::i18n::Locale;
+use crate::error::{Result, VeraError};
+use crate::events;
+use crate::storage::{self, Notification};
+
+/// How long "Snooze" puts a notification off
+pub const SNOOZE: Duration = Duration::from_secs(3600);
+
+/// Buttons offered on a desktop notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    View,
+    Snooze,
+}
+
+impl Action {
+    pub const ALL: [Action; 2] = [Action::View, Action::Snooze];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            Action::View => "view",
+            Action::Snooze => "snooze",
+        }
+    }
+
+    pub fn label(&self, locale: Locale) -> &'static str {
+        locale.text(&format!("action.{}", self.key())).unwrap_or_default()
+    }
+
+    /// "default" is a click on the notification itself
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "view" | "default" => Some(Action::View),
+            "snooze" => Some(Action::Snooze),
+            _ => None,
+        }
+    }
+}
+
+/// freedesktop urgency levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Urgency {
+    Low = 0,
+    Normal = 1,
+    Critical = 2,
+}
+
+impl Urgency {
+    /// From the notification type. Critical ones stay on screen until
+    /// dismissed, so only the explicit "critical" type gets it.
+    pub fn of(type_: &str) -> Self {
+        match type_ {
+            "critical" => Urgency::Critical,
+            "success" => Urgency::Low,
+            _ => Urgency::Normal,
+        }
+    }
+}
+
+/// What the application around the notifier provides
+pub trait Host: Send + 'static {
+    /// Show through the platform notifier where there is no daemon client
+    fn show(&self, _notification: &Notification) -> Result<()> {
+        Err(VeraError::Internal("no desktop notification service".to_string()))
+    }
+
+    /// "View report" was chosen
+    fn view(&self, _notification: &Notification) {}
+
+    /// The notification could not be shown by the OS
+    fn fallback(&self, _notification: &Notification) {}
+}
+
+/// The headless agent has no window to bring forward
+pub struct Headless;
+
+impl Host for Headless {}
+
+struct Desktop<H: Host> {
+    host: H,
+    /// Connected lazily and dropped when the daemon goes away
+    #[cfg(target_os = "linux")]
+    daemon: Option<freedesktop::Client>,
+    /// Whether the last notification reached the OS, to log changes only
+    available: bool,
+}
+
+impl<H: Host> Desktop<H> {
+    fn new(host: H) -> Self {
+        Self {
+            host,
+            #[cfg(target_os = "linux")]
+            daemon: None,
+            available: true,
+        }
+    }
+
+    async fn show(&mut self, notification: &Notification) {
+        match self.show_native(notification).await {
+            Ok(()) => {
+                if !self.available {
+                    eprintln!("Desktop notifications are available again");
+                }
+                self.available = true;
+            }
+            Err(e) => {
+                if self.available {
+                    eprintln!("Desktop notifications unavailable, showing them in the app only: {}", e);
+                }
+                self.available = false;
+                self.host.fallback(notification);
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn show_native(&mut self, notification: &Notification) -> Result<()> {
+        // Buttons in the language of the settings
+        let locale = storage::db::load_settings().await.map(|s| s.locale()).unwrap_or_default();
+        let daemon = match &mut self.daemon {
+            Some(daemon) => daemon,
+            None => self.daemon.insert(freedesktop::Client::connect_session().await?),
+        };
+        if let Err(e) = daemon.show(notification, locale).await {
+            // Reconnect next time, the daemon may have been restarted
+            self.daemon = None;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    async fn show_native(&mut self, notification: &Notification) -> Result<()> {
+        self.host.show(notification)
+    }
+
+    /// Next action chosen on one of our notifications
+    async fn next_action(&mut self) -> Option<(Action, Notification)> {
+        #[cfg(target_os = "linux")]
+        if let Some(daemon) = &mut self.daemon {
+            let action = daemon.next_action().await;
+            if action.is_none() {
+                self.daemon = None;
+            }
+            return action;
+        }
+        std::future::pending().await
+    }
+}
+
+/// Show every popup on the desktop and handle its actions
+pub async fn start(host: impl Host) {
+    let mut desktop = Desktop::new(host);
+    let mut events = events::subscribe();
+    let (snoozed_tx, mut snoozed) = mpsc::unbounded_channel();
+
+    loop {
+        tokio::select! {
+            received = events.recv() => match received {
+                Ok(event) if event.name == "notification-popup" => {
+                    match serde_json::from_value::<Notification>(event.payload) {
+                        Ok(notification) => desktop.show(&notification).await,
+                        Err(e) => eprintln!("Failed to read notification event: {}", e),
+                    }
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            },
+            Some((action, notification)) = desktop.next_action() => match action {
+                Action::View => {
+                    events::publish(
+                        "notification-action",
+                        &serde_json::json!({ "id": notification.id, "action": action.key() }),
+                    );
+                    desktop.host.view(&notification);
+                }
+                Action::Snooze => {
+                    let snoozed_tx = snoozed_tx.clone();
+                    tokio::spawn(async move {
+                        tokio::time::sleep(SNOOZE).await;
+                        snoozed_tx.send(notification).ok();
+                    });
+                }
+            },
+            Some(notification) = snoozed.recv() => desktop.show(&notification).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_actions_and_urgency() {
+        for action in Action::ALL {
+            assert_eq!(Action::from_key(action.key()), Some(action));
+            assert!(!action.label(Locale::En).is_empty());
+        }
+        assert_eq!(Action::from_key("default"), Some(Action::View));
+        assert_eq!(Action::from_key("dismiss"), None);
+
+        assert_eq!(Urgency::of("critical"), Urgency::Critical);
+        assert_eq!(Urgency::of("warning"), Urgency::Normal);
+        assert_eq!(Urgency::of("info"), Urgency::Normal);
+        assert_eq!(Urgency::of("success"), Urgency::Low);
+    }
+}
@@ -0,0 +1,820 @@
+// Rule evaluation
+//
+// The scheduler calls `Engine::tick` once a minute with the enabled rules.
+// Scheduled rules fire for every time they matched since their last run,
+// which is stored with the rule, so summaries missed while the app was
+// closed or in quiet hours are caught up with the figures of their own
+// period. Thresholds and anomalies only fire on the crossing. Alerts are
+// not part of the tick: `Engine::sample` checks them on every reading.
+use chrono::{DateTime, Duration, Local, TimeZone};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+
+use super::alerts::{AlertState, Sample};
+use super::i18n::Locale;
+use super::rules::{self, Condition, Metric, Period, Placeholder, Rule, Schedule, Trigger};
+use crate::energy::calculator::{kwh_to_euro, ITALY_AVG_PRICE_PER_KWH};
+use crate::energy::monitor::DEVICE_TYPE;
+use crate::energy::EnergyStats;
+use crate::error::Result;
+use crate::storage::{self, Notification};
+
+/// Device the summaries are about
+const DEVICE: &str = DEVICE_TYPE;
+/// Scheduled times older than this when delivered count as missed
+const ON_TIME: Duration = Duration::minutes(2);
+/// Missed notifications listed in a digest
+const DIGEST_LINES: usize = 10;
+
+/// Consumption figures the rules are evaluated against
+pub(crate) trait Source {
+    /// Stats of the period containing `at`, as they were at `at`, with
+    /// `weekly_trend` and `peak_today` computed for `period`
+    async fn stats(&mut self, period: Period, at: DateTime<Local>) -> Result<EnergyStats>;
+    /// Latest recorded power, W
+    async fn power(&mut self) -> Result<Option<f64>>;
+    /// Average power since `from`, W
+    async fn average_power(&mut self, from: i64) -> Result<Option<f64>>;
+    /// kWh in `[from, to)`
+    async fn kwh_between(&mut self, from: i64, to: i64) -> Result<f64>;
+}
+
+/// Reads from the database, each query at most once per tick
+#[derive(Default)]
+pub struct DbSource {
+    stats: HashMap<(Period, i64), EnergyStats>,
+}
+
+impl Source for DbSource {
+    async fn stats(&mut self, period: Period, at: DateTime<Local>) -> Result<EnergyStats> {
+        if let Some(stats) = self.stats.get(&(period, at.timestamp())) {
+            return Ok(stats.clone());
+        }
+        let stats = storage::db::get_stats_at(period.as_str(), DEVICE, at).await?;
+        self.stats.insert((period, at.timestamp()), stats.clone());
+        Ok(stats)
+    }
+
+    async fn power(&mut self) -> Result<Option<f64>> {
+        Ok(storage::db::get_latest_reading(Some(DEVICE)).await?.map(|r| r.power_watts))
+    }
+
+    async fn average_power(&mut self, from: i64) -> Result<Option<f64>> {
+        let to = Local::now().timestamp() + 1;
+        Ok(storage::db::get_summary(from, to, Some(DEVICE)).await?.avg_power)
+    }
+
+    async fn kwh_between(&mut self, from: i64, to: i64) -> Result<f64> {
+        Ok(storage::db::get_summary(from, to, Some(DEVICE)).await?.kwh)
+    }
+}
+
+#[derive(Default)]
+pub struct Engine {
+    /// Runs already committed, in case storing them failed
+    last_run: HashMap<String, i64>,
+    /// Whether threshold and anomaly rules were over their limit last tick
+    over: HashMap<String, bool>,
+    /// Period a goal rule last looked at
+    goal_period: HashMap<String, DateTime<Local>>,
+    alerts: HashMap<String, AlertState>,
+    /// Language notifications are written in, from the settings
+    pub locale: Locale,
+}
+
+/// What one tick produced
+#[derive(Debug, Default)]
+pub struct Tick {
+    /// Due now
+    pub notifications: Vec<Notification>,
+    /// Scheduled earlier but not delivered, with their time, oldest first
+    pub missed: Vec<(DateTime<Local>, Notification)>,
+    /// Latest scheduled time handled per rule, to store once delivered
+    pub runs: Vec<(String, i64)>,
+}
+
+/// Values a trigger adds to the template
+#[derive(Default)]
+struct Fired {
+    value: Option<f64>,
+    threshold: Option<f64>,
+    average: Option<f64>,
+    /// Power of the sample an alert fired on, which may not be stored yet
+    power: Option<f64>,
+    /// Start of the window the notification is about, so it is stored once
+    /// for it; unset for a scheduled time
+    window: Option<i64>,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Notifications due at `now`, for the enabled rules. Scheduled rules
+    /// fire again until their runs are passed to `commit`.
+    pub(crate) async fn tick(&mut self, rules: &[Rule], now: DateTime<Local>, source: &mut impl Source) -> Tick {
+        let mut tick = Tick::default();
+        for rule in rules.iter().filter(|r| r.enabled) {
+            if let Err(e) = self.evaluate(rule, now, source, &mut tick).await {
+                eprintln!("Failed to evaluate notification rule {}: {}", rule.id, e);
+            }
+        }
+        tick.missed.sort_by_key(|(time, _)| *time);
+        tick
+    }
+
+    /// Alerts fired by `sample`, for the enabled rules
+    pub(crate) async fn sample(&mut self, rules: &[Rule], sample: &Sample, source: &mut impl Source) -> Vec<Notification> {
+        let now = Local.timestamp_opt(sample.timestamp, 0).single().unwrap_or_else(Local::now);
+        // Rules deleted or switched off start over when they come back
+        self.alerts.retain(|id, _| rules.iter().any(|r| r.enabled && r.id == *id));
+
+        let mut notifications = Vec::new();
+        for rule in rules.iter().filter(|r| r.enabled) {
+            let Trigger::Alert { limits, duration_secs, cooldown_secs } = &rule.trigger else { continue };
+            let state = self.alerts.entry(rule.id.clone()).or_default();
+            if !state.update(limits, *duration_secs, *cooldown_secs, sample) {
+                continue;
+            }
+            // The first limit is the one the message is about
+            let fired = Fired {
+                value: limits.first().and_then(|l| sample.value(l.metric)),
+                threshold: limits.first().map(|l| l.threshold()),
+                average: None,
+                power: sample.power,
+                // An alert is about the stretch its limits have been exceeded
+                window: state.since(),
+            };
+            match self.notify(rule, now, now, &fired, source).await {
+                Ok(Some(notification)) => notifications.push(notification),
+                Ok(None) => {}
+                Err(e) => eprintln!("Failed to evaluate notification rule {}: {}", rule.id, e),
+            }
+        }
+        notifications
+    }
+
+    /// Remember runs the scheduler delivered or deliberately dropped
+    pub fn commit(&mut self, runs: &[(String, i64)]) {
+        for (id, run) in runs {
+            let last = self.last_run.entry(id.clone()).or_insert(*run);
+            *last = (*last).max(*run);
+        }
+    }
+
+    async fn evaluate(
+        &mut self,
+        rule: &Rule,
+        now: DateTime<Local>,
+        source: &mut impl Source,
+        tick: &mut Tick,
+    ) -> Result<()> {
+        if let Trigger::Schedule { cron } = &rule.trigger {
+            let schedule = match Schedule::parse(cron) {
+                Ok(schedule) => schedule,
+                Err(e) => {
+                    eprintln!("Invalid schedule in notification rule {}: {}", rule.id, e);
+                    return Ok(());
+                }
+            };
+
+            let last_run = rule.last_run.max(self.last_run.get(&rule.id).copied());
+            // A rule that never ran starts from the minute the app is in
+            let after = last_run
+                .and_then(|run| Local.timestamp_opt(run, 0).single())
+                .unwrap_or(now - Duration::seconds(60));
+            let times = schedule.between(after, now);
+
+            // Render everything first so an error retries the whole backlog
+            let mut due = Vec::new();
+            for &time in &times {
+                if let Some(notification) = self.notify(rule, time, now, &Fired::default(), source).await? {
+                    due.push((time, notification));
+                }
+            }
+            for (time, notification) in due {
+                if time < now - ON_TIME {
+                    tick.missed.push((time, notification));
+                } else {
+                    tick.notifications.push(notification);
+                }
+            }
+
+            match times.last() {
+                Some(time) => tick.runs.push((rule.id.clone(), time.timestamp())),
+                None if last_run.is_none() => tick.runs.push((rule.id.clone(), now.timestamp())),
+                None => {}
+            }
+            return Ok(());
+        }
+
+        if let Some(fired) = self.trigger(rule, now, source).await? {
+            if let Some(notification) = self.notify(rule, now, now, &fired, source).await? {
+                tick.notifications.push(notification);
+            }
+        }
+        Ok(())
+    }
+
+    /// The rule's notification for time `at`, if its conditions held then
+    async fn notify(
+        &self,
+        rule: &Rule,
+        at: DateTime<Local>,
+        now: DateTime<Local>,
+        fired: &Fired,
+        source: &mut impl Source,
+    ) -> Result<Option<Notification>> {
+        for condition in &rule.conditions {
+            let stats = source.stats(condition.period(), at).await?;
+            let value = match condition {
+                Condition::Kwh { period, .. } => period_kwh(&stats, *period),
+                Condition::Trend { .. } => stats.weekly_trend,
+            };
+            if !condition.holds(value) {
+                return Ok(None);
+            }
+        }
+
+        // The values of every language's texts, so the stored notification
+        // can be rendered again in another one
+        let texts = Locale::ALL.map(|locale| rule.template.texts(locale));
+        let mut stats = HashMap::new();
+        let mut params = BTreeMap::new();
+        let power = source.power().await?;
+        for placeholder in texts.iter().flat_map(|(title, message)| [*title, *message]).flat_map(rules::placeholders) {
+            if params.contains_key(placeholder.name) {
+                continue;
+            }
+            let value = match placeholder.period() {
+                Some((period, previous, field)) => {
+                    if let Entry::Vacant(entry) = stats.entry((period, previous)) {
+                        // The last second of the period before is all of it
+                        let at = if previous { period.start(at) - Duration::seconds(1) } else { at };
+                        entry.insert(source.stats(period, at).await?);
+                    }
+                    period_value(&stats[&(period, previous)], period, field)
+                }
+                None => match placeholder.name {
+                    "power" => fired.power.or(power),
+                    "value" => fired.value,
+                    "threshold" => fired.threshold,
+                    "average" => fired.average,
+                    "time" => Some(at.timestamp() as f64),
+                    _ => None,
+                },
+            };
+            if let Some(value) = value {
+                params.insert(placeholder.name.to_string(), value);
+            }
+        }
+
+        let (title, message) = rule.template.texts(self.locale);
+        let value = |p: &Placeholder| params.get(p.name).copied();
+        Ok(Some(Notification {
+            id: Notification::new_id(&rule.id)?,
+            title: rules::render(title, self.locale, value),
+            message: rules::render(message, self.locale, value),
+            timestamp: now.timestamp(),
+            read: false,
+            type_: rule.template.type_.clone(),
+            template: rule.template.id.clone(),
+            params,
+            // Keyed on the time or window it is for, so it is stored once
+            dedup_key: Some(format!("{}-{}", rule.id, fired.window.unwrap_or(at.timestamp()))),
+        }))
+    }
+
+    /// Whether an unscheduled rule fires at `now`
+    async fn trigger(&mut self, rule: &Rule, now: DateTime<Local>, source: &mut impl Source) -> Result<Option<Fired>> {
+        match &rule.trigger {
+            Trigger::Schedule { .. } | Trigger::Alert { .. } => Ok(None),
+            Trigger::Threshold { metric, above } => {
+                let value = match metric {
+                    Metric::Power => source.power().await?,
+                    Metric::DayKwh => Some(source.stats(Period::Day, now).await?.today_kwh),
+                    Metric::WeekKwh => Some(source.stats(Period::Week, now).await?.weekly_kwh),
+                    Metric::MonthKwh => Some(source.stats(Period::Month, now).await?.monthly_kwh),
+                };
+                let Some(value) = value else { return Ok(None) };
+                // The period of the consumption watched; power has none, so
+                // the shortest one of the conditions, or the day
+                let period = match metric {
+                    Metric::DayKwh => Period::Day,
+                    Metric::WeekKwh => Period::Week,
+                    Metric::MonthKwh => Period::Month,
+                    Metric::Power => rule.conditions.iter().map(Condition::period).min().unwrap_or(Period::Day),
+                };
+                let fired = Fired {
+                    value: Some(value),
+                    threshold: Some(*above),
+                    average: None,
+                    power: None,
+                    window: Some(period.start(now).timestamp()),
+                };
+                Ok(self.crossed(&rule.id, value > *above).then_some(fired))
+            }
+            Trigger::Goal { period, kwh } => {
+                // Looked at once per period; the first one seen is only remembered
+                let start = period.start(now);
+                match self.goal_period.insert(rule.id.clone(), start) {
+                    Some(seen) if seen != start => {}
+                    _ => return Ok(None),
+                }
+                let used = source
+                    .kwh_between(period.previous_start(now).timestamp(), start.timestamp())
+                    .await?;
+                let fired = Fired {
+                    value: Some(used),
+                    threshold: Some(*kwh),
+                    average: None,
+                    power: None,
+                    window: Some(period.previous_start(now).timestamp()),
+                };
+                // No readings at all is not a goal met
+                Ok((used > 0.0 && used <= *kwh).then_some(fired))
+            }
+            Trigger::Anomaly { factor, window_minutes } => {
+                let from = (now - Duration::minutes(*window_minutes as i64)).timestamp();
+                let (Some(power), Some(average)) = (source.power().await?, source.average_power(from).await?) else {
+                    return Ok(None);
+                };
+                // Once per stretch of the length averaged over
+                let length = (*window_minutes).max(1) as i64 * 60;
+                let fired = Fired {
+                    value: Some(power),
+                    threshold: Some(average * factor),
+                    average: Some(average),
+                    power: None,
+                    window: Some(now.timestamp() - now.timestamp().rem_euclid(length)),
+                };
+                Ok(self.crossed(&rule.id, average > 0.0 && power > average * factor).then_some(fired))
+            }
+        }
+    }
+
+    /// True when `over` turned on since the last tick; the first tick only
+    /// records where the value stands
+    fn crossed(&mut self, id: &str, over: bool) -> bool {
+        let was = self.over.insert(id.to_string(), over);
+        over && was == Some(false)
+    }
+}
+
+/// One popup for missed notifications: a single one as is, several in a
+/// digest
+pub fn digest(
+    mut missed: Vec<(DateTime<Local>, Notification)>,
+    now: DateTime<Local>,
+    locale: Locale,
+) -> Option<Notification> {
+    if missed.len() <= 1 {
+        return missed.pop().map(|(_, notification)| notification);
+    }
+
+    let count = missed.len();
+    let type_ = ["critical", "warning"]
+        .into_iter()
+        .find(|type_| missed.iter().any(|(_, n)| n.type_ == *type_))
+        .unwrap_or("info");
+    // Most recent first, like the inbox
+    let mut lines: Vec<String> = missed
+        .iter()
+        .rev()
+        .take(DIGEST_LINES)
+        .map(|(time, n)| format!("{} {}: {}", locale.date(*time), n.title, n.message))
+        .collect();
+    let text = |key: &str, count: usize| rules::render(locale.text(key).unwrap_or_default(), locale, |_| Some(count as f64));
+    if count > DIGEST_LINES {
+        lines.push(text("digest.more", count - DIGEST_LINES));
+    }
+
+    // Named after the newest, whose id is unique. Only shown: the
+    // notifications in it are stored one by one.
+    Some(Notification {
+        id: format!("digest-{}", missed[count - 1].1.id),
+        title: text("digest.title", count),
+        message: lines.join("\n"),
+        timestamp: now.timestamp(),
+        read: false,
+        type_: type_.to_string(),
+        template: None,
+        params: BTreeMap::new(),
+        dedup_key: None,
+    })
+}
+
+fn period_kwh(stats: &EnergyStats, period: Period) -> f64 {
+    match period {
+        Period::Day => stats.today_kwh,
+        Period::Week => stats.weekly_kwh,
+        Period::Month => stats.monthly_kwh,
+    }
+}
+
+fn period_value(stats: &EnergyStats, period: Period, field: &str) -> Option<f64> {
+    let kwh = period_kwh(stats, period);
+    let co2 = match period {
+        Period::Day => stats.co2_today,
+        Period::Week => stats.co2_week,
+        Period::Month => stats.co2_month,
+    };
+    Some(match field {
+        "kwh" => kwh,
+        "bottles" => match period {
+            Period::Day => stats.bottles_today,
+            Period::Week => stats.bottles_week,
+            Period::Month => stats.bottles_month,
+        },
+        "co2" => co2,
+        "co2Kg" => co2 / 1000.0,
+        // Fixed national average, as in /metrics; there is no price setting
+        "cost" => kwh_to_euro(kwh, ITALY_AVG_PRICE_PER_KWH),
+        "trend" => stats.weekly_trend,
+        "peak" => stats.peak_today,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::rules::{default_rules, Limit, SampleMetric, Template};
+
+    #[derive(Default)]
+    struct Fake {
+        stats: EnergyStats,
+        /// Stats as of a given time, `stats` otherwise
+        stats_at: HashMap<i64, EnergyStats>,
+        power: Option<f64>,
+        average: Option<f64>,
+        kwh: f64,
+    }
+
+    impl Source for Fake {
+        async fn stats(&mut self, _period: Period, at: DateTime<Local>) -> Result<EnergyStats> {
+            Ok(self.stats_at.get(&at.timestamp()).unwrap_or(&self.stats).clone())
+        }
+
+        async fn power(&mut self) -> Result<Option<f64>> {
+            Ok(self.power)
+        }
+
+        async fn average_power(&mut self, _from: i64) -> Result<Option<f64>> {
+            Ok(self.average)
+        }
+
+        async fn kwh_between(&mut self, _from: i64, _to: i64) -> Result<f64> {
+            Ok(self.kwh)
+        }
+    }
+
+    fn at(text: &str) -> DateTime<Local> {
+        let naive = chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").unwrap();
+        Local.from_local_datetime(&naive).earliest().unwrap()
+    }
+
+    fn rule(trigger: Trigger, message: &str) -> Rule {
+        Rule {
+            id: "test".to_string(),
+            name: "Test".to_string(),
+            enabled: true,
+            trigger,
+            conditions: vec![],
+            template: Template {
+                id: None,
+                title: "Test".to_string(),
+                message: message.to_string(),
+                type_: "info".to_string(),
+            },
+            last_run: None,
+        }
+    }
+
+    fn day(kwh: f64) -> EnergyStats {
+        EnergyStats {
+            today_kwh: kwh,
+            bottles_today: kwh * 3.0,
+            co2_today: kwh * 500.0,
+            ..Default::default()
+        }
+    }
+
+    /// Tick and store the runs, as the scheduler does when it can deliver
+    async fn deliver(engine: &mut Engine, rules: &mut [Rule], now: DateTime<Local>, source: &mut Fake) -> Tick {
+        let tick = engine.tick(rules, now, source).await;
+        for rule in rules.iter_mut() {
+            if let Some((_, run)) = tick.runs.iter().find(|(id, _)| *id == rule.id) {
+                rule.last_run = Some(*run);
+            }
+        }
+        tick
+    }
+
+    #[tokio::test]
+    async fn test_default_summaries() {
+        let mut rules = default_rules();
+        let mut source = Fake {
+            stats: day(2.5),
+            ..Default::default()
+        };
+        let mut engine = Engine::new();
+
+        let tick = deliver(&mut engine, &mut rules, at("2024-05-01 17:59:30"), &mut source).await;
+        assert!(tick.notifications.is_empty());
+        let tick = deliver(&mut engine, &mut rules, at("2024-05-01 18:00:30"), &mut source).await;
+        assert_eq!(tick.notifications.len(), 1);
+        assert!(tick.missed.is_empty());
+        let due = &tick.notifications[0];
+        assert_eq!(due.title, "Consumo elevato oggi");
+        assert_eq!(due.type_, "warning");
+        assert_eq!(due.message, "Oggi: 2,50 kWh ≈ 7,5 bottiglie 💧 | CO₂: 1.250g");
+        // Stored once per scheduled time, under an id of its own
+        let key = format!("daily-high-{}", at("2024-05-01 18:00:00").timestamp());
+        assert_eq!(due.dedup_key.as_deref(), Some(key.as_str()));
+        assert!(due.id.starts_with("daily-high-") && due.id != key);
+        // Kept to render it again in another language
+        assert_eq!(due.template.as_deref(), Some("daily-high"));
+        assert_eq!(due.params["day.kwh"], 2.5);
+
+        // Once per match, also across a restart
+        let tick = deliver(&mut engine, &mut rules, at("2024-05-01 18:01:30"), &mut source).await;
+        assert!(tick.notifications.is_empty());
+        let tick = deliver(&mut Engine::new(), &mut rules, at("2024-05-01 18:01:40"), &mut source).await;
+        assert!(tick.notifications.is_empty() && tick.missed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_catch_up_uses_the_missed_period() {
+        let mut rules = default_rules();
+        for rule in &mut rules {
+            rule.last_run = Some(at("2024-04-29 20:00:00").timestamp());
+        }
+        // Today is busy, but the two evenings missed were not
+        let mut source = Fake {
+            stats: day(5.0),
+            stats_at: HashMap::from([
+                (at("2024-04-30 18:00:00").timestamp(), day(1.2)),
+                (at("2024-05-01 18:00:00").timestamp(), day(2.4)),
+            ]),
+            ..Default::default()
+        };
+        let mut engine = Engine::new();
+
+        // Held back, e.g. by quiet hours: offered again on the next tick
+        let tick = engine.tick(&rules, at("2024-05-02 07:00:00"), &mut source).await;
+        assert_eq!(tick.missed.len(), 3);
+
+        let tick = deliver(&mut engine, &mut rules, at("2024-05-02 08:00:00"), &mut source).await;
+        assert!(tick.notifications.is_empty());
+        let missed: Vec<_> = tick.missed.iter().map(|(_, n)| (n.title.as_str(), n.message.as_str())).collect();
+        assert_eq!(
+            missed,
+            [
+                ("Riepilogo giornaliero", "Oggi: 1,20 kWh ≈ 3,6 bottiglie 💧 | CO₂: 600g"),
+                ("Report mensile 📊", "Report mensile: 0,00 kWh ≈ 0 bottiglie 💧 | CO₂: 0,0 kg | Costo stimato: 0,00 €"),
+                ("Consumo elevato oggi", "Oggi: 2,40 kWh ≈ 7,2 bottiglie 💧 | CO₂: 1.200g"),
+            ]
+        );
+        assert!(tick.runs.contains(&("monthly-summary".to_string(), at("2024-05-01 10:00:00").timestamp())));
+
+        // Delivered: nothing left
+        let tick = deliver(&mut engine, &mut rules, at("2024-05-02 08:01:00"), &mut source).await;
+        assert!(tick.missed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_monthly_summary_reports_the_month_that_ended() {
+        let mut rules = default_rules();
+        rules.retain(|r| r.id == "monthly-summary");
+        let month = |kwh: f64| EnergyStats {
+            monthly_kwh: kwh,
+            bottles_month: kwh * 3.0,
+            co2_month: kwh * 500.0,
+            ..Default::default()
+        };
+        // Ten hours into June, May is complete
+        let mut source = Fake {
+            stats: month(0.4),
+            stats_at: HashMap::from([(at("2024-05-31 23:59:59").timestamp(), month(120.0))]),
+            ..Default::default()
+        };
+        let mut engine = Engine::new();
+        engine.locale = Locale::En;
+
+        deliver(&mut engine, &mut rules, at("2024-06-01 09:59:30"), &mut source).await;
+        let tick = deliver(&mut engine, &mut rules, at("2024-06-01 10:00:30"), &mut source).await;
+        assert_eq!(tick.notifications.len(), 1);
+        assert_eq!(
+            tick.notifications[0].message,
+            "Monthly report: 120.00 kWh ≈ 360 bottles 💧 | CO₂: 60.0 kg | Estimated cost: €36.00"
+        );
+        assert_eq!(tick.notifications[0].params["previousMonth.kwh"], 120.0);
+    }
+
+    #[tokio::test]
+    async fn test_unstored_runs_do_not_repeat() {
+        let rules = default_rules();
+        let mut source = Fake::default();
+        let mut engine = Engine::new();
+        engine.locale = Locale::En;
+
+        // The rules keep no last run, as if the database were read-only
+        let tick = engine.tick(&rules, at("2024-05-01 18:00:30"), &mut source).await;
+        assert_eq!(tick.notifications.len(), 1);
+        assert_eq!(tick.notifications[0].message, "Today: 0.00 kWh ≈ 0.0 bottles 💧 | CO₂: 0g");
+        engine.commit(&tick.runs);
+        let tick = engine.tick(&rules, at("2024-05-01 18:01:30"), &mut source).await;
+        assert!(tick.notifications.is_empty());
+    }
+
+    #[test]
+    fn test_digest() {
+        let now = at("2024-05-02 09:00:00");
+        let notification = |time: &str, title: &str, type_: &str| {
+            let notification = Notification {
+                id: title.to_string(),
+                title: title.to_string(),
+                message: "…".to_string(),
+                timestamp: now.timestamp(),
+                read: false,
+                type_: type_.to_string(),
+                template: None,
+                params: BTreeMap::new(),
+                dedup_key: None,
+            };
+            (at(time), notification)
+        };
+
+        assert!(digest(vec![], now, Locale::It).is_none());
+        assert_eq!(digest(vec![notification("2024-04-30 18:00:00", "a", "info")], now, Locale::It).unwrap().id, "a");
+
+        let missed = vec![
+            notification("2024-04-30 18:00:00", "a", "info"),
+            notification("2024-05-01 18:00:00", "b", "warning"),
+        ];
+        let english = digest(missed.clone(), now, Locale::En).unwrap();
+        assert_eq!(english.title, "Missed summaries (2)");
+        assert_eq!(english.message, "1 May 18:00 b: …\n30 Apr 18:00 a: …");
+        let digest = digest(missed, now, Locale::It).unwrap();
+        assert_eq!(digest.title, "Riepiloghi arretrati (2)");
+        assert_eq!(digest.message, "01/05 18:00 b: …\n30/04 18:00 a: …");
+        assert_eq!(digest.type_, "warning");
+        assert_eq!(digest.id, "digest-b");
+    }
+
+    #[tokio::test]
+    async fn test_threshold_fires_on_crossing() {
+        let rules = [rule(Trigger::Threshold { metric: Metric::Power, above: 100.0 }, "{value:.0} W > {threshold:.0} W")];
+        let mut source = Fake::default();
+        let mut engine = Engine::new();
+        let start = at("2024-05-01 12:00:00");
+
+        // Already over at startup: no alert until it drops and rises again
+        let mut messages = Vec::new();
+        for (minute, power) in [150.0, 160.0, 90.0, 120.0, 130.0].into_iter().enumerate() {
+            source.power = Some(power);
+            let tick = engine.tick(&rules, start + Duration::minutes(minute as i64), &mut source).await;
+            messages.extend(tick.notifications.into_iter().map(|n| n.message));
+        }
+        assert_eq!(messages, ["120 W > 100 W"]);
+    }
+
+    #[tokio::test]
+    async fn test_alerts_fire_on_samples() {
+        let limits = vec![Limit { metric: SampleMetric::Power, above: Some(200.0), below: None, hysteresis: 20.0 }];
+        let trigger = Trigger::Alert { limits, duration_secs: 600, cooldown_secs: 3600 };
+        let mut alert = rule(trigger, "{value:.0} W > {threshold:.0} W, {power:.0} W");
+        alert.conditions = vec![Condition::Kwh { period: Period::Day, min: Some(0.5), max: None }];
+        let mut rules = [alert];
+        // The database is a sample behind
+        let mut source = Fake {
+            power: Some(100.0),
+            stats: day(1.0),
+            ..Default::default()
+        };
+        let mut engine = Engine::new();
+        let start = at("2024-05-01 12:00:00").timestamp();
+        let sample = |minute: i64, power: f64| Sample {
+            timestamp: start + minute * 60,
+            power: Some(power),
+            ..Default::default()
+        };
+
+        // Not part of the minute tick
+        source.power = Some(250.0);
+        assert!(engine.tick(&rules, at("2024-05-01 12:00:00"), &mut source).await.notifications.is_empty());
+
+        let mut fired = Vec::new();
+        for (minute, power) in [(0, 250.0), (5, 190.0), (10, 240.0), (12, 260.0)] {
+            fired.extend(engine.sample(&rules, &sample(minute, power), &mut source).await);
+        }
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].message, "240 W > 200 W, 240 W");
+        assert_eq!(fired[0].timestamp, start + 600);
+        // About the stretch over the limit, which began with the first sample
+        assert_eq!(fired[0].dedup_key, Some(format!("test-{}", start)));
+
+        // Switched off and on again: starts over, conditions still apply
+        rules[0].enabled = false;
+        assert!(engine.sample(&rules, &sample(13, 250.0), &mut source).await.is_empty());
+        rules[0].enabled = true;
+        source.stats.today_kwh = 0.1;
+        for minute in 14..=30 {
+            assert!(engine.sample(&rules, &sample(minute, 250.0), &mut source).await.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refire_is_stored_once_per_window() {
+        let db = rusqlite::Connection::open_in_memory().unwrap();
+        crate::storage::db::create_schema(&db).unwrap();
+        let inbox = crate::storage::notifications::NotificationRepository::new(&db);
+        let rules = [rule(Trigger::Threshold { metric: Metric::Power, above: 100.0 }, "{value:.0} W")];
+        let mut source = Fake::default();
+
+        // Crosses twice on the first day, then again the next; the second
+        // engine is the app started again
+        let mut stored = Vec::new();
+        let mut engine = Engine::new();
+        for (time, power) in [
+            ("2024-05-01 12:00:00", 90.0),
+            ("2024-05-01 12:01:00", 150.0),
+            ("2024-05-01 12:02:00", 90.0),
+            ("2024-05-01 12:03:00", 160.0),
+            ("restart", 0.0),
+            ("2024-05-01 12:10:00", 90.0),
+            ("2024-05-01 12:11:00", 170.0),
+            ("2024-05-02 09:00:00", 90.0),
+            ("2024-05-02 09:01:00", 180.0),
+        ] {
+            if time == "restart" {
+                engine = Engine::new();
+                continue;
+            }
+            source.power = Some(power);
+            for notification in engine.tick(&rules, at(time), &mut source).await.notifications {
+                stored.push((notification.message.clone(), inbox.save(&notification).unwrap()));
+            }
+        }
+        let key = |day: &str| format!("test-{}", at(day).timestamp());
+        assert_eq!(
+            stored,
+            [
+                ("150 W".to_string(), true),
+                ("160 W".to_string(), false),
+                ("170 W".to_string(), false),
+                ("180 W".to_string(), true),
+            ]
+        );
+        let page = inbox.page(&Default::default(), Locale::It).unwrap();
+        let keys: Vec<_> = page.notifications.iter().filter_map(|n| n.dedup_key.clone()).collect();
+        assert_eq!(keys, [key("2024-05-02 00:00:00"), key("2024-05-01 00:00:00")]);
+    }
+
+    #[tokio::test]
+    async fn test_goal_checks_the_finished_period() {
+        let rules = [rule(Trigger::Goal { period: Period::Day, kwh: 1.0 }, "Ieri: {value:.1} kWh")];
+        let mut source = Fake {
+            kwh: 0.8,
+            ..Default::default()
+        };
+        let mut engine = Engine::new();
+
+        assert!(engine.tick(&rules, at("2024-05-01 23:59:00"), &mut source).await.notifications.is_empty());
+        let tick = engine.tick(&rules, at("2024-05-02 00:00:10"), &mut source).await;
+        assert_eq!(tick.notifications[0].message, "Ieri: 0,8 kWh");
+        assert!(engine.tick(&rules, at("2024-05-02 00:01:10"), &mut source).await.notifications.is_empty());
+
+        // Over budget the next day
+        source.kwh = 1.4;
+        assert!(engine.tick(&rules, at("2024-05-03 00:00:10"), &mut source).await.notifications.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_anomaly_and_conditions() {
+        let mut anomaly = rule(Trigger::Anomaly { factor: 2.0, window_minutes: 60 }, "{power:.0} W, media {average:.0} W");
+        anomaly.conditions = vec![Condition::Kwh { period: Period::Day, min: Some(0.5), max: None }];
+        let rules = [anomaly];
+        let mut source = Fake {
+            power: Some(60.0),
+            average: Some(50.0),
+            stats: day(1.0),
+            ..Default::default()
+        };
+        let mut engine = Engine::new();
+
+        assert!(engine.tick(&rules, at("2024-05-01 12:00:00"), &mut source).await.notifications.is_empty());
+        source.power = Some(130.0);
+        let tick = engine.tick(&rules, at("2024-05-01 12:01:00"), &mut source).await;
+        assert_eq!(tick.notifications[0].message, "130 W, media 50 W");
+
+        // Condition not met: the crossing is consumed without a notification
+        source.power = Some(60.0);
+        engine.tick(&rules, at("2024-05-01 12:02:00"), &mut source).await;
+        source.power = Some(130.0);
+        source.stats.today_kwh = 0.1;
+        assert!(engine.tick(&rules, at("2024-05-01 12:03:00"), &mut source).await.notifications.is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+// Fullscreen detection on X11 through EWMH window manager hints
+//
+// https://specifications.freedesktop.org/wm-spec/latest/
+// The window manager names the focused window in `_NET_ACTIVE_WINDOW` on
+// the root window and lists `_NET_WM_STATE_FULLSCREEN` in that window's
+// `_NET_WM_STATE` while it covers the screen.
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{Atom, AtomEnum, ConnectionExt, Window};
+
+use crate::error::{Result, VeraError};
+
+fn x11_error(e: impl std::fmt::Display) -> VeraError {
+    VeraError::Internal(format!("X11: {}", e))
+}
+
+/// Whether the active window on `$DISPLAY` is fullscreen
+pub fn fullscreen_active() -> Result<bool> {
+    let (conn, screen) = x11rb::connect(None).map_err(x11_error)?;
+    let root = conn.setup().roots[screen].root;
+    is_fullscreen(&conn, root)
+}
+
+fn atom(conn: &impl Connection, name: &str) -> Result<Atom> {
+    let reply = conn.intern_atom(false, name.as_bytes()).map_err(x11_error)?.reply().map_err(x11_error)?;
+    Ok(reply.atom)
+}
+
+/// 32-bit values of `property` on `window`, empty when it is not set
+fn property(conn: &impl Connection, window: Window, property: Atom, type_: AtomEnum) -> Result<Vec<u32>> {
+    let reply = conn
+        .get_property(false, window, property, type_, 0, u32::MAX)
+        .map_err(x11_error)?
+        .reply()
+        .map_err(x11_error)?;
+    Ok(reply.value32().map(Iterator::collect).unwrap_or_default())
+}
+
+pub fn is_fullscreen(conn: &impl Connection, root: Window) -> Result<bool> {
+    let active = atom(conn, "_NET_ACTIVE_WINDOW")?;
+    // No window manager, or nothing focused
+    let Some(&window) = property(conn, root, active, AtomEnum::WINDOW)?.first() else { return Ok(false) };
+    if window == x11rb::NONE {
+        return Ok(false);
+    }
+    let state = atom(conn, "_NET_WM_STATE")?;
+    let fullscreen = atom(conn, "_NET_WM_STATE_FULLSCREEN")?;
+    Ok(property(conn, window, state, AtomEnum::ATOM)?.contains(&fullscreen))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x11rb::protocol::xproto::{CreateWindowAux, PropMode, WindowClass};
+    use x11rb::wrapper::ConnectionExt as _;
+
+    #[test]
+    #[ignore = "needs an X server: xvfb-run cargo test -- --ignored"]
+    fn test_fullscreen_window() {
+        let (conn, screen) = x11rb::connect(None).unwrap();
+        let root = conn.setup().roots[screen].root;
+        // Xvfb has no window manager; set the hints it would
+        let window = conn.generate_id().unwrap();
+        conn.create_window(0, window, root, 0, 0, 640, 480, 0, WindowClass::INPUT_OUTPUT, 0, &CreateWindowAux::new())
+            .unwrap();
+        let active = atom(&conn, "_NET_ACTIVE_WINDOW").unwrap();
+        let state = atom(&conn, "_NET_WM_STATE").unwrap();
+        let fullscreen = atom(&conn, "_NET_WM_STATE_FULLSCREEN").unwrap();
+        let above = atom(&conn, "_NET_WM_STATE_ABOVE").unwrap();
+
+        conn.delete_property(root, active).unwrap();
+        assert!(!is_fullscreen(&conn, root).unwrap());
+
+        conn.change_property32(PropMode::REPLACE, root, active, AtomEnum::WINDOW, &[window]).unwrap();
+        assert!(!is_fullscreen(&conn, root).unwrap());
+
+        conn.change_property32(PropMode::REPLACE, window, state, AtomEnum::ATOM, &[above, fullscreen]).unwrap();
+        assert!(is_fullscreen(&conn, root).unwrap());
+
+        conn.change_property32(PropMode::REPLACE, window, state, AtomEnum::ATOM, &[above]).unwrap();
+        assert!(!is_fullscreen(&conn, root).unwrap());
+
+        conn.change_property32(PropMode::REPLACE, root, active, AtomEnum::WINDOW, &[x11rb::NONE]).unwrap();
+        assert!(!is_fullscreen(&conn, root).unwrap());
+        conn.delete_property(root, active).unwrap();
+        conn.flush().unwrap();
+    }
+}
@@ -0,0 +1,290 @@
+// Client for the freedesktop notification daemon (org.freedesktop.Notifications)
+//
+// https://specifications.freedesktop.org/notification-spec/latest/
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use zbus::zvariant::Value;
+
+use super::desktop::{Action, Urgency};
+use super::i18n::Locale;
+use crate::error::{Result, VeraError};
+use crate::storage::Notification;
+
+const APP_NAME: &str = "VERA";
+/// Lets the daemon pick up the icon and name of the installed app
+const DESKTOP_ENTRY: &str = "vera";
+/// Daemon default expiry
+const EXPIRE_DEFAULT: i32 = -1;
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: HashMap<&str, Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+
+    fn get_capabilities(&self) -> zbus::Result<Vec<String>>;
+
+    #[zbus(signal)]
+    fn action_invoked(&self, id: u32, action_key: String) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn notification_closed(&self, id: u32, reason: u32) -> zbus::Result<()>;
+}
+
+fn dbus_error(e: zbus::Error) -> VeraError {
+    VeraError::Internal(format!("notification daemon: {}", e))
+}
+
+pub struct Client {
+    proxy: NotificationsProxy<'static>,
+    /// The daemon can show buttons
+    actions: bool,
+    invoked: ActionInvokedStream,
+    closed: NotificationClosedStream,
+    /// Notifications on screen that can still answer with an action
+    shown: HashMap<u32, Notification>,
+}
+
+impl Client {
+    /// Connect to the daemon on the user's session bus
+    pub async fn connect_session() -> Result<Self> {
+        let connection = zbus::Connection::session().await.map_err(dbus_error)?;
+        Self::connect(&connection).await
+    }
+
+    pub async fn connect(connection: &zbus::Connection) -> Result<Self> {
+        let proxy = NotificationsProxy::new(connection).await.map_err(dbus_error)?;
+        // Fails when no daemon is running
+        let capabilities = proxy.get_capabilities().await.map_err(dbus_error)?;
+        let invoked = proxy.receive_action_invoked().await.map_err(dbus_error)?;
+        let closed = proxy.receive_notification_closed().await.map_err(dbus_error)?;
+        Ok(Self {
+            proxy,
+            actions: capabilities.iter().any(|c| c == "actions"),
+            invoked,
+            closed,
+            shown: HashMap::new(),
+        })
+    }
+
+    pub async fn show(&mut self, notification: &Notification, locale: Locale) -> Result<()> {
+        let mut actions = Vec::new();
+        if self.actions {
+            actions.extend(["default", ""]);
+            for action in Action::ALL {
+                actions.extend([action.key(), action.label(locale)]);
+            }
+        }
+        let hints = HashMap::from([
+            ("urgency", Value::U8(Urgency::of(&notification.type_) as u8)),
+            ("desktop-entry", Value::from(DESKTOP_ENTRY)),
+        ]);
+
+        let id = self
+            .proxy
+            .notify(
+                APP_NAME,
+                0,
+                "",
+                &notification.title,
+                &notification.message,
+                &actions,
+                hints,
+                EXPIRE_DEFAULT,
+            )
+            .await
+            .map_err(dbus_error)?;
+        if self.actions {
+            self.shown.insert(id, notification.clone());
+        }
+        Ok(())
+    }
+
+    /// Next action chosen on one of our notifications; `None` once the connection is gone
+    pub async fn next_action(&mut self) -> Option<(Action, Notification)> {
+        loop {
+            tokio::select! {
+                signal = self.invoked.next() => {
+                    let signal = signal?;
+                    let args = signal.args().ok()?;
+                    // Other apps' notifications share the daemon
+                    let Some(notification) = self.shown.remove(&args.id) else { continue };
+                    if let Some(action) = Action::from_key(&args.action_key) {
+                        return Some((action, notification));
+                    }
+                }
+                signal = self.closed.next() => {
+                    if let Ok(args) = signal?.args() {
+                        self.shown.remove(&args.id);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use zbus::object_server::SignalEmitter;
+    use zbus::zvariant::OwnedValue;
+
+    const PATH: &str = "/org/freedesktop/Notifications";
+
+    #[derive(Debug, Clone)]
+    struct Call {
+        summary: String,
+        actions: Vec<String>,
+        urgency: u8,
+    }
+
+    /// Stands in for the session's notification daemon
+    struct Daemon {
+        capabilities: Vec<String>,
+        calls: Arc<Mutex<Vec<Call>>>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Notifications")]
+    impl Daemon {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &self,
+            _app_name: &str,
+            _replaces_id: u32,
+            _app_icon: &str,
+            summary: &str,
+            _body: &str,
+            actions: Vec<String>,
+            hints: HashMap<String, OwnedValue>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            let mut calls = self.calls.lock().unwrap();
+            calls.push(Call {
+                summary: summary.to_string(),
+                actions,
+                urgency: hints.get("urgency").and_then(|v| u8::try_from(v).ok()).unwrap_or(99),
+            });
+            calls.len() as u32
+        }
+
+        fn get_capabilities(&self) -> Vec<String> {
+            self.capabilities.clone()
+        }
+
+        #[zbus(signal)]
+        async fn action_invoked(emitter: &SignalEmitter<'_>, id: u32, action_key: &str) -> zbus::Result<()>;
+    }
+
+    /// Peer-to-peer connections standing in for the session bus
+    async fn connections(daemon: Option<Daemon>) -> (zbus::Connection, zbus::Connection) {
+        let (server, client) = tokio::net::UnixStream::pair().unwrap();
+        let mut server = zbus::connection::Builder::unix_stream(server)
+            .server(zbus::Guid::generate())
+            .unwrap()
+            .p2p();
+        if let Some(daemon) = daemon {
+            server = server.serve_at(PATH, daemon).unwrap();
+        }
+        let client = zbus::connection::Builder::unix_stream(client).p2p();
+        tokio::try_join!(server.build(), client.build()).unwrap()
+    }
+
+    async fn stand_in(capabilities: &[&str]) -> (zbus::Connection, Client, Arc<Mutex<Vec<Call>>>) {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let daemon = Daemon {
+            capabilities: capabilities.iter().map(|c| c.to_string()).collect(),
+            calls: Arc::clone(&calls),
+        };
+        let (server, client) = connections(Some(daemon)).await;
+        let client = Client::connect(&client).await.unwrap();
+        (server, client, calls)
+    }
+
+    fn notification(id: &str, type_: &str) -> Notification {
+        Notification {
+            id: id.to_string(),
+            title: format!("Title {}", id),
+            message: "Body".to_string(),
+            timestamp: 0,
+            read: false,
+            type_: type_.to_string(),
+            template: None,
+            params: Default::default(),
+            dedup_key: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_urgency_and_actions() {
+        let (_server, mut client, calls) = stand_in(&["body", "actions"]).await;
+        client.show(&notification("a", "critical"), Locale::It).await.unwrap();
+        client.show(&notification("b", "success"), Locale::En).await.unwrap();
+
+        let calls = calls.lock().unwrap().clone();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].summary, "Title a");
+        assert_eq!(calls[0].urgency, 2);
+        assert_eq!(calls[1].urgency, 0);
+        assert_eq!(
+            calls[0].actions,
+            ["default", "", "view", "Vedi report", "snooze", "Posticipa 1h"]
+        );
+        assert_eq!(calls[1].actions[3], "View report");
+    }
+
+    #[tokio::test]
+    async fn test_no_actions_without_capability() {
+        let (_server, mut client, calls) = stand_in(&["body"]).await;
+        client.show(&notification("a", "info"), Locale::It).await.unwrap();
+
+        let calls = calls.lock().unwrap().clone();
+        assert!(calls[0].actions.is_empty());
+        assert_eq!(calls[0].urgency, 1);
+    }
+
+    #[tokio::test]
+    async fn test_actions_are_routed() {
+        let (server, mut client, _calls) = stand_in(&["actions"]).await;
+        client.show(&notification("a", "info"), Locale::It).await.unwrap();
+        client.show(&notification("b", "info"), Locale::It).await.unwrap();
+
+        let daemon = server.object_server().interface::<_, Daemon>(PATH).await.unwrap();
+        let emitter = daemon.signal_emitter();
+        // Another app's notification
+        Daemon::action_invoked(emitter, 42, "view").await.unwrap();
+        Daemon::action_invoked(emitter, 2, "snooze").await.unwrap();
+
+        let (action, notification) = client.next_action().await.unwrap();
+        assert_eq!(action, Action::Snooze);
+        assert_eq!(notification.id, "b");
+        assert_eq!(client.shown.keys().collect::<Vec<_>>(), [&1]);
+
+        Daemon::action_invoked(emitter, 1, "default").await.unwrap();
+        let (action, notification) = client.next_action().await.unwrap();
+        assert_eq!(action, Action::View);
+        assert_eq!(notification.id, "a");
+    }
+
+    #[tokio::test]
+    async fn test_no_daemon() {
+        let (server, client) = connections(None).await;
+        // Answers with UnknownObject, like a session bus without a daemon
+        server.object_server();
+        let error = Client::connect(&client).await.err().unwrap();
+        assert_eq!(error.code(), "INTERNAL_ERROR");
+    }
+}
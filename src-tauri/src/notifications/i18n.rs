@@ -0,0 +1,272 @@
+// Message catalogue and locale-aware formatting
+//
+// The texts VERA ships (default rules, digests, desktop buttons) live here,
+// one table per language, keyed like `daily-summary.title`. Italian is the
+// source language: a key missing from another table falls back to it.
+// Numbers, amounts and dates in messages follow the locale of the settings.
+use chrono::{DateTime, Local};
+
+use super::rules;
+use crate::storage::Notification;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    It,
+    En,
+}
+
+const DAY_IT: &str = "Oggi: {day.kwh:.2} kWh ≈ {day.bottles:.1} {day.bottles:bottiglia|bottiglie} 💧 | CO₂: {day.co2:.0}g";
+const WEEK_IT: &str =
+    "Questa settimana: {week.kwh:.2} kWh ≈ {week.bottles:.1} {week.bottles:bottiglia|bottiglie} 💧 | Trend: {week.trend:.1}%";
+const DAY_EN: &str = "Today: {day.kwh:.2} kWh ≈ {day.bottles:.1} {day.bottles:bottle|bottles} 💧 | CO₂: {day.co2:.0}g";
+const WEEK_EN: &str =
+    "This week: {week.kwh:.2} kWh ≈ {week.bottles:.1} {week.bottles:bottle|bottles} 💧 | Trend: {week.trend:.1}%";
+
+const IT: &[(&str, &str)] = &[
+    ("daily-summary.title", "Riepilogo giornaliero"),
+    ("daily-summary.message", DAY_IT),
+    ("daily-high.title", "Consumo elevato oggi"),
+    ("daily-high.message", DAY_IT),
+    ("weekly-summary.title", "Riepilogo settimanale"),
+    ("weekly-summary.message", WEEK_IT),
+    ("weekly-improved.title", "Ottimo lavoro questa settimana! 🎉"),
+    ("weekly-improved.message", WEEK_IT),
+    ("monthly-summary.title", "Report mensile 📊"),
+    (
+        "monthly-summary.message",
+        "Report mensile: {previousMonth.kwh:.2} kWh ≈ {previousMonth.bottles:.0} {previousMonth.bottles:bottiglia|bottiglie} 💧 | CO₂: {previousMonth.co2Kg:.1} kg | Costo stimato: {previousMonth.cost:currency}",
+    ),
+    ("digest.title", "Riepiloghi arretrati ({count:.0})"),
+    ("digest.more", "… e altri {count:.0}"),
+    ("action.view", "Vedi report"),
+    ("action.snooze", "Posticipa 1h"),
+    ("webhook-test.title", "Prova webhook"),
+    ("webhook-test.message", "Se leggi questo messaggio, il webhook di VERA funziona."),
+];
+
+const EN: &[(&str, &str)] = &[
+    ("daily-summary.title", "Daily summary"),
+    ("daily-summary.message", DAY_EN),
+    ("daily-high.title", "High consumption today"),
+    ("daily-high.message", DAY_EN),
+    ("weekly-summary.title", "Weekly summary"),
+    ("weekly-summary.message", WEEK_EN),
+    ("weekly-improved.title", "Great job this week! 🎉"),
+    ("weekly-improved.message", WEEK_EN),
+    ("monthly-summary.title", "Monthly report 📊"),
+    (
+        "monthly-summary.message",
+        "Monthly report: {previousMonth.kwh:.2} kWh ≈ {previousMonth.bottles:.0} {previousMonth.bottles:bottle|bottles} 💧 | CO₂: {previousMonth.co2Kg:.1} kg | Estimated cost: {previousMonth.cost:currency}",
+    ),
+    ("digest.title", "Missed summaries ({count:.0})"),
+    ("digest.more", "… and {count:.0} more"),
+    ("action.view", "View report"),
+    ("action.snooze", "Snooze 1h"),
+    ("webhook-test.title", "Webhook test"),
+    ("webhook-test.message", "If you can read this, VERA's webhook works."),
+];
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::It, Locale::En];
+
+    /// `it`, `en`; a region such as `en-GB` is ignored
+    pub fn parse(code: &str) -> Option<Self> {
+        let language = code.split(['-', '_']).next().unwrap_or_default();
+        match language.to_ascii_lowercase().as_str() {
+            "it" => Some(Locale::It),
+            "en" => Some(Locale::En),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Locale::It => "it",
+            Locale::En => "en",
+        }
+    }
+
+    fn messages(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Locale::It => IT,
+            Locale::En => EN,
+        }
+    }
+
+    /// Catalogue text for `key`
+    pub fn text(&self, key: &str) -> Option<&'static str> {
+        let find = |table: &[(&str, &'static str)]| table.iter().find(|(k, _)| *k == key).map(|(_, text)| *text);
+        find(self.messages()).or_else(|| find(IT))
+    }
+
+    /// Decimal and thousands separators
+    fn separators(&self) -> (char, char) {
+        match self {
+            Locale::It => (',', '.'),
+            Locale::En => ('.', ','),
+        }
+    }
+
+    /// `1.234,56` or `1,234.56`
+    pub fn number(&self, value: f64, decimals: usize) -> String {
+        if !value.is_finite() {
+            return value.to_string();
+        }
+        let text = format!("{:.*}", decimals, value.abs());
+        let (integer, fraction) = text.split_once('.').unwrap_or((&text, ""));
+        let (decimal, group) = self.separators();
+
+        let mut out = String::with_capacity(text.len() + integer.len() / 3 + 1);
+        // No sign on values that round to zero
+        if value < 0.0 && text.bytes().any(|b| b.is_ascii_digit() && b != b'0') {
+            out.push('-');
+        }
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                out.push(group);
+            }
+            out.push(digit);
+        }
+        if !fraction.is_empty() {
+            out.push(decimal);
+            out.push_str(fraction);
+        }
+        out
+    }
+
+    /// Euro amount, `12,34 €` or `€12.34`
+    pub fn currency(&self, value: f64) -> String {
+        let amount = self.number(value, 2);
+        match self {
+            Locale::It => format!("{} €", amount),
+            Locale::En => match amount.strip_prefix('-') {
+                Some(amount) => format!("-€{}", amount),
+                None => format!("€{}", amount),
+            },
+        }
+    }
+
+    /// Day and time, `01/05 18:00` or `1 May 18:00`
+    pub fn date(&self, time: DateTime<Local>) -> String {
+        match self {
+            Locale::It => time.format("%d/%m %H:%M").to_string(),
+            Locale::En => time.format("%-d %b %H:%M").to_string(),
+        }
+    }
+
+    /// The word for a number as written by `number`
+    pub fn plural<'a>(&self, number: &str, one: &'a str, other: &'a str) -> &'a str {
+        if (self.plural_rule())(number) {
+            one
+        } else {
+            other
+        }
+    }
+
+    /// Whether a written number takes the singular, per language
+    fn plural_rule(&self) -> fn(&str) -> bool {
+        match self {
+            Locale::It | Locale::En => one_without_fraction,
+        }
+    }
+}
+
+/// CLDR "one" for Italian and English: exactly 1 with no decimals shown, so
+/// "1 bottle" but "1.0 bottles" and "0 bottles"
+fn one_without_fraction(number: &str) -> bool {
+    number.strip_prefix('-').unwrap_or(number) == "1"
+}
+
+/// Render a stored notification again in `locale`. Only notifications from
+/// a catalogue template can be; others keep the text they were stored with.
+pub fn localize(notification: &mut Notification, locale: Locale) {
+    let Some(template) = &notification.template else { return };
+    let (Some(title), Some(message)) = (
+        locale.text(&format!("{}.title", template)),
+        locale.text(&format!("{}.message", template)),
+    ) else {
+        return;
+    };
+    let value = |p: &rules::Placeholder| notification.params.get(p.name).copied();
+    let title = rules::render(title, locale, value);
+    let message = rules::render(message, locale, value);
+    notification.title = title;
+    notification.message = message;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(Locale::It.number(1234.5678, 2), "1.234,57");
+        assert_eq!(Locale::En.number(1234.5678, 2), "1,234.57");
+        assert_eq!(Locale::It.number(1234567.0, 0), "1.234.567");
+        assert_eq!(Locale::En.number(-12.5, 1), "-12.5");
+        assert_eq!(Locale::It.number(-0.001, 1), "0,0");
+        assert_eq!(Locale::It.number(999.996, 2), "1.000,00");
+
+        assert_eq!(Locale::It.currency(12.3), "12,30 €");
+        assert_eq!(Locale::En.currency(-1.5), "-€1.50");
+
+        let time = Local.with_ymd_and_hms(2024, 5, 1, 18, 0, 0).unwrap();
+        assert_eq!(Locale::It.date(time), "01/05 18:00");
+        assert_eq!(Locale::En.date(time), "1 May 18:00");
+
+        assert_eq!(Locale::En.plural("1", "bottle", "bottles"), "bottle");
+        assert_eq!(Locale::En.plural("1.0", "bottle", "bottles"), "bottles");
+        assert_eq!(Locale::En.plural("1.5", "bottle", "bottles"), "bottles");
+        assert_eq!(Locale::It.plural("0", "bottiglia", "bottiglie"), "bottiglie");
+        assert_eq!(Locale::It.plural("-1", "bottiglia", "bottiglie"), "bottiglia");
+    }
+
+    #[test]
+    fn test_catalogue() {
+        assert_eq!(Locale::parse("en-GB"), Some(Locale::En));
+        assert_eq!(Locale::parse("IT"), Some(Locale::It));
+        assert_eq!(Locale::parse("fr"), None);
+
+        // Every language has every key, with the same placeholders
+        for locale in Locale::ALL {
+            for (key, text) in IT {
+                let translated = locale.messages().iter().find(|(k, _)| k == key);
+                let (_, translated) = translated.unwrap_or_else(|| panic!("{} misses {}", locale.as_str(), key));
+                let names = |text: &str| rules::placeholders(text).iter().map(|p| p.name.to_string()).collect::<Vec<_>>();
+                assert_eq!(names(translated), names(text), "{} {}", locale.as_str(), key);
+            }
+        }
+    }
+
+    #[test]
+    fn test_localize() {
+        let mut notification = Notification {
+            id: "daily-summary-1".to_string(),
+            title: "Riepilogo giornaliero".to_string(),
+            message: String::new(),
+            timestamp: 0,
+            read: false,
+            type_: "info".to_string(),
+            template: Some("daily-summary".to_string()),
+            params: [("day.kwh", 1.5), ("day.bottles", 1.0), ("day.co2", 750.0)]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+            dedup_key: None,
+        };
+        localize(&mut notification, Locale::En);
+        assert_eq!(notification.title, "Daily summary");
+        assert_eq!(notification.message, "Today: 1.50 kWh ≈ 1.0 bottles 💧 | CO₂: 750g");
+
+        localize(&mut notification, Locale::It);
+        assert_eq!(notification.message, "Oggi: 1,50 kWh ≈ 1,0 bottiglie 💧 | CO₂: 750g");
+
+        // Texts written by the user stay as they are
+        notification.template = None;
+        notification.message = "custom".to_string();
+        localize(&mut notification, Locale::En);
+        assert_eq!(notification.message, "custom");
+    }
+}
@@ -1,2 +1,14 @@
 // Notifications module
+pub mod alerts;
+pub mod desktop;
+pub mod engine;
+#[cfg(target_os = "linux")]
+mod ewmh;
+#[cfg(target_os = "linux")]
+mod freedesktop;
+pub mod i18n;
+pub mod presence;
+pub mod quiet;
+pub mod rules;
 pub mod scheduler;
+pub mod webhook;
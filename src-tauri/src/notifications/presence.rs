@@ -0,0 +1,198 @@
+// Whether the user is busy at the screen
+//
+// Notifications wait while a game, presentation, video or call has the
+// screen. On Linux that is when the active X11 window is fullscreen, the
+// screensaver is on, or an application keeps the session from going idle
+// through the freedesktop inhibit services (video players and meeting apps
+// do). A check that cannot be made, such as X11 under Wayland or in the
+// headless agent, counts as not busy.
+#[cfg(target_os = "linux")]
+use super::ewmh;
+
+#[cfg(target_os = "linux")]
+mod session {
+    /// `org.gnome.SessionManager` inhibit flag for going idle
+    pub const INHIBIT_IDLE: u32 = 8;
+
+    #[zbus::proxy(
+        interface = "org.freedesktop.ScreenSaver",
+        default_service = "org.freedesktop.ScreenSaver",
+        default_path = "/org/freedesktop/ScreenSaver"
+    )]
+    pub trait ScreenSaver {
+        fn get_active(&self) -> zbus::Result<bool>;
+    }
+
+    /// KDE, Xfce
+    #[zbus::proxy(
+        interface = "org.freedesktop.PowerManagement.Inhibit",
+        default_service = "org.freedesktop.PowerManagement",
+        default_path = "/org/freedesktop/PowerManagement/Inhibit"
+    )]
+    pub trait PowerManagementInhibit {
+        fn has_inhibit(&self) -> zbus::Result<bool>;
+    }
+
+    /// GNOME
+    #[zbus::proxy(
+        interface = "org.gnome.SessionManager",
+        default_service = "org.gnome.SessionManager",
+        default_path = "/org/gnome/SessionManager"
+    )]
+    pub trait SessionManager {
+        fn is_inhibited(&self, flags: u32) -> zbus::Result<bool>;
+    }
+
+    /// A service this desktop does not run answers "no"
+    fn answer(result: zbus::Result<bool>) -> zbus::Result<bool> {
+        match result {
+            Err(zbus::Error::MethodError(..) | zbus::Error::FDO(_)) => Ok(false),
+            result => result,
+        }
+    }
+
+    /// Screensaver on, or idle inhibited by an application
+    pub async fn inhibited(connection: &zbus::Connection) -> zbus::Result<bool> {
+        Ok(answer(ScreenSaverProxy::new(connection).await?.get_active().await)?
+            || answer(PowerManagementInhibitProxy::new(connection).await?.has_inhibit().await)?
+            || answer(SessionManagerProxy::new(connection).await?.is_inhibited(INHIBIT_IDLE).await)?)
+    }
+}
+
+#[derive(Default)]
+pub struct Presence {
+    /// Connected lazily and dropped when the bus goes away
+    #[cfg(target_os = "linux")]
+    session: Option<zbus::Connection>,
+}
+
+impl Presence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[cfg(target_os = "linux")]
+    pub async fn is_busy(&mut self) -> bool {
+        // x11rb is blocking
+        let fullscreen = tokio::task::spawn_blocking(ewmh::fullscreen_active).await;
+        if matches!(fullscreen, Ok(Ok(true))) {
+            return true;
+        }
+
+        let connection = match &self.session {
+            Some(connection) => connection,
+            None => match zbus::Connection::session().await {
+                Ok(connection) => self.session.insert(connection),
+                Err(_) => return false,
+            },
+        };
+        match session::inhibited(connection).await {
+            Ok(inhibited) => inhibited,
+            Err(e) => {
+                eprintln!("Failed to read the session's idle state: {}", e);
+                self.session = None;
+                false
+            }
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    pub async fn is_busy(&mut self) -> bool {
+        // TODO: Implementare usando Windows API
+        // Richiede: winapi crate
+        // GetForegroundWindow() + GetWindowRect() + confronto con screen size
+        false
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    pub async fn is_busy(&mut self) -> bool {
+        false
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::session::*;
+
+    /// Stands in for the screensaver and session manager services
+    #[derive(Clone, Copy, Default)]
+    struct Desktop {
+        screensaver: bool,
+        inhibit: bool,
+        /// GNOME inhibit flags in force
+        flags: u32,
+    }
+
+    struct ScreenSaver(Desktop);
+
+    #[zbus::interface(name = "org.freedesktop.ScreenSaver")]
+    impl ScreenSaver {
+        fn get_active(&self) -> bool {
+            self.0.screensaver
+        }
+    }
+
+    struct Inhibit(Desktop);
+
+    #[zbus::interface(name = "org.freedesktop.PowerManagement.Inhibit")]
+    impl Inhibit {
+        fn has_inhibit(&self) -> bool {
+            self.0.inhibit
+        }
+    }
+
+    struct Gnome(Desktop);
+
+    #[zbus::interface(name = "org.gnome.SessionManager")]
+    impl Gnome {
+        fn is_inhibited(&self, flags: u32) -> bool {
+            self.0.flags & flags != 0
+        }
+    }
+
+    /// A peer-to-peer connection to `desktop`, serving only GNOME's
+    /// session manager or only the freedesktop services
+    async fn connect(desktop: Desktop, gnome: bool) -> (zbus::Connection, zbus::Connection) {
+        let (server, client) = tokio::net::UnixStream::pair().unwrap();
+        let mut server = zbus::connection::Builder::unix_stream(server)
+            .server(zbus::Guid::generate())
+            .unwrap()
+            .p2p();
+        server = if gnome {
+            server.serve_at("/org/gnome/SessionManager", Gnome(desktop)).unwrap()
+        } else {
+            server
+                .serve_at("/org/freedesktop/ScreenSaver", ScreenSaver(desktop))
+                .unwrap()
+                .serve_at("/org/freedesktop/PowerManagement/Inhibit", Inhibit(desktop))
+                .unwrap()
+        };
+        let client = zbus::connection::Builder::unix_stream(client).p2p();
+        tokio::try_join!(server.build(), client.build()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_freedesktop() {
+        let (_server, client) = connect(Desktop::default(), false).await;
+        assert!(!inhibited(&client).await.unwrap());
+
+        let screensaver = Desktop { screensaver: true, ..Default::default() };
+        let (_server, client) = connect(screensaver, false).await;
+        assert!(inhibited(&client).await.unwrap());
+
+        // A video playing
+        let inhibit = Desktop { inhibit: true, ..Default::default() };
+        let (_server, client) = connect(inhibit, false).await;
+        assert!(inhibited(&client).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_gnome() {
+        // Logout inhibited only
+        let (_server, client) = connect(Desktop { flags: 1, ..Default::default() }, true).await;
+        assert!(!inhibited(&client).await.unwrap());
+
+        let (_server, client) = connect(Desktop { flags: 1 | INHIBIT_IDLE, ..Default::default() }, true).await;
+        assert!(inhibited(&client).await.unwrap());
+    }
+}
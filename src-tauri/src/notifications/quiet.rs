@@ -0,0 +1,247 @@
+// Do not disturb
+//
+// Quiet windows per weekday at minute precision, holidays and vacations as
+// whole days, and a one-off pause. The scheduler queues what falls due
+// while quiet and delivers it once quiet ends.
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, VeraError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Day {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Day {
+    pub const ALL: [Day; 7] = [Day::Mon, Day::Tue, Day::Wed, Day::Thu, Day::Fri, Day::Sat, Day::Sun];
+
+    fn of(weekday: Weekday) -> Self {
+        Day::ALL[weekday.num_days_from_monday() as usize]
+    }
+}
+
+/// `HH:MM`, minutes since midnight
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay(u16);
+
+impl TimeOfDay {
+    pub fn new(hour: u32, minute: u32) -> Option<Self> {
+        (hour < 24 && minute < 60).then(|| TimeOfDay((hour * 60 + minute) as u16))
+    }
+
+    fn of(time: &NaiveDateTime) -> Self {
+        TimeOfDay((time.hour() * 60 + time.minute()) as u16)
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(text: String) -> std::result::Result<Self, String> {
+        let invalid = || format!("expected HH:MM, got {:?}", text);
+        let (hour, minute) = text.split_once(':').ok_or_else(invalid)?;
+        let hour = hour.parse().map_err(|_| invalid())?;
+        let minute = minute.parse().map_err(|_| invalid())?;
+        TimeOfDay::new(hour, minute).ok_or_else(invalid)
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(time: TimeOfDay) -> Self {
+        format!("{:02}:{:02}", time.0 / 60, time.0 % 60)
+    }
+}
+
+/// Quiet from `start` on each of `days`, until `end` that day, or the next
+/// day when `end` is before `start` (`22:30`–`07:15`). Equal times are an
+/// empty window, as equal quiet hours always meant none.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Window {
+    pub days: Vec<Day>,
+    pub start: TimeOfDay,
+    pub end: TimeOfDay,
+}
+
+impl Window {
+    pub fn every_day(start: TimeOfDay, end: TimeOfDay) -> Self {
+        Self {
+            days: Day::ALL.to_vec(),
+            start,
+            end,
+        }
+    }
+
+    fn contains(&self, time: &NaiveDateTime) -> bool {
+        let now = TimeOfDay::of(time);
+        let today = self.days.contains(&Day::of(time.weekday()));
+        if self.start == self.end {
+            return false;
+        }
+        if self.start < self.end {
+            return today && (self.start..self.end).contains(&now);
+        }
+        let yesterday = self.days.contains(&Day::of(time.weekday().pred()));
+        (today && now >= self.start) || (yesterday && now < self.end)
+    }
+}
+
+/// Quiet all day from `from` to `to`, both included
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Vacation {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DoNotDisturb {
+    #[serde(default)]
+    pub windows: Vec<Window>,
+    #[serde(default)]
+    pub vacations: Vec<Vacation>,
+    /// Paused until this Unix time
+    #[serde(default)]
+    pub paused_until: Option<i64>,
+}
+
+impl Default for DoNotDisturb {
+    /// 19:00–08:00 every day, as the old quiet hours
+    fn default() -> Self {
+        Self::from_hours(19, 8)
+    }
+}
+
+impl DoNotDisturb {
+    /// The whole-hour quiet hours of earlier versions; the same hour for
+    /// both is none at all
+    pub fn from_hours(start: u8, end: u8) -> Self {
+        let hour = |h: u8| TimeOfDay::new(h.min(23) as u32, 0).unwrap_or(TimeOfDay(0));
+        let windows = if hour(start) == hour(end) {
+            Vec::new()
+        } else {
+            vec![Window::every_day(hour(start), hour(end))]
+        };
+        Self {
+            windows,
+            vacations: Vec::new(),
+            paused_until: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.windows.iter().any(|w| w.days.is_empty()) {
+            return Err(VeraError::invalid_argument("doNotDisturb.windows", "each window needs a day"));
+        }
+        if self.vacations.iter().any(|v| v.from > v.to) {
+            return Err(VeraError::invalid_argument("doNotDisturb.vacations", "must not end before they start"));
+        }
+        Ok(())
+    }
+
+    pub fn is_quiet(&self, now: DateTime<Local>) -> bool {
+        if self.paused_until.is_some_and(|until| now.timestamp() < until) {
+            return true;
+        }
+        let local = now.naive_local();
+        self.vacations.iter().any(|v| (v.from..=v.to).contains(&local.date()))
+            || self.windows.iter().any(|w| w.contains(&local))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(text: &str) -> DateTime<Local> {
+        let naive = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap();
+        Local.from_local_datetime(&naive).earliest().unwrap()
+    }
+
+    fn time(text: &str) -> TimeOfDay {
+        TimeOfDay::try_from(text.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_windows() {
+        // 2024-05-03 is a Friday
+        let dnd = DoNotDisturb {
+            windows: vec![
+                Window {
+                    days: vec![Day::Mon, Day::Tue, Day::Wed, Day::Thu, Day::Fri],
+                    start: time("22:30"),
+                    end: time("07:15"),
+                },
+                Window {
+                    days: vec![Day::Sat, Day::Sun],
+                    start: time("00:00"),
+                    end: time("23:59"),
+                },
+                // Empty
+                Window::every_day(time("12:00"), time("12:00")),
+            ],
+            ..DoNotDisturb::from_hours(0, 0)
+        };
+
+        assert!(!dnd.is_quiet(at("2024-05-03 22:29")));
+        assert!(dnd.is_quiet(at("2024-05-03 22:30")));
+        // Friday night runs into Saturday, which is quiet anyway
+        assert!(dnd.is_quiet(at("2024-05-04 12:00")));
+        assert!(dnd.is_quiet(at("2024-05-05 23:58")));
+        // Sunday's window ends at 23:59, Monday's starts at 22:30
+        assert!(!dnd.is_quiet(at("2024-05-05 23:59")));
+        assert!(!dnd.is_quiet(at("2024-05-06 12:00")));
+        assert!(dnd.is_quiet(at("2024-05-07 07:14")));
+        assert!(!dnd.is_quiet(at("2024-05-07 07:15")));
+    }
+
+    #[test]
+    fn test_vacations_and_pause() {
+        let mut dnd = DoNotDisturb {
+            windows: vec![],
+            vacations: vec![Vacation {
+                from: NaiveDate::from_ymd_opt(2024, 8, 10).unwrap(),
+                to: NaiveDate::from_ymd_opt(2024, 8, 15).unwrap(),
+                name: "Ferragosto".to_string(),
+            }],
+            paused_until: None,
+        };
+        assert!(!dnd.is_quiet(at("2024-08-09 23:59")));
+        assert!(dnd.is_quiet(at("2024-08-15 23:59")));
+        assert!(!dnd.is_quiet(at("2024-08-16 00:00")));
+
+        dnd.paused_until = Some(at("2024-05-01 15:00").timestamp());
+        assert!(dnd.is_quiet(at("2024-05-01 14:59")));
+        assert!(!dnd.is_quiet(at("2024-05-01 15:00")));
+    }
+
+    #[test]
+    fn test_serde() {
+        let dnd = DoNotDisturb::default();
+        assert!(dnd.is_quiet(at("2024-05-01 07:59")));
+        assert!(!dnd.is_quiet(at("2024-05-01 08:00")));
+
+        let json = serde_json::to_value(&dnd).unwrap();
+        assert_eq!(json["windows"][0]["start"], "19:00");
+        assert_eq!(json["windows"][0]["days"][0], "mon");
+        assert_eq!(serde_json::from_value::<DoNotDisturb>(json).unwrap(), dnd);
+
+        let bad = serde_json::json!({ "windows": [{ "days": ["mon"], "start": "24:00", "end": "07:00" }] });
+        assert!(serde_json::from_value::<DoNotDisturb>(bad).is_err());
+
+        let mut dnd = DoNotDisturb::default();
+        dnd.windows[0].days.clear();
+        assert_eq!(dnd.validate().unwrap_err().code(), "INVALID_ARGUMENT");
+    }
+}
@@ -0,0 +1,745 @@
+// Notification rules
+//
+// A rule is a trigger, optional conditions on the current consumption and a
+// message template. Rules are stored in SQLite (`notification_rules`) and
+// edited from the settings panel; the daily, weekly and monthly summaries
+// are shipped as default rules.
+use chrono::{DateTime, Datelike, Local, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::i18n::Locale;
+use crate::error::{Result, VeraError};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    pub id: String,
+    pub name: String,
+    pub enabled: bool,
+    pub trigger: Trigger,
+    /// All must hold for the rule to fire
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    pub template: Template,
+    /// Latest scheduled time handled, Unix seconds; kept by the scheduler
+    #[serde(default)]
+    pub last_run: Option<i64>,
+}
+
+/// Shortest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+}
+
+impl Period {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Period::Day => "day",
+            Period::Week => "week",
+            Period::Month => "month",
+        }
+    }
+
+    /// Local start of the period containing `now`
+    pub fn start(&self, now: DateTime<Local>) -> DateTime<Local> {
+        let date = now.date_naive();
+        let date = match self {
+            Period::Day => date,
+            Period::Week => date - chrono::Duration::days(date.weekday().num_days_from_monday() as i64),
+            Period::Month => date.with_day(1).unwrap(),
+        };
+        local_midnight(date)
+    }
+
+    /// Local start of the period before the one containing `now`
+    pub fn previous_start(&self, now: DateTime<Local>) -> DateTime<Local> {
+        let start = self.start(now).date_naive();
+        let date = match self {
+            Period::Day => start - chrono::Duration::days(1),
+            Period::Week => start - chrono::Duration::days(7),
+            Period::Month => start
+                .checked_sub_months(chrono::Months::new(1))
+                .unwrap_or(start),
+        };
+        local_midnight(date)
+    }
+}
+
+/// Midnight may not exist on DST change days; use the first instant of the day
+fn local_midnight(date: chrono::NaiveDate) -> DateTime<Local> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .unwrap_or_else(|| Local.from_utc_datetime(&midnight))
+}
+
+/// Quantity a threshold watches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Metric {
+    /// Latest recorded power, W
+    Power,
+    DayKwh,
+    WeekKwh,
+    MonthKwh,
+}
+
+/// Value an alert reads from every sample
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SampleMetric {
+    /// PC power, W
+    Power,
+    /// Battery charge, %
+    BatteryPercent,
+    /// Power drawn from the battery, W; 0 unless discharging
+    BatteryDischarge,
+}
+
+/// `metric` over `above` or under `below`, exactly one of them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Limit {
+    pub metric: SampleMetric,
+    #[serde(default)]
+    pub above: Option<f64>,
+    #[serde(default)]
+    pub below: Option<f64>,
+    /// How far back past the threshold the value has to go to clear, in
+    /// the metric's unit
+    #[serde(default)]
+    pub hysteresis: f64,
+}
+
+impl Limit {
+    pub fn threshold(&self) -> f64 {
+        self.above.or(self.below).unwrap_or_default()
+    }
+
+    pub fn exceeded(&self, value: f64) -> bool {
+        match (self.above, self.below) {
+            (Some(above), _) => value > above,
+            (None, Some(below)) => value < below,
+            (None, None) => false,
+        }
+    }
+
+    /// Back within the threshold by the hysteresis
+    pub fn cleared(&self, value: f64) -> bool {
+        match (self.above, self.below) {
+            (Some(above), _) => value <= above - self.hysteresis,
+            (None, Some(below)) => value >= below + self.hysteresis,
+            (None, None) => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Trigger {
+    /// Cron expression, `minute hour day-of-month month day-of-week`, local time
+    Schedule { cron: String },
+    /// `metric` goes from at or below `above` to over it
+    Threshold { metric: Metric, above: f64 },
+    /// A period ended with consumption within `kwh`; fires early in the next one
+    #[serde(rename_all = "camelCase")]
+    Goal { period: Period, kwh: f64 },
+    /// Power rises above `factor` times its average over the last `window_minutes`
+    #[serde(rename_all = "camelCase")]
+    Anomaly { factor: f64, window_minutes: u32 },
+    /// Checked on every sample: all `limits` exceeded for `duration_secs`.
+    /// Fires once until one of them clears, and not within `cooldown_secs`
+    /// of the last time.
+    #[serde(rename_all = "camelCase")]
+    Alert {
+        limits: Vec<Limit>,
+        #[serde(default)]
+        duration_secs: u64,
+        #[serde(default)]
+        cooldown_secs: u64,
+    },
+}
+
+/// `min` is inclusive, `max` exclusive; either may be left out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Condition {
+    /// Consumption so far in the period, kWh
+    Kwh { period: Period, min: Option<f64>, max: Option<f64> },
+    /// Change against the previous period of the same length, %
+    Trend { period: Period, min: Option<f64>, max: Option<f64> },
+}
+
+impl Condition {
+    pub fn period(&self) -> Period {
+        match self {
+            Condition::Kwh { period, .. } | Condition::Trend { period, .. } => *period,
+        }
+    }
+
+    pub fn holds(&self, value: f64) -> bool {
+        let (min, max) = match self {
+            Condition::Kwh { min, max, .. } | Condition::Trend { min, max, .. } => (*min, *max),
+        };
+        min.is_none_or(|min| value >= min) && max.is_none_or(|max| value < max)
+    }
+}
+
+/// Title and message may use placeholders such as `{day.kwh:.2}`, see
+/// `PLACEHOLDERS`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Template {
+    /// Catalogue entry the texts are translated from (see `i18n`); unset
+    /// for texts written by the user
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub title: String,
+    pub message: String,
+    /// info, warning, critical or success
+    #[serde(rename = "type")]
+    pub type_: String,
+}
+
+/// Values per period: `{day.kwh}`, `{week.trend}`, ..., and for the
+/// complete period before it: `{previousMonth.cost}`, ...
+pub const PERIOD_FIELDS: &[&str] = &["kwh", "bottles", "co2", "co2Kg", "cost", "trend", "peak"];
+/// Values without a period; `value`, `threshold` and `average` are set by
+/// threshold, anomaly and alert triggers, `time` is when the rule fired
+pub const PLACEHOLDERS: &[&str] = &["power", "value", "threshold", "average", "time"];
+
+const NOTIFICATION_TYPES: &[&str] = &["info", "warning", "critical", "success"];
+
+const MAX_ALERT_DURATION_SECS: u64 = 24 * 3600;
+const MAX_ALERT_COOLDOWN_SECS: u64 = 7 * 24 * 3600;
+
+impl Rule {
+    pub fn validate(&self) -> Result<()> {
+        if self.id.trim().is_empty() {
+            return Err(VeraError::invalid_argument("id", "must not be empty"));
+        }
+        if self.name.trim().is_empty() {
+            return Err(VeraError::invalid_argument("name", "must not be empty"));
+        }
+
+        match &self.trigger {
+            Trigger::Schedule { cron } => {
+                Schedule::parse(cron).map_err(|e| VeraError::invalid_argument("trigger.cron", e))?;
+            }
+            Trigger::Threshold { above, .. } if !above.is_finite() => {
+                return Err(VeraError::invalid_argument("trigger.above", "must be a number"));
+            }
+            Trigger::Goal { kwh, .. } if !(kwh.is_finite() && *kwh > 0.0) => {
+                return Err(VeraError::invalid_argument("trigger.kwh", "must be greater than 0"));
+            }
+            Trigger::Anomaly { factor, window_minutes } => {
+                if !(factor.is_finite() && *factor > 1.0) {
+                    return Err(VeraError::invalid_argument("trigger.factor", "must be greater than 1"));
+                }
+                if !(5..=24 * 60).contains(window_minutes) {
+                    return Err(VeraError::invalid_argument("trigger.windowMinutes", "must be between 5 and 1440"));
+                }
+            }
+            Trigger::Alert { limits, duration_secs, cooldown_secs } => {
+                if limits.is_empty() {
+                    return Err(VeraError::invalid_argument("trigger.limits", "must not be empty"));
+                }
+                for limit in limits {
+                    let threshold = match (limit.above, limit.below) {
+                        (Some(threshold), None) | (None, Some(threshold)) => threshold,
+                        _ => return Err(VeraError::invalid_argument("trigger.limits", "needs above or below")),
+                    };
+                    if !threshold.is_finite() {
+                        return Err(VeraError::invalid_argument("trigger.limits", "threshold must be a number"));
+                    }
+                    if !(limit.hysteresis.is_finite() && limit.hysteresis >= 0.0) {
+                        return Err(VeraError::invalid_argument("trigger.limits", "hysteresis must not be negative"));
+                    }
+                }
+                if *duration_secs > MAX_ALERT_DURATION_SECS {
+                    return Err(VeraError::invalid_argument("trigger.durationSecs", "must be at most a day"));
+                }
+                if *cooldown_secs > MAX_ALERT_COOLDOWN_SECS {
+                    return Err(VeraError::invalid_argument("trigger.cooldownSecs", "must be at most a week"));
+                }
+            }
+            _ => {}
+        }
+
+        for condition in &self.conditions {
+            let (min, max) = match condition {
+                Condition::Kwh { min, max, .. } | Condition::Trend { min, max, .. } => (*min, *max),
+            };
+            if min.is_none() && max.is_none() {
+                return Err(VeraError::invalid_argument("conditions", "needs min or max"));
+            }
+            if let (Some(min), Some(max)) = (min, max) {
+                if min >= max {
+                    return Err(VeraError::invalid_argument("conditions", "min must be below max"));
+                }
+            }
+        }
+
+        if !NOTIFICATION_TYPES.contains(&self.template.type_.as_str()) {
+            return Err(VeraError::invalid_argument("template.type", "expected info, warning, critical or success"));
+        }
+        if self.template.title.trim().is_empty() {
+            return Err(VeraError::invalid_argument("template.title", "must not be empty"));
+        }
+        for (field, text) in [("template.title", &self.template.title), ("template.message", &self.template.message)] {
+            for placeholder in placeholders(text) {
+                placeholder.check().map_err(|e| VeraError::invalid_argument(field, e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Template {
+    /// Title and message in `locale`; texts without a translation as written
+    pub fn texts(&self, locale: Locale) -> (&str, &str) {
+        let translated = self.id.as_ref().and_then(|id| {
+            Some((locale.text(&format!("{}.title", id))?, locale.text(&format!("{}.message", id))?))
+        });
+        translated.unwrap_or((&self.title, &self.message))
+    }
+}
+
+/// How a placeholder's value is written
+#[derive(Debug, Clone, PartialEq)]
+pub enum Format<'a> {
+    /// `{name}` or `{name:.N}`, 2 decimals by default
+    Number(Option<usize>),
+    /// `{name:currency}`, in euro
+    Currency,
+    /// `{name:date}`, a Unix time
+    Date,
+    /// `{name:bottle|bottles}`, the word agreeing with the value as last
+    /// written in the same text
+    Plural { one: &'a str, other: &'a str },
+}
+
+/// `{name}` or `{name:format}` in a template
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placeholder<'a> {
+    pub name: &'a str,
+    /// `None` for a format that is not understood
+    pub format: Option<Format<'a>>,
+}
+
+impl Placeholder<'_> {
+    /// Period the value belongs to, for `{day.kwh}` style names, and
+    /// whether it is the one before, for `{previousMonth.kwh}`
+    pub fn period(&self) -> Option<(Period, bool, &str)> {
+        let (period, field) = self.name.split_once('.')?;
+        let (period, previous) = match period {
+            "day" => (Period::Day, false),
+            "week" => (Period::Week, false),
+            "month" => (Period::Month, false),
+            "previousDay" => (Period::Day, true),
+            "previousWeek" => (Period::Week, true),
+            "previousMonth" => (Period::Month, true),
+            _ => return None,
+        };
+        Some((period, previous, field))
+    }
+
+    fn check(&self) -> std::result::Result<(), String> {
+        let known = match self.period() {
+            Some((_, _, field)) => PERIOD_FIELDS.contains(&field),
+            None => PLACEHOLDERS.contains(&self.name),
+        };
+        if !known {
+            return Err(format!("unknown placeholder {{{}}}", self.name));
+        }
+        if self.format.is_none() {
+            return Err(format!("unknown format in {{{}}}, expected .N, currency, date or singular|plural", self.name));
+        }
+        Ok(())
+    }
+
+    /// The value written in `locale`; a plural agrees with `written`, the
+    /// number as it already appears in the text, if it does
+    pub fn format(&self, value: f64, locale: Locale, written: Option<&str>) -> Option<String> {
+        Some(match self.format.as_ref()? {
+            Format::Number(decimals) => locale.number(value, decimals.unwrap_or(2)),
+            Format::Currency => locale.currency(value),
+            Format::Date => locale.date(Local.timestamp_opt(value as i64, 0).single()?),
+            Format::Plural { one, other } => {
+                let number = written.map_or_else(|| locale.number(value, 2), str::to_string);
+                locale.plural(&number, one, other).to_string()
+            }
+        })
+    }
+}
+
+fn parse_format(spec: &str) -> Option<Format<'_>> {
+    if let Some(decimals) = spec.strip_prefix('.') {
+        return decimals.parse().ok().map(|d| Format::Number(Some(d)));
+    }
+    match spec {
+        "currency" => Some(Format::Currency),
+        "date" => Some(Format::Date),
+        _ => {
+            let (one, other) = spec.split_once('|')?;
+            Some(Format::Plural { one, other })
+        }
+    }
+}
+
+/// Placeholders in order of appearance; `{{` is a literal brace
+pub fn placeholders(text: &str) -> Vec<Placeholder<'_>> {
+    let mut found = Vec::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        if rest[open..].starts_with("{{") {
+            rest = &rest[open + 2..];
+            continue;
+        }
+        let Some(close) = rest[open..].find('}') else { break };
+        let inner = &rest[open + 1..open + close];
+        let (name, format) = match inner.split_once(':') {
+            Some((name, spec)) => (name, parse_format(spec)),
+            None => (inner, Some(Format::Number(None))),
+        };
+        found.push(Placeholder { name, format });
+        rest = &rest[open + close + 1..];
+    }
+    found
+}
+
+/// Fill placeholders with `value`, written for `locale`; unknown ones are
+/// left as written
+pub fn render(text: &str, locale: Locale, value: impl Fn(&Placeholder) -> Option<f64>) -> String {
+    let mut out = String::with_capacity(text.len());
+    // Numbers as written so far, for the plurals that follow them
+    let mut numbers: HashMap<&str, String> = HashMap::new();
+    let mut rest = text;
+    // `}}` is a literal `}` anywhere outside a placeholder
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open].replace("}}", "}"));
+        rest = &rest[open..];
+        if rest.starts_with("{{") {
+            out.push('{');
+            rest = &rest[2..];
+            continue;
+        }
+        let Some(close) = rest.find('}') else { break };
+        let written = &rest[..close + 1];
+        let placeholder = placeholders(written).pop();
+        let formatted = placeholder.and_then(|p| {
+            let text = p.format(value(&p)?, locale, numbers.get(p.name).map(String::as_str))?;
+            Some((text, p))
+        });
+        match formatted {
+            Some((text, p)) => {
+                out.push_str(&text);
+                if let Some(Format::Number(_)) = p.format {
+                    numbers.insert(p.name, text);
+                }
+            }
+            None => out.push_str(written),
+        }
+        rest = &rest[close + 1..];
+    }
+    out.push_str(&rest.replace("}}", "}"));
+    out
+}
+
+/// Parsed cron expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // Cron matches either day field when both are restricted
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Schedule {
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err("expected 5 fields: minute hour day month weekday".to_string());
+        };
+
+        // Sunday is both 0 and 7
+        let mut weekdays = parse_field(weekday, 0, 7)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days: parse_field(day, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    pub fn matches(&self, time: &DateTime<Local>) -> bool {
+        let bit = |mask: u64, n: u32| mask & (1 << n) != 0;
+        let day = bit(self.days, time.day());
+        let weekday = bit(self.weekdays, time.weekday().num_days_from_sunday());
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+
+        bit(self.minutes, time.minute()) && bit(self.hours, time.hour()) && bit(self.months, time.month()) && day_matches
+    }
+
+    /// Scheduled minutes in `(after, until]`, oldest first, going back at
+    /// most `MAX_CATCH_UP_DAYS`
+    pub fn between(&self, after: DateTime<Local>, until: DateTime<Local>) -> Vec<DateTime<Local>> {
+        let until_minute = until.timestamp().div_euclid(60);
+        let after_minute = after
+            .timestamp()
+            .div_euclid(60)
+            .max(until_minute - MAX_CATCH_UP_DAYS * 24 * 60);
+        (after_minute + 1..=until_minute)
+            .filter_map(|minute| Local.timestamp_opt(minute * 60, 0).single())
+            .filter(|time| self.matches(time))
+            .collect()
+    }
+}
+
+/// Enough to catch up on a monthly summary
+pub const MAX_CATCH_UP_DAYS: i64 = 31;
+
+/// `*`, `5`, `1-5`, `*/15`, `1-10/3` and comma lists, as a bit mask
+fn parse_field(text: &str, min: u32, max: u32) -> std::result::Result<u64, String> {
+    let mut mask = 0u64;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().map_err(|_| format!("invalid step in `{}`", part))?;
+                if step == 0 {
+                    return Err(format!("step must be positive in `{}`", part));
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let number = |s: &str| -> std::result::Result<u32, String> {
+            let n: u32 = s.parse().map_err(|_| format!("invalid value `{}`", s))?;
+            if (min..=max).contains(&n) {
+                Ok(n)
+            } else {
+                Err(format!("`{}` is outside {}-{}", n, min, max))
+            }
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (number(start)?, number(end)?),
+                None if step > 1 => (number(range)?, max),
+                None => {
+                    let n = number(range)?;
+                    (n, n)
+                }
+            },
+        };
+        if start > end {
+            return Err(format!("empty range `{}`", range));
+        }
+
+        for n in (start..=end).step_by(step as usize) {
+            mask |= 1 << n;
+        }
+    }
+    Ok(mask)
+}
+
+/// The summaries the scheduler used to send at fixed times, with their
+/// texts from the catalogue
+pub fn default_rules() -> Vec<Rule> {
+    let rule = |id: &str, name: &str, cron: &str, conditions: Vec<Condition>, type_: &str| {
+        let text = |part: &str| Locale::It.text(&format!("{}.{}", id, part)).unwrap_or_default().to_string();
+        Rule {
+            id: id.to_string(),
+            name: name.to_string(),
+            enabled: true,
+            trigger: Trigger::Schedule { cron: cron.to_string() },
+            conditions,
+            template: Template {
+                id: Some(id.to_string()),
+                title: text("title"),
+                message: text("message"),
+                type_: type_.to_string(),
+            },
+            last_run: None,
+        }
+    };
+    let kwh = |min, max| Condition::Kwh { period: Period::Day, min, max };
+    let trend = |min, max| Condition::Trend { period: Period::Week, min, max };
+
+    vec![
+        rule("daily-summary", "Riepilogo giornaliero", "0 18 * * *", vec![kwh(None, Some(2.0))], "info"),
+        rule("daily-high", "Consumo elevato", "0 18 * * *", vec![kwh(Some(2.0), None)], "warning"),
+        rule("weekly-summary", "Riepilogo settimanale", "0 10 * * 0", vec![trend(Some(-5.0), None)], "info"),
+        rule("weekly-improved", "Settimana in calo", "0 10 * * 0", vec![trend(None, Some(-5.0))], "success"),
+        rule("monthly-summary", "Report mensile", "0 10 1 * *", vec![], "info"),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> DateTime<Local> {
+        let naive = chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap();
+        Local.from_local_datetime(&naive).earliest().unwrap()
+    }
+
+    #[test]
+    fn test_cron() {
+        let daily = Schedule::parse("0 18 * * *").unwrap();
+        assert!(daily.matches(&at("2024-05-01 18:00")));
+        assert!(!daily.matches(&at("2024-05-01 18:01")));
+
+        // 2024-05-05 is a Sunday, 7 and 0 both mean Sunday
+        let sunday = Schedule::parse("0 10 * * 7").unwrap();
+        assert!(sunday.matches(&at("2024-05-05 10:00")));
+        assert!(!sunday.matches(&at("2024-05-06 10:00")));
+
+        let steps = Schedule::parse("*/15 9-17 * * 1-5").unwrap();
+        assert!(steps.matches(&at("2024-05-06 09:45")));
+        assert!(!steps.matches(&at("2024-05-06 09:50")));
+        assert!(!steps.matches(&at("2024-05-05 09:45")));
+
+        // Both day fields restricted: either one matches
+        let either = Schedule::parse("0 10 1 * 0").unwrap();
+        assert!(either.matches(&at("2024-05-01 10:00")));
+        assert!(either.matches(&at("2024-05-05 10:00")));
+
+        for bad in ["0 18 * *", "60 * * * *", "* 24 * * *", "*/0 * * * *", "5-1 * * * *", "x * * * *"] {
+            assert!(Schedule::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_schedule_is_not_tied_to_the_tick() {
+        let daily = Schedule::parse("0 18 * * *").unwrap();
+        // A tick at 17:59:40 and the next at 18:00:41 must still fire
+        let before = at("2024-05-01 17:59") + chrono::Duration::seconds(40);
+        let after = at("2024-05-01 18:00") + chrono::Duration::seconds(41);
+        assert_eq!(daily.between(before, after), [at("2024-05-01 18:00")]);
+        // ...but only once
+        assert!(daily.between(after, after + chrono::Duration::seconds(60)).is_empty());
+
+        // Everything missed while closed, capped to a month
+        let missed = daily.between(at("2024-04-28 19:00"), at("2024-05-01 09:00"));
+        assert_eq!(missed, [at("2024-04-29 18:00"), at("2024-04-30 18:00")]);
+        assert_eq!(daily.between(at("2023-01-01 00:00"), at("2024-05-01 09:00")).len(), 31);
+    }
+
+    #[test]
+    fn test_templates() {
+        let text = "{day.kwh:.2} kWh, {power:.0} W, {nope}, {{literal}}";
+        let names: Vec<_> = placeholders(text).iter().map(|p| p.name).collect();
+        assert_eq!(names, ["day.kwh", "power", "nope"]);
+
+        let value = |p: &Placeholder| match p.name {
+            "day.kwh" => Some(1.23456),
+            "power" => Some(85.4),
+            "month.cost" => Some(1234.5),
+            "day.bottles" => Some(1.0),
+            "time" => Some(at("2024-05-01 18:00").timestamp() as f64),
+            _ => None,
+        };
+        assert_eq!(render(text, Locale::En, value), "1.23 kWh, 85 W, {nope}, {literal}");
+        assert_eq!(render(text, Locale::It, value), "1,23 kWh, 85 W, {nope}, {literal}");
+        assert_eq!(render("{{a}} {power:.0}", Locale::En, value), "{a} 85");
+        assert_eq!(render("a}} {power:.0} {unclosed", Locale::En, value), "a} 85 {unclosed");
+
+        let text = "{month.cost:currency}, {day.bottles:.0} {day.bottles:bottiglia|bottiglie}, {time:date}, {power:x}";
+        assert_eq!(render(text, Locale::It, value), "1.234,50 €, 1 bottiglia, 01/05 18:00, {power:x}");
+
+        // The word agrees with the number as written, not the exact value
+        let bottles = |text: &str, bottles: f64, locale: Locale| render(text, locale, |_| Some(bottles));
+        let one_decimal = "{day.bottles:.1} {day.bottles:bottiglia|bottiglie}";
+        let no_decimals = "{day.bottles:.0} {day.bottles:bottle|bottles}";
+        for value in [0.96, 1.0, 1.04] {
+            assert_eq!(bottles(one_decimal, value, Locale::It), "1,0 bottiglie");
+            assert_eq!(bottles(no_decimals, value, Locale::En), "1 bottle");
+        }
+        assert_eq!(bottles(no_decimals, 1.6, Locale::En), "2 bottles");
+        // Without a number before it, as the default 2 decimals
+        assert_eq!(bottles("{day.bottles:bottle|bottles}", 1.0, Locale::En), "bottles");
+    }
+
+    #[test]
+    fn test_default_rules_are_valid() {
+        let rules = default_rules();
+        for rule in &rules {
+            rule.validate().unwrap();
+        }
+
+        // The daily summary splits on 2 kWh like the old scheduler did
+        let high = rules.iter().find(|r| r.id == "daily-high").unwrap();
+        assert!(high.conditions[0].holds(2.5));
+        assert!(!high.conditions[0].holds(1.0));
+
+        let json = serde_json::to_value(&rules[0]).unwrap();
+        assert_eq!(json["trigger"], serde_json::json!({ "kind": "schedule", "cron": "0 18 * * *" }));
+        assert_eq!(json["template"]["type"], "info");
+        let back: Rule = serde_json::from_value(json).unwrap();
+        assert_eq!(back, rules[0]);
+
+        // Translated from the catalogue, unless the text was rewritten
+        let mut template = rules[0].template.clone();
+        assert_eq!(template.texts(Locale::En).0, "Daily summary");
+        template.id = None;
+        assert_eq!(template.texts(Locale::En).0, "Riepilogo giornaliero");
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let mut rule = default_rules().remove(0);
+        rule.template.message = "{day.watts}".to_string();
+        assert_eq!(rule.validate().unwrap_err().code(), "INVALID_ARGUMENT");
+
+        let mut rule = default_rules().remove(0);
+        rule.template.message = "{day.kwh:.x}".to_string();
+        assert!(rule.validate().is_err());
+
+        let mut rule = default_rules().remove(0);
+        rule.trigger = Trigger::Anomaly { factor: 0.5, window_minutes: 60 };
+        assert!(rule.validate().is_err());
+
+        let mut rule = default_rules().remove(0);
+        rule.conditions = vec![Condition::Kwh { period: Period::Day, min: None, max: None }];
+        assert!(rule.validate().is_err());
+
+        // Battery below 20% while discharging over 40 W
+        let mut rule = default_rules().remove(0);
+        let battery = Limit { metric: SampleMetric::BatteryPercent, above: None, below: Some(20.0), hysteresis: 5.0 };
+        let discharge = Limit { metric: SampleMetric::BatteryDischarge, above: Some(40.0), below: None, hysteresis: 0.0 };
+        rule.trigger = Trigger::Alert { limits: vec![battery.clone(), discharge], duration_secs: 60, cooldown_secs: 1800 };
+        rule.validate().unwrap();
+        let json = serde_json::to_value(&rule.trigger).unwrap();
+        assert_eq!(json["kind"], "alert");
+        assert_eq!(json["limits"][0]["metric"], "batteryPercent");
+        assert_eq!(json["cooldownSecs"], 1800);
+
+        for limits in [vec![], vec![Limit { above: Some(30.0), ..battery.clone() }], vec![Limit { hysteresis: -1.0, ..battery.clone() }]] {
+            rule.trigger = Trigger::Alert { limits, duration_secs: 0, cooldown_secs: 0 };
+            assert!(rule.validate().is_err());
+        }
+        rule.trigger = Trigger::Alert { limits: vec![battery], duration_secs: 2 * 24 * 3600, cooldown_secs: 0 };
+        assert!(rule.validate().is_err());
+    }
+}
@@ -1,225 +1,395 @@
-// Notification scheduler respecting quiet hours
-use tauri::{AppHandle, Manager};
+// Notification scheduler respecting do not disturb
+//
+// Runs the notification rules once a minute, and alert rules on every
+// reading, and delivers what they produce. What falls due while do not
+// disturb is on, or while the user is busy in a fullscreen app or call, is
+// queued in the database and delivered when it ends: each one is stored on
+// its own, and several pop up on the desktop as one digest. Everything due
+// is also published as "notification-due" for the webhook, quiet or not,
+// once it is queued or stored, so a rule that fires again for the same
+// time or window is not posted twice.
+use tokio::sync::broadcast::error::RecvError;
 use tokio::time::{interval, Duration};
-use chrono::{Local, Timelike, Datelike, Weekday};
-
-use crate::storage;
-
-/// Check if current time is within quiet hours
-async fn is_quiet_hours() -> bool {
-    match storage::db::load_settings().await {
-        Ok(settings) => {
-            let now = Local::now();
-            let hour = now.hour() as u8;
-            
-            let start = settings.quiet_hours_start;
-            let end = settings.quiet_hours_end;
-            
-            // Handle wrap-around (e.g., 19:00 to 08:00)
-            if start > end {
-                hour >= start || hour < end
-            } else {
-                hour >= start && hour < end
-            }
-        }
-        Err(_) => {
-            // Default: 19:00 to 08:00
-            let hour = Local::now().hour();
-            hour >= 19 || hour < 8
-        }
-    }
-}
+use chrono::{DateTime, Local, TimeZone};
+
+use super::alerts::Sample;
+use super::engine::{self, DbSource, Engine};
+use super::i18n::Locale;
+use super::presence::Presence;
+use super::rules::Rule;
+use crate::energy::monitor::DEVICE_TYPE;
+use crate::error::Result;
+use crate::events::{self, Event};
+use crate::storage::{self, EnergyReading, Notification, UserSettings};
 
-/// Check if a fullscreen application is running
-#[cfg(target_os = "windows")]
-fn is_fullscreen_app_active() -> bool {
-    // TODO: Implementare usando Windows API
-    // Richiede: winapi crate
-    // GetForegroundWindow() + GetWindowRect() + confronto con screen size
-    false
+/// Seconds between retention passes over the inbox
+const PRUNE_INTERVAL: i64 = 3600;
+
+/// Where notifications are kept
+pub(crate) trait Store {
+    /// False when one with the same dedup key is already stored
+    async fn save(&mut self, notification: &Notification) -> Result<bool>;
+    /// Hold back until do not disturb ends; whether each was queued, not
+    /// when it already was or was delivered
+    async fn queue(&mut self, held: &[(i64, Notification)]) -> Result<Vec<bool>>;
+    /// Everything held back, oldest first, emptying the queue
+    async fn take_queued(&mut self) -> Result<Vec<(i64, Notification)>>;
 }
 
-#[cfg(not(target_os = "windows"))]
-fn is_fullscreen_app_active() -> bool {
-    false
+/// The app database
+struct DbStore;
+
+impl Store for DbStore {
+    async fn save(&mut self, notification: &Notification) -> Result<bool> {
+        storage::db::save_notification(notification.clone()).await
+    }
+
+    async fn queue(&mut self, held: &[(i64, Notification)]) -> Result<Vec<bool>> {
+        storage::db::queue_notifications(held).await
+    }
+
+    async fn take_queued(&mut self) -> Result<Vec<(i64, Notification)>> {
+        storage::db::take_queued_notifications().await
+    }
 }
 
 /// Start notification scheduler
-pub async fn start(app: AppHandle) {
+pub async fn start() {
     let mut check_interval = interval(Duration::from_secs(60)); // Check every minute
-    
-    let mut last_daily_notification = 0u32;
-    let mut last_weekly_notification = (0u32, 0u32); // (week, year)
-    let mut last_monthly_notification = (0u32, 0u32); // (month, year)
+    let mut engine = Engine::new();
+    let mut presence = Presence::new();
+    // Kept when the database cannot be read for a moment
+    let mut settings = UserSettings::default();
+    // As of the last tick; alerts are checked against them between ticks
+    let mut rules: Vec<Rule> = Vec::new();
+    let mut readings = events::subscribe();
+    let mut pruned_at = None;
     
     loop {
-        check_interval.tick().await;
-        
-        // Skip if in quiet hours
-        if is_quiet_hours().await {
-            continue;
+        tokio::select! {
+            _ = check_interval.tick() => {}
+            event = readings.recv() => {
+                match event {
+                    Ok(event) => alert(&event, &rules, &settings, &mut engine, &mut presence).await,
+                    Err(RecvError::Lagged(skipped)) => eprintln!("Alerts skipped {} events", skipped),
+                    Err(RecvError::Closed) => readings = events::subscribe(),
+                }
+                continue;
+            }
         }
         
-        // Skip if fullscreen app is active
-        if is_fullscreen_app_active() {
-            continue;
+        match storage::db::load_settings().await {
+            Ok(loaded) => settings = loaded,
+            Err(e) => eprintln!("Failed to load settings, using the last ones read: {}", e),
         }
+        engine.locale = settings.locale();
         
-        // Check if notifications are enabled
-        let settings = match storage::db::load_settings().await {
-            Ok(s) => s,
-            Err(_) => continue,
+        // Rules are evaluated every tick so thresholds keep tracking their
+        // value while notifications are held back
+        rules = match storage::db::get_notification_rules().await {
+            Ok(rules) => rules,
+            Err(e) => {
+                eprintln!("Failed to load notification rules: {}", e);
+                continue;
+            }
         };
+        let now = Local::now();
         
-        if !settings.notifications_enabled {
-            continue;
+        // Retention, on the first tick and hourly after that
+        if pruned_at.is_none_or(|at| now.timestamp() - at >= PRUNE_INTERVAL) {
+            prune(&settings, now.timestamp()).await;
+            pruned_at = Some(now.timestamp());
         }
         
-        let now = Local::now();
-        let hour = now.hour();
-        let minute = now.minute();
-        let day = now.day();
-        let weekday = now.weekday();
-        let month = now.month();
-        let year = now.year() as u32;
+        let tick = engine.tick(&rules, now, &mut DbSource::default()).await;
         
-        // Daily notification at 18:00
-        if hour == 18 && minute == 0 && last_daily_notification != day {
-            send_daily_summary(&app).await;
-            last_daily_notification = day;
+        // With notifications off the runs are still recorded, so turning
+        // them back on does not replay everything in between
+        if !settings.notifications_enabled {
+            record_runs(&mut engine, &tick.runs).await;
+            continue;
         }
         
-        // Weekly notification on Sunday at 10:00
-        if weekday == Weekday::Sun && hour == 10 && minute == 0 {
-            let week = now.iso_week().week();
-            if last_weekly_notification != (week, year) {
-                send_weekly_summary(&app).await;
-                last_weekly_notification = (week, year);
-            }
+        // Unrecorded runs make scheduled rules fire again next tick
+        if dispatch(&settings, &mut presence, &engine, now, tick.notifications, tick.missed).await {
+            record_runs(&mut engine, &tick.runs).await;
         }
-        
-        // Monthly notification on 1st day at 10:00
-        if day == 1 && hour == 10 && minute == 0 {
-            if last_monthly_notification != (month, year) {
-                send_monthly_summary(&app).await;
-                last_monthly_notification = (month, year);
-            }
+    }
+}
+
+/// Check the alert rules against a new reading and send what fires
+async fn alert(event: &Event, rules: &[Rule], settings: &UserSettings, engine: &mut Engine, presence: &mut Presence) {
+    if event.name != "reading" {
+        return;
+    }
+    let reading: EnergyReading = match serde_json::from_value(event.payload.clone()) {
+        Ok(reading) => reading,
+        Err(e) => {
+            eprintln!("Failed to read reading event: {}", e);
+            return;
         }
+    };
+    if reading.device_type != DEVICE_TYPE {
+        return;
+    }
+    
+    let sample = Sample::read(&reading, rules);
+    // Evaluated even with notifications off, so they do not all fire at
+    // once when they are turned back on
+    let fired = engine.sample(rules, &sample, &mut DbSource::default()).await;
+    if fired.is_empty() || !settings.notifications_enabled {
+        return;
     }
+    dispatch(settings, presence, engine, Local::now(), fired, Vec::new()).await;
 }
 
-async fn send_daily_summary(app: &AppHandle) {
-    match storage::db::get_stats("day", "pc").await {
-        Ok(stats) => {
-            let notification_type = if stats.today_kwh > 2.0 { "warning" } else { "info" };
-            let title = if stats.today_kwh > 2.0 { 
-                "Consumo elevato oggi" 
-            } else { 
-                "Riepilogo giornaliero" 
-            };
-            
-            let message = format!(
-                "Oggi: {:.2} kWh ≈ {:.1} bottiglie 💧 | CO₂: {:.0}g",
-                stats.today_kwh,
-                stats.bottles_today,
-                stats.co2_today
-            );
-            
-            // Save notification to database
-            let notification = storage::Notification {
-                id: format!("daily-{}", chrono::Utc::now().timestamp()),
-                title: title.to_string(),
-                message,
-                timestamp: chrono::Utc::now().timestamp(),
-                read: false,
-                type_: notification_type.to_string(),
-            };
-            
-            if let Err(e) = storage::db::save_notification(notification.clone()).await {
-                eprintln!("Failed to save notification: {}", e);
+/// Deliver `due` and `missed` with anything queued, or queue them while do
+/// not disturb is on or the user is busy. False when they could not be
+/// queued.
+async fn dispatch(
+    settings: &UserSettings,
+    presence: &mut Presence,
+    engine: &Engine,
+    now: DateTime<Local>,
+    due: Vec<Notification>,
+    missed: Vec<(DateTime<Local>, Notification)>,
+) -> bool {
+    let quiet = settings.do_not_disturb.is_quiet(now) || presence.is_busy().await;
+    deliver(&mut DbStore, quiet, engine.locale, now, due, missed).await
+}
+
+/// `dispatch` once do not disturb and presence are known
+async fn deliver(
+    store: &mut impl Store,
+    quiet: bool,
+    locale: Locale,
+    now: DateTime<Local>,
+    due: Vec<Notification>,
+    missed: Vec<(DateTime<Local>, Notification)>,
+) -> bool {
+    if quiet {
+        let held: Vec<_> = missed
+            .into_iter()
+            .map(|(due, notification)| (due.timestamp(), notification))
+            .chain(due.into_iter().map(|n| (n.timestamp, n)))
+            .collect();
+        if held.is_empty() {
+            return true;
+        }
+        match store.queue(&held).await {
+            Ok(added) => {
+                for ((_, notification), _) in held.iter().zip(added).filter(|(_, added)| *added) {
+                    events::publish("notification-due", notification);
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to queue notifications: {}", e);
+                return false;
             }
-            
-            // Emit to frontend
-            app.emit_all("new-notification", &notification).ok();
         }
+        return true;
+    }
+    
+    // Queued ones were published as due when they were queued
+    let mut queued: Vec<_> = match store.take_queued().await {
+        Ok(queued) => queued.into_iter().map(|(due, n)| (local_time(due), n, false)).collect(),
         Err(e) => {
-            eprintln!("Failed to get daily stats: {}", e);
+            eprintln!("Failed to read queued notifications: {}", e);
+            Vec::new()
+        }
+    };
+    queued.extend(missed.into_iter().map(|(due, n)| (due, n, true)));
+    queued.sort_by_key(|(due, _, _)| *due);
+    
+    for notification in due {
+        if save(store, &notification, true).await {
+            events::publish("new-notification", &notification);
+            events::publish("notification-popup", &notification);
         }
     }
+    // Held back or missed ones go to the inbox as they are, with their
+    // template and dedup key; several pop up as one digest
+    let mut stored = Vec::new();
+    for (due, notification, fresh) in queued {
+        if save(store, &notification, fresh).await {
+            events::publish("new-notification", &notification);
+            stored.push((due, notification));
+        }
+    }
+    if let Some(popup) = engine::digest(stored, now, locale) {
+        events::publish("notification-popup", &popup);
+    }
+    true
 }
 
-async fn send_weekly_summary(app: &AppHandle) {
-    match storage::db::get_stats("week", "pc").await {
-        Ok(stats) => {
-            let notification_type = if stats.weekly_trend < -5.0 { "success" } else { "info" };
-            let title = if stats.weekly_trend < -5.0 {
-                "Ottimo lavoro questa settimana! 🎉"
-            } else {
-                "Riepilogo settimanale"
-            };
-            
-            let message = format!(
-                "Questa settimana: {:.2} kWh ≈ {:.1} bottiglie 💧 | Trend: {:.1}%",
-                stats.weekly_kwh,
-                stats.bottles_week,
-                stats.weekly_trend
-            );
-            
-            // Save notification to database
-            let notification = storage::Notification {
-                id: format!("weekly-{}", chrono::Utc::now().timestamp()),
-                title: title.to_string(),
-                message,
-                timestamp: chrono::Utc::now().timestamp(),
-                read: false,
-                type_: notification_type.to_string(),
-            };
-            
-            if let Err(e) = storage::db::save_notification(notification.clone()).await {
-                eprintln!("Failed to save notification: {}", e);
+fn local_time(timestamp: i64) -> DateTime<Local> {
+    Local.timestamp_opt(timestamp, 0).single().unwrap_or_else(Local::now)
+}
+
+/// False when it was already delivered for its time or window. A `fresh`
+/// one is published as due once stored.
+async fn save(store: &mut impl Store, notification: &Notification, fresh: bool) -> bool {
+    match store.save(notification).await {
+        Ok(saved) => {
+            if saved && fresh {
+                events::publish("notification-due", notification);
             }
-            
-            // Emit to frontend
-            app.emit_all("new-notification", &notification).ok();
+            saved
         }
+        // Shown all the same, but not sent on: it may be sent again
         Err(e) => {
-            eprintln!("Failed to get weekly stats: {}", e);
+            eprintln!("Failed to save notification: {}", e);
+            true
         }
     }
 }
 
-async fn send_monthly_summary(app: &AppHandle) {
-    match storage::db::get_stats("month", "pc").await {
-        Ok(stats) => {
-            let euro_cost = stats.monthly_kwh * 0.30; // €0.30/kWh average
-            
-            let message = format!(
-                "Report mensile: {:.2} kWh ≈ {:.0} bottiglie 💧 | CO₂: {:.1} kg | Costo stimato: €{:.2}",
-                stats.monthly_kwh,
-                stats.bottles_month,
-                stats.co2_month / 1000.0,
-                euro_cost
-            );
-            
-            // Save notification to database
-            let notification = storage::Notification {
-                id: format!("monthly-{}", chrono::Utc::now().timestamp()),
-                title: "Report mensile 📊".to_string(),
-                message,
-                timestamp: chrono::Utc::now().timestamp(),
-                read: false,
-                type_: "info".to_string(),
+async fn prune(settings: &UserSettings, now: i64) {
+    let (days, count) = (settings.notification_retention_days, settings.notification_retention_count);
+    if let Err(e) = storage::db::prune_notifications(days, count, now).await {
+        eprintln!("Failed to delete old notifications: {}", e);
+    }
+}
+
+/// Store the latest run of each rule; the engine also keeps them in case
+/// the database refuses
+async fn record_runs(engine: &mut Engine, runs: &[(String, i64)]) {
+    for (id, run) in runs {
+        if let Err(e) = storage::db::set_notification_rule_run(id, *run).await {
+            eprintln!("Failed to record notification rule run: {}", e);
+        }
+    }
+    engine.commit(runs);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::db::create_schema;
+    use crate::storage::notifications::NotificationRepository;
+    use crate::storage::{NotificationPage, NotificationQuery};
+    use rusqlite::Connection;
+    use std::collections::BTreeMap;
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    /// The notification tables of an in-memory database
+    struct MemoryStore(Connection);
+
+    impl MemoryStore {
+        fn new() -> Self {
+            let db = Connection::open_in_memory().unwrap();
+            create_schema(&db).unwrap();
+            Self(db)
+        }
+
+        fn inbox(&self) -> NotificationPage {
+            NotificationRepository::new(&self.0).page(&NotificationQuery::default(), Locale::En).unwrap()
+        }
+    }
+
+    impl Store for MemoryStore {
+        async fn save(&mut self, notification: &Notification) -> Result<bool> {
+            NotificationRepository::new(&self.0).save(notification)
+        }
+
+        async fn queue(&mut self, held: &[(i64, Notification)]) -> Result<Vec<bool>> {
+            NotificationRepository::new(&self.0).queue(held)
+        }
+
+        async fn take_queued(&mut self) -> Result<Vec<(i64, Notification)>> {
+            NotificationRepository::new(&self.0).take_queued()
+        }
+    }
+
+    fn summary(id: &str, due: DateTime<Local>) -> Notification {
+        Notification {
+            id: id.to_string(),
+            title: "Daily summary".to_string(),
+            message: format!("{} kWh", id),
+            timestamp: due.timestamp(),
+            read: false,
+            type_: "info".to_string(),
+            template: Some("daily-summary".to_string()),
+            params: BTreeMap::from([("day.kwh".to_string(), 1.5)]),
+            dedup_key: Some(format!("{}-{}", id, due.timestamp())),
+        }
+    }
+
+    /// Names and ids of the events about notifications starting with `prefix`
+    fn published(events: &mut tokio::sync::broadcast::Receiver<Event>, prefix: &str) -> Vec<(&'static str, String)> {
+        let mut found = Vec::new();
+        loop {
+            let event = match events.try_recv() {
+                Ok(event) => event,
+                // Other tests publish too
+                Err(TryRecvError::Lagged(_)) => continue,
+                Err(_) => break,
             };
-            
-            if let Err(e) = storage::db::save_notification(notification.clone()).await {
-                eprintln!("Failed to save notification: {}", e);
+            let id = event.payload["id"].as_str().unwrap_or_default().to_string();
+            if id.contains(prefix) {
+                found.push((event.name, id));
             }
-            
-            // Emit to frontend
-            app.emit_all("new-notification", &notification).ok();
-        }
-        Err(e) => {
-            eprintln!("Failed to get monthly stats: {}", e);
         }
+        found
+    }
+
+    #[tokio::test]
+    async fn test_held_notifications_are_stored_one_by_one() {
+        let mut store = MemoryStore::new();
+        let mut events = events::subscribe();
+        let now = Local::now();
+        let missed = vec![
+            (now - chrono::Duration::days(2), summary("held-a", now - chrono::Duration::days(2))),
+            (now - chrono::Duration::days(1), summary("held-b", now - chrono::Duration::days(1))),
+        ];
+
+        // Quiet: nothing stored yet
+        assert!(deliver(&mut store, true, Locale::En, now, Vec::new(), missed).await);
+        assert!(store.inbox().notifications.is_empty());
+
+        // Quiet ends: both in the inbox as they were, one popup for the two
+        let due = vec![summary("held-c", now)];
+        assert!(deliver(&mut store, false, Locale::En, now, due, Vec::new()).await);
+        let inbox = store.inbox().notifications;
+        let ids: Vec<_> = inbox.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, ["held-c", "held-b", "held-a"]);
+        assert!(inbox.iter().all(|n| n.template.as_deref() == Some("daily-summary") && n.params["day.kwh"] == 1.5));
+
+        let popups: Vec<_> = published(&mut events, "held-")
+            .into_iter()
+            .filter(|(name, _)| *name == "notification-popup")
+            .map(|(_, id)| id)
+            .collect();
+        assert_eq!(popups, ["held-c", "digest-held-b"]);
+    }
+
+    #[tokio::test]
+    async fn test_refire_is_due_once() {
+        let now = Local::now();
+        // Each tick renders the rule again, with a new id but the same key
+        let tick = |id: &str| {
+            let mut notification = summary(id, now);
+            notification.dedup_key = Some(format!("refire-{}", now.timestamp()));
+            notification
+        };
+        let due = |events: &mut tokio::sync::broadcast::Receiver<Event>, prefix: &str| {
+            published(events, prefix).into_iter().filter(|(name, _)| *name == "notification-due").count()
+        };
+
+        // Quiet: queued once, and once more when the queue is delivered
+        let mut store = MemoryStore::new();
+        let mut events = events::subscribe();
+        assert!(deliver(&mut store, true, Locale::En, now, vec![tick("refire-q1")], Vec::new()).await);
+        assert!(deliver(&mut store, true, Locale::En, now, vec![tick("refire-q2")], Vec::new()).await);
+        assert!(deliver(&mut store, false, Locale::En, now, Vec::new(), Vec::new()).await);
+        assert_eq!(due(&mut events, "refire-q"), 1);
+        assert_eq!(store.inbox().notifications.len(), 1);
+
+        // Not quiet
+        let mut store = MemoryStore::new();
+        assert!(deliver(&mut store, false, Locale::En, now, vec![tick("refire-d1")], Vec::new()).await);
+        assert!(deliver(&mut store, false, Locale::En, now, vec![tick("refire-d2")], Vec::new()).await);
+        assert_eq!(due(&mut events, "refire-d"), 1);
+        assert_eq!(store.inbox().notifications.len(), 1);
     }
 }
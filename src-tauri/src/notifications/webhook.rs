@@ -0,0 +1,622 @@
+// Outbound webhook
+//
+// Notifications can also be posted to an HTTP endpoint, such as a team
+// chat's incoming webhook. The body is a JSON template filled from the
+// notification and is optionally signed with HMAC-SHA256. Failed requests
+// are retried with exponential backoff, and every attempt is written to
+// the `webhook_deliveries` log. Do not disturb only holds the webhook back
+// when it asks for it; otherwise notifications go out as they fall due.
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::{Method, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+
+use super::i18n::Locale;
+use crate::error::{Result, VeraError};
+use crate::events;
+use crate::storage::{self, Notification, WebhookDelivery};
+
+/// `sha256=` and the hex HMAC of `<timestamp>.<body>`
+pub const SIGNATURE_HEADER: &str = "X-Vera-Signature";
+/// Unix seconds the signature was made at, to reject replays
+pub const TIMESTAMP_HEADER: &str = "X-Vera-Timestamp";
+
+/// Per request, including the response
+const TIMEOUT: Duration = Duration::from_secs(10);
+const METHODS: &[&str] = &["POST", "PUT", "PATCH"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub url: String,
+    /// POST, PUT or PATCH
+    #[serde(default = "default_method")]
+    pub method: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// JSON whose strings may use `{id}`, `{title}`, `{message}`, `{type}`
+    /// and `{timestamp}`; a string that is only `{timestamp}` or
+    /// `{notification}` becomes that number or object. The notification as
+    /// is when unset.
+    #[serde(default)]
+    pub body: Option<Value>,
+    /// Signs every request when set
+    #[serde(default)]
+    pub secret: String,
+    /// Hold notifications back during do not disturb, like the desktop
+    #[serde(default)]
+    pub quiet_hours: bool,
+}
+
+fn default_method() -> String {
+    "POST".to_string()
+}
+
+impl Default for Webhook {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: String::new(),
+            method: default_method(),
+            headers: BTreeMap::new(),
+            body: None,
+            secret: String::new(),
+            quiet_hours: false,
+        }
+    }
+}
+
+impl Webhook {
+    pub fn validate(&self) -> Result<()> {
+        if self.enabled || !self.url.is_empty() {
+            self.url()?;
+        }
+        self.method()?;
+        self.header_map()?;
+        if !matches!(self.body, None | Some(Value::Object(_)) | Some(Value::Array(_))) {
+            return Err(VeraError::invalid_argument("webhook.body", "expected a JSON object or array"));
+        }
+        Ok(())
+    }
+
+    /// The URL as logged: scheme, host and at most the first path segment,
+    /// since chat webhooks carry their secret in the rest of the path or in
+    /// the query
+    pub fn logged_url(&self) -> String {
+        match Url::parse(&self.url) {
+            Ok(url) => redact(&url),
+            Err(_) => "(invalid URL)".to_string(),
+        }
+    }
+
+    fn url(&self) -> Result<Url> {
+        let url = Url::parse(&self.url).map_err(|e| VeraError::invalid_argument("webhook.url", e.to_string()))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(VeraError::invalid_argument("webhook.url", "expected an http or https URL"));
+        }
+        Ok(url)
+    }
+
+    fn method(&self) -> Result<Method> {
+        let method = self.method.to_ascii_uppercase();
+        if !METHODS.contains(&method.as_str()) {
+            return Err(VeraError::invalid_argument("webhook.method", "expected POST, PUT or PATCH"));
+        }
+        Method::from_bytes(method.as_bytes()).map_err(|e| VeraError::invalid_argument("webhook.method", e.to_string()))
+    }
+
+    fn header_map(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        for (name, value) in &self.headers {
+            let invalid = |e: &dyn std::fmt::Display| VeraError::invalid_argument("webhook.headers", format!("{}: {}", name, e));
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(&e))?;
+            let value = HeaderValue::from_str(value).map_err(|e| invalid(&e))?;
+            headers.insert(name, value);
+        }
+        Ok(headers)
+    }
+
+    fn request(&self, notification: &Notification) -> Result<(Url, Method, HeaderMap, Vec<u8>)> {
+        Ok((self.url()?, self.method()?, self.header_map()?, self.render(notification)?))
+    }
+
+    /// The request body for `notification`
+    pub fn render(&self, notification: &Notification) -> Result<Vec<u8>> {
+        let whole = serde_json::to_value(notification).map_err(|e| VeraError::Internal(e.to_string()))?;
+        let body = match &self.body {
+            Some(template) => fill(template, notification, &whole),
+            None => whole,
+        };
+        serde_json::to_vec(&body).map_err(|e| VeraError::Internal(e.to_string()))
+    }
+}
+
+fn fill(template: &Value, notification: &Notification, whole: &Value) -> Value {
+    match template {
+        Value::String(text) => match text.as_str() {
+            "{notification}" => whole.clone(),
+            "{timestamp}" => Value::from(notification.timestamp),
+            _ => Value::String(substitute(text, notification)),
+        },
+        Value::Array(items) => Value::Array(items.iter().map(|item| fill(item, notification, whole)).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, value)| (name.clone(), fill(value, notification, whole)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Placeholders in one pass, so braces in the values are left alone;
+/// unknown ones stay as written
+fn substitute(text: &str, notification: &Notification) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}') else { break };
+        out.push_str(&rest[..open]);
+        let written = &rest[open..open + close + 1];
+        match &written[1..written.len() - 1] {
+            "id" => out.push_str(&notification.id),
+            "title" => out.push_str(&notification.title),
+            "message" => out.push_str(&notification.message),
+            "type" => out.push_str(&notification.type_),
+            "timestamp" => out.push_str(&notification.timestamp.to_string()),
+            _ => out.push_str(written),
+        }
+        rest = &rest[open + close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Value of `X-Vera-Signature` for `body` sent at `timestamp`
+pub fn signature(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    // Any key length is accepted
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC key");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Attempts per notification and the wait between them, doubling each time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Retry {
+    pub attempts: u32,
+    pub first_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Retry {
+    /// Waits 5, 10, 20 and 40 seconds
+    pub const DEFAULT: Retry = Retry {
+        attempts: 5,
+        first_delay: Duration::from_secs(5),
+        max_delay: Duration::from_secs(60),
+    };
+
+    /// Wait before `attempt`, counted from 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let doublings = attempt.saturating_sub(2).min(16);
+        (self.first_delay * 2u32.pow(doublings)).min(self.max_delay)
+    }
+}
+
+/// Whether a failed request may succeed later
+fn retryable(status: Option<StatusCode>) -> bool {
+    match status {
+        None => true,
+        Some(status) => {
+            status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::REQUEST_TIMEOUT
+        }
+    }
+}
+
+/// `scheme://host[:port]/first-segment/…`, without credentials or query
+fn redact(url: &Url) -> String {
+    let mut shown = format!("{}://{}", url.scheme(), url.host_str().unwrap_or_default());
+    if let Some(port) = url.port() {
+        shown.push_str(&format!(":{}", port));
+    }
+    let segments: Vec<&str> = url.path_segments().map(|s| s.filter(|s| !s.is_empty()).collect()).unwrap_or_default();
+    match segments[..] {
+        [] if url.query().is_none() => shown.push('/'),
+        [first, _, ..] => shown.push_str(&format!("/{}/…", first)),
+        _ => shown.push_str("/…"),
+    }
+    shown
+}
+
+/// The error and what caused it, e.g. "error sending request: connection refused"
+fn describe(error: &reqwest::Error) -> String {
+    let mut text = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        text.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    text
+}
+
+#[derive(Clone)]
+pub struct Sender {
+    client: reqwest::Client,
+    retry: Retry,
+}
+
+impl Sender {
+    pub fn new(retry: Retry) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .user_agent(concat!("VERA/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| VeraError::Internal(format!("Failed to create the webhook client: {}", e)))?;
+        Ok(Self { client, retry })
+    }
+
+    /// Post `notification`, retrying while it may still get through, and
+    /// hand every attempt to `log`. Returns the last attempt.
+    pub async fn send(
+        &self,
+        webhook: &Webhook,
+        notification: &Notification,
+        mut log: impl AsyncFnMut(&WebhookDelivery),
+    ) -> WebhookDelivery {
+        let shown = webhook.logged_url();
+        let (url, method, headers, body) = match webhook.request(notification) {
+            Ok(request) => request,
+            Err(e) => {
+                let delivery = WebhookDelivery {
+                    notification_id: notification.id.clone(),
+                    url: shown,
+                    attempt: 1,
+                    timestamp: chrono::Utc::now().timestamp(),
+                    status: None,
+                    error: Some(e.to_string()),
+                    duration_ms: 0,
+                    delivered: false,
+                };
+                log(&delivery).await;
+                return delivery;
+            }
+        };
+
+        let mut attempt = 1;
+        loop {
+            let timestamp = chrono::Utc::now().timestamp();
+            let mut headers = headers.clone();
+            if !webhook.secret.is_empty() {
+                let signature = signature(&webhook.secret, timestamp, &body);
+                headers.insert(TIMESTAMP_HEADER, HeaderValue::from(timestamp));
+                headers.insert(SIGNATURE_HEADER, HeaderValue::from_str(&signature).expect("hex is a valid header"));
+            }
+
+            let started = Instant::now();
+            let response = self
+                .client
+                .request(method.clone(), url.clone())
+                .headers(headers)
+                .body(body.clone())
+                .send()
+                .await;
+            let (status, error) = match &response {
+                Ok(response) => (Some(response.status()), None),
+                // The error names the whole URL
+                Err(e) => (e.status(), Some(describe(e).replace(url.as_str(), &shown))),
+            };
+            let delivery = WebhookDelivery {
+                notification_id: notification.id.clone(),
+                url: shown.clone(),
+                attempt,
+                timestamp,
+                status: status.map(|s| s.as_u16()),
+                error,
+                duration_ms: started.elapsed().as_millis() as u64,
+                delivered: status.is_some_and(|s| s.is_success()),
+            };
+            log(&delivery).await;
+
+            if delivery.delivered || !retryable(status) || attempt >= self.retry.attempts {
+                return delivery;
+            }
+            attempt += 1;
+            tokio::time::sleep(self.retry.delay(attempt)).await;
+        }
+    }
+}
+
+async fn record(delivery: &WebhookDelivery) {
+    if let Err(e) = storage::db::record_webhook_delivery(delivery).await {
+        eprintln!("Failed to log webhook delivery: {}", e);
+    }
+}
+
+/// Post a sample notification to `webhook` once, logging the attempt
+pub async fn test(webhook: &Webhook, locale: Locale) -> Result<WebhookDelivery> {
+    if webhook.url.is_empty() {
+        return Err(VeraError::invalid_argument("webhook.url", "no webhook configured"));
+    }
+    let text = |part: &str| locale.text(&format!("webhook-test.{}", part)).unwrap_or_default().to_string();
+    let notification = Notification {
+        id: Notification::new_id("webhook-test")?,
+        title: text("title"),
+        message: text("message"),
+        timestamp: chrono::Utc::now().timestamp(),
+        read: false,
+        type_: "info".to_string(),
+        template: None,
+        params: BTreeMap::new(),
+        dedup_key: None,
+    };
+    let sender = Sender::new(Retry { attempts: 1, ..Retry::DEFAULT })?;
+    Ok(sender.send(webhook, &notification, async |delivery| record(delivery).await).await)
+}
+
+/// Post notifications to the webhook in the settings: as they fall due
+/// (`notification-due`), or as they are delivered (`new-notification`) when
+/// the webhook keeps quiet hours
+pub async fn start() {
+    let sender = match Sender::new(Retry::DEFAULT) {
+        Ok(sender) => sender,
+        Err(e) => {
+            eprintln!("Webhook disabled: {}", e);
+            return;
+        }
+    };
+    let mut events = events::subscribe();
+
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped)) => {
+                eprintln!("Webhook skipped {} events", skipped);
+                continue;
+            }
+            Err(RecvError::Closed) => break,
+        };
+        let shown = match event.name {
+            "notification-due" => false,
+            "new-notification" => true,
+            _ => continue,
+        };
+        let webhook = match storage::db::load_settings().await {
+            Ok(settings) => settings.webhook,
+            Err(e) => {
+                eprintln!("Failed to load webhook settings: {}", e);
+                continue;
+            }
+        };
+        if !webhook.enabled || webhook.quiet_hours != shown {
+            continue;
+        }
+        let notification: Notification = match serde_json::from_value(event.payload) {
+            Ok(notification) => notification,
+            Err(e) => {
+                eprintln!("Failed to read notification event: {}", e);
+                continue;
+            }
+        };
+
+        // Retries must not hold up the notifications after it
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            let delivery = sender.send(&webhook, &notification, async |delivery| record(delivery).await).await;
+            if !delivery.delivered {
+                let reason = delivery.error.or(delivery.status.map(|s| format!("HTTP {}", s))).unwrap_or_default();
+                eprintln!("Webhook delivery of {} failed after {} attempts: {}", notification.id, delivery.attempt, reason);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::{HeaderMap as Headers, StatusCode as Status};
+    use std::sync::{Arc, Mutex};
+
+    /// What the stand-in endpoint received
+    #[derive(Debug, Clone)]
+    struct Request {
+        method: String,
+        headers: Headers,
+        body: Vec<u8>,
+    }
+
+    #[derive(Clone, Default)]
+    struct Endpoint {
+        requests: Arc<Mutex<Vec<Request>>>,
+        /// Answers in order, 200 once they run out
+        statuses: Arc<Mutex<Vec<u16>>>,
+    }
+
+    async fn receive(
+        State(endpoint): State<Endpoint>,
+        method: axum::http::Method,
+        headers: Headers,
+        body: axum::body::Bytes,
+    ) -> Status {
+        endpoint.requests.lock().unwrap().push(Request {
+            method: method.to_string(),
+            headers,
+            body: body.to_vec(),
+        });
+        let mut statuses = endpoint.statuses.lock().unwrap();
+        let status = if statuses.is_empty() { 200 } else { statuses.remove(0) };
+        Status::from_u16(status).unwrap()
+    }
+
+    /// A local HTTP server answering `statuses` on `/hook`
+    async fn serve(statuses: &[u16]) -> (Endpoint, String) {
+        let endpoint = Endpoint {
+            statuses: Arc::new(Mutex::new(statuses.to_vec())),
+            ..Default::default()
+        };
+        let app = axum::Router::new()
+            .route("/hook", axum::routing::any(receive))
+            .with_state(endpoint.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (endpoint, url)
+    }
+
+    const FAST: Retry = Retry {
+        attempts: 4,
+        first_delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(40),
+    };
+
+    fn notification() -> Notification {
+        Notification {
+            id: "alert-1".to_string(),
+            title: "PC sopra 200 W".to_string(),
+            message: "Da 10 minuti: {braces} \"quoted\"".to_string(),
+            timestamp: 1_714_579_200,
+            read: false,
+            type_: "warning".to_string(),
+            template: None,
+            params: BTreeMap::new(),
+            dedup_key: None,
+        }
+    }
+
+    async fn send(webhook: &Webhook, retry: Retry) -> (WebhookDelivery, Vec<WebhookDelivery>) {
+        let mut log = Vec::new();
+        let last = Sender::new(retry)
+            .unwrap()
+            .send(webhook, &notification(), async |delivery| log.push(delivery.clone()))
+            .await;
+        (last, log)
+    }
+
+    #[tokio::test]
+    async fn test_template_headers_and_signature() {
+        let (endpoint, url) = serve(&[]).await;
+        let webhook = Webhook {
+            enabled: true,
+            url,
+            method: "put".to_string(),
+            headers: BTreeMap::from([("Authorization".to_string(), "Bearer abc".to_string())]),
+            body: Some(serde_json::json!({
+                "text": "*{title}*: {message} {unknown} {",
+                "at": "{timestamp}",
+                "raw": "{notification}",
+                "tags": ["{type}", 3],
+            })),
+            secret: "s3cret".to_string(),
+            quiet_hours: false,
+        };
+        webhook.validate().unwrap();
+
+        let (last, log) = send(&webhook, FAST).await;
+        assert!(last.delivered);
+        assert_eq!((last.attempt, last.status), (1, Some(200)));
+        assert_eq!(log.len(), 1);
+
+        let requests = endpoint.requests.lock().unwrap();
+        let request = &requests[0];
+        assert_eq!(request.method, "PUT");
+        assert_eq!(request.headers["authorization"], "Bearer abc");
+        assert_eq!(request.headers["content-type"], "application/json");
+        let body: Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["text"], "*PC sopra 200 W*: Da 10 minuti: {braces} \"quoted\" {unknown} {");
+        assert_eq!(body["at"], 1_714_579_200);
+        assert_eq!(body["raw"]["id"], "alert-1");
+        assert_eq!(body["tags"], serde_json::json!(["warning", 3]));
+
+        // The receiver checks the signature over the timestamp and the body as sent
+        let timestamp: i64 = request.headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+        let expected = signature("s3cret", timestamp, &request.body);
+        assert_eq!(request.headers[SIGNATURE_HEADER].to_str().unwrap(), expected);
+        assert_ne!(expected, signature("other", timestamp, &request.body));
+    }
+
+    #[tokio::test]
+    async fn test_retries_with_backoff() {
+        let (endpoint, url) = serve(&[503, 429, 200]).await;
+        let webhook = Webhook { enabled: true, url, ..Default::default() };
+
+        let started = Instant::now();
+        let (last, log) = send(&webhook, FAST).await;
+        // 10 ms, then 20 ms
+        assert!(started.elapsed() >= Duration::from_millis(30));
+        assert!(last.delivered);
+        let attempts: Vec<_> = log.iter().map(|d| (d.attempt, d.status, d.delivered)).collect();
+        assert_eq!(attempts, [(1, Some(503), false), (2, Some(429), false), (3, Some(200), true)]);
+
+        // Without a template the notification is posted as is, unsigned
+        let requests = endpoint.requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].method, "POST");
+        assert!(!requests[0].headers.contains_key(SIGNATURE_HEADER));
+        let body: Notification = serde_json::from_slice(&requests[2].body).unwrap();
+        assert_eq!(body.id, "alert-1");
+    }
+
+    #[tokio::test]
+    async fn test_gives_up() {
+        // Rejected: retrying will not help
+        let (endpoint, url) = serve(&[400]).await;
+        let (last, log) = send(&Webhook { enabled: true, url, ..Default::default() }, FAST).await;
+        assert_eq!((last.attempt, last.status, last.delivered), (1, Some(400), false));
+        assert_eq!((log.len(), endpoint.requests.lock().unwrap().len()), (1, 1));
+
+        // Failing every time, up to the attempts allowed
+        let (endpoint, url) = serve(&[500; 10]).await;
+        let (last, log) = send(&Webhook { enabled: true, url, ..Default::default() }, FAST).await;
+        assert_eq!((last.attempt, last.status), (4, Some(500)));
+        assert_eq!((log.len(), endpoint.requests.lock().unwrap().len()), (4, 4));
+
+        // Nothing listening
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        drop(listener);
+        let (last, log) = send(&Webhook { enabled: true, url, ..Default::default() }, FAST).await;
+        assert_eq!((last.attempt, last.status), (4, None));
+        assert!(last.error.is_some_and(|error| !error.contains("/hook")));
+        assert_eq!(log.len(), 4);
+        assert!(log.iter().all(|delivery| delivery.url.ends_with("/…")));
+    }
+
+    #[test]
+    fn test_validation_and_backoff() {
+        assert!(Webhook::default().validate().is_ok());
+        let webhook = |url: &str| Webhook { enabled: true, url: url.to_string(), ..Default::default() };
+        assert!(webhook("https://chat.example.com/hooks/abc").validate().is_ok());
+        for bad in ["", "ftp://example.com", "not a url"] {
+            assert_eq!(webhook(bad).validate().unwrap_err().code(), "INVALID_ARGUMENT", "{}", bad);
+        }
+        assert!(Webhook { method: "GET".to_string(), ..webhook("http://localhost") }.validate().is_err());
+        let headers = BTreeMap::from([("Bad Header".to_string(), "x".to_string())]);
+        assert!(Webhook { headers, ..webhook("http://localhost") }.validate().is_err());
+        assert!(Webhook { body: Some(Value::from("text")), ..webhook("http://localhost") }.validate().is_err());
+
+        // Only the start of the URL is logged
+        for (url, logged) in [
+            ("https://hooks.slack.com/services/T000/B000/XXXX", "https://hooks.slack.com/services/…"),
+            ("https://user:pw@chat.example.com:8443/hook?token=abc", "https://chat.example.com:8443/…"),
+            ("https://chat.example.com?token=abc", "https://chat.example.com/…"),
+            ("http://localhost", "http://localhost/"),
+            ("not a url", "(invalid URL)"),
+        ] {
+            assert_eq!(webhook(url).logged_url(), logged);
+        }
+
+        let delays: Vec<_> = (2..=7).map(|attempt| Retry::DEFAULT.delay(attempt).as_secs()).collect();
+        assert_eq!(delays, [5, 10, 20, 40, 60, 60]);
+    }
+}
@@ -1,9 +1,8 @@
 // Permission management for VERA Tauri application
-
-use tauri::{Manager, AppHandle};
+use crate::error::{Result, VeraError};
 
 /// Check if the application has required system permissions
-pub fn check_system_permissions() -> Result<bool, String> {
+pub fn check_system_permissions() -> Result<bool> {
     // Check for required permissions based on platform
     #[cfg(target_os = "windows")]
     {
@@ -22,12 +21,12 @@ pub fn check_system_permissions() -> Result<bool, String> {
 }
 
 #[cfg(target_os = "windows")]
-fn check_windows_permissions() -> Result<bool, String> {
+fn check_windows_permissions() -> Result<bool> {
     // Check for Windows performance counter access
     use std::process::Command;
     
     let output = Command::new("powershell")
-        .args(&[
+        .args([
             "-Command",
             "Get-Counter -ListSet 'Processor' -ErrorAction SilentlyContinue"
         ])
@@ -38,20 +37,20 @@ fn check_windows_permissions() -> Result<bool, String> {
             if result.status.success() {
                 Ok(true)
             } else {
-                Err("Performance counter access denied".to_string())
+                Err(VeraError::PermissionDenied("Performance counter access denied".to_string()))
             }
         }
-        Err(e) => Err(format!("Failed to check permissions: {}", e))
+        Err(e) => Err(VeraError::Internal(format!("Failed to check permissions: {}", e)))
     }
 }
 
 #[cfg(target_os = "macos")]
-fn check_macos_permissions() -> Result<bool, String> {
+fn check_macos_permissions() -> Result<bool> {
     // Check for macOS system monitoring permissions
     use std::process::Command;
     
     let output = Command::new("system_profiler")
-        .args(&["SPHardwareDataType"])
+        .args(["SPHardwareDataType"])
         .output();
     
     match output {
@@ -59,47 +58,39 @@ fn check_macos_permissions() -> Result<bool, String> {
             if result.status.success() {
                 Ok(true)
             } else {
-                Err("System profiler access denied".to_string())
+                Err(VeraError::PermissionDenied("System profiler access denied".to_string()))
             }
         }
-        Err(e) => Err(format!("Failed to check permissions: {}", e))
+        Err(e) => Err(VeraError::Internal(format!("Failed to check permissions: {}", e)))
     }
 }
 
 #[cfg(target_os = "linux")]
-fn check_linux_permissions() -> Result<bool, String> {
+fn check_linux_permissions() -> Result<bool> {
     // Check for Linux /proc filesystem access
     use std::fs;
     
     match fs::read_to_string("/proc/cpuinfo") {
         Ok(_) => Ok(true),
-        Err(e) => Err(format!("Failed to access /proc filesystem: {}", e))
+        Err(e) => Err(VeraError::PermissionDenied(format!("Failed to access /proc filesystem: {}", e)))
     }
 }
 
 /// Request elevated permissions if needed
-pub async fn request_permissions(app_handle: AppHandle) -> Result<(), String> {
+///
+/// There is no modal dialog here: the error message is returned to the
+/// frontend, which shows it to the user.
+pub async fn request_permissions() -> Result<()> {
     let has_permissions = check_system_permissions()?;
     
     if !has_permissions {
-        // Show permission request dialog
-        let message = "VERA requires system monitoring permissions to function properly. Please grant the necessary permissions.";
-        
-        match app_handle.dialog().message(message).show().await {
-            true => {
-                // User accepted, attempt to request permissions
-                request_system_permissions().await
-            }
-            false => {
-                Err("User denied permission request".to_string())
-            }
-        }
+        request_system_permissions().await
     } else {
         Ok(())
     }
 }
 
-async fn request_system_permissions() -> Result<(), String> {
+async fn request_system_permissions() -> Result<()> {
     #[cfg(target_os = "windows")]
     {
         // On Windows, we might need to run as administrator
@@ -121,22 +112,22 @@ async fn request_system_permissions() -> Result<(), String> {
 }
 
 #[cfg(target_os = "macos")]
-async fn request_macos_accessibility_permissions() -> Result<(), String> {
+async fn request_macos_accessibility_permissions() -> Result<()> {
     use std::process::Command;
     
     // Open System Preferences to accessibility settings
     let output = Command::new("open")
-        .args(&["x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility"])
+        .args(["x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility"])
         .output();
     
     match output {
         Ok(_) => Ok(()),
-        Err(e) => Err(format!("Failed to open accessibility settings: {}", e))
+        Err(e) => Err(VeraError::Internal(format!("Failed to open accessibility settings: {}", e)))
     }
 }
 
 #[cfg(target_os = "linux")]
-fn check_linux_user_groups() -> Result<(), String> {
+fn check_linux_user_groups() -> Result<()> {
     use std::process::Command;
     
     let output = Command::new("groups")
@@ -148,10 +139,10 @@ fn check_linux_user_groups() -> Result<(), String> {
             if groups.contains("adm") || groups.contains("sudo") {
                 Ok(())
             } else {
-                Err("User needs to be in 'adm' or 'sudo' group for system monitoring".to_string())
+                Err(VeraError::PermissionDenied("User needs to be in 'adm' or 'sudo' group for system monitoring".to_string()))
             }
         }
-        Err(e) => Err(format!("Failed to check user groups: {}", e))
+        Err(e) => Err(VeraError::Internal(format!("Failed to check user groups: {}", e)))
     }
 }
 
@@ -162,7 +153,7 @@ pub fn is_elevated() -> bool {
         use std::process::Command;
         
         let output = Command::new("net")
-            .args(&["session"])
+            .args(["session"])
             .output();
         
         match output {
@@ -176,13 +167,13 @@ pub fn is_elevated() -> bool {
         use std::process::Command;
         
         let output = Command::new("id")
-            .args(&["-u"])
+            .args(["-u"])
             .output();
         
         match output {
             Ok(result) => {
-                let uid = String::from_utf8_lossy(&result.stdout).trim();
-                uid == "0"
+                let uid = String::from_utf8_lossy(&result.stdout);
+                uid.trim() == "0"
             }
             Err(_) => false
         }
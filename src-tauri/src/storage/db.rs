@@ -1,24 +1,110 @@
 // SQLite database for local data storage
-use rusqlite::{Connection, Result, params};
-use tokio::sync::Mutex;
+use rusqlite::{Connection, OpenFlags, OptionalExtension, params};
+use tokio::sync::{Mutex, MutexGuard};
 use std::sync::Arc;
-use chrono::{Local, NaiveDate, Datelike};
-use once_cell::sync::Lazy;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, Datelike, Offset, TimeZone};
+use once_cell::sync::{Lazy, OnceCell};
+use std::path::PathBuf;
 
 use crate::energy::EnergyStats;
-use super::{UserSettings, Notification};
+use crate::error::{Result, VeraError};
+use crate::notifications::i18n::Locale;
+use crate::notifications::quiet::DoNotDisturb;
+use crate::notifications::rules::Rule;
+use super::notifications::NotificationRepository;
+use super::webhooks::DeliveryLog;
+use super::{
+    EnergyReading, HistoryBucket, Notification, NotificationPage, NotificationQuery, ReadingSummary, UserSettings,
+    WebhookDelivery,
+};
+use std::collections::BTreeMap;
+
+// Database file location, set once at startup before the first query
+static DB_PATH: OnceCell<PathBuf> = OnceCell::new();
+// Set when the GUI attaches to a database owned by `vera-agent`
+static READ_ONLY: OnceCell<bool> = OnceCell::new();
 
 // Global database connection
+// Readings are written by `storage::writer` on its own connection
 static DB: Lazy<Arc<Mutex<Connection>>> = Lazy::new(|| {
-    let conn = Connection::open("vera_data.db")
-        .expect("Failed to open database");
+    let conn = if is_read_only() {
+        Connection::open_with_flags(
+            database_path(),
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX | OpenFlags::SQLITE_OPEN_URI,
+        )
+    } else {
+        Connection::open(database_path())
+    }
+    .expect("Failed to open database");
+    if let Err(e) = conn.busy_timeout(std::time::Duration::from_secs(5)) {
+        eprintln!("Failed to set busy timeout: {}", e);
+    }
     Arc::new(Mutex::new(conn))
 });
 
+pub fn database_path() -> PathBuf {
+    DB_PATH
+        .get()
+        .cloned()
+        .unwrap_or_else(|| PathBuf::from("vera_data.db"))
+}
+
+/// Use `path` for the database instead of `vera_data.db` in the working
+/// directory. Has no effect once the connection has been opened.
+pub fn set_database_path(path: PathBuf) {
+    if DB_PATH.set(path).is_err() {
+        eprintln!("Database path already set, ignoring");
+    }
+}
+
+/// Open the database at `path` without write access. Readings, settings
+/// and notifications keep being written by the process that owns it.
+pub fn attach_read_only(path: PathBuf) {
+    set_database_path(path);
+    READ_ONLY.set(true).ok();
+}
+
+pub fn is_read_only() -> bool {
+    READ_ONLY.get().copied().unwrap_or(false)
+}
+
+async fn lock_writable() -> Result<MutexGuard<'static, Connection>> {
+    if is_read_only() {
+        return Err(VeraError::PermissionDenied(
+            "database is attached read-only".to_string(),
+        ));
+    }
+    Ok(DB.lock().await)
+}
+
 /// Initialize database schema
 pub async fn init_database() -> Result<()> {
     let db = DB.lock().await;
     
+    // The owner creates the schema; an attached reader only checks for it
+    if is_read_only() {
+        let tables: i64 = db.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'energy_readings'",
+            [],
+            |row| row.get(0),
+        )?;
+        if tables == 0 {
+            return Err(VeraError::Config(format!(
+                "{} is not a VERA database",
+                database_path().display()
+            )));
+        }
+        return Ok(());
+    }
+    
+    // WAL lets the writer thread commit while stats are being read
+    db.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+    
+    create_schema(&db)
+}
+
+/// Create or migrate every table on `db`
+pub(crate) fn create_schema(db: &Connection) -> Result<()> {
     // Energy readings table
     db.execute(
         "CREATE TABLE IF NOT EXISTS energy_readings (
@@ -38,6 +124,17 @@ pub async fn init_database() -> Result<()> {
         [],
     )?;
 
+    // Intervals with no trustworthy readings (suspend, stall, restart, pause)
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS energy_gaps (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            start_timestamp INTEGER NOT NULL,
+            end_timestamp INTEGER NOT NULL,
+            reason TEXT NOT NULL
+        )",
+        [],
+    )?;
+
     // User settings table
     db.execute(
         "CREATE TABLE IF NOT EXISTS user_settings (
@@ -52,6 +149,18 @@ pub async fn init_database() -> Result<()> {
         [],
     )?;
 
+    // Columns added after the first release
+    add_column_if_missing(db, "user_settings", "api_enabled", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(db, "user_settings", "api_bind", "TEXT NOT NULL DEFAULT '127.0.0.1:7878'")?;
+    add_column_if_missing(db, "user_settings", "api_token", "TEXT NOT NULL DEFAULT ''")?;
+    add_column_if_missing(db, "user_settings", "locale", "TEXT NOT NULL DEFAULT 'it'")?;
+    // Replaces quiet_hours_start/end, which are only read to migrate them
+    add_column_if_missing(db, "user_settings", "do_not_disturb", "TEXT")?;
+    add_column_if_missing(db, "user_settings", "notification_retention_days", "INTEGER NOT NULL DEFAULT 90")?;
+    add_column_if_missing(db, "user_settings", "notification_retention_count", "INTEGER NOT NULL DEFAULT 1000")?;
+    // Webhook settings as JSON, NULL until first saved
+    add_column_if_missing(db, "user_settings", "webhook", "TEXT")?;
+
     // Insert default settings if not exists
     db.execute(
         "INSERT OR IGNORE INTO user_settings 
@@ -73,6 +182,32 @@ pub async fn init_database() -> Result<()> {
         [],
     )?;
 
+    // Template and its values as JSON, to render the texts in another language
+    add_column_if_missing(db, "notifications", "template", "TEXT")?;
+    add_column_if_missing(db, "notifications", "params", "TEXT")?;
+    // One notification per alert and window; NULLs never collide
+    add_column_if_missing(db, "notifications", "dedup_key", "TEXT")?;
+    db.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_notification_dedup ON notifications(dedup_key)",
+        [],
+    )?;
+    
+    // Notifications held back by do not disturb, as JSON
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS notification_queue (
+            id TEXT PRIMARY KEY,
+            due INTEGER NOT NULL,
+            notification TEXT NOT NULL
+        )",
+        [],
+    )?;
+    // Held once per dedup key, like the inbox
+    add_column_if_missing(db, "notification_queue", "dedup_key", "TEXT")?;
+    db.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_notification_queue_dedup ON notification_queue(dedup_key)",
+        [],
+    )?;
+
     // Create index for faster queries
     db.execute(
         "CREATE INDEX IF NOT EXISTS idx_notification_timestamp 
@@ -80,60 +215,281 @@ pub async fn init_database() -> Result<()> {
         [],
     )?;
 
+    // Notification rules; trigger, conditions and template are JSON
+    let has_rules: bool = db.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'notification_rules'",
+        [],
+        |row| row.get(0),
+    )?;
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS notification_rules (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            enabled INTEGER NOT NULL DEFAULT 1,
+            trigger TEXT NOT NULL,
+            conditions TEXT NOT NULL,
+            template TEXT NOT NULL
+        )",
+        [],
+    )?;
+    // Latest scheduled time each rule handled, so restarts neither repeat nor lose one
+    add_column_if_missing(db, "notification_rules", "last_run", "INTEGER")?;
+    
+    // Every attempt to post a notification to the webhook
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS webhook_deliveries (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            notification_id TEXT NOT NULL,
+            url TEXT NOT NULL,
+            attempt INTEGER NOT NULL,
+            timestamp INTEGER NOT NULL,
+            status INTEGER,
+            error TEXT,
+            duration_ms INTEGER NOT NULL,
+            delivered INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    
+    // The summaries that used to be hardcoded, only on first creation so
+    // deleted defaults stay deleted
+    if !has_rules {
+        for rule in crate::notifications::rules::default_rules() {
+            upsert_rule(db, &rule)?;
+        }
+    }
+
     Ok(())
 }
 
-/// Save energy reading
-pub async fn save_reading(power: f64, kwh: f64) -> Result<()> {
+fn add_column_if_missing(db: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let exists: bool = db.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info(?1) WHERE name = ?2",
+        params![table, column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        db.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    }
+    Ok(())
+}
+
+/// Timestamp of the most recent energy reading, if any
+pub async fn last_reading_timestamp() -> Result<Option<i64>> {
     let db = DB.lock().await;
-    let timestamp = chrono::Utc::now().timestamp();
     
-    db.execute(
-        "INSERT INTO energy_readings (timestamp, device_type, power_watts, kwh_increment) 
-         VALUES (?1, ?2, ?3, ?4)",
-        params![timestamp, "pc", power, kwh],
+    let timestamp = db.query_row(
+        "SELECT MAX(timestamp) FROM energy_readings",
+        [],
+        |row| row.get(0),
     )?;
     
-    Ok(())
+    Ok(timestamp)
+}
+
+/// Readings in `[from, to)` summed into buckets of `bucket_secs`, aligned
+/// to local midnight so daily buckets are calendar days
+pub async fn get_history(from: i64, to: i64, bucket_secs: i64, device_type: &str) -> Result<Vec<HistoryBucket>> {
+    let db = DB.lock().await;
+    history_at(&db, from, to, bucket_secs, device_type, &Local)
+}
+
+/// `get_history` in time zone `tz`. Buckets are counted in wall-clock time,
+/// so hours and days stay whole on either side of a DST change; the day of
+/// the change is 23 or 25 hours long.
+fn history_at<Tz: TimeZone>(
+    db: &Connection,
+    from: i64,
+    to: i64,
+    bucket_secs: i64,
+    device_type: &str,
+    tz: &Tz,
+) -> Result<Vec<HistoryBucket>> {
+    if bucket_secs <= 0 {
+        return Err(VeraError::invalid_argument("bucket", "must be positive"));
+    }
+    if from >= to {
+        return Err(VeraError::invalid_argument("from", "must be before `to`"));
+    }
+    
+    let mut stmt = db.prepare_cached(
+        "SELECT timestamp, kwh_increment, power_watts
+         FROM energy_readings
+         WHERE timestamp >= ?1 AND timestamp < ?2 AND device_type = ?3
+         ORDER BY timestamp",
+    )?;
+    let mut rows = stmt.query(params![from, to, device_type])?;
+    
+    let mut buckets: Vec<HistoryBucket> = Vec::new();
+    let mut end = i64::MIN;
+    while let Some(row) = rows.next()? {
+        let (timestamp, kwh, power): (i64, f64, f64) = (row.get(0)?, row.get(1)?, row.get(2)?);
+        if timestamp >= end {
+            let start;
+            (start, end) = local_bucket(tz, timestamp, bucket_secs);
+            buckets.push(HistoryBucket { start, kwh: 0.0, avg_power: 0.0, max_power: power, samples: 0 });
+        }
+        if let Some(bucket) = buckets.last_mut() {
+            bucket.kwh += kwh;
+            bucket.avg_power += power;
+            bucket.max_power = bucket.max_power.max(power);
+            bucket.samples += 1;
+        }
+    }
+    for bucket in &mut buckets {
+        bucket.avg_power /= bucket.samples as f64;
+    }
+    
+    Ok(buckets)
+}
+
+/// The wall-clock bucket `[start, end)` of `bucket_secs` holding `timestamp`
+fn local_bucket<Tz: TimeZone>(tz: &Tz, timestamp: i64, bucket_secs: i64) -> (i64, i64) {
+    let wall = tz.timestamp_opt(timestamp, 0).unwrap().naive_local().and_utc().timestamp();
+    let start = wall.div_euclid(bucket_secs) * bucket_secs;
+    (local_instant(tz, start), local_instant(tz, start + bucket_secs))
+}
+
+/// The instant wall-clock time `wall` (seconds as if UTC) names in `tz`.
+/// The first of a repeated hour; a time skipped by the clocks going forward
+/// counts with the offset from before the change.
+fn local_instant<Tz: TimeZone>(tz: &Tz, wall: i64) -> i64 {
+    let naive = DateTime::from_timestamp(wall, 0).map(|t| t.naive_utc()).unwrap_or(NaiveDateTime::MIN);
+    match tz.from_local_datetime(&naive).earliest() {
+        Some(time) => time.timestamp(),
+        None => {
+            let before = tz.offset_from_utc_datetime(&(naive - chrono::Duration::days(1))).fix();
+            wall - before.local_minus_utc() as i64
+        }
+    }
+}
+
+/// Raw readings in `[from, to)`, oldest first
+pub async fn get_readings(from: i64, to: i64, device_type: Option<&str>) -> Result<Vec<EnergyReading>> {
+    get_readings_page(from, to, device_type, u64::MAX, 0).await
+}
+
+/// At most `limit` readings in `[from, to)` after skipping `offset`, oldest first
+pub async fn get_readings_page(
+    from: i64,
+    to: i64,
+    device_type: Option<&str>,
+    limit: u64,
+    offset: u64,
+) -> Result<Vec<EnergyReading>> {
+    let db = DB.lock().await;
+    
+    let mut stmt = db.prepare_cached(
+        "SELECT timestamp, device_type, power_watts, kwh_increment
+         FROM energy_readings
+         WHERE timestamp >= ?1 AND timestamp < ?2 AND (?3 IS NULL OR device_type = ?3)
+         ORDER BY timestamp, id
+         LIMIT ?4 OFFSET ?5",
+    )?;
+    
+    // SQLite integers are signed
+    let limit = limit.min(i64::MAX as u64) as i64;
+    let offset = offset.min(i64::MAX as u64) as i64;
+    let readings = stmt
+        .query_map(params![from, to, device_type, limit, offset], |row| {
+            Ok(EnergyReading {
+                timestamp: row.get(0)?,
+                device_type: row.get(1)?,
+                power_watts: row.get(2)?,
+                kwh_increment: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    
+    Ok(readings)
+}
+
+/// Most recent reading, optionally for one device
+pub async fn get_latest_reading(device_type: Option<&str>) -> Result<Option<EnergyReading>> {
+    let db = DB.lock().await;
+    
+    let reading = db
+        .query_row(
+            "SELECT timestamp, device_type, power_watts, kwh_increment
+             FROM energy_readings
+             WHERE ?1 IS NULL OR device_type = ?1
+             ORDER BY timestamp DESC, id DESC
+             LIMIT 1",
+            params![device_type],
+            |row| {
+                Ok(EnergyReading {
+                    timestamp: row.get(0)?,
+                    device_type: row.get(1)?,
+                    power_watts: row.get(2)?,
+                    kwh_increment: row.get(3)?,
+                })
+            },
+        )
+        .optional()?;
+    
+    Ok(reading)
+}
+
+/// Start of the current day, week, month or year, and now
+pub fn period_range(period: &str) -> Result<(i64, i64)> {
+    period_range_at(period, Local::now())
+}
+
+/// Start of the day, week, month or year containing `now`, and `now`
+pub fn period_range_at<Tz: TimeZone>(period: &str, now: DateTime<Tz>) -> Result<(i64, i64)> {
+    let start = match period {
+        "day" => now.date_naive(),
+        "week" => now.date_naive() - chrono::Duration::days(now.weekday().num_days_from_monday() as i64),
+        "month" => NaiveDate::from_ymd_opt(now.year(), now.month(), 1).unwrap(),
+        "year" => NaiveDate::from_ymd_opt(now.year(), 1, 1).unwrap(),
+        _ => return Err(VeraError::invalid_argument("period", "expected day, week, month or year")),
+    };
+    
+    let start_timestamp = local_instant(&now.timezone(), start.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp());
+    Ok((start_timestamp, now.timestamp()))
+}
+
+/// Aggregates over the readings in `[from, to)`
+pub async fn get_summary(from: i64, to: i64, device_type: Option<&str>) -> Result<ReadingSummary> {
+    let db = DB.lock().await;
+    
+    let summary = db.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(kwh_increment), 0.0),
+                AVG(power_watts), MAX(power_watts), MIN(power_watts),
+                MIN(timestamp), MAX(timestamp)
+         FROM energy_readings
+         WHERE timestamp >= ?1 AND timestamp < ?2 AND (?3 IS NULL OR device_type = ?3)",
+        params![from, to, device_type],
+        |row| {
+            Ok(ReadingSummary {
+                count: row.get::<_, i64>(0)? as u64,
+                kwh: row.get(1)?,
+                avg_power: row.get(2)?,
+                max_power: row.get(3)?,
+                min_power: row.get(4)?,
+                first: row.get(5)?,
+                last: row.get(6)?,
+            })
+        },
+    )?;
+    
+    Ok(summary)
 }
 
 /// Get statistics for a given period
 pub async fn get_stats(period: &str, device_type: &str) -> Result<EnergyStats> {
+    get_stats_at(period, device_type, Local::now()).await
+}
+
+/// Statistics as they were at `now`, for summaries delivered late
+pub async fn get_stats_at(period: &str, device_type: &str, now: DateTime<Local>) -> Result<EnergyStats> {
     let db = DB.lock().await;
-    let now = Local::now();
-    
-    let (start_timestamp, end_timestamp) = match period {
-        "day" => {
-            let start_of_day = now
-                .date_naive()
-                .and_hms_opt(0, 0, 0)
-                .unwrap()
-                .and_utc()
-                .timestamp();
-            let end = now.timestamp();
-            (start_of_day, end)
-        },
-        "week" => {
-            let days_since_monday = now.weekday().num_days_from_monday();
-            let start_of_week = (now - chrono::Duration::days(days_since_monday as i64))
-                .date_naive()
-                .and_hms_opt(0, 0, 0)
-                .unwrap()
-                .and_utc()
-                .timestamp();
-            (start_of_week, now.timestamp())
-        },
-        "month" => {
-            let start_of_month = NaiveDate::from_ymd_opt(now.year(), now.month(), 1)
-                .unwrap()
-                .and_hms_opt(0, 0, 0)
-                .unwrap()
-                .and_utc()
-                .timestamp();
-            (start_of_month, now.timestamp())
-        },
-        _ => return Err(rusqlite::Error::InvalidQuery),
-    };
+    stats_at(&db, period, device_type, now)
+}
+
+/// `get_stats_at` in the time zone of `now`
+fn stats_at<Tz: TimeZone>(db: &Connection, period: &str, device_type: &str, now: DateTime<Tz>) -> Result<EnergyStats> {
+    let (start_timestamp, end_timestamp) = period_range_at(period, now.clone())?;
 
     // Query total kWh for period
     let total_kwh: f64 = db.query_row(
@@ -148,15 +504,11 @@ pub async fn get_stats(period: &str, device_type: &str) -> Result<EnergyStats> {
     let peak_power: f64 = db.query_row(
         "SELECT COALESCE(MAX(power_watts), 0.0) 
          FROM energy_readings 
-         WHERE timestamp >= ?1 AND device_type = ?2",
-        params![start_timestamp, device_type],
+         WHERE timestamp >= ?1 AND timestamp <= ?2 AND device_type = ?3",
+        params![start_timestamp, end_timestamp, device_type],
         |row| row.get(0),
     ).unwrap_or(0.0);
 
-    // Calculate CO2 and bottles
-    let co2_grams = total_kwh * 500.0; // 0.5 kg/kWh * 1000
-    let bottles = total_kwh * 3.0;
-
     // Get previous period for trend calculation
     let prev_period_duration = end_timestamp - start_timestamp;
     let prev_start = start_timestamp - prev_period_duration;
@@ -179,19 +531,19 @@ pub async fn get_stats(period: &str, device_type: &str) -> Result<EnergyStats> {
     let today_kwh = if period == "day" { 
         total_kwh 
     } else {
-        get_period_kwh(&db, "day", device_type)?
+        get_period_kwh(db, "day", device_type, now.clone())?
     };
 
     let weekly_kwh = if period == "week" { 
         total_kwh 
     } else {
-        get_period_kwh(&db, "week", device_type)?
+        get_period_kwh(db, "week", device_type, now.clone())?
     };
 
     let monthly_kwh = if period == "month" { 
         total_kwh 
     } else {
-        get_period_kwh(&db, "month", device_type)?
+        get_period_kwh(db, "month", device_type, now)?
     };
 
     Ok(EnergyStats {
@@ -211,66 +563,55 @@ pub async fn get_stats(period: &str, device_type: &str) -> Result<EnergyStats> {
     })
 }
 
-fn get_period_kwh(db: &Connection, period: &str, device_type: &str) -> Result<f64> {
-    let now = Local::now();
-    
-    let start_timestamp = match period {
-        "day" => {
-            now.date_naive()
-                .and_hms_opt(0, 0, 0)
-                .unwrap()
-                .and_utc()
-                .timestamp()
-        },
-        "week" => {
-            let days_since_monday = now.weekday().num_days_from_monday();
-            (now - chrono::Duration::days(days_since_monday as i64))
-                .date_naive()
-                .and_hms_opt(0, 0, 0)
-                .unwrap()
-                .and_utc()
-                .timestamp()
-        },
-        "month" => {
-            NaiveDate::from_ymd_opt(now.year(), now.month(), 1)
-                .unwrap()
-                .and_hms_opt(0, 0, 0)
-                .unwrap()
-                .and_utc()
-                .timestamp()
-        },
-        _ => return Ok(0.0),
-    };
+fn get_period_kwh<Tz: TimeZone>(db: &Connection, period: &str, device_type: &str, now: DateTime<Tz>) -> Result<f64> {
+    let (start_timestamp, end_timestamp) = period_range_at(period, now)?;
 
-    db.query_row(
+    let kwh = db.query_row(
         "SELECT COALESCE(SUM(kwh_increment), 0.0) 
          FROM energy_readings 
-         WHERE timestamp >= ?1 AND device_type = ?2",
-        params![start_timestamp, device_type],
+         WHERE timestamp >= ?1 AND timestamp <= ?2 AND device_type = ?3",
+        params![start_timestamp, end_timestamp, device_type],
         |row| row.get(0),
-    )
+    )?;
+
+    Ok(kwh)
 }
 
 /// Save user settings
 pub async fn save_settings(settings: UserSettings) -> Result<()> {
-    let db = DB.lock().await;
-    
+    let db = lock_writable().await?;
+    write_settings(&db, &settings)
+}
+
+fn write_settings(db: &Connection, settings: &UserSettings) -> Result<()> {
     db.execute(
         "UPDATE user_settings SET 
-         quiet_hours_start = ?1,
-         quiet_hours_end = ?2,
+         do_not_disturb = ?1,
+         locale = ?2,
          region = ?3,
          theme = ?4,
          notifications_enabled = ?5,
-         device_type = ?6
+         device_type = ?6,
+         api_enabled = ?7,
+         api_bind = ?8,
+         api_token = ?9,
+         notification_retention_days = ?10,
+         notification_retention_count = ?11,
+         webhook = ?12
          WHERE id = 1",
         params![
-            settings.quiet_hours_start,
-            settings.quiet_hours_end,
+            serde_json::to_string(&settings.do_not_disturb).map_err(|e| VeraError::Internal(e.to_string()))?,
+            settings.locale,
             settings.region,
             settings.theme,
             settings.notifications_enabled as i32,
-            settings.device_type
+            settings.device_type,
+            settings.api_enabled as i32,
+            settings.api_bind,
+            settings.api_token,
+            settings.notification_retention_days,
+            settings.notification_retention_count,
+            serde_json::to_string(&settings.webhook).map_err(|e| VeraError::Internal(e.to_string()))?
         ],
     )?;
     
@@ -280,196 +621,521 @@ pub async fn save_settings(settings: UserSettings) -> Result<()> {
 /// Load user settings
 pub async fn load_settings() -> Result<UserSettings> {
     let db = DB.lock().await;
-    
-    db.query_row(
-        "SELECT quiet_hours_start, quiet_hours_end, region, theme, notifications_enabled, device_type 
+    read_settings(&db)
+}
+
+fn read_settings(db: &Connection) -> Result<UserSettings> {
+    let (mut settings, do_not_disturb, hours, webhook) = db.query_row(
+        "SELECT do_not_disturb, quiet_hours_start, quiet_hours_end, region, theme, notifications_enabled, device_type,
+                api_enabled, api_bind, api_token, locale, notification_retention_days, notification_retention_count,
+                webhook
          FROM user_settings WHERE id = 1",
         [],
         |row| {
-            Ok(UserSettings {
-                quiet_hours_start: row.get(0)?,
-                quiet_hours_end: row.get(1)?,
-                region: row.get(2)?,
-                theme: row.get(3)?,
-                notifications_enabled: row.get::<_, i32>(4)? != 0,
-                device_type: row.get(5)?,
-            })
+            let settings = UserSettings {
+                do_not_disturb: DoNotDisturb::default(),
+                region: row.get(3)?,
+                theme: row.get(4)?,
+                notifications_enabled: row.get::<_, i32>(5)? != 0,
+                device_type: row.get(6)?,
+                api_enabled: row.get::<_, i32>(7)? != 0,
+                api_bind: row.get(8)?,
+                api_token: row.get(9)?,
+                locale: row.get(10)?,
+                notification_retention_days: row.get(11)?,
+                notification_retention_count: row.get(12)?,
+                webhook: Default::default(),
+            };
+            let hours: (Option<u8>, Option<u8>) = (row.get(1)?, row.get(2)?);
+            Ok((settings, row.get::<_, Option<String>>(0)?, hours, row.get::<_, Option<String>>(13)?))
         },
-    )
+    )?;
+    
+    settings.do_not_disturb = match (do_not_disturb, hours) {
+        (Some(json), _) => serde_json::from_str(&json)
+            .map_err(|e| VeraError::Config(format!("Unreadable do not disturb settings: {}", e)))?,
+        // Saved by a version with whole-hour quiet hours
+        (None, (Some(start), Some(end))) => DoNotDisturb::from_hours(start, end),
+        (None, _) => DoNotDisturb::default(),
+    };
+    if let Some(json) = webhook {
+        settings.webhook = serde_json::from_str(&json)
+            .map_err(|e| VeraError::Config(format!("Unreadable webhook settings: {}", e)))?;
+    }
+
+    Ok(settings)
 }
 
-/// Clear old data (keep last N days)
-pub async fn cleanup_old_data(days_to_keep: i64) -> Result<()> {
-    let db = DB.lock().await;
+/// Clear old data (keep last N days), returning how many readings went
+pub async fn cleanup_old_data(days_to_keep: i64) -> Result<usize> {
+    let db = lock_writable().await?;
     let cutoff = (Local::now() - chrono::Duration::days(days_to_keep))
         .timestamp();
     
-    db.execute(
-        "DELETE FROM energy_readings WHERE timestamp < ?1",
+    delete_readings_before(&db, cutoff)
+}
+
+/// Merge readings older than N days into one per hour and device,
+/// returning how many rows went
+pub async fn rollup_old_data(days_to_keep: i64) -> Result<usize> {
+    let db = lock_writable().await?;
+    let cutoff = (Local::now() - chrono::Duration::days(days_to_keep))
+        .timestamp();
+    
+    rollup_readings_before(&db, cutoff)
+}
+
+/// Replace the readings of every hour before `cutoff` with one holding
+/// their energy and mean power. Hours already down to one row are left
+/// alone, so running it again changes nothing.
+fn rollup_readings_before(db: &Connection, cutoff: i64) -> Result<usize> {
+    // Only whole hours, and only rows there before the merged ones
+    let cutoff = cutoff - cutoff.rem_euclid(3600);
+    let tx = db.unchecked_transaction()?;
+    let last_id: Option<i64> = tx.query_row(
+        "SELECT MAX(id) FROM energy_readings WHERE timestamp < ?1",
         params![cutoff],
+        |row| row.get(0),
     )?;
+    let Some(last_id) = last_id else {
+        return Ok(0);
+    };
+
+    let merged = "SELECT timestamp - timestamp % 3600 AS hour, device_type FROM energy_readings
+         WHERE timestamp < ?1 AND id <= ?2
+         GROUP BY hour, device_type HAVING COUNT(*) > 1";
+    let inserted = tx.execute(
+        "INSERT INTO energy_readings (timestamp, device_type, power_watts, kwh_increment)
+         SELECT timestamp - timestamp % 3600 AS hour, device_type, AVG(power_watts), SUM(kwh_increment)
+         FROM energy_readings
+         WHERE timestamp < ?1 AND id <= ?2
+         GROUP BY hour, device_type HAVING COUNT(*) > 1",
+        params![cutoff, last_id],
+    )?;
+    let deleted = tx.execute(
+        &format!(
+            "DELETE FROM energy_readings
+             WHERE timestamp < ?1 AND id <= ?2
+             AND (timestamp - timestamp % 3600, device_type) IN ({})",
+            merged
+        ),
+        params![cutoff, last_id],
+    )?;
+    tx.commit()?;
     
-    Ok(())
+    Ok(deleted - inserted)
 }
 
-/// Save notification to database
-pub async fn save_notification(notification: super::Notification) -> Result<()> {
-    let db = DB.lock().await;
-    
-    db.execute(
-        "INSERT INTO notifications (id, title, message, timestamp, read, type) 
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-        params![
-            notification.id,
-            notification.title,
-            notification.message,
-            notification.timestamp,
-            notification.read as i32,
-            notification.type_
-        ],
+/// Delete readings before `cutoff`, returning how many went
+fn delete_readings_before(db: &Connection, cutoff: i64) -> Result<usize> {
+    let deleted = db.execute(
+        "DELETE FROM energy_readings WHERE timestamp < ?1",
+        params![cutoff],
     )?;
     
-    Ok(())
+    Ok(deleted)
+}
+
+/// Save notification to database; false when one with the same id or
+/// dedup key is already there
+pub async fn save_notification(notification: Notification) -> Result<bool> {
+    let db = lock_writable().await?;
+    NotificationRepository::new(&db).save(&notification)
 }
 
-/// Get all notifications
-pub async fn get_notifications() -> Result<Vec<super::Notification>> {
+/// A page of notifications matching `query`, newest first, in the language
+/// of the settings
+pub async fn get_notifications(query: &NotificationQuery) -> Result<NotificationPage> {
     let db = DB.lock().await;
     
-    let mut stmt = db.prepare(
-        "SELECT id, title, message, timestamp, read, type 
-         FROM notifications 
-         ORDER BY timestamp DESC 
-         LIMIT 50"
-    )?;
+    let locale: Option<String> = db
+        .query_row("SELECT locale FROM user_settings WHERE id = 1", [], |row| row.get(0))
+        .optional()?;
+    let locale = locale.and_then(|code| Locale::parse(&code)).unwrap_or_default();
     
-    let notifications = stmt.query_map([], |row| {
-        Ok(super::Notification {
-            id: row.get(0)?,
-            title: row.get(1)?,
-            message: row.get(2)?,
-            timestamp: row.get(3)?,
-            read: row.get::<_, i32>(4)? != 0,
-            type_: row.get(5)?,
-        })
-    })?
-    .collect::<Result<Vec<_>>>()?;
-    
-    Ok(notifications)
+    NotificationRepository::new(&db).page(query, locale)
+}
+
+/// Unread notifications per type
+pub async fn count_unread_notifications() -> Result<BTreeMap<String, u64>> {
+    let db = DB.lock().await;
+    NotificationRepository::new(&db).count_unread()
+}
+
+/// See `NotificationRepository::prune`
+pub async fn prune_notifications(days: u32, count: u32, now: i64) -> Result<usize> {
+    let db = lock_writable().await?;
+    NotificationRepository::new(&db).prune(days, count, now)
 }
 
 /// Mark notification as read
 pub async fn mark_notification_read(id: &str) -> Result<()> {
-    let db = DB.lock().await;
-    
-    db.execute(
-        "UPDATE notifications SET read = 1 WHERE id = ?1",
-        params![id],
-    )?;
-    
-    Ok(())
+    let db = lock_writable().await?;
+    NotificationRepository::new(&db).mark_read(id)
 }
 
 /// Mark all notifications as read
 pub async fn mark_all_notifications_read() -> Result<()> {
-    let db = DB.lock().await;
-    
-    db.execute("UPDATE notifications SET read = 1", [])?;
-    
-    Ok(())
+    let db = lock_writable().await?;
+    NotificationRepository::new(&db).mark_all_read()
 }
 
 /// Delete notification
 pub async fn delete_notification(id: &str) -> Result<()> {
+    let db = lock_writable().await?;
+    NotificationRepository::new(&db).delete(id)
+}
+
+/// See `NotificationRepository::queue`
+pub async fn queue_notifications(queued: &[(i64, Notification)]) -> Result<Vec<bool>> {
+    let db = lock_writable().await?;
+    NotificationRepository::new(&db).queue(queued)
+}
+
+/// Empty the queue of held back notifications, oldest first
+pub async fn take_queued_notifications() -> Result<Vec<(i64, Notification)>> {
+    let db = lock_writable().await?;
+    NotificationRepository::new(&db).take_queued()
+}
+
+/// Log one attempt to post a notification to the webhook
+pub async fn record_webhook_delivery(delivery: &WebhookDelivery) -> Result<()> {
+    let db = lock_writable().await?;
+    DeliveryLog::new(&db).record(delivery)
+}
+
+/// Latest webhook attempts, newest first
+pub async fn get_webhook_deliveries(limit: usize) -> Result<Vec<WebhookDelivery>> {
     let db = DB.lock().await;
-    
-    db.execute(
-        "DELETE FROM notifications WHERE id = ?1",
-        params![id],
-    )?;
-    
-    Ok(())
+    DeliveryLog::new(&db).recent(limit)
 }
 
-/// Get all notifications
-pub async fn get_notifications() -> Result<Vec<Notification>> {
+/// Notification rules in creation order
+pub async fn get_notification_rules() -> Result<Vec<Rule>> {
     let db = DB.lock().await;
-    
+    read_rules(&db)
+}
+
+fn read_rules(db: &Connection) -> Result<Vec<Rule>> {
     let mut stmt = db.prepare(
-        "SELECT id, title, message, timestamp, read, type 
-         FROM notifications 
-         ORDER BY timestamp DESC 
-         LIMIT 50"
+        "SELECT id, name, enabled, trigger, conditions, template, last_run 
+         FROM notification_rules 
+         ORDER BY rowid"
     )?;
     
-    let notifications = stmt.query_map([], |row| {
-        Ok(Notification {
-            id: row.get(0)?,
-            title: row.get(1)?,
-            message: row.get(2)?,
-            timestamp: row.get(3)?,
-            read: row.get::<_, i32>(4)? != 0,
-            type_: row.get(5)?,
-        })
-    })?;
-    
-    let mut result = Vec::new();
-    for notification in notifications {
-        result.push(notification?);
-    }
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, i32>(2)? != 0,
+            row.get::<_, String>(3)?,
+            row.get::<_, String>(4)?,
+            row.get::<_, String>(5)?,
+            row.get::<_, Option<i64>>(6)?,
+        ))
+    })?
+    .collect::<rusqlite::Result<Vec<_>>>()?;
     
-    Ok(result)
+    rows.into_iter()
+        .map(|(id, name, enabled, trigger, conditions, template, last_run)| {
+            let invalid = |e: serde_json::Error| VeraError::Internal(format!("notification rule {}: {}", id, e));
+            Ok(Rule {
+                trigger: serde_json::from_str(&trigger).map_err(invalid)?,
+                conditions: serde_json::from_str(&conditions).map_err(invalid)?,
+                template: serde_json::from_str(&template).map_err(invalid)?,
+                id,
+                name,
+                enabled,
+                last_run,
+            })
+        })
+        .collect()
 }
 
-/// Save a new notification
-pub async fn save_notification(notification: Notification) -> Result<()> {
-    let db = DB.lock().await;
+/// Insert or replace a notification rule by id
+pub async fn save_notification_rule(rule: &Rule) -> Result<()> {
+    let db = lock_writable().await?;
+    upsert_rule(&db, rule)
+}
+
+fn upsert_rule(db: &Connection, rule: &Rule) -> Result<()> {
+    let json = |value: serde_json::Result<String>| value.map_err(|e| VeraError::Internal(e.to_string()));
     
     db.execute(
-        "INSERT INTO notifications (id, title, message, timestamp, read, type) 
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        "INSERT INTO notification_rules (id, name, enabled, trigger, conditions, template) 
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6) 
+         ON CONFLICT(id) DO UPDATE SET 
+         name = excluded.name, 
+         enabled = excluded.enabled, 
+         trigger = excluded.trigger, 
+         conditions = excluded.conditions, 
+         template = excluded.template",
         params![
-            notification.id,
-            notification.title,
-            notification.message,
-            notification.timestamp,
-            notification.read as i32,
-            notification.type_
+            rule.id,
+            rule.name,
+            rule.enabled as i32,
+            json(serde_json::to_string(&rule.trigger))?,
+            json(serde_json::to_string(&rule.conditions))?,
+            json(serde_json::to_string(&rule.template))?
         ],
     )?;
     
     Ok(())
 }
 
-/// Mark notification as read
-pub async fn mark_notification_read(id: &str) -> Result<()> {
-    let db = DB.lock().await;
-    
+/// Record the latest scheduled time a rule handled
+pub async fn set_notification_rule_run(id: &str, last_run: i64) -> Result<()> {
+    let db = lock_writable().await?;
+    record_rule_run(&db, id, last_run)
+}
+
+fn record_rule_run(db: &Connection, id: &str, last_run: i64) -> Result<()> {
     db.execute(
-        "UPDATE notifications SET read = 1 WHERE id = ?1",
-        params![id],
+        "UPDATE notification_rules SET last_run = ?2 WHERE id = ?1",
+        params![id, last_run],
     )?;
     
     Ok(())
 }
 
-/// Mark all notifications as read
-pub async fn mark_all_notifications_read() -> Result<()> {
-    let db = DB.lock().await;
-    
-    db.execute("UPDATE notifications SET read = 1", [])?;
-    
-    Ok(())
+/// Delete notification rule
+pub async fn delete_notification_rule(id: &str) -> Result<()> {
+    let db = lock_writable().await?;
+    remove_rule(&db, id)
 }
 
-/// Delete notification
-pub async fn delete_notification(id: &str) -> Result<()> {
-    let db = DB.lock().await;
-    
-    db.execute(
-        "DELETE FROM notifications WHERE id = ?1",
+fn remove_rule(db: &Connection, id: &str) -> Result<()> {
+    let deleted = db.execute(
+        "DELETE FROM notification_rules WHERE id = ?1",
         params![id],
     )?;
     
+    if deleted == 0 {
+        return Err(VeraError::NotFound(id.to_string()));
+    }
+    
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::notifications::webhook::Webhook;
+
+    fn database() -> Connection {
+        let db = Connection::open_in_memory().unwrap();
+        create_schema(&db).unwrap();
+        db
+    }
+
+    fn insert_reading(db: &Connection, timestamp: i64, device_type: &str, power_watts: f64, kwh: f64) {
+        db.execute(
+            "INSERT INTO energy_readings (timestamp, device_type, power_watts, kwh_increment) VALUES (?1, ?2, ?3, ?4)",
+            params![timestamp, device_type, power_watts, kwh],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_schema_is_idempotent() {
+        let db = database();
+        let rules = read_rules(&db).unwrap().len();
+        assert_eq!(rules, crate::notifications::rules::default_rules().len());
+        remove_rule(&db, "daily-summary").unwrap();
+
+        // Reopening migrates nothing twice and leaves deleted defaults deleted
+        create_schema(&db).unwrap();
+        assert_eq!(read_rules(&db).unwrap().len(), rules - 1);
+    }
+
+    #[test]
+    fn test_settings_round_trip() {
+        let db = database();
+        let json = |settings: &UserSettings| serde_json::to_value(settings).unwrap();
+        assert_eq!(json(&read_settings(&db).unwrap()), json(&UserSettings::default()));
+
+        let settings = UserSettings {
+            do_not_disturb: DoNotDisturb::from_hours(22, 7),
+            locale: "en".to_string(),
+            notification_retention_days: 30,
+            notification_retention_count: 0,
+            api_enabled: true,
+            api_token: "token".to_string(),
+            webhook: Webhook {
+                enabled: true,
+                url: "https://chat.example.com/hook".to_string(),
+                secret: "secret".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        write_settings(&db, &settings).unwrap();
+        assert_eq!(json(&read_settings(&db).unwrap()), json(&settings));
+
+        // Whole-hour quiet hours of a database from before do not disturb
+        db.execute("UPDATE user_settings SET do_not_disturb = NULL, quiet_hours_start = 21, quiet_hours_end = 6", [])
+            .unwrap();
+        assert_eq!(read_settings(&db).unwrap().do_not_disturb, DoNotDisturb::from_hours(21, 6));
+        // The same hour for start and end turned them off
+        db.execute("UPDATE user_settings SET quiet_hours_start = 8, quiet_hours_end = 8", []).unwrap();
+        let dnd = read_settings(&db).unwrap().do_not_disturb;
+        assert!(dnd.windows.is_empty());
+        assert!(!dnd.is_quiet(Local::now()));
+
+        db.execute("UPDATE user_settings SET do_not_disturb = '{'", []).unwrap();
+        assert_eq!(read_settings(&db).unwrap_err().code(), "CONFIG_ERROR");
+        db.execute("UPDATE user_settings SET do_not_disturb = NULL, webhook = '{'", []).unwrap();
+        assert_eq!(read_settings(&db).unwrap_err().code(), "CONFIG_ERROR");
+    }
+
+    #[test]
+    fn test_stats_aggregation() {
+        let db = database();
+        // A Wednesday evening
+        let now = Local.with_ymd_and_hms(2024, 5, 15, 23, 0, 0).unwrap();
+        let (day, _) = period_range_at("day", now).unwrap();
+        let (week, _) = period_range_at("week", now).unwrap();
+        let (month, _) = period_range_at("month", now).unwrap();
+        assert!(month < week && week < day);
+
+        insert_reading(&db, day + 60, "pc", 100.0, 0.5);
+        insert_reading(&db, day + 120, "pc", 150.0, 0.25);
+        insert_reading(&db, day + 180, "laptop", 400.0, 9.0);
+        // The day before, earlier this week and earlier this month
+        insert_reading(&db, day - 3600, "pc", 80.0, 0.25);
+        insert_reading(&db, week + 60, "pc", 80.0, 1.0);
+        insert_reading(&db, month + 60, "pc", 80.0, 2.0);
+        // After `now`
+        insert_reading(&db, now.timestamp() + 60, "pc", 500.0, 5.0);
+
+        let stats = stats_at(&db, "day", "pc", now).unwrap();
+        assert_eq!(stats.today_kwh, 0.75);
+        assert_eq!(stats.weekly_kwh, 2.0);
+        assert_eq!(stats.monthly_kwh, 4.0);
+        assert_eq!(stats.peak_today, 150.0);
+        assert_eq!(stats.bottles_today, 2.25);
+        assert_eq!(stats.co2_today, 375.0);
+        // 0.75 kWh today against 0.25 over the day before
+        assert_eq!(stats.weekly_trend, 200.0);
+
+        let stats = stats_at(&db, "week", "pc", now).unwrap();
+        assert_eq!((stats.today_kwh, stats.weekly_kwh), (0.75, 2.0));
+        assert_eq!(stats_at(&db, "day", "laptop", now).unwrap().today_kwh, 9.0);
+        assert_eq!(stats_at(&db, "hour", "pc", now).unwrap_err().code(), "INVALID_ARGUMENT");
+    }
+
+    #[test]
+    fn test_stats_follow_local_midnight() {
+        // Monday 1 July, half an hour old in Rome but still Sunday in UTC
+        let rome = chrono_tz::Europe::Rome;
+        let db = database();
+        let now = rome.with_ymd_and_hms(2024, 7, 1, 0, 30, 0).unwrap();
+        insert_reading(&db, rome.with_ymd_and_hms(2024, 7, 1, 0, 10, 0).unwrap().timestamp(), "pc", 100.0, 0.5);
+        insert_reading(&db, rome.with_ymd_and_hms(2024, 6, 30, 23, 50, 0).unwrap().timestamp(), "pc", 100.0, 2.0);
+
+        // The day, week and month all began at local midnight
+        for period in ["day", "week", "month"] {
+            let stats = stats_at(&db, period, "pc", now).unwrap();
+            assert_eq!((stats.today_kwh, stats.weekly_kwh, stats.monthly_kwh), (0.5, 0.5, 0.5), "{}", period);
+        }
+    }
+
+    #[test]
+    fn test_cleanup() {
+        let db = database();
+        for timestamp in [100, 200, 300] {
+            insert_reading(&db, timestamp, "pc", 50.0, 0.1);
+        }
+        assert_eq!(delete_readings_before(&db, 250).unwrap(), 2);
+        assert_eq!(delete_readings_before(&db, 250).unwrap(), 0);
+        let left: Vec<i64> = db
+            .prepare("SELECT timestamp FROM energy_readings")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(left, [300]);
+    }
+
+    #[test]
+    fn test_rollup() {
+        let db = database();
+        // Two hours of pc readings, a lone tablet one and one after the cutoff
+        for (timestamp, power) in [(3600, 40.0), (4800, 60.0), (6000, 80.0), (7200, 100.0), (7300, 200.0)] {
+            insert_reading(&db, timestamp, "pc", power, 0.25);
+        }
+        insert_reading(&db, 3700, "tablet", 10.0, 0.5);
+        insert_reading(&db, 10_900, "pc", 50.0, 0.1);
+
+        // The cutoff is rounded down to 10800
+        assert_eq!(rollup_readings_before(&db, 11_000).unwrap(), 3);
+        assert_eq!(rollup_readings_before(&db, 11_000).unwrap(), 0);
+        let rows: Vec<(i64, String, f64, f64)> = db
+            .prepare("SELECT timestamp, device_type, power_watts, kwh_increment FROM energy_readings ORDER BY timestamp, device_type")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                (3600, "pc".to_string(), 60.0, 0.75),
+                (3700, "tablet".to_string(), 10.0, 0.5),
+                (7200, "pc".to_string(), 150.0, 0.5),
+                (10_900, "pc".to_string(), 50.0, 0.1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_history_across_dst() {
+        // Clocks go forward at 02:00 on the last Sunday of March
+        let rome = chrono_tz::Europe::Rome;
+        let db = database();
+        let first = rome.with_ymd_and_hms(2024, 3, 30, 0, 0, 0).unwrap().timestamp();
+        let end = rome.with_ymd_and_hms(2024, 4, 1, 0, 0, 0).unwrap().timestamp();
+        for timestamp in (first..end).step_by(3600) {
+            insert_reading(&db, timestamp, "pc", 100.0, 0.1);
+        }
+
+        let days = history_at(&db, first, end, 86400, "pc", &rome).unwrap();
+        let starts: Vec<i64> = days.iter().map(|b| b.start).collect();
+        assert_eq!(starts, [first, rome.with_ymd_and_hms(2024, 3, 31, 0, 0, 0).unwrap().timestamp()]);
+        assert_eq!(days.iter().map(|b| b.samples).collect::<Vec<_>>(), [24, 23]);
+
+        // Every hour is its own bucket, before and after the change
+        let hours = history_at(&db, first, end, 3600, "pc", &rome).unwrap();
+        assert_eq!(hours.len(), 47);
+        assert!(hours.iter().zip((first..end).step_by(3600)).all(|(b, t)| b.start == t && b.samples == 1));
+
+        assert_eq!(history_at(&db, end, first, 3600, "pc", &rome).unwrap_err().code(), "INVALID_ARGUMENT");
+    }
+
+    #[test]
+    fn test_rules_crud() {
+        let db = database();
+        let mut rule = read_rules(&db).unwrap().remove(0);
+        rule.enabled = false;
+        rule.name = "Renamed".to_string();
+        upsert_rule(&db, &rule).unwrap();
+        record_rule_run(&db, &rule.id, 1_714_579_200).unwrap();
+
+        let stored = read_rules(&db).unwrap().into_iter().find(|r| r.id == rule.id).unwrap();
+        assert!(!stored.enabled);
+        assert_eq!(stored.name, "Renamed");
+        assert_eq!(stored.last_run, Some(1_714_579_200));
+        // Saving the rule again keeps the run the scheduler recorded
+        upsert_rule(&db, &rule).unwrap();
+        assert_eq!(read_rules(&db).unwrap()[0].last_run, Some(1_714_579_200));
+
+        remove_rule(&db, &rule.id).unwrap();
+        assert_eq!(remove_rule(&db, &rule.id).unwrap_err().code(), "NOT_FOUND");
+        assert!(read_rules(&db).unwrap().iter().all(|r| r.id != rule.id));
+
+        db.execute("UPDATE notification_rules SET trigger = 'nope'", []).unwrap();
+        assert_eq!(read_rules(&db).unwrap_err().code(), "INTERNAL_ERROR");
+    }
+}
@@ -1,31 +1,146 @@
 // Storage module for local database
 pub mod db;
+pub mod notifications;
+pub mod webhooks;
+pub mod writer;
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::error::{Result, VeraError};
+use crate::notifications::i18n::Locale;
+use crate::notifications::quiet::DoNotDisturb;
+use crate::notifications::webhook::Webhook;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct UserSettings {
-    pub quiet_hours_start: u8,    // 0-23
-    pub quiet_hours_end: u8,      // 0-23
+    /// When notifications are held back
+    pub do_not_disturb: DoNotDisturb,
     pub region: String,
     pub theme: String,
     pub notifications_enabled: bool,
     pub device_type: String,
+    /// Language of notifications, `it` or `en`
+    pub locale: String,
+    /// Notifications older than this many days are deleted; 0 keeps them
+    pub notification_retention_days: u32,
+    /// Only the newest this many notifications are kept; 0 keeps them all
+    pub notification_retention_count: u32,
+    /// Local REST API (see `api`), off by default
+    pub api_enabled: bool,
+    pub api_bind: String,         // host:port
+    pub api_token: String,        // Bearer token, generated when the API is enabled
+    /// Where notifications are also posted, off by default
+    pub webhook: Webhook,
 }
 
 impl Default for UserSettings {
     fn default() -> Self {
         Self {
-            quiet_hours_start: 19,
-            quiet_hours_end: 8,
+            do_not_disturb: DoNotDisturb::default(),
             region: "Lombardia".to_string(),
             theme: "light".to_string(),
             notifications_enabled: true,
             device_type: "pc".to_string(),
+            locale: "it".to_string(),
+            notification_retention_days: 90,
+            notification_retention_count: 1000,
+            api_enabled: false,
+            api_bind: "127.0.0.1:7878".to_string(),
+            api_token: String::new(),
+            webhook: Webhook::default(),
+        }
+    }
+}
+
+/// Partial settings update sent by the frontend (e.g. only `theme`)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserSettingsPatch {
+    pub do_not_disturb: Option<DoNotDisturb>,
+    pub region: Option<String>,
+    pub theme: Option<String>,
+    pub notifications_enabled: Option<bool>,
+    pub device_type: Option<String>,
+    pub locale: Option<String>,
+    pub notification_retention_days: Option<u32>,
+    pub notification_retention_count: Option<u32>,
+    pub api_enabled: Option<bool>,
+    pub api_bind: Option<String>,
+    pub api_token: Option<String>,
+    pub webhook: Option<Webhook>,
+}
+
+impl UserSettings {
+    pub fn validate(&self) -> Result<()> {
+        self.do_not_disturb.validate()?;
+        if Locale::parse(&self.locale).is_none() {
+            return Err(VeraError::invalid_argument("locale", "expected it or en"));
+        }
+        if self.api_bind.parse::<std::net::SocketAddr>().is_err() {
+            return Err(VeraError::invalid_argument("apiBind", "expected an address like 127.0.0.1:7878"));
+        }
+        self.webhook.validate()?;
+        Ok(())
+    }
+
+    /// Language of notifications, Italian if the setting is not understood
+    pub fn locale(&self) -> Locale {
+        Locale::parse(&self.locale).unwrap_or_default()
+    }
+
+    /// Enabling the API without a token generates one; clearing it rotates it
+    pub fn ensure_api_token(&mut self) -> Result<()> {
+        if self.api_enabled && self.api_token.is_empty() {
+            self.api_token = crate::utils::random_hex(24)
+                .map_err(|e| VeraError::Internal(format!("Failed to generate API token: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    /// Apply the fields present in `patch`, leaving the others untouched
+    pub fn apply(&mut self, patch: UserSettingsPatch) {
+        if let Some(v) = patch.do_not_disturb {
+            self.do_not_disturb = v;
+        }
+        if let Some(v) = patch.region {
+            self.region = v;
+        }
+        if let Some(v) = patch.theme {
+            self.theme = v;
+        }
+        if let Some(v) = patch.notifications_enabled {
+            self.notifications_enabled = v;
+        }
+        if let Some(v) = patch.device_type {
+            self.device_type = v;
+        }
+        if let Some(v) = patch.locale {
+            self.locale = v;
+        }
+        if let Some(v) = patch.notification_retention_days {
+            self.notification_retention_days = v;
+        }
+        if let Some(v) = patch.notification_retention_count {
+            self.notification_retention_count = v;
+        }
+        if let Some(v) = patch.api_enabled {
+            self.api_enabled = v;
+        }
+        if let Some(v) = patch.api_bind {
+            self.api_bind = v;
+        }
+        if let Some(v) = patch.api_token {
+            self.api_token = v;
+        }
+        if let Some(v) = patch.webhook {
+            self.webhook = v;
         }
     }
 }
 
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Notification {
     pub id: String,
@@ -35,15 +150,158 @@ pub struct Notification {
     pub read: bool,
     #[serde(rename = "type")]
     pub type_: String,
+    /// Catalogue template the texts were rendered from, see `notifications::i18n`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Placeholder values, to render the template again in another language
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, f64>,
+    /// The same alert for the same window; a second one with this key is
+    /// not stored
+    #[serde(rename = "dedupKey", default, skip_serializing_if = "Option::is_none")]
+    pub dedup_key: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Notification {
-    pub id: String,
-    pub title: String,
-    pub message: String,
-    pub timestamp: i64,
-    pub read: bool,
+impl Notification {
+    /// Unique id starting with `prefix`, e.g. `daily-summary-3f9a…`
+    pub fn new_id(prefix: &str) -> Result<String> {
+        let suffix = crate::utils::random_hex(6)
+            .map_err(|e| VeraError::Internal(format!("Failed to generate notification id: {}", e)))?;
+        Ok(format!("{}-{}", prefix, suffix))
+    }
+}
+
+/// Which notifications to list, newest first
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationQuery {
     #[serde(rename = "type")]
-    pub type_: String,
+    pub type_: Option<String>,
+    pub read: Option<bool>,
+    pub from: Option<i64>,        // Unix seconds, included
+    pub to: Option<i64>,          // Unix seconds, excluded
+    /// `nextCursor` of the previous page
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl NotificationQuery {
+    pub const DEFAULT_LIMIT: usize = 50;
+    pub const MAX_LIMIT: usize = 500;
+
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(Self::DEFAULT_LIMIT).clamp(1, Self::MAX_LIMIT)
+    }
+}
+
+/// One page of notifications; `next_cursor` is set when there are more
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationPage {
+    pub notifications: Vec<Notification>,
+    pub next_cursor: Option<String>,
+}
+
+/// Position after a notification in newest-first order. Opaque to clients:
+/// the timestamp and id, which together are unique.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotificationCursor {
+    pub timestamp: i64,
+    pub id: String,
+}
+
+impl NotificationCursor {
+    pub fn after(notification: &Notification) -> Self {
+        Self {
+            timestamp: notification.timestamp,
+            id: notification.id.clone(),
+        }
+    }
+
+    pub fn parse(cursor: &str) -> Result<Self> {
+        let invalid = || VeraError::invalid_argument("cursor", "expected the nextCursor of a previous page");
+        let (timestamp, id) = cursor.split_once(':').ok_or_else(invalid)?;
+        Ok(Self {
+            timestamp: timestamp.parse().map_err(|_| invalid())?,
+            id: id.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for NotificationCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.timestamp, self.id)
+    }
+}
+
+/// One stored sample, as written by `writer`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnergyReading {
+    pub timestamp: i64,           // Unix seconds
+    pub device_type: String,
+    pub power_watts: f64,
+    pub kwh_increment: f64,
+}
+
+/// Readings aggregated over `[start, start + bucket)`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryBucket {
+    pub start: i64,               // Unix seconds
+    pub kwh: f64,
+    pub avg_power: f64,           // Watts
+    pub max_power: f64,
+    pub samples: u64,
+}
+
+/// Aggregates over a time range; power fields are `None` without readings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadingSummary {
+    pub count: u64,
+    pub kwh: f64,
+    pub avg_power: Option<f64>,   // Watts
+    pub max_power: Option<f64>,
+    pub min_power: Option<f64>,
+    pub first: Option<i64>,       // Unix seconds
+    pub last: Option<i64>,
+}
+
+/// One attempt to post a notification to the webhook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookDelivery {
+    pub notification_id: String,
+    /// Scheme, host and first path segment only, see `Webhook::logged_url`
+    pub url: String,
+    pub attempt: u32,             // from 1
+    pub timestamp: i64,           // Unix seconds
+    /// HTTP status, unset when no response came
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    /// Answered with a 2xx status
+    pub delivered: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notification_cursor() {
+        // Ids may contain the separator
+        let cursor = NotificationCursor {
+            timestamp: 1714572000,
+            id: "rule-a:b".to_string(),
+        };
+        assert_eq!(cursor.to_string(), "1714572000:rule-a:b");
+        assert_eq!(NotificationCursor::parse(&cursor.to_string()).unwrap(), cursor);
+        assert_eq!(NotificationCursor::parse("nope").unwrap_err().code(), "INVALID_ARGUMENT");
+
+        let query = NotificationQuery { limit: Some(10_000), ..Default::default() };
+        assert_eq!(query.limit(), NotificationQuery::MAX_LIMIT);
+        assert_eq!(NotificationQuery::default().limit(), NotificationQuery::DEFAULT_LIMIT);
+    }
 }
@@ -0,0 +1,364 @@
+// Notification inbox and delivery queue
+//
+// Every query on the `notifications` and `notification_queue` tables. The
+// repository borrows a connection, so `db` runs it on the shared one and
+// the tests on an in-memory database.
+use rusqlite::{params, Connection};
+use std::collections::BTreeMap;
+
+use super::{Notification, NotificationCursor, NotificationPage, NotificationQuery};
+use crate::error::{Result, VeraError};
+use crate::notifications::i18n::{self, Locale};
+
+pub struct NotificationRepository<'a> {
+    db: &'a Connection,
+}
+
+impl<'a> NotificationRepository<'a> {
+    pub fn new(db: &'a Connection) -> Self {
+        Self { db }
+    }
+
+    /// Store a notification; false when one with the same id or dedup key
+    /// is already there
+    pub fn save(&self, notification: &Notification) -> Result<bool> {
+        let params = if notification.params.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&notification.params).map_err(|e| VeraError::Internal(e.to_string()))?)
+        };
+
+        let inserted = self.db.execute(
+            "INSERT INTO notifications (id, title, message, timestamp, read, type, template, params, dedup_key)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT DO NOTHING",
+            params![
+                notification.id,
+                notification.title,
+                notification.message,
+                notification.timestamp,
+                notification.read as i32,
+                notification.type_,
+                notification.template,
+                params,
+                notification.dedup_key
+            ],
+        )?;
+
+        Ok(inserted > 0)
+    }
+
+    /// A page of notifications matching `query`, newest first, rendered in
+    /// `locale` where they come from the catalogue
+    pub fn page(&self, query: &NotificationQuery, locale: Locale) -> Result<NotificationPage> {
+        let cursor = query.cursor.as_deref().map(NotificationCursor::parse).transpose()?;
+        let limit = query.limit();
+
+        let mut stmt = self.db.prepare_cached(
+            "SELECT id, title, message, timestamp, read, type, template, params, dedup_key
+             FROM notifications
+             WHERE (?1 IS NULL OR type = ?1)
+               AND (?2 IS NULL OR read = ?2)
+               AND (?3 IS NULL OR timestamp >= ?3)
+               AND (?4 IS NULL OR timestamp < ?4)
+               AND (?5 IS NULL OR timestamp < ?5 OR (timestamp = ?5 AND id < ?6))
+             ORDER BY timestamp DESC, id DESC
+             LIMIT ?7"
+        )?;
+
+        // One more than asked tells whether there is a next page
+        let mut notifications = stmt.query_map(
+            params![
+                query.type_,
+                query.read.map(|read| read as i32),
+                query.from,
+                query.to,
+                cursor.as_ref().map(|c| c.timestamp),
+                cursor.as_ref().map(|c| c.id.as_str()),
+                limit as i64 + 1
+            ],
+            |row| {
+                let params: Option<String> = row.get(7)?;
+                let mut notification = Notification {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    message: row.get(2)?,
+                    timestamp: row.get(3)?,
+                    read: row.get::<_, i32>(4)? != 0,
+                    type_: row.get(5)?,
+                    template: row.get(6)?,
+                    // Unreadable values only cost the translation
+                    params: params.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default(),
+                    dedup_key: row.get(8)?,
+                };
+                i18n::localize(&mut notification, locale);
+                Ok(notification)
+            },
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let next_cursor = if notifications.len() > limit {
+            notifications.truncate(limit);
+            notifications.last().map(|n| NotificationCursor::after(n).to_string())
+        } else {
+            None
+        };
+        Ok(NotificationPage { notifications, next_cursor })
+    }
+
+    /// Unread notifications per type
+    pub fn count_unread(&self) -> Result<BTreeMap<String, u64>> {
+        let mut stmt = self.db.prepare_cached("SELECT type, COUNT(*) FROM notifications WHERE read = 0 GROUP BY type")?;
+        let counts = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as u64)))?
+            .collect::<rusqlite::Result<BTreeMap<_, _>>>()?;
+
+        Ok(counts)
+    }
+
+    pub fn mark_read(&self, id: &str) -> Result<()> {
+        let updated = self.db.execute(
+            "UPDATE notifications SET read = 1 WHERE id = ?1",
+            params![id],
+        )?;
+
+        if updated == 0 {
+            return Err(VeraError::NotFound(id.to_string()));
+        }
+
+        Ok(())
+    }
+
+    pub fn mark_all_read(&self) -> Result<()> {
+        self.db.execute("UPDATE notifications SET read = 1", [])?;
+
+        Ok(())
+    }
+
+    pub fn delete(&self, id: &str) -> Result<()> {
+        let deleted = self.db.execute(
+            "DELETE FROM notifications WHERE id = ?1",
+            params![id],
+        )?;
+
+        if deleted == 0 {
+            return Err(VeraError::NotFound(id.to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Delete notifications older than `days` before `now` and all but the
+    /// newest `count`; 0 turns either limit off. Returns how many went.
+    pub fn prune(&self, days: u32, count: u32, now: i64) -> Result<usize> {
+        let mut deleted = 0;
+
+        if days > 0 {
+            let cutoff = now - days as i64 * 86_400;
+            deleted += self.db.execute("DELETE FROM notifications WHERE timestamp < ?1", params![cutoff])?;
+        }
+        if count > 0 {
+            deleted += self.db.execute(
+                "DELETE FROM notifications WHERE id IN (
+                    SELECT id FROM notifications ORDER BY timestamp DESC, id DESC LIMIT -1 OFFSET ?1
+                )",
+                params![count],
+            )?;
+        }
+
+        Ok(deleted)
+    }
+
+    /// Hold notifications back until do not disturb ends, with the time
+    /// each was due. Whether each was queued: not when it already is, or
+    /// was delivered, under its id or dedup key.
+    pub fn queue(&self, queued: &[(i64, Notification)]) -> Result<Vec<bool>> {
+        let tx = self.db.unchecked_transaction()?;
+
+        let mut added = Vec::with_capacity(queued.len());
+        for (due, notification) in queued {
+            let json = serde_json::to_string(notification).map_err(|e| VeraError::Internal(e.to_string()))?;
+            let inserted = tx.execute(
+                "INSERT OR IGNORE INTO notification_queue (id, due, notification, dedup_key)
+                 SELECT ?1, ?2, ?3, ?4
+                 WHERE NOT EXISTS (SELECT 1 FROM notifications WHERE id = ?1 OR dedup_key = ?4)",
+                params![notification.id, due, json, notification.dedup_key],
+            )?;
+            added.push(inserted > 0);
+        }
+        tx.commit()?;
+
+        Ok(added)
+    }
+
+    /// Empty the queue, oldest first
+    pub fn take_queued(&self) -> Result<Vec<(i64, Notification)>> {
+        let tx = self.db.unchecked_transaction()?;
+
+        let queued = {
+            let mut stmt = tx.prepare("SELECT due, notification FROM notification_queue ORDER BY due, rowid")?;
+            let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+            let mut queued = Vec::new();
+            for row in rows {
+                let (due, json) = row?;
+                match serde_json::from_str(&json) {
+                    Ok(notification) => queued.push((due, notification)),
+                    Err(e) => eprintln!("Dropping unreadable queued notification: {}", e),
+                }
+            }
+            queued
+        };
+        tx.execute("DELETE FROM notification_queue", [])?;
+        tx.commit()?;
+
+        Ok(queued)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::db::create_schema;
+
+    fn database() -> Connection {
+        let db = Connection::open_in_memory().unwrap();
+        create_schema(&db).unwrap();
+        db
+    }
+
+    fn notification(id: &str, timestamp: i64, type_: &str) -> Notification {
+        Notification {
+            id: id.to_string(),
+            title: format!("Title {}", id),
+            message: "Body".to_string(),
+            timestamp,
+            read: false,
+            type_: type_.to_string(),
+            template: None,
+            params: BTreeMap::new(),
+            dedup_key: None,
+        }
+    }
+
+    fn ids(page: &NotificationPage) -> Vec<&str> {
+        page.notifications.iter().map(|n| n.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_crud() {
+        let db = database();
+        let repo = NotificationRepository::new(&db);
+        assert!(repo.save(&notification("a", 100, "info")).unwrap());
+        assert!(repo.save(&notification("b", 200, "warning")).unwrap());
+        // Same id
+        assert!(!repo.save(&notification("a", 300, "info")).unwrap());
+
+        let page = repo.page(&NotificationQuery::default(), Locale::It).unwrap();
+        assert_eq!(ids(&page), ["b", "a"]);
+        assert_eq!(page.notifications[1].title, "Title a");
+        assert_eq!(page.next_cursor, None);
+
+        repo.mark_read("a").unwrap();
+        assert_eq!(repo.mark_read("missing").unwrap_err().code(), "NOT_FOUND");
+        assert_eq!(repo.count_unread().unwrap(), BTreeMap::from([("warning".to_string(), 1)]));
+        repo.mark_all_read().unwrap();
+        assert!(repo.count_unread().unwrap().is_empty());
+
+        repo.delete("a").unwrap();
+        assert_eq!(repo.delete("a").unwrap_err().code(), "NOT_FOUND");
+        assert_eq!(ids(&repo.page(&NotificationQuery::default(), Locale::It).unwrap()), ["b"]);
+    }
+
+    #[test]
+    fn test_dedup_and_localized_reads() {
+        let db = database();
+        let repo = NotificationRepository::new(&db);
+        let mut first = notification("daily-summary-1", 100, "info");
+        first.dedup_key = Some("daily-summary-86400".to_string());
+        first.template = Some("daily-summary".to_string());
+        first.params = BTreeMap::from([
+            ("day.kwh".to_string(), 1.5),
+            ("day.bottles".to_string(), 4.5),
+            ("day.co2".to_string(), 750.0),
+        ]);
+        let mut again = notification("daily-summary-2", 160, "info");
+        again.dedup_key = first.dedup_key.clone();
+        assert!(repo.save(&first).unwrap());
+        assert!(!repo.save(&again).unwrap());
+
+        let page = repo.page(&NotificationQuery::default(), Locale::En).unwrap();
+        assert_eq!(ids(&page), ["daily-summary-1"]);
+        assert_eq!(page.notifications[0].title, "Daily summary");
+        assert_eq!(page.notifications[0].message, "Today: 1.50 kWh ≈ 4.5 bottles 💧 | CO₂: 750g");
+        assert_eq!(page.notifications[0].dedup_key, first.dedup_key);
+    }
+
+    #[test]
+    fn test_pages_and_filters() {
+        let db = database();
+        let repo = NotificationRepository::new(&db);
+        // Two share a timestamp, so the cursor has to tell them apart by id
+        for (id, timestamp, type_) in [("a", 100, "info"), ("b", 200, "warning"), ("c", 200, "info"), ("d", 300, "info")] {
+            repo.save(&notification(id, timestamp, type_)).unwrap();
+        }
+        repo.mark_read("d").unwrap();
+
+        let mut query = NotificationQuery { limit: Some(2), ..Default::default() };
+        let first = repo.page(&query, Locale::It).unwrap();
+        assert_eq!(ids(&first), ["d", "c"]);
+        query.cursor = first.next_cursor.clone();
+        let second = repo.page(&query, Locale::It).unwrap();
+        assert_eq!(ids(&second), ["b", "a"]);
+        assert_eq!(second.next_cursor, None);
+
+        let filtered = |query: NotificationQuery| ids(&repo.page(&query, Locale::It).unwrap()).join(",");
+        assert_eq!(filtered(NotificationQuery { type_: Some("info".to_string()), ..Default::default() }), "d,c,a");
+        assert_eq!(filtered(NotificationQuery { read: Some(false), ..Default::default() }), "c,b,a");
+        assert_eq!(filtered(NotificationQuery { from: Some(200), to: Some(300), ..Default::default() }), "c,b");
+
+        let bad = NotificationQuery { cursor: Some("x".to_string()), ..Default::default() };
+        assert_eq!(repo.page(&bad, Locale::It).unwrap_err().code(), "INVALID_ARGUMENT");
+    }
+
+    #[test]
+    fn test_prune() {
+        let db = database();
+        let repo = NotificationRepository::new(&db);
+        let day = 86_400;
+        for i in 0..5 {
+            repo.save(&notification(&format!("n{}", i), 10 * day + i * day, "info")).unwrap();
+        }
+        let now = 15 * day;
+
+        // Both limits off
+        assert_eq!(repo.prune(0, 0, now).unwrap(), 0);
+        // Older than 3 days: n0 and n1
+        assert_eq!(repo.prune(3, 0, now).unwrap(), 2);
+        // Newest 2 kept
+        assert_eq!(repo.prune(0, 2, now).unwrap(), 1);
+        assert_eq!(ids(&repo.page(&NotificationQuery::default(), Locale::It).unwrap()), ["n4", "n3"]);
+    }
+
+    #[test]
+    fn test_queue() {
+        let db = database();
+        let repo = NotificationRepository::new(&db);
+        repo.queue(&[(200, notification("b", 200, "info")), (100, notification("a", 100, "info"))]).unwrap();
+        // Queued twice while quiet
+        assert_eq!(repo.queue(&[(100, notification("a", 100, "info"))]).unwrap(), [false]);
+        // A summary rendered again, under a new id, on the next tick
+        let summary = |id: &str, key: &str| Notification {
+            dedup_key: Some(key.to_string()),
+            ..notification(id, 300, "info")
+        };
+        assert_eq!(repo.queue(&[(300, summary("s1", "s-300"))]).unwrap(), [true]);
+        assert_eq!(repo.queue(&[(300, summary("s2", "s-300"))]).unwrap(), [false]);
+        // Or already delivered
+        assert!(repo.save(&summary("d1", "d-400")).unwrap());
+        assert_eq!(repo.queue(&[(400, summary("d2", "d-400"))]).unwrap(), [false]);
+
+        let queued = repo.take_queued().unwrap();
+        assert_eq!(queued.iter().map(|(due, n)| (*due, n.id.as_str())).collect::<Vec<_>>(), [(100, "a"), (200, "b"), (300, "s1")]);
+        assert!(repo.take_queued().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+// Webhook delivery log
+//
+// Every attempt to post a notification to the webhook, newest kept up to
+// `DeliveryLog::KEEP`, so a failing endpoint can be diagnosed from the
+// settings panel or `vera webhook log`.
+use rusqlite::{params, Connection};
+
+use super::WebhookDelivery;
+use crate::error::Result;
+
+pub struct DeliveryLog<'a> {
+    db: &'a Connection,
+}
+
+impl<'a> DeliveryLog<'a> {
+    /// Attempts kept; older ones are deleted as new ones are logged
+    pub const KEEP: i64 = 1000;
+
+    pub fn new(db: &'a Connection) -> Self {
+        Self { db }
+    }
+
+    pub fn record(&self, delivery: &WebhookDelivery) -> Result<()> {
+        self.db.execute(
+            "INSERT INTO webhook_deliveries
+             (notification_id, url, attempt, timestamp, status, error, duration_ms, delivered)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                delivery.notification_id,
+                delivery.url,
+                delivery.attempt,
+                delivery.timestamp,
+                delivery.status,
+                delivery.error,
+                delivery.duration_ms as i64,
+                delivery.delivered as i32
+            ],
+        )?;
+        self.db.execute(
+            "DELETE FROM webhook_deliveries WHERE id <= (SELECT MAX(id) FROM webhook_deliveries) - ?1",
+            params![Self::KEEP],
+        )?;
+        Ok(())
+    }
+
+    /// The latest `limit` attempts, newest first
+    pub fn recent(&self, limit: usize) -> Result<Vec<WebhookDelivery>> {
+        let mut stmt = self.db.prepare_cached(
+            "SELECT notification_id, url, attempt, timestamp, status, error, duration_ms, delivered
+             FROM webhook_deliveries
+             ORDER BY id DESC
+             LIMIT ?1",
+        )?;
+        let deliveries = stmt
+            .query_map(params![limit as i64], |row| {
+                Ok(WebhookDelivery {
+                    notification_id: row.get(0)?,
+                    url: row.get(1)?,
+                    attempt: row.get(2)?,
+                    timestamp: row.get(3)?,
+                    status: row.get(4)?,
+                    error: row.get(5)?,
+                    duration_ms: row.get::<_, i64>(6)? as u64,
+                    delivered: row.get::<_, i32>(7)? != 0,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(deliveries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_is_capped() {
+        let db = Connection::open_in_memory().unwrap();
+        crate::storage::db::create_schema(&db).unwrap();
+        let log = DeliveryLog::new(&db);
+        let delivery = |attempt: u32| WebhookDelivery {
+            notification_id: "alert-1".to_string(),
+            url: "https://chat.example.com/hook".to_string(),
+            attempt,
+            timestamp: 1_714_579_200 + attempt as i64,
+            status: (attempt > 1).then_some(500),
+            error: (attempt == 1).then(|| "connection refused".to_string()),
+            duration_ms: 12,
+            delivered: false,
+        };
+
+        log.record(&delivery(1)).unwrap();
+        log.record(&delivery(2)).unwrap();
+        assert_eq!(log.recent(10).unwrap(), [delivery(2), delivery(1)]);
+        assert_eq!(log.recent(1).unwrap(), [delivery(2)]);
+
+        for attempt in 3..=DeliveryLog::KEEP as u32 + 5 {
+            log.record(&delivery(attempt)).unwrap();
+        }
+        let kept = log.recent(2 * DeliveryLog::KEEP as usize).unwrap();
+        assert_eq!(kept.len(), DeliveryLog::KEEP as usize);
+        assert_eq!(kept.last().unwrap().attempt, 6);
+    }
+}
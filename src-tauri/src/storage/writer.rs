@@ -0,0 +1,432 @@
+// Write-behind persistence for energy readings
+//
+// Samples are queued on a bounded channel and written by a dedicated thread
+// that owns its own SQLite connection, batching inserts in one transaction
+// per window. `submit` never waits: when the queue is full the op is
+// dropped and counted, so the monitoring loop cannot stall on the disk.
+// A batch that fails to commit, e.g. on a busy database or a full disk,
+// stays queued and is retried with backoff together with what arrives
+// meanwhile.
+// The database runs in WAL mode, so the read connection in `db` keeps
+// serving stats while a batch commits.
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::{Result, VeraError};
+
+pub const QUEUE_CAPACITY: usize = 1024;
+const MAX_BATCH: usize = 256;
+/// How long the writer keeps collecting after the first queued op
+const BATCH_WINDOW: Duration = Duration::from_millis(500);
+/// Queue depth above which the writer is considered to be falling behind
+const BACKPRESSURE_THRESHOLD: usize = QUEUE_CAPACITY / 2;
+/// Wait before retrying a failed batch, doubling up to `MAX_RETRY_DELAY`
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WriteOp {
+    Reading {
+        timestamp: i64,
+        device_type: String,
+        power_watts: f64,
+        kwh_increment: f64,
+    },
+    Gap {
+        start: i64,
+        end: i64,
+        reason: String,
+    },
+}
+
+enum Message {
+    Op(WriteOp),
+    Flush(mpsc::Sender<Result<()>>),
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriterStatus {
+    pub queue_depth: usize,
+    pub capacity: usize,
+    pub backpressure: bool,
+    pub written: u64,
+    pub dropped: u64,
+    pub batches: u64,
+    pub last_batch_ms: Option<f64>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Default)]
+struct Counters {
+    queue_depth: AtomicUsize,
+    written: AtomicU64,
+    dropped: AtomicU64,
+    batches: AtomicU64,
+    last_batch_us: AtomicU64,
+    last_error: Mutex<Option<String>>,
+}
+
+#[derive(Clone)]
+pub struct ReadingWriter {
+    tx: SyncSender<Message>,
+    counters: Arc<Counters>,
+}
+
+impl ReadingWriter {
+    /// Open a dedicated connection to `path` and start the writer thread
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        Self::start(conn)
+    }
+
+    pub fn start(conn: Connection) -> Result<Self> {
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.busy_timeout(Duration::from_secs(5))?;
+
+        let (tx, rx) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let counters = Arc::new(Counters::default());
+        let thread_counters = Arc::clone(&counters);
+
+        std::thread::Builder::new()
+            .name("vera-writer".to_string())
+            .spawn(move || run(conn, rx, thread_counters))
+            .map_err(|e| VeraError::Internal(format!("Failed to start writer thread: {}", e)))?;
+
+        Ok(Self { tx, counters })
+    }
+
+    /// Queue an op without blocking
+    pub fn submit(&self, op: WriteOp) -> Result<()> {
+        // Counted before sending so the writer never decrements below zero.
+        // The depth includes a failed batch awaiting its retry.
+        if self.counters.queue_depth.fetch_add(1, Ordering::Relaxed) >= QUEUE_CAPACITY {
+            self.counters.queue_depth.fetch_sub(1, Ordering::Relaxed);
+            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+            return Err(VeraError::Backpressure(QUEUE_CAPACITY));
+        }
+
+        match self.tx.try_send(Message::Op(op)) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                self.counters.queue_depth.fetch_sub(1, Ordering::Relaxed);
+                self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                Err(VeraError::Backpressure(QUEUE_CAPACITY))
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                self.counters.queue_depth.fetch_sub(1, Ordering::Relaxed);
+                Err(VeraError::Internal("writer thread has stopped".to_string()))
+            }
+        }
+    }
+
+    pub fn queue_reading(&self, device_type: &str, power_watts: f64, kwh_increment: f64) -> Result<()> {
+        self.submit(WriteOp::Reading {
+            timestamp: chrono::Utc::now().timestamp(),
+            device_type: device_type.to_string(),
+            power_watts,
+            kwh_increment,
+        })
+    }
+
+    pub fn queue_gap(&self, start: i64, end: i64, reason: &str) -> Result<()> {
+        self.submit(WriteOp::Gap {
+            start,
+            end,
+            reason: reason.to_string(),
+        })
+    }
+
+    /// Commit everything queued so far and wait for it (shutdown, tests).
+    /// Fails when the batch did not commit: it stays queued for a retry, or
+    /// is dropped once every handle is gone.
+    pub async fn flush(&self) -> Result<()> {
+        let (done_tx, done_rx) = mpsc::channel();
+        let tx = self.tx.clone();
+
+        tokio::task::spawn_blocking(move || {
+            tx.send(Message::Flush(done_tx))
+                .map_err(|_| VeraError::Internal("writer thread has stopped".to_string()))?;
+            done_rx
+                .recv()
+                .map_err(|_| VeraError::Internal("writer thread has stopped".to_string()))?
+        })
+        .await
+        .map_err(|e| VeraError::Internal(e.to_string()))?
+    }
+
+    pub fn status(&self) -> WriterStatus {
+        let c = &self.counters;
+        let queue_depth = c.queue_depth.load(Ordering::Relaxed);
+        let last_batch_us = c.last_batch_us.load(Ordering::Relaxed);
+
+        WriterStatus {
+            queue_depth,
+            capacity: QUEUE_CAPACITY,
+            backpressure: queue_depth >= BACKPRESSURE_THRESHOLD,
+            written: c.written.load(Ordering::Relaxed),
+            dropped: c.dropped.load(Ordering::Relaxed),
+            batches: c.batches.load(Ordering::Relaxed),
+            last_batch_ms: (last_batch_us > 0).then(|| last_batch_us as f64 / 1000.0),
+            last_error: c.last_error.lock().ok().and_then(|e| e.clone()),
+        }
+    }
+}
+
+fn run(mut conn: Connection, rx: Receiver<Message>, counters: Arc<Counters>) {
+    // Ops not committed yet, oldest first; a failed batch stays here
+    let mut batch = Vec::with_capacity(MAX_BATCH);
+    // Consecutive failures to commit `batch`
+    let mut failures = 0;
+    let mut open = true;
+
+    while open {
+        let mut flush_waiters = Vec::new();
+        let mut next = None;
+        if batch.is_empty() {
+            // Ends when every ReadingWriter handle has been dropped
+            match rx.recv() {
+                Ok(message) => next = Some(message),
+                Err(_) => break,
+            }
+        }
+        let retrying = failures > 0;
+        let deadline = Instant::now() + if retrying { retry_delay(failures) } else { BATCH_WINDOW };
+
+        loop {
+            match next.take() {
+                Some(Message::Op(op)) => batch.push(op),
+                Some(Message::Flush(done)) => {
+                    flush_waiters.push(done);
+                    break;
+                }
+                None => {}
+            }
+            // While backing off, keep collecting until the retry is due
+            if !retrying && batch.len() >= MAX_BATCH {
+                break;
+            }
+            match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(message) => next = Some(message),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    open = false;
+                    break;
+                }
+            }
+        }
+
+        // What flush waiters are told: an error when the batch did not commit
+        let mut outcome = Ok(());
+        if !batch.is_empty() {
+            let started = Instant::now();
+            let count = batch.len();
+
+            match write_batch(&mut conn, &batch) {
+                Ok(()) => {
+                    counters.queue_depth.fetch_sub(count, Ordering::Relaxed);
+                    counters.written.fetch_add(count as u64, Ordering::Relaxed);
+                    counters.batches.fetch_add(1, Ordering::Relaxed);
+                    counters
+                        .last_batch_us
+                        .store(started.elapsed().as_micros().max(1) as u64, Ordering::Relaxed);
+                    batch.clear();
+                    failures = 0;
+                }
+                Err(e) => {
+                    outcome = Err(format!("{} readings not written: {}", count, e));
+                    failures += 1;
+                    if let Ok(mut last_error) = counters.last_error.lock() {
+                        *last_error = Some(e.to_string());
+                    }
+                    if open {
+                        eprintln!("Failed to write {} readings, retrying: {}", count, e);
+                    } else {
+                        // Shutting down: nothing is left to retry them
+                        eprintln!("Failed to write {} readings: {}", count, e);
+                        counters.queue_depth.fetch_sub(count, Ordering::Relaxed);
+                        counters.dropped.fetch_add(count as u64, Ordering::Relaxed);
+                        batch.clear();
+                    }
+                }
+            }
+        }
+
+        for done in flush_waiters {
+            done.send(outcome.clone().map_err(VeraError::Internal)).ok();
+        }
+    }
+}
+
+fn retry_delay(failures: u32) -> Duration {
+    (RETRY_DELAY * 2u32.pow(failures.saturating_sub(1).min(16))).min(MAX_RETRY_DELAY)
+}
+
+fn write_batch(conn: &mut Connection, batch: &[WriteOp]) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    {
+        let mut insert_reading = tx.prepare_cached(
+            "INSERT INTO energy_readings (timestamp, device_type, power_watts, kwh_increment)
+             VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        let mut insert_gap = tx.prepare_cached(
+            "INSERT INTO energy_gaps (start_timestamp, end_timestamp, reason) VALUES (?1, ?2, ?3)",
+        )?;
+
+        for op in batch {
+            match op {
+                WriteOp::Reading { timestamp, device_type, power_watts, kwh_increment } => {
+                    insert_reading.execute(params![timestamp, device_type, power_watts, kwh_increment])?;
+                }
+                WriteOp::Gap { start, end, reason } => {
+                    insert_gap.execute(params![start, end, reason])?;
+                }
+            }
+        }
+    }
+    tx.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(path: &Path) {
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE energy_readings (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp INTEGER NOT NULL,
+                device_type TEXT NOT NULL,
+                power_watts REAL NOT NULL,
+                kwh_increment REAL NOT NULL
+            );
+            CREATE TABLE energy_gaps (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                start_timestamp INTEGER NOT NULL,
+                end_timestamp INTEGER NOT NULL,
+                reason TEXT NOT NULL
+            );",
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_batches_are_committed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vera.db");
+        setup(&path);
+
+        let writer = ReadingWriter::open(&path).unwrap();
+        for i in 0..10 {
+            writer.queue_reading("pc", 50.0 + i as f64, 0.001).unwrap();
+        }
+        writer.queue_gap(100, 200, "suspend").unwrap();
+        writer.flush().await.unwrap();
+
+        let status = writer.status();
+        assert_eq!(status.written, 11);
+        assert_eq!(status.queue_depth, 0);
+        assert_eq!(status.dropped, 0);
+        assert!(status.batches >= 1);
+
+        // A separate connection sees the committed rows
+        let reader = Connection::open(&path).unwrap();
+        let (count, kwh): (i64, f64) = reader
+            .query_row("SELECT COUNT(*), SUM(kwh_increment) FROM energy_readings", [], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(count, 10);
+        assert!((kwh - 0.01).abs() < 1e-9);
+        let mode: String = reader.query_row("PRAGMA journal_mode", [], |r| r.get(0)).unwrap();
+        assert_eq!(mode, "wal");
+    }
+
+    #[tokio::test]
+    async fn test_failed_batch_is_retried() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vera.db");
+        let writer = ReadingWriter::open(&path).unwrap();
+
+        // No tables yet, so the first batch fails
+        for _ in 0..5 {
+            writer.queue_reading("pc", 40.0, 0.001).unwrap();
+        }
+        let started = Instant::now();
+        while writer.status().last_error.is_none() {
+            assert!(started.elapsed() < Duration::from_secs(5), "the batch never failed");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(writer.status().queue_depth, 5);
+
+        // Readings arriving meanwhile join the retried batch
+        setup(&path);
+        for _ in 0..3 {
+            writer.queue_reading("pc", 40.0, 0.001).unwrap();
+        }
+        writer.flush().await.unwrap();
+
+        let status = writer.status();
+        assert_eq!((status.written, status.dropped, status.queue_depth), (8, 0, 0));
+        let reader = Connection::open(&path).unwrap();
+        let count: i64 = reader.query_row("SELECT COUNT(*) FROM energy_readings", [], |r| r.get(0)).unwrap();
+        assert_eq!(count, 8);
+        assert_eq!(retry_delay(1), RETRY_DELAY);
+        assert_eq!(retry_delay(3), RETRY_DELAY * 4);
+        assert_eq!(retry_delay(30), MAX_RETRY_DELAY);
+    }
+
+    #[tokio::test]
+    async fn test_flush_fails_while_the_batch_is_not_committed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vera.db");
+        let writer = ReadingWriter::open(&path).unwrap();
+
+        // No tables, so every attempt fails and the readings stay queued
+        for _ in 0..5 {
+            writer.queue_reading("pc", 40.0, 0.001).unwrap();
+        }
+        assert!(writer.flush().await.is_err());
+        let status = writer.status();
+        assert_eq!((status.written, status.dropped, status.queue_depth), (0, 0, 5));
+
+        setup(&path);
+        writer.flush().await.unwrap();
+        assert_eq!(writer.status().written, 5);
+    }
+
+    #[tokio::test]
+    async fn test_submit_does_not_block_when_database_is_locked() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vera.db");
+        setup(&path);
+        let writer = ReadingWriter::open(&path).unwrap();
+
+        // Another connection holds the write lock, so the writer is stuck
+        let blocker = Connection::open(&path).unwrap();
+        blocker.execute_batch("BEGIN IMMEDIATE").unwrap();
+
+        let started = Instant::now();
+        let mut rejected = 0;
+        for _ in 0..(QUEUE_CAPACITY + 300) {
+            if writer.queue_reading("pc", 40.0, 0.0001).is_err() {
+                rejected += 1;
+            }
+        }
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert!(rejected > 0);
+        assert!(writer.status().backpressure || writer.status().dropped > 0);
+
+        blocker.execute_batch("COMMIT").unwrap();
+        writer.flush().await.unwrap();
+        let status = writer.status();
+        assert_eq!(status.written + status.dropped, (QUEUE_CAPACITY + 300) as u64);
+    }
+}
@@ -0,0 +1,362 @@
+// Per-core CPU metrics: cpufreq scaling, cpuidle residency and hybrid topology
+//
+// Usage comes from sysinfo; everything else is read from
+// /sys/devices/system/cpu/cpuN. Intel hybrid parts list their P-cores and
+// E-cores in /sys/devices/cpu_core/cpus and /sys/devices/cpu_atom/cpus,
+// ARM big.LITTLE exposes a relative `cpu_capacity` per core instead.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use super::reading::Reading;
+use super::sysfs;
+
+const DEVICES_ROOT: &str = "/sys/devices";
+
+/// Platform baseline (board, RAM, storage, fans) outside the CPU package
+pub const PLATFORM_BASE_WATTS: f32 = 22.0;
+/// Package idle/leakage power, saved proportionally to deep C-state residency
+pub const PACKAGE_IDLE_WATTS: f32 = 8.0;
+/// Dynamic CPU power with every core busy at maximum frequency
+pub const MAX_CPU_DYNAMIC_WATTS: f32 = 150.0;
+/// Relative dynamic power of an efficiency core compared to a performance core
+const EFFICIENCY_CORE_WEIGHT: f32 = 0.45;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoreType {
+    Performance,
+    Efficiency,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdleStateResidency {
+    pub name: String,
+    pub residency: Reading<f32>, // percentage of the last interval
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoreMetrics {
+    pub id: usize,
+    pub core_type: CoreType,
+    pub usage: Reading<f32>,
+    pub frequency: Reading<u64>,     // MHz
+    pub min_frequency: Reading<u64>, // MHz, scaling limit
+    pub max_frequency: Reading<u64>, // MHz, scaling limit
+    pub governor: Option<String>,
+    pub idle_states: Vec<IdleStateResidency>,
+}
+
+impl CoreMetrics {
+    /// Share of the last interval spent in idle states deeper than C1
+    pub fn deep_idle_fraction(&self) -> Option<f32> {
+        let deep: Vec<f32> = self
+            .idle_states
+            .iter()
+            .filter(|state| state.name != "POLL" && state.name != "C1" && !state.name.starts_with("C1_"))
+            .filter_map(|state| state.residency.get())
+            .collect();
+
+        if deep.is_empty() {
+            None
+        } else {
+            Some((deep.iter().sum::<f32>() / 100.0).clamp(0.0, 1.0))
+        }
+    }
+}
+
+pub struct CpuMonitor {
+    cpu_root: PathBuf,
+    core_types: Vec<CoreType>,
+    // (cpu, state index) -> (cumulative idle time in µs, when it was read)
+    last_idle: HashMap<(usize, u32), (u64, Instant)>,
+}
+
+impl CpuMonitor {
+    pub fn new() -> Self {
+        Self::with_root(DEVICES_ROOT)
+    }
+
+    /// Create a monitor reading from a custom `/sys/devices` directory
+    pub fn with_root(root: impl AsRef<Path>) -> Self {
+        let root = root.as_ref();
+        let cpu_root = root.join("system/cpu");
+        let core_types = detect_core_types(root, &cpu_root);
+
+        Self {
+            cpu_root,
+            core_types,
+            last_idle: HashMap::new(),
+        }
+    }
+
+    pub fn is_hybrid(&self) -> bool {
+        self.core_types.contains(&CoreType::Performance) && self.core_types.contains(&CoreType::Efficiency)
+    }
+
+    /// Build per-core metrics from sysinfo's (usage %, frequency MHz) per logical CPU
+    pub fn read(&mut self, cores: &[(f32, u64)]) -> Vec<CoreMetrics> {
+        self.read_at(cores, Instant::now())
+    }
+
+    fn read_at(&mut self, cores: &[(f32, u64)], now: Instant) -> Vec<CoreMetrics> {
+        cores
+            .iter()
+            .enumerate()
+            .map(|(id, &(usage, sysinfo_freq))| {
+                let cpu_dir = self.cpu_root.join(format!("cpu{}", id));
+                let cpufreq = cpu_dir.join("cpufreq");
+                // cpufreq reports kHz
+                let khz_to_mhz = |name: &str| sysfs::read_u64(&cpufreq.join(name)).map(|khz| khz / 1000);
+
+                let frequency = khz_to_mhz("scaling_cur_freq")
+                    .or(Some(sysinfo_freq).filter(|f| *f > 0));
+
+                CoreMetrics {
+                    id,
+                    core_type: self.core_types.get(id).copied().unwrap_or(CoreType::Unknown),
+                    usage: Reading::measured(usage),
+                    frequency: Reading::from_option(frequency),
+                    min_frequency: Reading::from_option(khz_to_mhz("scaling_min_freq")),
+                    max_frequency: Reading::from_option(khz_to_mhz("scaling_max_freq")),
+                    governor: sysfs::read_string(&cpufreq.join("scaling_governor")),
+                    idle_states: self.read_idle_states(id, &cpu_dir.join("cpuidle"), now),
+                }
+            })
+            .collect()
+    }
+
+    fn read_idle_states(&mut self, cpu: usize, cpuidle: &Path, now: Instant) -> Vec<IdleStateResidency> {
+        sysfs::numbered_entries(cpuidle, "state")
+            .into_iter()
+            .filter_map(|(index, state_dir)| {
+                let name = sysfs::read_string(&state_dir.join("name"))?;
+                let time_us = sysfs::read_u64(&state_dir.join("time"))?;
+
+                let previous = self.last_idle.insert((cpu, index), (time_us, now));
+                let residency = match previous {
+                    Some((prev_us, prev_time)) if time_us >= prev_us => {
+                        let elapsed_us = now.duration_since(prev_time).as_secs_f64() * 1_000_000.0;
+                        if elapsed_us > 0.0 {
+                            let percent = ((time_us - prev_us) as f64 / elapsed_us * 100.0).clamp(0.0, 100.0);
+                            Reading::measured(percent as f32)
+                        } else {
+                            Reading::Unavailable
+                        }
+                    }
+                    _ => Reading::Unavailable,
+                };
+
+                Some(IdleStateResidency { name, residency })
+            })
+            .collect()
+    }
+}
+
+impl Default for CpuMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Estimate CPU package plus platform power from per-core load and frequency.
+///
+/// Dynamic power scales with load and roughly with the square of the
+/// frequency ratio (voltage follows frequency under DVFS); E-cores are
+/// weighted lower, and deep C-state residency removes part of the idle power.
+/// Returns `None` when there is no per-core data to model.
+pub fn estimate_cpu_power(cores: &[CoreMetrics]) -> Option<f32> {
+    if cores.is_empty() {
+        return None;
+    }
+
+    let mut weight_total = 0.0f32;
+    let mut dynamic = 0.0f32;
+    let mut awake = 0.0f32;
+
+    for core in cores {
+        let weight = match core.core_type {
+            CoreType::Efficiency => EFFICIENCY_CORE_WEIGHT,
+            _ => 1.0,
+        };
+        let load = core.usage.get().unwrap_or(0.0) / 100.0;
+        let freq_ratio = match (core.frequency.get(), core.max_frequency.get()) {
+            (Some(cur), Some(max)) if max > 0 => (cur as f32 / max as f32).min(1.0),
+            _ => 1.0,
+        };
+
+        weight_total += weight;
+        dynamic += weight * load * freq_ratio * freq_ratio;
+        awake += weight * (1.0 - core.deep_idle_fraction().unwrap_or(0.0));
+    }
+
+    let cpu_dynamic = MAX_CPU_DYNAMIC_WATTS * dynamic / weight_total;
+    let package_idle = PACKAGE_IDLE_WATTS * awake / weight_total;
+
+    Some(PLATFORM_BASE_WATTS + package_idle + cpu_dynamic)
+}
+
+fn detect_core_types(devices_root: &Path, cpu_root: &Path) -> Vec<CoreType> {
+    let count = sysfs::numbered_entries(cpu_root, "cpu").last().map(|(i, _)| *i as usize + 1).unwrap_or(0);
+    let mut types = vec![CoreType::Unknown; count];
+
+    // Intel hybrid: PMU devices list which logical CPUs belong to each core type
+    let p_cores = sysfs::read_string(&devices_root.join("cpu_core/cpus")).map(|l| parse_cpu_list(&l));
+    let e_cores = sysfs::read_string(&devices_root.join("cpu_atom/cpus")).map(|l| parse_cpu_list(&l));
+
+    if p_cores.is_some() || e_cores.is_some() {
+        for (list, core_type) in [(p_cores, CoreType::Performance), (e_cores, CoreType::Efficiency)] {
+            for cpu in list.unwrap_or_default() {
+                if let Some(slot) = types.get_mut(cpu) {
+                    *slot = core_type;
+                }
+            }
+        }
+        return types;
+    }
+
+    // ARM big.LITTLE: cores below the highest capacity are efficiency cores
+    let capacities: Vec<Option<u64>> = (0..count)
+        .map(|cpu| sysfs::read_u64(&cpu_root.join(format!("cpu{}/cpu_capacity", cpu))))
+        .collect();
+    let max_capacity = capacities.iter().flatten().max().copied();
+    let min_capacity = capacities.iter().flatten().min().copied();
+
+    if let (Some(max), Some(min)) = (max_capacity, min_capacity) {
+        if max != min {
+            for (slot, capacity) in types.iter_mut().zip(&capacities) {
+                if let Some(capacity) = capacity {
+                    *slot = if *capacity == max { CoreType::Performance } else { CoreType::Efficiency };
+                }
+            }
+        }
+    }
+
+    types
+}
+
+/// Parse a kernel cpu list such as `0-7,16,18-19`
+fn parse_cpu_list(list: &str) -> Vec<usize> {
+    list.split(',')
+        .filter_map(|part| {
+            let part = part.trim();
+            match part.split_once('-') {
+                Some((start, end)) => Some((start.parse().ok()?..=end.parse().ok()?).collect::<Vec<_>>()),
+                None => Some(vec![part.parse().ok()?]),
+            }
+        })
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Duration;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_parse_cpu_list() {
+        assert_eq!(parse_cpu_list("0-3,8,10-11\n"), vec![0, 1, 2, 3, 8, 10, 11]);
+        assert_eq!(parse_cpu_list("5"), vec![5]);
+    }
+
+    #[test]
+    fn test_cpufreq_and_hybrid_topology() {
+        let root = tempfile::tempdir().unwrap();
+        for cpu in 0..4 {
+            let cpufreq = root.path().join(format!("system/cpu/cpu{}/cpufreq", cpu));
+            write(&cpufreq.join("scaling_cur_freq"), "2400000\n");
+            write(&cpufreq.join("scaling_min_freq"), "800000\n");
+            write(&cpufreq.join("scaling_max_freq"), "4800000\n");
+            write(&cpufreq.join("scaling_governor"), "powersave\n");
+        }
+        write(&root.path().join("cpu_core/cpus"), "0-1\n");
+        write(&root.path().join("cpu_atom/cpus"), "2-3\n");
+
+        let mut monitor = CpuMonitor::with_root(root.path());
+        assert!(monitor.is_hybrid());
+
+        let cores = monitor.read(&[(50.0, 0), (10.0, 0), (0.0, 0), (100.0, 0)]);
+        assert_eq!(cores.len(), 4);
+        assert_eq!(cores[0].core_type, CoreType::Performance);
+        assert_eq!(cores[3].core_type, CoreType::Efficiency);
+        assert_eq!(cores[0].frequency.get(), Some(2400));
+        assert_eq!(cores[0].min_frequency.get(), Some(800));
+        assert_eq!(cores[0].max_frequency.get(), Some(4800));
+        assert_eq!(cores[1].governor.as_deref(), Some("powersave"));
+        assert_eq!(cores[3].usage.get(), Some(100.0));
+    }
+
+    #[test]
+    fn test_idle_residency() {
+        let root = tempfile::tempdir().unwrap();
+        let cpuidle = root.path().join("system/cpu/cpu0/cpuidle");
+        write(&cpuidle.join("state0/name"), "POLL\n");
+        write(&cpuidle.join("state0/time"), "0\n");
+        write(&cpuidle.join("state2/name"), "C6\n");
+        write(&cpuidle.join("state2/time"), "1000000\n");
+
+        let mut monitor = CpuMonitor::with_root(root.path());
+        let start = Instant::now();
+
+        let first = monitor.read_at(&[(5.0, 1200)], start);
+        assert_eq!(first[0].idle_states.len(), 2);
+        assert!(!first[0].idle_states[1].residency.is_available());
+        // No cpufreq directory: fall back to sysinfo's frequency
+        assert_eq!(first[0].frequency.get(), Some(1200));
+        assert_eq!(first[0].core_type, CoreType::Unknown);
+
+        write(&cpuidle.join("state2/time"), "1800000\n");
+        let second = monitor.read_at(&[(5.0, 1200)], start + Duration::from_secs(1));
+        let c6 = second[0].idle_states[1].residency.get().unwrap();
+        assert!((c6 - 80.0).abs() < 0.01);
+        assert!((second[0].deep_idle_fraction().unwrap() - 0.8).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_power_model() {
+        let core = |usage: f32, freq: u64, core_type: CoreType| CoreMetrics {
+            id: 0,
+            core_type,
+            usage: Reading::measured(usage),
+            frequency: Reading::measured(freq),
+            min_frequency: Reading::Unavailable,
+            max_frequency: Reading::measured(4000),
+            governor: None,
+            idle_states: Vec::new(),
+        };
+
+        assert_eq!(estimate_cpu_power(&[]), None);
+
+        let idle = estimate_cpu_power(&[core(0.0, 800, CoreType::Performance)]).unwrap();
+        assert!((idle - (PLATFORM_BASE_WATTS + PACKAGE_IDLE_WATTS)).abs() < 0.01);
+
+        let full = estimate_cpu_power(&[core(100.0, 4000, CoreType::Performance)]).unwrap();
+        assert!((full - (PLATFORM_BASE_WATTS + PACKAGE_IDLE_WATTS + MAX_CPU_DYNAMIC_WATTS)).abs() < 0.01);
+
+        // Same load at half frequency costs a quarter of the dynamic power
+        let half = estimate_cpu_power(&[core(100.0, 2000, CoreType::Performance)]).unwrap();
+        assert!((half - (PLATFORM_BASE_WATTS + PACKAGE_IDLE_WATTS + MAX_CPU_DYNAMIC_WATTS / 4.0)).abs() < 0.01);
+
+        // A busy E-core next to an idle P-core draws less than the reverse
+        let e_busy = estimate_cpu_power(&[
+            core(0.0, 4000, CoreType::Performance),
+            core(100.0, 4000, CoreType::Efficiency),
+        ])
+        .unwrap();
+        let p_busy = estimate_cpu_power(&[
+            core(100.0, 4000, CoreType::Performance),
+            core(0.0, 4000, CoreType::Efficiency),
+        ])
+        .unwrap();
+        assert!(e_busy < p_busy);
+    }
+}
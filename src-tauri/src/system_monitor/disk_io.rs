@@ -0,0 +1,62 @@
+// Cumulative disk I/O counters from /proc/diskstats
+use std::path::Path;
+
+const DISKSTATS: &str = "/proc/diskstats";
+const SYS_BLOCK: &str = "/sys/block";
+const SECTOR_SIZE: u64 = 512; // diskstats always counts 512-byte sectors
+
+/// Total bytes (read, written) across physical disks since boot
+pub fn read_totals() -> Option<(u64, u64)> {
+    let content = std::fs::read_to_string(DISKSTATS).ok()?;
+    Some(parse_diskstats(&content, |name| {
+        Path::new(SYS_BLOCK).join(name).exists()
+    }))
+}
+
+/// Sum whole-disk counters; partitions and virtual devices stacked on top of
+/// a disk (loop, dm, md, zram) are skipped so nothing is counted twice
+pub fn parse_diskstats(content: &str, is_whole_disk: impl Fn(&str) -> bool) -> (u64, u64) {
+    let mut read = 0u64;
+    let mut written = 0u64;
+
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 10 {
+            continue;
+        }
+
+        let name = fields[2];
+        let is_virtual = ["loop", "ram", "zram", "dm-", "md", "sr"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix));
+        if is_virtual || !is_whole_disk(name) {
+            continue;
+        }
+
+        let sectors_read: u64 = fields[5].parse().unwrap_or(0);
+        let sectors_written: u64 = fields[9].parse().unwrap_or(0);
+        read += sectors_read * SECTOR_SIZE;
+        written += sectors_written * SECTOR_SIZE;
+    }
+
+    (read, written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_diskstats() {
+        let content = "\
+ 259       0 nvme0n1 1000 0 2000 0 500 0 4000 0 0 0 0
+ 259       1 nvme0n1p1 900 0 1800 0 400 0 3000 0 0 0 0
+   7       0 loop0 10 0 80 0 0 0 0 0 0 0 0
+   8       0 sda 10 0 20 0 5 0 40 0 0 0 0
+";
+        let (read, written) = parse_diskstats(content, |name| name == "nvme0n1" || name == "sda");
+
+        assert_eq!(read, (2000 + 20) * 512);
+        assert_eq!(written, (4000 + 40) * 512);
+    }
+}
@@ -0,0 +1,331 @@
+// GPU detection and metrics via Linux DRM/hwmon sysfs
+//
+// amdgpu exposes busy percentage, VRAM and power directly under
+// /sys/class/drm/cardN/device, i915 and xe only expose frequency and
+// RC6 (idle) residency, so utilisation is derived from the residency delta.
+// Anything the driver does not report is returned as unavailable.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use super::reading::Reading;
+use super::sysfs;
+
+const DRM_ROOT: &str = "/sys/class/drm";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GpuVendor {
+    Amd,
+    Intel,
+    Nvidia,
+    Unknown,
+}
+
+impl GpuVendor {
+    /// Map a PCI vendor id (e.g. `0x1002`) to a vendor
+    fn from_pci_id(id: &str) -> Self {
+        match id.trim().to_lowercase().as_str() {
+            "0x1002" | "0x1022" => GpuVendor::Amd,
+            "0x8086" => GpuVendor::Intel,
+            "0x10de" => GpuVendor::Nvidia,
+            _ => GpuVendor::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GpuMetrics {
+    pub id: String,
+    pub vendor: GpuVendor,
+    pub driver: Option<String>,
+    pub pci_slot: Option<String>,
+    pub usage: Reading<f32>,          // percentage
+    pub temperature: Reading<f32>,    // °C
+    pub memory: Reading<u64>,         // bytes of VRAM in use
+    pub memory_total: Reading<u64>,   // bytes
+    pub power_draw: Reading<f32>,     // Watts
+    pub frequency: Reading<u64>,      // MHz
+    pub rc6_residency: Reading<f32>,  // percentage of the last interval spent idle
+}
+
+#[derive(Debug, Clone)]
+struct GpuCard {
+    id: String,
+    card_path: PathBuf,
+    device_path: PathBuf,
+    vendor: GpuVendor,
+    driver: Option<String>,
+    pci_slot: Option<String>,
+    hwmon: Option<PathBuf>,
+}
+
+pub struct GpuMonitor {
+    root: PathBuf,
+    cards: Vec<GpuCard>,
+    // card id -> (residency in ms, when it was read)
+    last_rc6: HashMap<String, (u64, Instant)>,
+}
+
+impl GpuMonitor {
+    pub fn new() -> Self {
+        Self::with_root(DRM_ROOT)
+    }
+
+    /// Create a monitor reading from a custom DRM class directory
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        let cards = detect_cards(&root);
+
+        Self {
+            root,
+            cards,
+            last_rc6: HashMap::new(),
+        }
+    }
+
+    /// Re-scan the DRM directory (e.g. after an eGPU hotplug)
+    pub fn rescan(&mut self) {
+        self.cards = detect_cards(&self.root);
+        self.last_rc6.clear();
+    }
+
+    pub fn gpu_count(&self) -> usize {
+        self.cards.len()
+    }
+
+    /// Read the current metrics of every detected GPU
+    pub fn read(&mut self) -> Vec<GpuMetrics> {
+        self.read_at(Instant::now())
+    }
+
+    fn read_at(&mut self, now: Instant) -> Vec<GpuMetrics> {
+        let mut result = Vec::with_capacity(self.cards.len());
+
+        for card in &self.cards {
+            let mut metrics = GpuMetrics {
+                id: card.id.clone(),
+                vendor: card.vendor,
+                driver: card.driver.clone(),
+                pci_slot: card.pci_slot.clone(),
+                usage: Reading::Unavailable,
+                temperature: Reading::Unavailable,
+                memory: Reading::Unavailable,
+                memory_total: Reading::Unavailable,
+                power_draw: Reading::Unavailable,
+                frequency: Reading::Unavailable,
+                rc6_residency: Reading::Unavailable,
+            };
+
+            match card.driver.as_deref() {
+                Some("amdgpu") | Some("radeon") => read_amd(card, &mut metrics),
+                Some("i915") | Some("xe") => {
+                    read_intel(card, &mut metrics);
+
+                    if let Some(residency_ms) = read_rc6_residency(card) {
+                        let previous = self.last_rc6.insert(card.id.clone(), (residency_ms, now));
+                        if let Some((prev_ms, prev_time)) = previous {
+                            let elapsed_ms = now.duration_since(prev_time).as_secs_f64() * 1000.0;
+                            if elapsed_ms > 0.0 && residency_ms >= prev_ms {
+                                let idle = ((residency_ms - prev_ms) as f64 / elapsed_ms * 100.0)
+                                    .clamp(0.0, 100.0) as f32;
+                                metrics.rc6_residency = Reading::measured(idle);
+                                metrics.usage = Reading::estimated(100.0 - idle, "rc6-residency");
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            // Common hwmon attributes (amdgpu, nouveau, xe on recent kernels)
+            if let Some(hwmon) = &card.hwmon {
+                read_hwmon(hwmon, &mut metrics);
+            }
+
+            result.push(metrics);
+        }
+
+        result
+    }
+}
+
+impl Default for GpuMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Enumerate `cardN` entries, skipping connectors like `card0-DP-1`
+fn detect_cards(root: &Path) -> Vec<GpuCard> {
+    sysfs::numbered_entries(root, "card")
+        .into_iter()
+        .filter_map(|(index, card_path)| {
+            let device_path = card_path.join("device");
+            if !device_path.exists() {
+                return None;
+            }
+
+            let vendor = sysfs::read_string(&device_path.join("vendor"))
+                .map(|id| GpuVendor::from_pci_id(&id))
+                .unwrap_or(GpuVendor::Unknown);
+
+            let uevent = device_path.join("uevent");
+            let driver = sysfs::read_uevent(&uevent, "DRIVER");
+            let pci_slot = sysfs::read_uevent(&uevent, "PCI_SLOT_NAME");
+
+            let hwmon = sysfs::numbered_entries(&device_path.join("hwmon"), "hwmon")
+                .into_iter()
+                .next()
+                .map(|(_, path)| path);
+
+            Some(GpuCard {
+                id: format!("card{}", index),
+                card_path,
+                device_path,
+                vendor,
+                driver,
+                pci_slot,
+                hwmon,
+            })
+        })
+        .collect()
+}
+
+fn read_amd(card: &GpuCard, metrics: &mut GpuMetrics) {
+    let device = &card.device_path;
+
+    metrics.usage = sysfs::read_u64(&device.join("gpu_busy_percent")).map(|v| v as f32).into();
+    metrics.memory = sysfs::read_u64(&device.join("mem_info_vram_used")).into();
+    metrics.memory_total = sysfs::read_u64(&device.join("mem_info_vram_total")).into();
+}
+
+fn read_intel(card: &GpuCard, metrics: &mut GpuMetrics) {
+    // i915 publishes the frequency on the card node, xe per tile/gt
+    metrics.frequency = sysfs::read_u64(&card.card_path.join("gt_cur_freq_mhz"))
+        .or_else(|| sysfs::read_u64(&card.device_path.join("tile0/gt0/freq0/cur_freq")))
+        .into();
+}
+
+fn read_rc6_residency(card: &GpuCard) -> Option<u64> {
+    [
+        card.card_path.join("gt/gt0/rc6_residency_ms"),
+        card.card_path.join("power/rc6_residency_ms"),
+        card.device_path.join("tile0/gt0/gtidle/idle_residency_ms"),
+    ]
+    .iter()
+    .find_map(|path| sysfs::read_u64(path))
+}
+
+fn read_hwmon(hwmon: &Path, metrics: &mut GpuMetrics) {
+    if !metrics.temperature.is_available() {
+        // millidegrees Celsius
+        metrics.temperature = sysfs::read_i64(&hwmon.join("temp1_input"))
+            .map(|v| v as f32 / 1000.0)
+            .into();
+    }
+
+    if !metrics.power_draw.is_available() {
+        // microwatts
+        metrics.power_draw = sysfs::read_u64(&hwmon.join("power1_average"))
+            .or_else(|| sysfs::read_u64(&hwmon.join("power1_input")))
+            .map(|v| v as f32 / 1_000_000.0)
+            .into();
+    }
+
+    if !metrics.frequency.is_available() {
+        // Hz
+        metrics.frequency = sysfs::read_u64(&hwmon.join("freq1_input"))
+            .map(|v| v / 1_000_000)
+            .into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Duration;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_amdgpu_metrics() {
+        let root = tempfile::tempdir().unwrap();
+        let device = root.path().join("card0/device");
+        write(&device.join("vendor"), "0x1002\n");
+        write(&device.join("uevent"), "DRIVER=amdgpu\nPCI_SLOT_NAME=0000:03:00.0\n");
+        write(&device.join("gpu_busy_percent"), "37\n");
+        write(&device.join("mem_info_vram_used"), "1073741824\n");
+        write(&device.join("mem_info_vram_total"), "8589934592\n");
+        write(&device.join("hwmon/hwmon3/temp1_input"), "54000\n");
+        write(&device.join("hwmon/hwmon3/power1_average"), "23000000\n");
+        // Connector entries must not be reported as GPUs
+        fs::create_dir_all(root.path().join("card0-DP-1")).unwrap();
+
+        let mut monitor = GpuMonitor::with_root(root.path());
+        let gpus = monitor.read();
+
+        assert_eq!(gpus.len(), 1);
+        let gpu = &gpus[0];
+        assert_eq!(gpu.vendor, GpuVendor::Amd);
+        assert_eq!(gpu.driver.as_deref(), Some("amdgpu"));
+        assert_eq!(gpu.pci_slot.as_deref(), Some("0000:03:00.0"));
+        assert_eq!(gpu.usage.get(), Some(37.0));
+        assert_eq!(gpu.memory.get(), Some(1073741824));
+        assert_eq!(gpu.memory_total.get(), Some(8589934592));
+        assert_eq!(gpu.temperature.get(), Some(54.0));
+        assert_eq!(gpu.power_draw.get(), Some(23.0));
+    }
+
+    #[test]
+    fn test_i915_usage_from_rc6() {
+        let root = tempfile::tempdir().unwrap();
+        let card = root.path().join("card1");
+        write(&card.join("device/vendor"), "0x8086\n");
+        write(&card.join("device/uevent"), "DRIVER=i915\n");
+        write(&card.join("gt_cur_freq_mhz"), "1100\n");
+        write(&card.join("gt/gt0/rc6_residency_ms"), "1000\n");
+
+        let mut monitor = GpuMonitor::with_root(root.path());
+        let start = Instant::now();
+
+        // First sample has no previous residency to compare against
+        let first = monitor.read_at(start);
+        assert_eq!(first[0].frequency.get(), Some(1100));
+        assert_eq!(first[0].usage.get(), None);
+        assert_eq!(first[0].memory_total.get(), None);
+
+        write(&card.join("gt/gt0/rc6_residency_ms"), "1750\n");
+        let second = monitor.read_at(start + Duration::from_secs(1));
+        let usage = second[0].usage.get().unwrap();
+        assert!((usage - 25.0).abs() < 0.01);
+        assert!(!second[0].usage.is_measured());
+        assert!((second[0].rc6_residency.get().unwrap() - 75.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_unknown_driver_reports_none() {
+        let root = tempfile::tempdir().unwrap();
+        let device = root.path().join("card0/device");
+        write(&device.join("vendor"), "0x10de\n");
+        write(&device.join("uevent"), "DRIVER=nvidia\n");
+
+        let gpus = GpuMonitor::with_root(root.path()).read();
+
+        assert_eq!(gpus[0].vendor, GpuVendor::Nvidia);
+        assert_eq!(gpus[0].usage.get(), None);
+        assert_eq!(gpus[0].temperature.get(), None);
+        assert_eq!(gpus[0].power_draw.get(), None);
+    }
+
+    #[test]
+    fn test_missing_root() {
+        let monitor = GpuMonitor::with_root("/nonexistent/drm");
+        assert_eq!(monitor.gpu_count(), 0);
+    }
+}
@@ -1,60 +1,92 @@
+pub mod cpu;
+pub mod gpu;
+pub mod reading;
+pub mod sensors;
+mod disk_io;
+pub mod power_supply;
+mod sysfs;
+
 use serde::{Deserialize, Serialize};
-use sysinfo::{System, Disk, Process, Pid, CpuRefreshKind, RefreshKind};
+use sysinfo::{
+    CpuRefreshKind, Disks, MemoryRefreshKind, Pid, ProcessRefreshKind, RefreshKind, System,
+};
 use std::collections::HashMap;
+use std::time::Instant;
+
+pub use cpu::{CoreMetrics, CpuMonitor};
+pub use gpu::{GpuMetrics, GpuMonitor};
+pub use reading::{Reading, METRICS_SCHEMA_VERSION};
+pub use sensors::{Sensor, SensorInventory, SensorKind};
+
+/// Method reported for values derived from the CPU-usage power model
+const POWER_MODEL_METHOD: &str = "cpu-usage-model";
+/// Method reported when per-core load, frequency and C-states feed the model
+const PER_CORE_POWER_MODEL_METHOD: &str = "per-core-dvfs-model";
+
+/// Ticks between process list refreshes; only the count is needed per tick
+const PROCESS_LIST_REFRESH_TICKS: u64 = 10;
+/// Ticks between disk rediscovery, mounts rarely change
+const DISK_LIST_REFRESH_TICKS: u64 = 60;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemMetrics {
+    pub schema_version: u32,
     pub cpu: CpuMetrics,
-    pub gpu: GpuMetrics,
+    pub gpus: Vec<GpuMetrics>,
     pub ram: RamMetrics,
     pub disk: DiskMetrics,
     pub power: PowerMetrics,
     pub system: SystemInfo,
+    pub sensors: Vec<Sensor>,
+    pub overhead: SelfOverhead,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CpuMetrics {
-    pub usage: f32,
-    pub temperature: f32,
-    pub cores: usize,
-    pub frequency: u64,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct GpuMetrics {
-    pub usage: f32,
-    pub temperature: f32,
-    pub memory: u64,
-    pub memory_total: u64,
+    pub usage: Reading<f32>,
+    pub temperature: Reading<f32>,
+    pub cores: Reading<usize>,
+    pub frequency: Reading<u64>,
+    pub hybrid: bool,
+    pub per_core: Vec<CoreMetrics>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RamMetrics {
-    pub used: u64,
-    pub total: u64,
-    pub percentage: f32,
+    pub used: Reading<u64>,
+    pub total: Reading<u64>,
+    pub percentage: Reading<f32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiskMetrics {
-    pub read_speed: f32,
-    pub write_speed: f32,
-    pub usage: f32,
+    pub read_speed: Reading<f32>,   // MB/s
+    pub write_speed: Reading<f32>,  // MB/s
+    pub usage: Reading<f32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PowerMetrics {
-    pub source: String,
-    pub battery_percentage: f32,
-    pub power_draw: f32,
-    pub estimated_time: u64,
+    pub source: Reading<String>,
+    pub battery_percentage: Reading<f32>,
+    pub power_draw: Reading<f32>,
+    pub estimated_time: Reading<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemInfo {
-    pub uptime: u64,
-    pub processes: usize,
-    pub temperature: f32,
+    pub uptime: Reading<u64>,
+    pub processes: Reading<usize>,
+    pub temperature: Reading<f32>,
+}
+
+/// Resources used by VERA itself, so the monitor's own load is visible
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelfOverhead {
+    pub cpu_usage: Reading<f32>,        // % of one core
+    pub memory: Reading<u64>,           // bytes
+    pub estimated_power: Reading<f32>,  // Watts
+    pub sample_duration_ms: Reading<f32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -68,146 +100,213 @@ pub struct ActiveApplication {
 
 pub struct SystemMonitor {
     system: System,
-    last_disk_read: u64,
-    last_disk_write: u64,
+    disks: Disks,
+    own_pid: Option<Pid>,
+    tick: u64,
+    cpu_monitor: CpuMonitor,
+    gpu_monitor: GpuMonitor,
+    sensor_inventory: SensorInventory,
+    // (bytes read, bytes written, when) at the previous sample
+    last_disk_io: Option<(u64, u64, Instant)>,
     app_start_times: HashMap<Pid, u64>,
 }
 
 impl SystemMonitor {
     pub fn new() -> Self {
-        let mut system = System::new_all();
-        system.refresh_all();
+        // Only what get_metrics reads; processes are listed without details
+        let system = System::new_with_specifics(
+            RefreshKind::new()
+                .with_cpu(CpuRefreshKind::everything())
+                .with_memory(MemoryRefreshKind::new().with_ram())
+                .with_processes(ProcessRefreshKind::new()),
+        );
         
         Self {
             system,
-            last_disk_read: 0,
-            last_disk_write: 0,
+            disks: Disks::new_with_refreshed_list(),
+            own_pid: sysinfo::get_current_pid().ok(),
+            tick: 0,
+            cpu_monitor: CpuMonitor::new(),
+            gpu_monitor: GpuMonitor::new(),
+            sensor_inventory: SensorInventory::new(),
+            last_disk_io: None,
             app_start_times: HashMap::new(),
         }
     }
 
     pub fn get_metrics(&mut self) -> SystemMetrics {
-        // Refresh system information
-        self.system.refresh_all();
+        let started = Instant::now();
+        self.refresh();
+        let sensors = self.sensor_inventory.read();
 
-        let cpu_metrics = self.get_cpu_metrics();
-        let gpu_metrics = self.get_gpu_metrics();
+        let cpu_metrics = self.get_cpu_metrics(&sensors);
+        let gpus = self.gpu_monitor.read();
         let ram_metrics = self.get_ram_metrics();
         let disk_metrics = self.get_disk_metrics();
-        let power_metrics = self.get_power_metrics();
-        let system_info = self.get_system_info();
+        let power_metrics = self.get_power_metrics(&cpu_metrics.per_core);
+        let system_info = self.get_system_info(&sensors);
+        let overhead = self.get_self_overhead(started);
 
         SystemMetrics {
+            schema_version: METRICS_SCHEMA_VERSION,
             cpu: cpu_metrics,
-            gpu: gpu_metrics,
+            gpus,
             ram: ram_metrics,
             disk: disk_metrics,
             power: power_metrics,
             system: system_info,
+            sensors,
+            overhead,
         }
     }
 
-    fn get_cpu_metrics(&self) -> CpuMetrics {
-        let global_cpu = self.system.global_cpu_info();
-        let cpu_usage = global_cpu.cpu_usage();
-        let cores = self.system.cpus().len();
-        
-        // Get CPU frequency (in MHz)
-        let frequency = global_cpu.frequency();
-        
-        // CPU temperature (may not be available on all systems)
-        let temperature = self.get_cpu_temperature();
-
-        CpuMetrics {
-            usage: cpu_usage,
-            temperature,
-            cores,
-            frequency,
-        }
+    /// Whole-system power draw, as in `get_metrics`, refreshing only the CPU
+    pub fn power_draw(&mut self) -> Reading<f32> {
+        self.refresh_cpu();
+        let cores = self.read_cores();
+        self.get_power_metrics(&cores).power_draw
     }
 
-    fn get_cpu_temperature(&self) -> f32 {
-        // Try to get CPU temperature using sysinfo components
-        // This may not work on all platforms
-        #[cfg(target_os = "linux")]
-        {
-            use sysinfo::Components;
-            let components = Components::new_with_refreshed_list();
-            for component in &components {
-                if component.label().contains("CPU") || component.label().contains("Core") {
-                    return component.temperature();
-                }
-            }
+    /// Targeted refresh of what a metrics tick reads, instead of `refresh_all`
+    fn refresh(&mut self) {
+        self.refresh_cpu();
+        self.system.refresh_memory_specifics(MemoryRefreshKind::new().with_ram());
+
+        if self.tick.is_multiple_of(PROCESS_LIST_REFRESH_TICKS) {
+            self.system.refresh_processes_specifics(ProcessRefreshKind::new());
         }
-        
-        #[cfg(target_os = "windows")]
-        {
-            // Windows temperature monitoring requires WMI or other methods
-            // For now, return estimated temperature based on CPU usage
-            let cpu_usage = self.system.global_cpu_info().cpu_usage();
-            return 40.0 + (cpu_usage * 0.5); // Estimate: 40°C base + usage factor
+        if let Some(pid) = self.own_pid {
+            self.system
+                .refresh_process_specifics(pid, ProcessRefreshKind::new().with_cpu().with_memory());
         }
-        
-        #[cfg(target_os = "macos")]
-        {
-            // macOS temperature monitoring is complex
-            // Return estimated temperature for now
-            let cpu_usage = self.system.global_cpu_info().cpu_usage();
-            return 45.0 + (cpu_usage * 0.4);
+
+        if self.tick.is_multiple_of(DISK_LIST_REFRESH_TICKS) {
+            self.disks.refresh_list();
+        } else {
+            self.disks.refresh();
         }
-        
-        50.0 // Default fallback
+
+        self.tick += 1;
     }
 
-    fn get_gpu_metrics(&self) -> GpuMetrics {
-        // GPU monitoring is platform-specific and complex
-        // For now, we'll return estimated values
-        // In production, you'd use NVML for NVIDIA, AMD libraries, etc.
-        
-        let cpu_usage = self.system.global_cpu_info().cpu_usage();
-        let estimated_gpu_usage = (cpu_usage * 0.7).min(100.0);
+    fn refresh_cpu(&mut self) {
+        // On Linux the per-core frequency comes from cpufreq via CpuMonitor
+        let cpu_refresh = if cfg!(target_os = "linux") {
+            CpuRefreshKind::new().with_cpu_usage()
+        } else {
+            CpuRefreshKind::new().with_cpu_usage().with_frequency()
+        };
+        self.system.refresh_cpu_specifics(cpu_refresh);
+    }
+
+    fn get_self_overhead(&self, started: Instant) -> SelfOverhead {
+        let process = self.own_pid.and_then(|pid| self.system.process(pid));
+        let cpu_usage = process.map(|p| p.cpu_usage());
+        let cores = self.system.cpus().len().max(1) as f32;
+
+        // Our share of total CPU capacity times the model's full-load dynamic power
+        let estimated_power = match cpu_usage {
+            Some(usage) => Reading::estimated(
+                usage / (100.0 * cores) * cpu::MAX_CPU_DYNAMIC_WATTS,
+                "process-cpu-share",
+            ),
+            None => Reading::Unavailable,
+        };
+
+        SelfOverhead {
+            cpu_usage: Reading::from_option(cpu_usage),
+            memory: Reading::from_option(process.map(|p| p.memory())),
+            estimated_power,
+            sample_duration_ms: Reading::measured(started.elapsed().as_secs_f32() * 1000.0),
+        }
+    }
+
+    fn get_cpu_metrics(&mut self, sensors: &[Sensor]) -> CpuMetrics {
+        let global_cpu = self.system.global_cpu_info();
+        let cores = self.system.cpus().len();
         
-        GpuMetrics {
-            usage: estimated_gpu_usage,
-            temperature: 50.0 + (estimated_gpu_usage * 0.4),
-            memory: 2048 * 1024 * 1024, // 2 GB in bytes
-            memory_total: 8192 * 1024 * 1024, // 8 GB in bytes
+        let usage = global_cpu.cpu_usage();
+        let per_core = self.read_cores();
+
+        // Average of per-core frequencies (MHz), sysinfo's global value otherwise;
+        // 0 means the platform did not report it
+        let core_freqs: Vec<u64> = per_core.iter().filter_map(|c| c.frequency.get()).collect();
+        let frequency = if core_freqs.is_empty() {
+            self.system.global_cpu_info().frequency()
+        } else {
+            core_freqs.iter().sum::<u64>() / core_freqs.len() as u64
+        };
+
+        CpuMetrics {
+            usage: Reading::measured(usage),
+            temperature: get_cpu_temperature(sensors),
+            cores: Reading::from_option(Some(cores).filter(|c| *c > 0)),
+            frequency: Reading::from_option(Some(frequency).filter(|f| *f > 0)),
+            hybrid: self.cpu_monitor.is_hybrid(),
+            per_core,
         }
     }
 
+    fn read_cores(&mut self) -> Vec<CoreMetrics> {
+        let per_core_input: Vec<(f32, u64)> = self
+            .system
+            .cpus()
+            .iter()
+            .map(|cpu| (cpu.cpu_usage(), cpu.frequency()))
+            .collect();
+        self.cpu_monitor.read(&per_core_input)
+    }
+
     fn get_ram_metrics(&self) -> RamMetrics {
         let total = self.system.total_memory();
         let used = self.system.used_memory();
-        let percentage = (used as f32 / total as f32) * 100.0;
+
+        if total == 0 {
+            return RamMetrics {
+                used: Reading::Unavailable,
+                total: Reading::Unavailable,
+                percentage: Reading::Unavailable,
+            };
+        }
 
         RamMetrics {
-            used,
-            total,
-            percentage,
+            used: Reading::measured(used),
+            total: Reading::measured(total),
+            percentage: Reading::measured((used as f32 / total as f32) * 100.0),
         }
     }
 
     fn get_disk_metrics(&mut self) -> DiskMetrics {
-        let disks = sysinfo::Disks::new_with_refreshed_list();
-        
         let mut total_space = 0u64;
         let mut available_space = 0u64;
         
-        for disk in &disks {
+        for disk in &self.disks {
             total_space += disk.total_space();
             available_space += disk.available_space();
         }
         
         let usage = if total_space > 0 {
-            ((total_space - available_space) as f32 / total_space as f32) * 100.0
+            Reading::measured(((total_space - available_space) as f32 / total_space as f32) * 100.0)
         } else {
-            0.0
+            Reading::Unavailable
         };
 
-        // Disk speed monitoring would require tracking I/O over time
-        // For now, return simulated values based on system activity
-        let read_speed = (self.system.global_cpu_info().cpu_usage() * 5.0).min(500.0);
-        let write_speed = (self.system.global_cpu_info().cpu_usage() * 3.0).min(300.0);
+        // Throughput needs two samples of the cumulative counters
+        let mut read_speed = Reading::Unavailable;
+        let mut write_speed = Reading::Unavailable;
+
+        if let Some((read, written)) = disk_io::read_totals() {
+            let now = Instant::now();
+            if let Some((last_read, last_written, last_time)) = self.last_disk_io {
+                let elapsed = now.duration_since(last_time).as_secs_f32();
+                if elapsed > 0.0 {
+                    let mb_per_sec = |delta: u64| delta as f32 / elapsed / (1024.0 * 1024.0);
+                    read_speed = Reading::measured(mb_per_sec(read.saturating_sub(last_read)));
+                    write_speed = Reading::measured(mb_per_sec(written.saturating_sub(last_written)));
+                }
+            }
+            self.last_disk_io = Some((read, written, now));
+        }
 
         DiskMetrics {
             read_speed,
@@ -216,68 +315,73 @@ impl SystemMonitor {
         }
     }
 
-    fn get_power_metrics(&self) -> PowerMetrics {
-        // Power monitoring is platform-specific
-        // This is a simplified implementation
-        
-        #[cfg(target_os = "windows")]
-        {
-            // On Windows, you'd use Windows API to get battery info
-            // For now, return AC power as default
-            return PowerMetrics {
-                source: "ac".to_string(),
-                battery_percentage: 100.0,
-                power_draw: self.estimate_power_draw(),
-                estimated_time: 0,
-            };
-        }
-        
-        #[cfg(target_os = "linux")]
-        {
-            // On Linux, check /sys/class/power_supply/
-            // For now, return estimated values
-            return PowerMetrics {
-                source: "ac".to_string(),
-                battery_percentage: 100.0,
-                power_draw: self.estimate_power_draw(),
-                estimated_time: 0,
-            };
-        }
-        
-        #[cfg(target_os = "macos")]
-        {
-            // On macOS, you'd use IOKit
-            return PowerMetrics {
-                source: "ac".to_string(),
-                battery_percentage: 100.0,
-                power_draw: self.estimate_power_draw(),
-                estimated_time: 0,
-            };
+    fn get_power_metrics(&self, cores: &[CoreMetrics]) -> PowerMetrics {
+        // Battery/AC state is only read on Linux for now; other platforms
+        // report the source as unavailable and rely on the power model
+        let supply = power_supply::read();
+
+        let on_battery = supply.battery_present
+            && (supply.discharging || supply.ac_online == Some(false));
+        let source = if on_battery {
+            Reading::measured("battery".to_string())
+        } else if supply.ac_online == Some(true) || supply.battery_present {
+            Reading::measured("ac".to_string())
+        } else {
+            Reading::Unavailable
+        };
+
+        // While discharging, the battery drain is the whole system draw
+        let power_draw = match supply.battery_power {
+            Some(watts) if supply.discharging && watts > 0.0 => Reading::measured(watts),
+            _ => self.estimate_power_draw(cores),
+        };
+
+        let estimated_time = match supply.time_to_empty {
+            Some(seconds) => Reading::estimated(seconds, "energy-now/power-now"),
+            None => Reading::Unavailable,
+        };
+
+        PowerMetrics {
+            source,
+            battery_percentage: Reading::from_option(supply.battery_percentage),
+            power_draw,
+            estimated_time,
         }
     }
 
-    fn estimate_power_draw(&self) -> f32 {
+    fn estimate_power_draw(&self, cores: &[CoreMetrics]) -> Reading<f32> {
+        if let Some(watts) = cpu::estimate_cpu_power(cores) {
+            return Reading::estimated(watts, PER_CORE_POWER_MODEL_METHOD);
+        }
+
         let cpu_usage = self.system.global_cpu_info().cpu_usage();
         // Estimate: Base 30W + CPU usage factor
-        30.0 + (cpu_usage * 1.5)
+        Reading::estimated(30.0 + (cpu_usage * 1.5), POWER_MODEL_METHOD)
     }
 
-    fn get_system_info(&self) -> SystemInfo {
-        let uptime = System::uptime();
-        let processes = self.system.processes().len();
-        
-        // Average temperature estimate
-        let cpu_temp = self.get_cpu_temperature();
-        let temperature = cpu_temp * 0.9; // System temp slightly lower than CPU
+    fn get_system_info(&self, sensors: &[Sensor]) -> SystemInfo {
+        // Hottest motherboard/ACPI zone
+        let temperature = sensors
+            .iter()
+            .filter(|s| s.kind == SensorKind::Temperature && s.component() == "motherboard")
+            .filter_map(|s| s.value)
+            .fold(None, |max: Option<f64>, v| Some(max.map_or(v, |m| m.max(v))))
+            .map(|v| v as f32);
 
         SystemInfo {
-            uptime,
-            processes,
-            temperature,
+            uptime: Reading::measured(System::uptime()),
+            processes: Reading::measured(self.system.processes().len()),
+            temperature: Reading::from_option(temperature),
         }
     }
 
     pub fn get_active_applications(&mut self) -> Vec<ActiveApplication> {
+        self.get_top_applications(10)
+    }
+
+    /// The `limit` busiest processes by CPU and memory. CPU usage is measured
+    /// since the previous call, so the first one reports 0%.
+    pub fn get_top_applications(&mut self, limit: usize) -> Vec<ActiveApplication> {
         self.system.refresh_processes();
         
         let mut apps: Vec<ActiveApplication> = Vec::new();
@@ -294,8 +398,11 @@ impl SystemMonitor {
             b_score.partial_cmp(&a_score).unwrap()
         });
 
-        for (pid, process) in processes.iter().take(10) {
-            let name = process.name().to_string_lossy().to_string();
+        for (pid, process) in processes.iter() {
+            if apps.len() >= limit {
+                break;
+            }
+            let name = process.name().to_string();
             
             // Skip system processes
             if name.is_empty() || name.starts_with('[') {
@@ -325,6 +432,21 @@ impl SystemMonitor {
     }
 }
 
+/// CPU package temperature from the hwmon inventory: prefer the package/control
+/// sensor (coretemp "Package id 0", k10temp "Tctl") over single cores
+fn get_cpu_temperature(sensors: &[Sensor]) -> Reading<f32> {
+    let cpu_temps: Vec<&Sensor> = sensors
+        .iter()
+        .filter(|s| s.kind == SensorKind::Temperature && s.component() == "cpu")
+        .collect();
+    let package = cpu_temps
+        .iter()
+        .find(|s| s.label.starts_with("Package") || s.label == "Tctl" || s.label == "Tdie")
+        .or_else(|| cpu_temps.first());
+
+    Reading::from_option(package.and_then(|s| s.value).map(|v| v as f32))
+}
+
 fn categorize_application(name: &str) -> String {
     let name_lower = name.to_lowercase();
     
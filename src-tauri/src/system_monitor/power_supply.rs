@@ -0,0 +1,134 @@
+// Battery and AC adapter state from /sys/class/power_supply
+use std::path::Path;
+
+use super::sysfs;
+
+const POWER_SUPPLY_ROOT: &str = "/sys/class/power_supply";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PowerSupplyState {
+    pub ac_online: Option<bool>,
+    pub battery_present: bool,
+    pub discharging: bool,
+    pub battery_percentage: Option<f32>,
+    pub battery_power: Option<f32>,    // Watts drawn from/into the battery
+    pub time_to_empty: Option<u64>,    // seconds
+}
+
+pub fn read() -> PowerSupplyState {
+    read_from(Path::new(POWER_SUPPLY_ROOT))
+}
+
+pub fn read_from(root: &Path) -> PowerSupplyState {
+    let mut state = PowerSupplyState::default();
+
+    let entries = match std::fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(_) => return state,
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let supply = entry.path();
+        let kind = sysfs::read_string(&supply.join("type")).unwrap_or_default();
+
+        match kind.as_str() {
+            "Mains" | "USB" => {
+                if let Some(online) = sysfs::read_u64(&supply.join("online")) {
+                    state.ac_online = Some(state.ac_online.unwrap_or(false) || online == 1);
+                }
+            }
+            "Battery" => {
+                // Peripheral batteries (mice, headsets) report scope=Device
+                if sysfs::read_string(&supply.join("scope")).as_deref() == Some("Device") {
+                    continue;
+                }
+                if sysfs::read_u64(&supply.join("present")) == Some(0) {
+                    continue;
+                }
+                read_battery(&supply, &mut state);
+            }
+            _ => {}
+        }
+    }
+
+    state
+}
+
+fn read_battery(battery: &Path, state: &mut PowerSupplyState) {
+    state.battery_present = true;
+    state.discharging = sysfs::read_string(&battery.join("status")).as_deref() == Some("Discharging");
+    state.battery_percentage = sysfs::read_u64(&battery.join("capacity")).map(|v| v as f32);
+
+    // power_now in µW, or current_now (µA) * voltage_now (µV) on some firmwares
+    let power_watts = sysfs::read_u64(&battery.join("power_now"))
+        .map(|uw| uw as f64 / 1_000_000.0)
+        .or_else(|| {
+            let current = sysfs::read_u64(&battery.join("current_now"))?;
+            let voltage = sysfs::read_u64(&battery.join("voltage_now"))?;
+            Some(current as f64 * voltage as f64 / 1e12)
+        });
+    state.battery_power = power_watts.map(|w| w as f32);
+
+    if state.discharging {
+        // energy_now in µWh, or charge_now in µAh
+        let remaining_wh = sysfs::read_u64(&battery.join("energy_now"))
+            .map(|uwh| uwh as f64 / 1_000_000.0)
+            .or_else(|| {
+                let charge = sysfs::read_u64(&battery.join("charge_now"))?;
+                let voltage = sysfs::read_u64(&battery.join("voltage_now"))?;
+                Some(charge as f64 * voltage as f64 / 1e12)
+            });
+
+        if let (Some(wh), Some(watts)) = (remaining_wh, power_watts) {
+            if watts > 0.0 {
+                state.time_to_empty = Some((wh / watts * 3600.0) as u64);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_discharging_laptop() {
+        let root = tempfile::tempdir().unwrap();
+        write(&root.path().join("AC/type"), "Mains\n");
+        write(&root.path().join("AC/online"), "0\n");
+        let bat = root.path().join("BAT0");
+        write(&bat.join("type"), "Battery\n");
+        write(&bat.join("status"), "Discharging\n");
+        write(&bat.join("capacity"), "64\n");
+        write(&bat.join("power_now"), "12500000\n");
+        write(&bat.join("energy_now"), "25000000\n");
+
+        let state = read_from(root.path());
+        assert_eq!(state.ac_online, Some(false));
+        assert!(state.battery_present);
+        assert!(state.discharging);
+        assert_eq!(state.battery_percentage, Some(64.0));
+        assert_eq!(state.battery_power, Some(12.5));
+        assert_eq!(state.time_to_empty, Some(7200));
+    }
+
+    #[test]
+    fn test_desktop_ignores_peripheral_battery() {
+        let root = tempfile::tempdir().unwrap();
+        let mouse = root.path().join("hidpp_battery_0");
+        write(&mouse.join("type"), "Battery\n");
+        write(&mouse.join("scope"), "Device\n");
+        write(&mouse.join("capacity"), "80\n");
+
+        let state = read_from(root.path());
+        assert!(!state.battery_present);
+        assert_eq!(state.battery_percentage, None);
+        assert_eq!(state.ac_online, None);
+    }
+}
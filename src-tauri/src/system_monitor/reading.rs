@@ -0,0 +1,114 @@
+// Value provenance for system metrics
+//
+// A reading is either measured by hardware/OS counters, estimated by a
+// named model, or unavailable. The dashboard uses the status to show
+// "n/a" or an "estimated" badge instead of a plausible-looking number.
+use serde::{Deserialize, Serialize};
+
+/// Version of the `SystemMetrics` JSON contract sent to the frontend.
+/// v1: plain numbers with invented fallbacks; v2: every value is a `Reading`.
+pub const METRICS_SCHEMA_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum Reading<T> {
+    Measured { value: T },
+    Estimated { value: T, method: String },
+    #[default]
+    Unavailable,
+}
+
+impl<T> Reading<T> {
+    pub fn measured(value: T) -> Self {
+        Reading::Measured { value }
+    }
+
+    pub fn estimated(value: T, method: &str) -> Self {
+        Reading::Estimated {
+            value,
+            method: method.to_string(),
+        }
+    }
+
+    /// `Some` becomes a measured value, `None` unavailable
+    pub fn from_option(value: Option<T>) -> Self {
+        match value {
+            Some(value) => Reading::Measured { value },
+            None => Reading::Unavailable,
+        }
+    }
+
+    pub fn value(&self) -> Option<&T> {
+        match self {
+            Reading::Measured { value } | Reading::Estimated { value, .. } => Some(value),
+            Reading::Unavailable => None,
+        }
+    }
+
+    pub fn is_measured(&self) -> bool {
+        matches!(self, Reading::Measured { .. })
+    }
+
+    pub fn is_available(&self) -> bool {
+        !matches!(self, Reading::Unavailable)
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Reading<U> {
+        match self {
+            Reading::Measured { value } => Reading::Measured { value: f(value) },
+            Reading::Estimated { value, method } => Reading::Estimated {
+                value: f(value),
+                method,
+            },
+            Reading::Unavailable => Reading::Unavailable,
+        }
+    }
+
+    /// Keep this reading if available, otherwise use `other`
+    pub fn or_else(self, other: impl FnOnce() -> Reading<T>) -> Self {
+        match self {
+            Reading::Unavailable => other(),
+            available => available,
+        }
+    }
+}
+
+impl<T: Copy> Reading<T> {
+    pub fn get(&self) -> Option<T> {
+        self.value().copied()
+    }
+}
+
+impl<T> From<Option<T>> for Reading<T> {
+    fn from(value: Option<T>) -> Self {
+        Reading::from_option(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialization_shape() {
+        let measured = serde_json::to_value(Reading::measured(42.5)).unwrap();
+        assert_eq!(measured, serde_json::json!({"status": "measured", "value": 42.5}));
+
+        let estimated = serde_json::to_value(Reading::estimated(60.0, "cpu-usage-model")).unwrap();
+        assert_eq!(
+            estimated,
+            serde_json::json!({"status": "estimated", "value": 60.0, "method": "cpu-usage-model"})
+        );
+
+        let unavailable = serde_json::to_value(Reading::<f32>::Unavailable).unwrap();
+        assert_eq!(unavailable, serde_json::json!({"status": "unavailable"}));
+    }
+
+    #[test]
+    fn test_map_keeps_provenance() {
+        let reading = Reading::estimated(2.0, "model").map(|v| v * 1000.0);
+        assert_eq!(reading, Reading::estimated(2000.0, "model"));
+        assert_eq!(Reading::<f32>::Unavailable.map(|v| v * 2.0).get(), None);
+        assert_eq!(Reading::from_option(Some(3)).get(), Some(3));
+    }
+}
@@ -0,0 +1,328 @@
+// Hardware sensor inventory from Linux hwmon
+//
+// Every chip under /sys/class/hwmon (k10temp, coretemp, nvme, acpitz,
+// amdgpu, nct6775, ...) is enumerated once; values are re-read on demand.
+// hwmonN indices change between boots, so sensor ids are built from the
+// chip name and the underlying device instead.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::sysfs;
+use crate::utils;
+
+const HWMON_ROOT: &str = "/sys/class/hwmon";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SensorKind {
+    Temperature,
+    Fan,
+    Voltage,
+    Power,
+    Current,
+}
+
+impl SensorKind {
+    fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix {
+            "temp" => Some(SensorKind::Temperature),
+            "fan" => Some(SensorKind::Fan),
+            "in" => Some(SensorKind::Voltage),
+            "power" => Some(SensorKind::Power),
+            "curr" => Some(SensorKind::Current),
+            _ => None,
+        }
+    }
+
+    /// Divisor from the raw hwmon value to `unit()`
+    fn scale(&self) -> f64 {
+        match self {
+            SensorKind::Temperature => 1000.0, // millidegrees
+            SensorKind::Fan => 1.0,            // RPM
+            SensorKind::Voltage => 1000.0,     // millivolts
+            SensorKind::Power => 1_000_000.0,  // microwatts
+            SensorKind::Current => 1000.0,     // milliamperes
+        }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            SensorKind::Temperature => "°C",
+            SensorKind::Fan => "RPM",
+            SensorKind::Voltage => "V",
+            SensorKind::Power => "W",
+            SensorKind::Current => "A",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sensor {
+    pub id: String,
+    pub chip: String,
+    pub kind: SensorKind,
+    pub label: String,
+    pub value: Option<f64>,
+    pub max: Option<f64>,
+    pub crit: Option<f64>,
+    pub unit: String,
+}
+
+impl Sensor {
+    /// Hardware component the chip belongs to: cpu, gpu, storage or motherboard
+    pub fn component(&self) -> &'static str {
+        chip_component(&self.chip)
+    }
+
+    /// Whether a temperature reading is within the chip's limits.
+    /// Non-temperature sensors and missing readings are considered safe.
+    pub fn is_safe(&self) -> bool {
+        match (self.kind, self.value) {
+            (SensorKind::Temperature, Some(value)) => utils::is_temperature_safe(
+                self.component(),
+                value as f32,
+                self.crit.map(|crit| crit as f32),
+            ),
+            _ => true,
+        }
+    }
+}
+
+fn chip_component(chip: &str) -> &'static str {
+    match chip {
+        "k10temp" | "coretemp" | "zenpower" | "cpu_thermal" | "cpu" => "cpu",
+        "amdgpu" | "radeon" | "nouveau" | "i915" | "xe" => "gpu",
+        "nvme" | "drivetemp" => "storage",
+        _ => "motherboard",
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SensorSource {
+    id: String,
+    chip: String,
+    kind: SensorKind,
+    label: String,
+    input: PathBuf,
+    max: Option<PathBuf>,
+    crit: Option<PathBuf>,
+}
+
+pub struct SensorInventory {
+    sources: Vec<SensorSource>,
+}
+
+impl SensorInventory {
+    pub fn new() -> Self {
+        Self::with_root(HWMON_ROOT)
+    }
+
+    /// Build the inventory from a custom hwmon class directory
+    pub fn with_root(root: impl AsRef<Path>) -> Self {
+        Self {
+            sources: discover(root.as_ref()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Read current values of every sensor in the inventory
+    pub fn read(&self) -> Vec<Sensor> {
+        self.sources
+            .iter()
+            .map(|source| {
+                let scale = source.kind.scale();
+                let read = |path: Option<&PathBuf>| {
+                    path.and_then(|p| sysfs::read_i64(p))
+                        .map(|raw| raw as f64 / scale)
+                };
+
+                Sensor {
+                    id: source.id.clone(),
+                    chip: source.chip.clone(),
+                    kind: source.kind,
+                    label: source.label.clone(),
+                    value: read(Some(&source.input)),
+                    max: read(source.max.as_ref()),
+                    crit: read(source.crit.as_ref()),
+                    unit: source.kind.unit().to_string(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Default for SensorInventory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn discover(root: &Path) -> Vec<SensorSource> {
+    let mut sources = Vec::new();
+    let mut seen_chips: HashMap<String, u32> = HashMap::new();
+
+    for (index, hwmon) in sysfs::numbered_entries(root, "hwmon") {
+        let chip = sysfs::read_string(&hwmon.join("name"))
+            .unwrap_or_else(|| format!("hwmon{}", index));
+
+        // Prefer the backing device (e.g. PCI address) for a stable id,
+        // fall back to the chip name plus an occurrence counter
+        let device = fs::canonicalize(hwmon.join("device"))
+            .ok()
+            .and_then(|path| path.file_name().map(|n| n.to_string_lossy().to_string()));
+        let chip_id = match device {
+            Some(device) => format!("{}@{}", chip, device),
+            None => {
+                let count = seen_chips.entry(chip.clone()).or_insert(0);
+                *count += 1;
+                if *count == 1 {
+                    chip.clone()
+                } else {
+                    format!("{}.{}", chip, count)
+                }
+            }
+        };
+
+        let mut attributes: Vec<(SensorKind, String, u32, PathBuf)> = match fs::read_dir(&hwmon) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let name = entry.file_name().to_str()?.to_string();
+                    let (channel, suffix) = name.split_once('_')?;
+                    if suffix != "input" && !(suffix == "average" && channel.starts_with("power")) {
+                        return None;
+                    }
+                    let split = channel.find(|c: char| c.is_ascii_digit())?;
+                    let (prefix, number) = channel.split_at(split);
+                    let kind = SensorKind::from_prefix(prefix)?;
+                    Some((kind, prefix.to_string(), number.parse().ok()?, entry.path()))
+                })
+                .collect(),
+            Err(_) => continue,
+        };
+
+        attributes.sort_by(|a, b| (a.1.as_str(), a.2, &a.3).cmp(&(b.1.as_str(), b.2, &b.3)));
+        // power1_average and power1_input describe the same channel, keep the average
+        attributes.dedup_by(|a, b| a.1 == b.1 && a.2 == b.2);
+
+        for (kind, prefix, number, input) in attributes {
+            let channel = format!("{}{}", prefix, number);
+            let label = sysfs::read_string(&hwmon.join(format!("{}_label", channel)))
+                .unwrap_or_else(|| format!("{} {}", chip, channel));
+            let optional = |suffix: &str| {
+                let path = hwmon.join(format!("{}_{}", channel, suffix));
+                if path.exists() {
+                    Some(path)
+                } else {
+                    None
+                }
+            };
+
+            sources.push(SensorSource {
+                id: format!("{}/{}", chip_id, channel),
+                chip: chip.clone(),
+                kind,
+                label,
+                input,
+                max: optional("max"),
+                crit: optional("crit"),
+            });
+        }
+    }
+
+    sources
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_inventory_units_and_labels() {
+        let root = tempfile::tempdir().unwrap();
+        let k10 = root.path().join("hwmon2");
+        write(&k10.join("name"), "k10temp\n");
+        write(&k10.join("temp1_input"), "61250\n");
+        write(&k10.join("temp1_label"), "Tctl\n");
+        write(&k10.join("temp1_crit"), "95000\n");
+
+        let nct = root.path().join("hwmon3");
+        write(&nct.join("name"), "nct6798\n");
+        write(&nct.join("fan2_input"), "1180\n");
+        write(&nct.join("in0_input"), "1224\n");
+        write(&nct.join("in0_max"), "1744\n");
+
+        let sensors = SensorInventory::with_root(root.path()).read();
+        assert_eq!(sensors.len(), 3);
+
+        let tctl = &sensors[0];
+        assert_eq!(tctl.id, "k10temp/temp1");
+        assert_eq!(tctl.label, "Tctl");
+        assert_eq!(tctl.kind, SensorKind::Temperature);
+        assert_eq!(tctl.value, Some(61.25));
+        assert_eq!(tctl.crit, Some(95.0));
+        assert_eq!(tctl.unit, "°C");
+        assert_eq!(tctl.component(), "cpu");
+
+        let fan = sensors.iter().find(|s| s.kind == SensorKind::Fan).unwrap();
+        assert_eq!(fan.id, "nct6798/fan2");
+        assert_eq!(fan.label, "nct6798 fan2");
+        assert_eq!(fan.value, Some(1180.0));
+
+        let vcore = sensors.iter().find(|s| s.kind == SensorKind::Voltage).unwrap();
+        assert_eq!(vcore.value, Some(1.224));
+        assert_eq!(vcore.max, Some(1.744));
+        assert_eq!(vcore.crit, None);
+    }
+
+    #[test]
+    fn test_duplicate_chips_get_distinct_ids() {
+        let root = tempfile::tempdir().unwrap();
+        for index in 0..2 {
+            let hwmon = root.path().join(format!("hwmon{}", index));
+            write(&hwmon.join("name"), "acpitz\n");
+            write(&hwmon.join("temp1_input"), "27800\n");
+        }
+
+        let sensors = SensorInventory::with_root(root.path()).read();
+        assert_eq!(sensors[0].id, "acpitz/temp1");
+        assert_eq!(sensors[1].id, "acpitz.2/temp1");
+    }
+
+    #[test]
+    fn test_safety_uses_hardware_crit() {
+        let mut sensor = Sensor {
+            id: "nvme/temp1".to_string(),
+            chip: "nvme".to_string(),
+            kind: SensorKind::Temperature,
+            label: "Composite".to_string(),
+            value: Some(62.0),
+            max: Some(81.85),
+            crit: Some(84.85),
+            unit: "°C".to_string(),
+        };
+        // Above the generic storage limit, but below what the drive reports
+        assert!(sensor.is_safe());
+
+        sensor.value = Some(85.0);
+        assert!(!sensor.is_safe());
+
+        sensor.crit = None;
+        sensor.value = Some(62.0);
+        assert!(!sensor.is_safe());
+    }
+}
@@ -0,0 +1,58 @@
+// Small helpers for reading Linux sysfs attributes
+use std::fs;
+use std::path::Path;
+
+/// Read an attribute as a trimmed string, `None` if missing or empty
+pub(crate) fn read_string(path: &Path) -> Option<String> {
+    let value = fs::read_to_string(path).ok()?;
+    let value = value.trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+/// Read an unsigned integer attribute
+pub(crate) fn read_u64(path: &Path) -> Option<u64> {
+    read_string(path)?.parse().ok()
+}
+
+/// Read a signed integer attribute (temperatures can be negative)
+pub(crate) fn read_i64(path: &Path) -> Option<i64> {
+    read_string(path)?.parse().ok()
+}
+
+/// Look up `KEY=value` in a `uevent` file
+pub(crate) fn read_uevent(path: &Path, key: &str) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    content.lines().find_map(|line| {
+        let (k, v) = line.split_once('=')?;
+        if k == key {
+            Some(v.trim().to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// List directory entries whose name starts with `prefix` followed by a number,
+/// sorted by that number (e.g. `hwmon0`, `hwmon1`, ..., `hwmon10`)
+pub(crate) fn numbered_entries(dir: &Path, prefix: &str) -> Vec<(u32, std::path::PathBuf)> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut result: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let index = name.to_str()?.strip_prefix(prefix)?.parse().ok()?;
+            Some((index, entry.path()))
+        })
+        .collect();
+
+    result.sort_by_key(|(index, _)| *index);
+    result
+}
@@ -1,4 +1,5 @@
 // Utility functions for VERA Tauri application
+pub mod parse;
 
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -10,6 +11,13 @@ pub fn get_timestamp_ms() -> u64 {
         .as_millis() as u64
 }
 
+/// `len` random bytes from the OS, hex encoded (tokens, generated ids)
+pub fn random_hex(len: usize) -> Result<String, getrandom::Error> {
+    let mut bytes = vec![0u8; len];
+    getrandom::getrandom(&mut bytes)?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
 /// Format bytes to human readable string
 pub fn format_bytes(bytes: u64) -> String {
     const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
@@ -39,7 +47,7 @@ pub fn calculate_efficiency_score(
     let efficiency = 100.0 - ((cpu_usage + memory_usage) / 2.0);
     let power_penalty = (power_consumption / 150.0) * 10.0; // Assume 150W max
     
-    (efficiency - power_penalty).max(0.0).min(100.0)
+    (efficiency - power_penalty).clamp(0.0, 100.0)
 }
 
 /// Convert temperature from Celsius to Fahrenheit
@@ -47,8 +55,14 @@ pub fn celsius_to_fahrenheit(celsius: f32) -> f32 {
     (celsius * 9.0 / 5.0) + 32.0
 }
 
-/// Validate hardware temperature ranges
-pub fn is_temperature_safe(component: &str, temp_celsius: f32) -> bool {
+/// Validate hardware temperature ranges.
+/// Uses the critical threshold reported by the hardware when available,
+/// otherwise a conservative default for the component type.
+pub fn is_temperature_safe(component: &str, temp_celsius: f32, crit_celsius: Option<f32>) -> bool {
+    if let Some(crit) = crit_celsius {
+        return temp_celsius < crit;
+    }
+
     match component {
         "cpu" => temp_celsius < 80.0,
         "gpu" => temp_celsius < 85.0,
@@ -91,14 +105,22 @@ mod tests {
     #[test]
     fn test_efficiency_score() {
         let score = calculate_efficiency_score(50.0, 60.0, 75.0);
-        assert!(score >= 0.0 && score <= 100.0);
+        assert!((0.0..=100.0).contains(&score));
     }
 
     #[test]
     fn test_temperature_safety() {
-        assert!(is_temperature_safe("cpu", 70.0));
-        assert!(!is_temperature_safe("cpu", 90.0));
-        assert!(is_temperature_safe("gpu", 80.0));
-        assert!(!is_temperature_safe("gpu", 95.0));
+        assert!(is_temperature_safe("cpu", 70.0, None));
+        assert!(!is_temperature_safe("cpu", 90.0, None));
+        assert!(is_temperature_safe("gpu", 80.0, None));
+        assert!(!is_temperature_safe("gpu", 95.0, None));
+    }
+
+    #[test]
+    fn test_temperature_safety_hardware_crit() {
+        // Tjmax of 100°C on a laptop CPU overrides the generic 80°C limit
+        assert!(is_temperature_safe("cpu", 90.0, Some(100.0)));
+        assert!(!is_temperature_safe("cpu", 100.0, Some(100.0)));
+        assert!(!is_temperature_safe("storage", 72.0, Some(70.0)));
     }
 }
\ No newline at end of file
@@ -0,0 +1,90 @@
+// Parsing of user-supplied times and durations (CLI arguments, API queries)
+//
+// Times: `now`, Unix seconds, `2024-05-01`, `2024-05-01 18:30`, RFC 3339, or
+// a duration ago such as `24h` / `7d`. Local time unless an offset is given.
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+/// `30s`, `15m`, `1h`, `7d`, `2w` → seconds
+pub fn parse_duration(text: &str) -> Result<i64, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("`{}` needs a unit (s, m, h, d, w)", text))?;
+    let (value, unit) = text.split_at(split);
+    let value: i64 = value
+        .parse()
+        .map_err(|_| format!("`{}` is not a duration", text))?;
+
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        "w" => 7 * 86_400,
+        _ => return Err(format!("unknown unit `{}` in `{}` (use s, m, h, d, w)", unit, text)),
+    };
+    if value == 0 {
+        return Err("duration must be positive".to_string());
+    }
+    Ok(value * unit_secs)
+}
+
+/// Parse a point in time relative to `now`, as Unix seconds
+pub fn parse_time(text: &str, now: DateTime<Local>) -> Result<i64, String> {
+    let text = text.trim();
+
+    if text == "now" {
+        return Ok(now.timestamp());
+    }
+    if let Ok(seconds) = text.parse::<i64>() {
+        return Ok(seconds);
+    }
+    if let Ok(ago) = parse_duration(text) {
+        return Ok(now.timestamp() - ago);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time.timestamp());
+    }
+
+    let naive = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M"))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(text, "%Y-%m-%d").map(|d| d.and_hms_opt(0, 0, 0).unwrap())
+        })
+        .map_err(|_| format!("`{}` is not a time (try 2024-05-01, \"2024-05-01 18:30\" or 24h)", text))?;
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|t| t.timestamp())
+        .ok_or_else(|| format!("`{}` does not exist in the local time zone", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_durations() {
+        assert_eq!(parse_duration("30s"), Ok(30));
+        assert_eq!(parse_duration("1h"), Ok(3600));
+        assert_eq!(parse_duration("7d"), Ok(604_800));
+        assert!(parse_duration("10").is_err());
+        assert!(parse_duration("0h").is_err());
+        assert!(parse_duration("5y").is_err());
+    }
+
+    #[test]
+    fn test_times() {
+        let now = Local.with_ymd_and_hms(2024, 5, 10, 12, 0, 0).unwrap();
+        assert_eq!(parse_time("now", now), Ok(now.timestamp()));
+        assert_eq!(parse_time("24h", now), Ok(now.timestamp() - 86_400));
+        assert_eq!(parse_time("1700000000", now), Ok(1_700_000_000));
+        assert_eq!(parse_time("2024-05-01T00:00:00Z", now), Ok(1_714_521_600));
+
+        let midnight = Local.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap().timestamp();
+        assert_eq!(parse_time("2024-05-01", now), Ok(midnight));
+        assert_eq!(parse_time("2024-05-01 18:30", now), Ok(midnight + 18 * 3600 + 30 * 60));
+        assert!(parse_time("yesterday", now).is_err());
+    }
+}